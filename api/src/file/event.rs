@@ -0,0 +1,134 @@
+use core::{
+    any::Any,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+use alloc::sync::Arc;
+use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
+use axtask::WaitQueue;
+
+use super::{FileLike, Kstat, PollSet, PollWaiter};
+use crate::signal::wait_interruptible;
+
+/// The largest value the counter can hold.
+const MAX_COUNT: u64 = u64::MAX - 1;
+
+/// An event notification object created by `eventfd`.
+pub struct EventFd {
+    count: AtomicU64,
+    /// Each `read` takes 1 from the counter instead of all of it.
+    semaphore: bool,
+    nonblocking: AtomicBool,
+    wq: WaitQueue,
+    poll_set: PollSet,
+}
+
+impl EventFd {
+    pub fn new(initval: u64, semaphore: bool) -> Self {
+        Self {
+            count: AtomicU64::new(initval),
+            semaphore,
+            nonblocking: AtomicBool::new(false),
+            wq: WaitQueue::new(),
+            poll_set: PollSet::new(),
+        }
+    }
+}
+
+impl FileLike for EventFd {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        if buf.len() < size_of::<u64>() {
+            return Err(LinuxError::EINVAL);
+        }
+
+        loop {
+            let count = self.count.load(Ordering::Acquire);
+            if count > 0 {
+                let value = if self.semaphore { 1 } else { count };
+                if self
+                    .count
+                    .compare_exchange(count, count - value, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err()
+                {
+                    continue;
+                }
+                self.wq.notify_all(false);
+                self.poll_set.wake();
+                buf[..size_of::<u64>()].copy_from_slice(&value.to_ne_bytes());
+                return Ok(size_of::<u64>());
+            }
+
+            if self.nonblocking() {
+                return Err(LinuxError::EAGAIN);
+            }
+            wait_interruptible(&self.wq, || self.count.load(Ordering::Acquire) > 0)?;
+        }
+    }
+
+    fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
+        let Some(bytes) = buf.first_chunk::<{ size_of::<u64>() }>() else {
+            return Err(LinuxError::EINVAL);
+        };
+        let value = u64::from_ne_bytes(*bytes);
+        if value > MAX_COUNT {
+            return Err(LinuxError::EINVAL);
+        }
+
+        loop {
+            let count = self.count.load(Ordering::Acquire);
+            if MAX_COUNT - count >= value {
+                if self
+                    .count
+                    .compare_exchange(count, count + value, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err()
+                {
+                    continue;
+                }
+                self.wq.notify_all(false);
+                self.poll_set.wake();
+                return Ok(size_of::<u64>());
+            }
+
+            if self.nonblocking() {
+                return Err(LinuxError::EAGAIN);
+            }
+            wait_interruptible(&self.wq, || {
+                MAX_COUNT - self.count.load(Ordering::Acquire) >= value
+            })?;
+        }
+    }
+
+    fn stat(&self) -> LinuxResult<Kstat> {
+        Ok(Kstat {
+            mode: 0o600u32, // rw-------
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        let count = self.count.load(Ordering::Acquire);
+        Ok(PollState {
+            readable: count > 0,
+            writable: count < MAX_COUNT,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
+
+    fn register_waiter(&self, waiter: &PollWaiter) -> bool {
+        self.poll_set.register(waiter);
+        true
+    }
+}
@@ -0,0 +1,67 @@
+use alloc::{collections::BTreeMap, string::String};
+use axerrno::{LinuxError, LinuxResult};
+use linux_raw_sys::general::{FS_APPEND_FL, FS_IMMUTABLE_FL, FS_NODUMP_FL};
+use spin::RwLock;
+
+/// Inode flags that can be changed with `FS_IOC_SETFLAGS`.
+pub const SUPPORTED_INODE_FLAGS: u32 = FS_IMMUTABLE_FL | FS_APPEND_FL | FS_NODUMP_FL;
+
+/// Per-inode flags (`FS_*_FL`), keyed by the real path of the file.
+///
+/// The file system backends have no place to store these flags, so they are
+/// kept in memory and follow the file on rename and removal.
+pub static INODE_FLAGS: InodeFlagsManager = InodeFlagsManager::new();
+
+pub struct InodeFlagsManager {
+    flags: RwLock<BTreeMap<String, u32>>,
+}
+
+impl InodeFlagsManager {
+    const fn new() -> Self {
+        Self {
+            flags: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Get the flags of the file at `path`.
+    pub fn get(&self, path: &str) -> u32 {
+        self.flags.read().get(path).copied().unwrap_or(0)
+    }
+
+    /// Set the flags of the file at `path`.
+    pub fn set(&self, path: &str, flags: u32) {
+        let mut map = self.flags.write();
+        if flags == 0 {
+            map.remove(path);
+        } else {
+            map.insert(path.into(), flags);
+        }
+    }
+
+    /// Check that the file at `path` may be modified, removed or renamed,
+    /// which immutable files never may.
+    ///
+    /// Append-only files can only be appended to, so `EPERM` is also returned
+    /// for them unless `append` is set.
+    pub fn check_modify(&self, path: &str, append: bool) -> LinuxResult {
+        let flags = self.get(path);
+        if flags & FS_IMMUTABLE_FL != 0 || (flags & FS_APPEND_FL != 0 && !append) {
+            return Err(LinuxError::EPERM);
+        }
+        Ok(())
+    }
+
+    /// Forget the flags of a removed file.
+    pub fn remove(&self, path: &str) {
+        self.flags.write().remove(path);
+    }
+
+    /// Move the flags of a renamed file.
+    pub fn rename(&self, old_path: &str, new_path: &str) {
+        let mut map = self.flags.write();
+        map.remove(new_path);
+        if let Some(flags) = map.remove(old_path) {
+            map.insert(new_path.into(), flags);
+        }
+    }
+}
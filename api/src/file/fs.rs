@@ -1,25 +1,101 @@
-use core::{any::Any, ffi::c_int};
+use core::{
+    any::Any,
+    ffi::c_int,
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+};
 
 use alloc::{string::String, sync::Arc};
 use axerrno::{LinuxError, LinuxResult};
 use axfs::fops::DirEntry;
-use axio::PollState;
+use axio::{PollState, SeekFrom};
 use axsync::{Mutex, MutexGuard};
-use linux_raw_sys::general::S_IFDIR;
+use linux_raw_sys::general::{
+    FS_APPEND_FL, IN_MODIFY, O_APPEND, O_DIRECT, O_DIRECTORY, O_NONBLOCK, O_RDONLY, S_IFDIR,
+};
 
-use super::{FileLike, Kstat, get_file_like};
+use super::{
+    BLOCK_SIZE, FILE_LOCKS, FileLike, INODE_FLAGS, INODES, Inode, Kstat, PollWaiter, SETFL_MASK,
+    SUPPORTED_INODE_FLAGS, get_file_like, notify_file_event,
+};
+use crate::path::{FilePath, HARDLINK_MANAGER};
+
+/// The number of upcoming write-backs to fail with `EIO`, set through
+/// `/proc/sys/debug/fail_writeback` to test how the failures are reported.
+pub static FAIL_WRITEBACK: AtomicUsize = AtomicUsize::new(0);
 
 /// File wrapper for `axfs::fops::File`.
 pub struct File {
     inner: Mutex<axfs::fops::File>,
     path: String,
+    /// The inode of the file, which it keeps after being unlinked.
+    inode: Arc<Inode>,
+    /// The number of failed write-backs of the inode this open file has
+    /// reported, see [`Inode::check_writeback_error`].
+    wb_seen: AtomicU32,
+    /// The access mode and the flags in [`SETFL_MASK`] of the open file.
+    flags: AtomicU32,
 }
 
 impl File {
-    pub fn new(inner: axfs::fops::File, path: String) -> Self {
+    /// Wrap a file opened with the open `flags`.
+    pub fn new(inner: axfs::fops::File, path: String, flags: u32) -> Self {
+        let inode = INODES.get(&path);
         Self {
             inner: Mutex::new(inner),
+            wb_seen: AtomicU32::new(inode.writeback_seq()),
+            inode,
             path,
+            flags: AtomicU32::new(flags & (0b11 | SETFL_MASK)),
+        }
+    }
+
+    /// Report a change of the file's data to the inotify watches.
+    fn notify_modified(&self) {
+        notify_file_event(&self.path, IN_MODIFY, self.inode.is_unlinked());
+    }
+
+    /// Whether every write goes to the end of the file.
+    fn appends(&self) -> bool {
+        self.flags.load(Ordering::Acquire) & O_APPEND != 0
+            || INODE_FLAGS.get(&self.path) & FS_APPEND_FL != 0
+    }
+
+    /// Check that an I/O on `buf` at `offset` is aligned to [`BLOCK_SIZE`] if
+    /// the file is opened with `O_DIRECT`.
+    ///
+    /// The data still goes through the file system as usual, but programs
+    /// get `EINVAL` for unaligned I/O as they would on Linux.
+    fn check_direct_io(
+        &self,
+        buf: &[u8],
+        offset: impl FnOnce() -> LinuxResult<u64>,
+    ) -> LinuxResult {
+        if self.flags.load(Ordering::Acquire) & O_DIRECT == 0 {
+            return Ok(());
+        }
+        let align = BLOCK_SIZE as usize;
+        if buf.as_ptr() as usize % align != 0
+            || buf.len() % align != 0
+            || offset()? % align as u64 != 0
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        Ok(())
+    }
+
+    /// Write the data of the file to storage, recording a failure in the
+    /// inode for `fsync` to report.
+    pub fn write_back(&self) {
+        let result = if FAIL_WRITEBACK
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+            .is_ok()
+        {
+            Err(LinuxError::EIO)
+        } else {
+            self.inner().fsync().map_err(LinuxError::from)
+        };
+        if let Err(err) = result {
+            self.inode.set_writeback_error(err);
         }
     }
 
@@ -28,6 +104,11 @@ impl File {
         &self.path
     }
 
+    /// Get the inode of the file.
+    pub fn inode(&self) -> &Inode {
+        &self.inode
+    }
+
     /// Get the inner node of the file.
     pub fn inner(&self) -> MutexGuard<axfs::fops::File> {
         self.inner.lock()
@@ -36,19 +117,45 @@ impl File {
 
 impl FileLike for File {
     fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
-        Ok(self.inner().read(buf)?)
+        let mut inner = self.inner();
+        self.check_direct_io(buf, || Ok(inner.seek(SeekFrom::Current(0))?))?;
+        Ok(inner.read(buf)?)
     }
 
     fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
-        Ok(self.inner().write(buf)?)
+        INODE_FLAGS.check_modify(&self.path, true)?;
+        let mut inner = self.inner();
+        if self.appends() {
+            inner.seek(SeekFrom::End(0))?;
+        }
+        self.check_direct_io(buf, || Ok(inner.seek(SeekFrom::Current(0))?))?;
+        let written = inner.write(buf)?;
+        if written > 0 {
+            self.notify_modified();
+        }
+        Ok(written)
     }
 
     fn read_at(&self, offset: u64, buf: &mut [u8]) -> LinuxResult<usize> {
+        self.check_direct_io(buf, || Ok(offset))?;
         Ok(self.inner().read_at(offset, buf)?)
     }
 
     fn write_at(&self, offset: u64, buf: &[u8]) -> LinuxResult<usize> {
-        Ok(self.inner().write_at(offset, buf)?)
+        INODE_FLAGS.check_modify(&self.path, true)?;
+        let inner = self.inner();
+        // Like on Linux, `pwrite` also appends if `O_APPEND` is set.
+        let offset = if self.appends() {
+            inner.get_attr()?.size()
+        } else {
+            offset
+        };
+        self.check_direct_io(buf, || Ok(offset))?;
+        let written = inner.write_at(offset, buf)?;
+        if written > 0 {
+            self.notify_modified();
+        }
+        Ok(written)
     }
 
     fn stat(&self) -> LinuxResult<Kstat> {
@@ -56,23 +163,37 @@ impl FileLike for File {
         let ty = metadata.file_type() as u8;
         let perm = metadata.perm().bits() as u32;
 
-        Ok(Kstat {
+        let mut kstat = Kstat {
+            ino: self.inode.ino(),
             mode: ((ty as u32) << 12) | perm,
+            nlink: FilePath::new(&self.path).map_or(1, |path| HARDLINK_MANAGER.link_count(&path))
+                as _,
             size: metadata.size(),
-            blocks: metadata.blocks(),
-            blksize: 512,
+            blocks: Kstat::blocks_of(metadata.size()),
+            // `FS_*_FL` flags share their values with `STATX_ATTR_*`.
+            attributes: INODE_FLAGS.get(&self.path) & SUPPORTED_INODE_FLAGS,
             ..Default::default()
-        })
+        };
+        self.inode.fill(&mut kstat);
+        Ok(kstat)
     }
 
     fn truncate(&self, len: u64) -> LinuxResult {
+        INODE_FLAGS.check_modify(&self.path, false)?;
         self.inner().truncate(len)?;
+        self.notify_modified();
         Ok(())
     }
 
     fn fsync(&self) -> LinuxResult {
-        self.inner().fsync()?;
-        Ok(())
+        self.write_back();
+        self.inode.check_writeback_error(&self.wb_seen)
+    }
+
+    fn flush(&self) -> LinuxResult {
+        // Writes go straight to the file system, so there is nothing to write
+        // out. Only a write-back error that was not reported yet is left.
+        self.inode.check_writeback_error(&self.wb_seen)
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
@@ -80,30 +201,74 @@ impl FileLike for File {
     }
 
     fn poll(&self) -> LinuxResult<PollState> {
+        // Regular files never block, so they are always ready.
         Ok(PollState {
             readable: true,
             writable: true,
         })
     }
 
-    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        if nonblocking {
+            self.flags.fetch_or(O_NONBLOCK, Ordering::AcqRel);
+        } else {
+            self.flags.fetch_and(!O_NONBLOCK, Ordering::AcqRel);
+        }
         Ok(())
     }
+
+    fn nonblocking(&self) -> bool {
+        self.flags.load(Ordering::Acquire) & O_NONBLOCK != 0
+    }
+
+    fn status_flags(&self) -> u32 {
+        self.flags.load(Ordering::Acquire)
+    }
+
+    fn set_status_flags(&self, flags: u32) -> LinuxResult {
+        // Append-only files cannot be opened for other writes.
+        if flags & O_APPEND == 0 && INODE_FLAGS.get(&self.path) & FS_APPEND_FL != 0 {
+            return Err(LinuxError::EPERM);
+        }
+        let _ = self
+            .flags
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |old| {
+                Some(old & !SETFL_MASK | flags & SETFL_MASK)
+            });
+        Ok(())
+    }
+
+    fn register_waiter(&self, _waiter: &PollWaiter) -> bool {
+        true
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        FILE_LOCKS.release(self as *const Self as usize);
+    }
 }
 
 /// Directory wrapper for `axfs::fops::Directory`.
 pub struct Directory {
     inner: Mutex<axfs::fops::Directory>,
     path: String,
+    /// The inode of the directory, which it keeps after being removed.
+    inode: Arc<Inode>,
     last_dirent: Mutex<Option<DirEntry>>,
+    /// The file status flags, which are always `O_RDONLY | O_DIRECTORY` and
+    /// the ones changed by `F_SETFL`.
+    flags: AtomicU32,
 }
 
 impl Directory {
     pub fn new(inner: axfs::fops::Directory, path: String) -> Self {
         Self {
             inner: Mutex::new(inner),
+            inode: INODES.get(&path),
             path,
             last_dirent: Mutex::new(None),
+            flags: AtomicU32::new(O_RDONLY | O_DIRECTORY),
         }
     }
 
@@ -112,6 +277,11 @@ impl Directory {
         &self.path
     }
 
+    /// Get the inode of the directory.
+    pub fn inode(&self) -> &Inode {
+        &self.inode
+    }
+
     /// Get the inner node of the directory.
     pub fn inner(&self) -> MutexGuard<axfs::fops::Directory> {
         self.inner.lock()
@@ -133,10 +303,14 @@ impl FileLike for Directory {
     }
 
     fn stat(&self) -> LinuxResult<Kstat> {
-        Ok(Kstat {
+        let mut kstat = Kstat {
+            ino: self.inode.ino(),
             mode: S_IFDIR | 0o755u32, // rwxr-xr-x
+            attributes: INODE_FLAGS.get(&self.path) & SUPPORTED_INODE_FLAGS,
             ..Default::default()
-        })
+        };
+        self.inode.fill(&mut kstat);
+        Ok(kstat)
     }
 
     fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
@@ -144,16 +318,44 @@ impl FileLike for Directory {
     }
 
     fn poll(&self) -> LinuxResult<PollState> {
+        // Like regular files, and like Linux reports for any file without a
+        // `poll` operation.
         Ok(PollState {
             readable: true,
-            writable: false,
+            writable: true,
         })
     }
 
-    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        if nonblocking {
+            self.flags.fetch_or(O_NONBLOCK, Ordering::AcqRel);
+        } else {
+            self.flags.fetch_and(!O_NONBLOCK, Ordering::AcqRel);
+        }
         Ok(())
     }
 
+    fn nonblocking(&self) -> bool {
+        self.flags.load(Ordering::Acquire) & O_NONBLOCK != 0
+    }
+
+    fn status_flags(&self) -> u32 {
+        self.flags.load(Ordering::Acquire)
+    }
+
+    fn set_status_flags(&self, flags: u32) -> LinuxResult {
+        let _ = self
+            .flags
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |old| {
+                Some(old & !SETFL_MASK | flags & SETFL_MASK)
+            });
+        Ok(())
+    }
+
+    fn register_waiter(&self, _waiter: &PollWaiter) -> bool {
+        true
+    }
+
     fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
         get_file_like(fd)?
             .into_any()
@@ -161,3 +363,9 @@ impl FileLike for Directory {
             .map_err(|_| LinuxError::ENOTDIR)
     }
 }
+
+impl Drop for Directory {
+    fn drop(&mut self) {
+        FILE_LOCKS.release(self as *const Self as usize);
+    }
+}
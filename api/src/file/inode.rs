@@ -0,0 +1,174 @@
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use alloc::{collections::BTreeMap, string::String, sync::Arc};
+use axerrno::{LinuxError, LinuxResult};
+use linux_raw_sys::general::{S_IFMT, S_ISGID, S_ISUID, S_IXGRP};
+use spin::{Mutex, RwLock};
+
+use super::{Kstat, inotify::trim_path};
+
+/// The owner and mode of a file.
+#[derive(Clone, Copy)]
+struct InodeAttr {
+    uid: u32,
+    gid: u32,
+    /// The permission bits, including the set-user-ID and set-group-ID bits,
+    /// of a file created since boot. Other files report those of the backend.
+    mode: Option<u32>,
+}
+
+/// The state of a file that the file system backends cannot store, shared by
+/// all of its names and open files.
+pub struct Inode {
+    ino: u64,
+    attr: Mutex<InodeAttr>,
+    /// The number of failed write-backs of the file's data, and the error of
+    /// the last one.
+    wb_error: Mutex<(u32, Option<LinuxError>)>,
+    /// Whether the file was removed, and is only kept by its open files.
+    unlinked: AtomicBool,
+}
+
+impl Inode {
+    /// Get the inode number of the file.
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Get the user and group ID of the owner of the file.
+    pub fn owner(&self) -> (u32, u32) {
+        let attr = self.attr.lock();
+        (attr.uid, attr.gid)
+    }
+
+    /// Whether the file was removed while open.
+    pub fn is_unlinked(&self) -> bool {
+        self.unlinked.load(Ordering::Acquire)
+    }
+
+    /// Set the owner and permission bits of a newly created file.
+    pub fn init(&self, uid: u32, gid: u32, mode: u32) {
+        *self.attr.lock() = InodeAttr {
+            uid,
+            gid,
+            mode: Some(mode & 0o7777),
+        };
+    }
+
+    /// Change the owner of the file.
+    ///
+    /// Like Linux, this clears the set-user-ID bit of a file that is not a
+    /// directory, and its set-group-ID bit if it is group-executable.
+    pub fn chown(&self, uid: u32, gid: u32, is_dir: bool) {
+        let mut attr = self.attr.lock();
+        attr.uid = uid;
+        attr.gid = gid;
+        if !is_dir && let Some(mode) = attr.mode.as_mut() {
+            *mode &= !S_ISUID;
+            if *mode & S_IXGRP != 0 {
+                *mode &= !S_ISGID;
+            }
+        }
+    }
+
+    /// Fill in the owner and permission bits of the file.
+    pub fn fill(&self, kstat: &mut Kstat) {
+        let attr = *self.attr.lock();
+        (kstat.uid, kstat.gid) = (attr.uid, attr.gid);
+        if let Some(mode) = attr.mode {
+            kstat.mode = (kstat.mode & S_IFMT) | mode;
+        }
+    }
+
+    /// Record a failed write-back of the file's data.
+    ///
+    /// Like the `errseq_t` of Linux, the error is reported once by `fsync`
+    /// on every open file of the inode, whichever one the failure happened
+    /// on.
+    pub fn set_writeback_error(&self, err: LinuxError) {
+        let mut wb_error = self.wb_error.lock();
+        *wb_error = (wb_error.0.wrapping_add(1), Some(err));
+    }
+
+    /// The number of failed write-backs so far, which a newly opened file
+    /// has already seen.
+    pub fn writeback_seq(&self) -> u32 {
+        self.wb_error.lock().0
+    }
+
+    /// Report the last write-back error if it happened after `seen`, the
+    /// number of failed write-backs the open file has seen, and mark it seen.
+    pub fn check_writeback_error(&self, seen: &AtomicU32) -> LinuxResult {
+        let (seq, err) = *self.wb_error.lock();
+        match err {
+            Some(err) if seen.swap(seq, Ordering::AcqRel) != seq => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The inodes of the files, keyed by the real path of the file.
+///
+/// An inode is created the first time its file is looked up, and follows the
+/// file when its real path moves to another hardlink. A file created later at
+/// the path of a removed one gets a new inode.
+pub static INODES: InodeTable = InodeTable::new();
+
+pub struct InodeTable {
+    inodes: RwLock<BTreeMap<String, Arc<Inode>>>,
+    /// The next inode number. 0 marks deleted directory entries and 1 is
+    /// reported by files that have no path, like pipes and sockets.
+    next_ino: AtomicU64,
+}
+
+impl InodeTable {
+    const fn new() -> Self {
+        Self {
+            inodes: RwLock::new(BTreeMap::new()),
+            next_ino: AtomicU64::new(2),
+        }
+    }
+
+    /// Get the inode of the file whose real path is `path`.
+    pub fn get(&self, path: &str) -> Arc<Inode> {
+        let path = trim_path(path);
+        if let Some(inode) = self.inodes.read().get(path) {
+            return inode.clone();
+        }
+        self.inodes
+            .write()
+            .entry(path.into())
+            .or_insert_with(|| {
+                let kstat = Kstat::default();
+                Arc::new(Inode {
+                    ino: self.next_ino.fetch_add(1, Ordering::Relaxed),
+                    attr: Mutex::new(InodeAttr {
+                        uid: kstat.uid,
+                        gid: kstat.gid,
+                        mode: None,
+                    }),
+                    wb_error: Mutex::new((0, None)),
+                    unlinked: AtomicBool::new(false),
+                })
+            })
+            .clone()
+    }
+
+    /// Forget the inode of a removed file. Its open files keep it.
+    pub fn remove(&self, path: &str) {
+        if let Some(inode) = self.inodes.write().remove(trim_path(path)) {
+            inode.unlinked.store(true, Ordering::Release);
+        }
+    }
+
+    /// Move the inode of a file whose real path changed.
+    pub fn rename(&self, old_path: &str, new_path: &str) {
+        let mut inodes = self.inodes.write();
+        if let Some(replaced) = inodes.remove(trim_path(new_path)) {
+            replaced.unlinked.store(true, Ordering::Release);
+        }
+        if let Some(inode) = inodes.remove(trim_path(old_path)) {
+            inodes.insert(trim_path(new_path).into(), inode);
+        }
+    }
+}
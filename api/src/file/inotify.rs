@@ -0,0 +1,283 @@
+use core::{
+    any::Any,
+    sync::atomic::{AtomicBool, AtomicI32, Ordering},
+};
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
+use axtask::WaitQueue;
+use linux_raw_sys::general::{
+    IN_ALL_EVENTS, IN_DELETE, IN_DELETE_SELF, IN_EXCL_UNLINK, IN_IGNORED, IN_ISDIR, IN_MASK_ADD,
+    IN_MASK_CREATE, IN_ONESHOT, IN_Q_OVERFLOW, inotify_event,
+};
+use spin::Mutex;
+
+use super::{FileLike, Kstat, PollSet, PollWaiter};
+use crate::signal::wait_interruptible;
+
+/// The size of an `inotify_event` without its name.
+const EVENT_SIZE: usize = size_of::<inotify_event>();
+/// The most events queued on one instance, like the default
+/// `max_queued_events`.
+const MAX_QUEUED_EVENTS: usize = 16384;
+
+/// The inotify instances that file system events are reported to.
+static INSTANCES: Mutex<Vec<Weak<Inotify>>> = Mutex::new(Vec::new());
+
+/// A path watched by an inotify instance.
+struct InotifyWatch {
+    path: String,
+    mask: u32,
+}
+
+/// A file reporting changes to the watched paths, created by
+/// `inotify_init1`.
+///
+/// Watches are keyed by path, like the other in-memory file state, so they
+/// do not follow a file that is replaced by another one.
+pub struct Inotify {
+    watches: Mutex<BTreeMap<i32, InotifyWatch>>,
+    next_wd: AtomicI32,
+    /// The encoded `inotify_event` records waiting to be read.
+    events: Mutex<VecDeque<Vec<u8>>>,
+    nonblocking: AtomicBool,
+    wq: WaitQueue,
+    poll_set: PollSet,
+}
+
+impl Inotify {
+    pub fn new() -> Arc<Self> {
+        let inotify = Arc::new(Self {
+            watches: Mutex::new(BTreeMap::new()),
+            next_wd: AtomicI32::new(1),
+            events: Mutex::new(VecDeque::new()),
+            nonblocking: AtomicBool::new(false),
+            wq: WaitQueue::new(),
+            poll_set: PollSet::new(),
+        });
+        let mut instances = INSTANCES.lock();
+        instances.retain(|instance| instance.strong_count() > 0);
+        instances.push(Arc::downgrade(&inotify));
+        inotify
+    }
+
+    /// Watch the file at `path` for the events in `mask`, or change the mask
+    /// of the watch it already has.
+    ///
+    /// Return the watch descriptor.
+    pub fn add_watch(&self, path: &str, mask: u32) -> LinuxResult<i32> {
+        let path = trim_path(path);
+        let mut watches = self.watches.lock();
+        if let Some((&wd, watch)) = watches.iter_mut().find(|(_, watch)| watch.path == path) {
+            if mask & IN_MASK_CREATE != 0 {
+                return Err(LinuxError::EEXIST);
+            }
+            if mask & IN_MASK_ADD != 0 {
+                watch.mask |= mask;
+            } else {
+                watch.mask = mask;
+            }
+            return Ok(wd);
+        }
+        let wd = self.next_wd.fetch_add(1, Ordering::Relaxed);
+        watches.insert(
+            wd,
+            InotifyWatch {
+                path: path.into(),
+                mask,
+            },
+        );
+        Ok(wd)
+    }
+
+    /// Remove the watch `wd`, queueing an `IN_IGNORED` event for it.
+    pub fn rm_watch(&self, wd: i32) -> LinuxResult {
+        self.watches.lock().remove(&wd).ok_or(LinuxError::EINVAL)?;
+        self.push_event(wd, IN_IGNORED, "");
+        Ok(())
+    }
+
+    /// Queue an event, unless it is the same as the last one, like Linux.
+    fn push_event(&self, wd: i32, mask: u32, name: &str) {
+        let mut record = Vec::with_capacity(EVENT_SIZE + name.len() + 1);
+        // The name is null-terminated and padded so that the next record is
+        // aligned.
+        let len = if name.is_empty() {
+            0
+        } else {
+            (name.len() + 1).next_multiple_of(EVENT_SIZE)
+        };
+        record.extend_from_slice(&wd.to_ne_bytes());
+        record.extend_from_slice(&mask.to_ne_bytes());
+        // cookie: only renames pair events.
+        record.extend_from_slice(&0u32.to_ne_bytes());
+        record.extend_from_slice(&(len as u32).to_ne_bytes());
+        record.extend_from_slice(name.as_bytes());
+        record.resize(EVENT_SIZE + len, 0);
+
+        let mut events = self.events.lock();
+        if events.back() == Some(&record) {
+            return;
+        }
+        if events.len() >= MAX_QUEUED_EVENTS {
+            // Report the lost events once, with a watch descriptor of -1.
+            if events
+                .back()
+                .is_some_and(|last| last[..4] == (-1i32).to_ne_bytes())
+            {
+                return;
+            }
+            record.clear();
+            record.extend_from_slice(&(-1i32).to_ne_bytes());
+            record.extend_from_slice(&IN_Q_OVERFLOW.to_ne_bytes());
+            record.resize(EVENT_SIZE, 0);
+        }
+        events.push_back(record);
+        drop(events);
+        self.wq.notify_all(false);
+        self.poll_set.wake();
+    }
+
+    /// Report the event `mask` on the file at `path`, named `name` in the
+    /// directory `parent`, or on an unlinked file that was there if
+    /// `unlinked` is set.
+    fn report(&self, path: &str, parent: Option<(&str, &str)>, mask: u32, unlinked: bool) {
+        let mut watches = self.watches.lock();
+        let mut removed = Vec::new();
+        for (&wd, watch) in watches.iter() {
+            let (event, name) = match parent {
+                Some((dir, name)) if watch.path == dir => {
+                    if unlinked && watch.mask & IN_EXCL_UNLINK != 0 {
+                        continue;
+                    }
+                    (mask, name)
+                }
+                // The watches at the path of an unlinked file are on
+                // another one.
+                _ if unlinked => continue,
+                // Events on the watched file itself carry no name.
+                _ if watch.path == path && mask & IN_DELETE != 0 => (IN_DELETE_SELF, ""),
+                _ if watch.path == path => (mask & !IN_ISDIR, ""),
+                _ => continue,
+            };
+            if watch.mask & event & IN_ALL_EVENTS != 0 {
+                self.push_event(wd, event, name);
+                if watch.mask & IN_ONESHOT != 0 {
+                    removed.push(wd);
+                }
+            }
+            // The watch goes away with the file.
+            if event == IN_DELETE_SELF && !removed.contains(&wd) {
+                removed.push(wd);
+            }
+        }
+        for wd in removed {
+            watches.remove(&wd);
+            self.push_event(wd, IN_IGNORED, "");
+        }
+    }
+}
+
+/// Strip the trailing slash that [`FilePath`](crate::path::FilePath) keeps
+/// on directories.
+pub(super) fn trim_path(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        path => path,
+    }
+}
+
+/// Report the file system event `mask` on the file at `path` to the inotify
+/// instances watching the file or its directory.
+///
+/// Watches on a deleted file get `IN_DELETE_SELF` and are removed.
+pub fn notify_fs_event(path: &str, mask: u32) {
+    notify_file_event(path, mask, false);
+}
+
+/// Like [`notify_fs_event`], for an event on an open file, which watches
+/// with `IN_EXCL_UNLINK` on its directory miss once it is `unlinked`.
+pub fn notify_file_event(path: &str, mask: u32, unlinked: bool) {
+    let path = trim_path(path);
+    let parent = path
+        .rsplit_once('/')
+        .filter(|(_, name)| !name.is_empty())
+        .map(|(dir, name)| (trim_path(dir), name));
+    let instances: Vec<_> = INSTANCES.lock().iter().filter_map(Weak::upgrade).collect();
+    for inotify in instances {
+        inotify.report(path, parent, mask, unlinked);
+    }
+}
+
+impl FileLike for Inotify {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        loop {
+            let mut events = self.events.lock();
+            if let Some(first) = events.front() {
+                // Records cannot be split, so the first one must fit.
+                if buf.len() < first.len() {
+                    return Err(LinuxError::EINVAL);
+                }
+                let mut len = 0;
+                while let Some(event) = events.front() {
+                    let end = len + event.len();
+                    if end > buf.len() {
+                        break;
+                    }
+                    buf[len..end].copy_from_slice(event);
+                    len = end;
+                    events.pop_front();
+                }
+                return Ok(len);
+            }
+            drop(events);
+
+            if self.nonblocking() {
+                return Err(LinuxError::EAGAIN);
+            }
+            wait_interruptible(&self.wq, || !self.events.lock().is_empty())?;
+        }
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn stat(&self) -> LinuxResult<Kstat> {
+        Ok(Kstat {
+            mode: 0o600u32, // rw-------
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        Ok(PollState {
+            readable: !self.events.lock().is_empty(),
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
+
+    fn register_waiter(&self, waiter: &PollWaiter) -> bool {
+        self.poll_set.register(waiter);
+        true
+    }
+}
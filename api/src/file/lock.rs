@@ -0,0 +1,118 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use axerrno::{LinuxError, LinuxResult};
+use axtask::WaitQueue;
+use spin::Mutex;
+
+use crate::signal::wait_interruptible;
+
+/// The holders of the `flock` lock of a file.
+#[derive(Default)]
+struct FileLock {
+    /// The open file holding the lock exclusively, if any.
+    exclusive: Option<usize>,
+    /// The open files sharing the lock.
+    shared: Vec<usize>,
+}
+
+impl FileLock {
+    /// Whether `owner` can take the lock without waiting for other holders.
+    fn allows(&self, owner: usize, exclusive: bool) -> bool {
+        let shared_by_others = self.shared.iter().any(|&holder| holder != owner);
+        self.exclusive.is_none_or(|holder| holder == owner) && !(exclusive && shared_by_others)
+    }
+
+    /// Drop the lock of `owner`, if it holds it.
+    fn release(&mut self, owner: usize) {
+        self.shared.retain(|&holder| holder != owner);
+        if self.exclusive == Some(owner) {
+            self.exclusive = None;
+        }
+    }
+
+    fn is_free(&self) -> bool {
+        self.exclusive.is_none() && self.shared.is_empty()
+    }
+}
+
+/// The `flock` advisory locks, keyed by the inode number of the file.
+///
+/// Locks belong to open files, identified by the address of their
+/// [`FileLike`](super::FileLike), so they are shared by all the descriptors
+/// duplicated from one and released when the last of them is closed.
+pub static FILE_LOCKS: FileLockManager = FileLockManager::new();
+
+pub struct FileLockManager {
+    locks: Mutex<BTreeMap<u64, FileLock>>,
+    /// Tasks waiting for a lock to be released.
+    wq: WaitQueue,
+}
+
+impl FileLockManager {
+    const fn new() -> Self {
+        Self {
+            locks: Mutex::new(BTreeMap::new()),
+            wq: WaitQueue::new(),
+        }
+    }
+
+    /// Take the lock of the file `ino` for `owner`, converting the lock it
+    /// already holds if any.
+    ///
+    /// Wait for conflicting locks to be released, or fail with `EAGAIN`
+    /// if `nonblocking` is set. The wait fails with `EINTR` on a signal.
+    pub fn lock(&self, ino: u64, owner: usize, exclusive: bool, nonblocking: bool) -> LinuxResult {
+        loop {
+            {
+                let mut locks = self.locks.lock();
+                let lock = locks.entry(ino).or_default();
+                if lock.allows(owner, exclusive) {
+                    lock.release(owner);
+                    if exclusive {
+                        lock.exclusive = Some(owner);
+                    } else {
+                        lock.shared.push(owner);
+                    }
+                    return Ok(());
+                }
+            }
+            if nonblocking {
+                return Err(LinuxError::EAGAIN);
+            }
+            wait_interruptible(&self.wq, || {
+                self.locks
+                    .lock()
+                    .get(&ino)
+                    .is_none_or(|lock| lock.allows(owner, exclusive))
+            })?;
+        }
+    }
+
+    /// Release the lock of the file `ino` held by `owner`.
+    pub fn unlock(&self, ino: u64, owner: usize) {
+        let mut locks = self.locks.lock();
+        if let Some(lock) = locks.get_mut(&ino) {
+            lock.release(owner);
+            if lock.is_free() {
+                locks.remove(&ino);
+            }
+        }
+        drop(locks);
+        self.wq.notify_all(false);
+    }
+
+    /// Release all the locks held by `owner`, once the open file is closed.
+    pub fn release(&self, owner: usize) {
+        let mut locks = self.locks.lock();
+        let mut released = false;
+        locks.retain(|_, lock| {
+            let held = lock.exclusive == Some(owner) || lock.shared.contains(&owner);
+            lock.release(owner);
+            released |= held;
+            !lock.is_free()
+        });
+        drop(locks);
+        if released {
+            self.wq.notify_all(false);
+        }
+    }
+}
@@ -1,7 +1,16 @@
+mod event;
+mod flags;
 mod fs;
+mod inode;
+mod inotify;
+mod lock;
 mod net;
 mod pipe;
+mod proc;
+mod signalfd;
 mod stdio;
+mod timerfd;
+mod wait;
 
 use core::{any::Any, ffi::c_int};
 
@@ -9,18 +18,49 @@ use alloc::{sync::Arc, vec::Vec};
 use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
 use axns::{ResArc, def_resource};
+use axtask::{TaskExtRef, current};
 use flatten_objects::FlattenObjects;
-use linux_raw_sys::general::{stat, statx};
+use linux_raw_sys::general::{
+    O_APPEND, O_DIRECT, O_NONBLOCK, O_RDWR, RLIMIT_NOFILE, S_IFDIR, S_IFMT, STATX_ATTR_APPEND,
+    STATX_ATTR_IMMUTABLE, STATX_ATTR_NODUMP, X_OK, stat, statx,
+};
 use spin::RwLock;
 
 pub use self::{
-    fs::{Directory, File},
+    event::EventFd,
+    flags::{INODE_FLAGS, SUPPORTED_INODE_FLAGS},
+    fs::{Directory, FAIL_WRITEBACK, File},
+    inode::{INODES, Inode},
+    inotify::{Inotify, notify_file_event, notify_fs_event},
+    lock::FILE_LOCKS,
     net::Socket,
-    pipe::Pipe,
+    pipe::{FIFOS, Pipe},
+    proc::{add_proc_pid_dir, init_procfs, remove_proc_pid_dir},
+    signalfd::SignalFd,
+    timerfd::{TimerClock, TimerFd},
+    wait::{PollSet, PollWaiter},
 };
 
 pub const AX_FILE_LIMIT: usize = 1024;
 
+/// The file status flags that `F_SETFL` can change.
+pub const SETFL_MASK: u32 = O_APPEND | O_NONBLOCK | O_DIRECT;
+
+/// The block size reported in `st_blksize`.
+///
+/// The file system backends do not expose their allocation unit, so this is
+/// the 4 KiB block size that ext4 and FAT images are formatted with by
+/// default.
+pub const BLOCK_SIZE: u32 = 4096;
+
+/// The inode number of the file whose real path is `path`.
+///
+/// The file system backends do not expose inode numbers, so one is allocated
+/// in [`INODES`] the first time the file is looked up.
+pub fn inode_number(path: &str) -> u64 {
+    INODES.get(path).ino()
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Kstat {
     ino: u64,
@@ -31,6 +71,8 @@ pub struct Kstat {
     size: u64,
     blocks: u64,
     blksize: u32,
+    /// `STATX_ATTR_*` attributes of the file.
+    attributes: u32,
 }
 
 impl Default for Kstat {
@@ -43,7 +85,8 @@ impl Default for Kstat {
             mode: 0,
             size: 0,
             blocks: 0,
-            blksize: 4096,
+            blksize: BLOCK_SIZE,
+            attributes: 0,
         }
     }
 }
@@ -61,10 +104,51 @@ impl Kstat {
         }
     }
 
+    /// Set the inode number of the file.
+    pub fn set_ino(&mut self, ino: u64) {
+        self.ino = ino;
+    }
+
+    /// The number of 512-byte blocks reported in `st_blocks` for a file of
+    /// `size` bytes.
+    pub fn blocks_of(size: u64) -> u64 {
+        size.div_ceil(512)
+    }
+
     /// Get the size of the file
     pub fn size(&self) -> u64 {
         self.size
     }
+
+    /// Get the file type and mode of the file
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    /// Check whether the user `uid` in the group `gid` may access the file as
+    /// in `access`, a mask of `R_OK`, `W_OK` and `X_OK`.
+    ///
+    /// Root may read and write any file, and execute any that has an execute
+    /// bit set or is a directory.
+    pub fn check_access(&self, access: u32, uid: u32, gid: u32) -> LinuxResult {
+        let allowed = if uid == 0 {
+            if self.mode & S_IFMT == S_IFDIR || self.mode & 0o111 != 0 {
+                0o7
+            } else {
+                0o7 & !X_OK
+            }
+        } else if uid == self.uid {
+            (self.mode >> 6) & 0o7
+        } else if gid == self.gid {
+            (self.mode >> 3) & 0o7
+        } else {
+            self.mode & 0o7
+        };
+        if access & !allowed != 0 {
+            return Err(LinuxError::EACCES);
+        }
+        Ok(())
+    }
 }
 
 impl From<Kstat> for stat {
@@ -89,7 +173,9 @@ impl From<Kstat> for statx {
         // SAFETY: valid for statx
         let mut statx: statx = unsafe { core::mem::zeroed() };
         statx.stx_blksize = value.blksize as _;
-        statx.stx_attributes = value.mode as _;
+        statx.stx_attributes = value.attributes as _;
+        statx.stx_attributes_mask =
+            (STATX_ATTR_IMMUTABLE | STATX_ATTR_APPEND | STATX_ATTR_NODUMP) as _;
         statx.stx_nlink = value.nlink as _;
         statx.stx_uid = value.uid as _;
         statx.stx_gid = value.gid as _;
@@ -102,6 +188,12 @@ impl From<Kstat> for statx {
     }
 }
 
+/// An open file description.
+///
+/// Each `open` creates its own object, which the descriptors duplicated from
+/// it and the forked processes share. It keeps the offset and the status
+/// flags of the open file, while the close-on-exec flag belongs to each
+/// [`FileDescriptor`].
 #[allow(dead_code)]
 pub trait FileLike: Send + Sync {
     fn read(&self, buf: &mut [u8]) -> LinuxResult<usize>;
@@ -124,9 +216,41 @@ pub trait FileLike: Send + Sync {
     fn fsync(&self) -> LinuxResult {
         Err(LinuxError::EINVAL)
     }
+    /// Called each time a descriptor of the file is closed, to report a
+    /// deferred write error. It must not sync the file.
+    fn flush(&self) -> LinuxResult {
+        Ok(())
+    }
     fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
     fn poll(&self) -> LinuxResult<PollState>;
     fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult;
+    /// Whether the open file description is in non-blocking mode.
+    fn nonblocking(&self) -> bool {
+        false
+    }
+    /// The file status flags reported by `F_GETFL`, that is the access mode
+    /// and the flags in [`SETFL_MASK`].
+    fn status_flags(&self) -> u32 {
+        if self.nonblocking() {
+            O_RDWR | O_NONBLOCK
+        } else {
+            O_RDWR
+        }
+    }
+    /// Change the flags in [`SETFL_MASK`], like `F_SETFL`.
+    fn set_status_flags(&self, flags: u32) -> LinuxResult {
+        self.set_nonblocking(flags & O_NONBLOCK != 0)
+    }
+    /// Register `waiter` to be woken the next time the file may become
+    /// ready, and return whether it will be, so that tasks polling the file
+    /// can sleep instead of spinning.
+    ///
+    /// Files whose readiness never changes, like regular files, return `true`
+    /// without registering it. Sockets cannot be waited for, as their state
+    /// only changes when the network interfaces are polled.
+    fn register_waiter(&self, _waiter: &PollWaiter) -> bool {
+        false
+    }
 
     fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>>
     where
@@ -138,21 +262,33 @@ pub trait FileLike: Send + Sync {
             .map_err(|_| LinuxError::EINVAL)
     }
 
-    fn add_to_fd_table(self) -> LinuxResult<c_int>
+    fn add_to_fd_table(self, cloexec: bool) -> LinuxResult<c_int>
     where
         Self: Sized + 'static,
     {
-        add_file_like(Arc::new(self))
+        add_file_like(Arc::new(self), cloexec)
     }
 }
 
+/// An entry in the file descriptor table.
+///
+/// Duplicated descriptors share the same `file`, while the close-on-exec flag
+/// belongs to each descriptor.
+#[derive(Clone)]
+pub struct FileDescriptor {
+    /// The underlying open file.
+    pub file: Arc<dyn FileLike>,
+    /// Whether the descriptor is closed on `execve` (`FD_CLOEXEC`).
+    pub cloexec: bool,
+}
+
 def_resource! {
-    pub static FD_TABLE: ResArc<RwLock<FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>>> = ResArc::new();
+    pub static FD_TABLE: ResArc<RwLock<FlattenObjects<FileDescriptor, AX_FILE_LIMIT>>> = ResArc::new();
 }
 
 impl FD_TABLE {
     /// Return a copy of the inner table.
-    pub fn copy_inner(&self) -> RwLock<FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>> {
+    pub fn copy_inner(&self) -> RwLock<FlattenObjects<FileDescriptor, AX_FILE_LIMIT>> {
         let table = self.read();
         let mut new_table = FlattenObjects::new();
         for id in table.ids() {
@@ -169,15 +305,33 @@ impl FD_TABLE {
         }
     }
 
-    /// Synchronize all open files in the file descriptor table.
-    pub fn sync_all(&self) -> LinuxResult {
+    /// Close all file descriptors with the close-on-exec flag set.
+    pub fn close_on_exec(&self) {
+        let mut table = self.write();
+        let ids = table
+            .ids()
+            .filter(|&id| table.get(id).is_some_and(|fd| fd.cloexec))
+            .collect::<Vec<_>>();
+        for id in ids {
+            let _ = table.remove(id);
+        }
+    }
+
+    /// Write the data of all the open regular files in the file descriptor
+    /// table to storage.
+    ///
+    /// Failures are recorded in the inodes for `fsync` to report, as `sync`
+    /// itself cannot fail.
+    pub fn sync_all(&self) {
         let table = self.read();
         for id in table.ids() {
-            if let Some(file) = table.get(id) {
-                file.fsync()?;
+            if let Some(file) = table
+                .get(id)
+                .and_then(|fd| fd.file.clone().into_any().downcast::<File>().ok())
+            {
+                file.write_back();
             }
         }
-        Ok(())
     }
 }
 
@@ -186,13 +340,52 @@ pub fn get_file_like(fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
     FD_TABLE
         .read()
         .get(fd as usize)
-        .cloned()
+        .map(|fd| fd.file.clone())
         .ok_or(LinuxError::EBADF)
 }
 
+/// The number of file descriptors the current process may have, bounded by
+/// its `RLIMIT_NOFILE`.
+pub fn fd_limit() -> usize {
+    let limit = current()
+        .task_ext()
+        .process_data()
+        .rlimits
+        .read()
+        .get(RLIMIT_NOFILE)
+        .soft;
+    limit.min(AX_FILE_LIMIT as u64) as usize
+}
+
 /// Add a file to the file descriptor table.
-pub fn add_file_like(f: Arc<dyn FileLike>) -> LinuxResult<c_int> {
-    Ok(FD_TABLE.write().add(f).map_err(|_| LinuxError::EMFILE)? as c_int)
+pub fn add_file_like(f: Arc<dyn FileLike>, cloexec: bool) -> LinuxResult<c_int> {
+    let fd = FileDescriptor { file: f, cloexec };
+    let mut table = FD_TABLE.write();
+    let fd = table.add(fd).map_err(|_| LinuxError::EMFILE)?;
+    if fd >= fd_limit() {
+        table.remove(fd);
+        return Err(LinuxError::EMFILE);
+    }
+    Ok(fd as c_int)
+}
+
+/// Get the close-on-exec flag of `fd`.
+pub fn get_fd_cloexec(fd: c_int) -> LinuxResult<bool> {
+    FD_TABLE
+        .read()
+        .get(fd as usize)
+        .map(|fd| fd.cloexec)
+        .ok_or(LinuxError::EBADF)
+}
+
+/// Set the close-on-exec flag of `fd`.
+pub fn set_fd_cloexec(fd: c_int, cloexec: bool) -> LinuxResult {
+    FD_TABLE
+        .write()
+        .get_mut(fd as usize)
+        .ok_or(LinuxError::EBADF)?
+        .cloexec = cloexec;
+    Ok(())
 }
 
 /// Close a file by `fd`.
@@ -201,21 +394,40 @@ pub fn close_file_like(fd: c_int) -> LinuxResult {
         .write()
         .remove(fd as usize)
         .ok_or(LinuxError::EBADF)?;
-    debug!("close_file_like <= count: {}", Arc::strong_count(&f));
-    Ok(())
+    debug!("close_file_like <= count: {}", Arc::strong_count(&f.file));
+    // The descriptor is released even if flushing fails.
+    f.file.flush()
 }
 
 #[ctor_bare::register_ctor]
 fn init_stdio() {
     let mut fd_table = flatten_objects::FlattenObjects::new();
     fd_table
-        .add_at(0, Arc::new(stdio::stdin()) as _)
+        .add_at(
+            0,
+            FileDescriptor {
+                file: Arc::new(stdio::stdin()),
+                cloexec: false,
+            },
+        )
         .unwrap_or_else(|_| panic!()); // stdin
     fd_table
-        .add_at(1, Arc::new(stdio::stdout()) as _)
+        .add_at(
+            1,
+            FileDescriptor {
+                file: Arc::new(stdio::stdout()),
+                cloexec: false,
+            },
+        )
         .unwrap_or_else(|_| panic!()); // stdout
     fd_table
-        .add_at(2, Arc::new(stdio::stdout()) as _)
+        .add_at(
+            2,
+            FileDescriptor {
+                file: Arc::new(stdio::stdout()),
+                cloexec: false,
+            },
+        )
         .unwrap_or_else(|_| panic!()); // stderr
     FD_TABLE.init_new(spin::RwLock::new(fd_table));
 }
@@ -108,4 +108,11 @@ impl FileLike for Socket {
         }
         Ok(())
     }
+
+    fn nonblocking(&self) -> bool {
+        match self {
+            Socket::Udp(udpsocket) => udpsocket.lock().is_nonblocking(),
+            Socket::Tcp(tcpsocket) => tcpsocket.lock().is_nonblocking(),
+        }
+    }
 }
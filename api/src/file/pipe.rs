@@ -1,12 +1,26 @@
-use core::{any::Any, ffi::c_int};
+use core::{
+    any::Any,
+    ffi::c_int,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-use alloc::sync::Arc;
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{Arc, Weak},
+    vec,
+    vec::Vec,
+};
 use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::PAGE_SIZE_4K;
 use axio::PollState;
-use axsync::Mutex;
-use linux_raw_sys::general::S_IFIFO;
+use axsync::{Mutex, MutexGuard};
+use axtask::WaitQueue;
+use linux_raw_sys::general::{O_NONBLOCK, O_RDONLY, O_RDWR, O_WRONLY, S_IFIFO};
+use spin::RwLock;
 
-use super::{FileLike, Kstat, get_file_like};
+use super::{FileLike, Kstat, PollSet, PollWaiter, get_file_like};
+use crate::signal::{send_sigpipe, wait_interruptible};
 
 #[derive(Copy, Clone, PartialEq)]
 enum RingBufferStatus {
@@ -15,38 +29,81 @@ enum RingBufferStatus {
     Normal,
 }
 
-const RING_BUFFER_SIZE: usize = 256;
+/// The default capacity of a pipe.
+const DEFAULT_PIPE_SIZE: usize = 16 * PAGE_SIZE_4K;
+/// Writes of up to this many bytes are not interleaved with other writes.
+const PIPE_BUF: usize = 4096;
+/// The largest capacity unprivileged users can set, like
+/// `/proc/sys/fs/pipe-max-size`.
+const MAX_PIPE_SIZE: usize = 1024 * 1024;
 
 struct PipeRingBuffer {
-    arr: [u8; RING_BUFFER_SIZE],
+    arr: Vec<u8>,
     head: usize,
     tail: usize,
     status: RingBufferStatus,
+    /// The number of open read ends.
+    readers: usize,
+    /// The number of open write ends.
+    writers: usize,
 }
 
 impl PipeRingBuffer {
-    const fn new() -> Self {
+    fn new(capacity: usize) -> Self {
         Self {
-            arr: [0; RING_BUFFER_SIZE],
+            arr: vec![0; capacity],
             head: 0,
             tail: 0,
             status: RingBufferStatus::Empty,
+            readers: 0,
+            writers: 0,
+        }
+    }
+
+    /// Whether all the ends that could write to a read end, or read from a
+    /// write end if `readable` is not set, are closed.
+    fn peer_closed(&self, readable: bool) -> bool {
+        if readable {
+            self.writers == 0
+        } else {
+            self.readers == 0
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.arr.len()
+    }
+
+    /// Move the buffered data into a buffer of `capacity` bytes, which must
+    /// be large enough to hold it.
+    fn resize(&mut self, capacity: usize) {
+        let mut new = Self::new(capacity);
+        new.readers = self.readers;
+        new.writers = self.writers;
+        while self.available_read() > 0 {
+            new.write_byte(self.read_byte());
         }
+        *self = new;
     }
 
     fn write_byte(&mut self, byte: u8) {
         self.status = RingBufferStatus::Normal;
         self.arr[self.tail] = byte;
-        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
+        self.tail = (self.tail + 1) % self.capacity();
         if self.tail == self.head {
             self.status = RingBufferStatus::Full;
         }
     }
 
+    /// Get the byte `offset` bytes past the next one to read.
+    fn peek_byte(&self, offset: usize) -> u8 {
+        self.arr[(self.head + offset) % self.capacity()]
+    }
+
     fn read_byte(&mut self) -> u8 {
         self.status = RingBufferStatus::Normal;
         let c = self.arr[self.head];
-        self.head = (self.head + 1) % RING_BUFFER_SIZE;
+        self.head = (self.head + 1) % self.capacity();
         if self.head == self.tail {
             self.status = RingBufferStatus::Empty;
         }
@@ -54,61 +111,186 @@ impl PipeRingBuffer {
     }
 
     /// Get the length of remaining data in the buffer
-    const fn available_read(&self) -> usize {
+    fn available_read(&self) -> usize {
         if matches!(self.status, RingBufferStatus::Empty) {
             0
         } else if self.tail > self.head {
             self.tail - self.head
         } else {
-            self.tail + RING_BUFFER_SIZE - self.head
+            self.tail + self.capacity() - self.head
         }
     }
 
     /// Get the length of remaining space in the buffer
-    const fn available_write(&self) -> usize {
+    fn available_write(&self) -> usize {
         if matches!(self.status, RingBufferStatus::Full) {
             0
         } else {
-            RING_BUFFER_SIZE - self.available_read()
+            self.capacity() - self.available_read()
         }
     }
 }
 
+/// The state shared by the ends of a pipe.
+struct PipeBuffer {
+    ring: Mutex<PipeRingBuffer>,
+    /// Woken when an end is opened, which the opens of a named pipe wait for.
+    open_wq: WaitQueue,
+    /// The tasks waiting for data or room in the buffer, or for the other
+    /// side to be closed.
+    poll_set: PollSet,
+}
+
+impl PipeBuffer {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            ring: Mutex::new(PipeRingBuffer::new(DEFAULT_PIPE_SIZE)),
+            open_wq: WaitQueue::new(),
+            poll_set: PollSet::new(),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PipeRingBuffer> {
+        self.ring.lock()
+    }
+
+    /// Get a waiter woken the next time the ends may become ready.
+    fn waiter(&self) -> PollWaiter {
+        let waiter = PollWaiter::new();
+        self.poll_set.register(&waiter);
+        waiter
+    }
+}
+
 pub struct Pipe {
     readable: bool,
-    buffer: Arc<Mutex<PipeRingBuffer>>,
+    writable: bool,
+    buffer: Arc<PipeBuffer>,
+    nonblocking: AtomicBool,
 }
 
 impl Pipe {
     pub fn new() -> (Pipe, Pipe) {
-        let buffer = Arc::new(Mutex::new(PipeRingBuffer::new()));
-        let read_end = Pipe {
-            readable: true,
-            buffer: buffer.clone(),
-        };
-        let write_end = Pipe {
-            readable: false,
-            buffer,
-        };
+        let buffer = PipeBuffer::new();
+        let read_end = Pipe::open_end(buffer.clone(), true, false);
+        let write_end = Pipe::open_end(buffer, false, true);
         (read_end, write_end)
     }
 
+    /// Open another end on `buffer`. A named pipe opened with `O_RDWR` has
+    /// both a read and a write end.
+    fn open_end(buffer: Arc<PipeBuffer>, readable: bool, writable: bool) -> Pipe {
+        let mut ring_buffer = buffer.lock();
+        if readable {
+            ring_buffer.readers += 1;
+        }
+        if writable {
+            ring_buffer.writers += 1;
+        }
+        drop(ring_buffer);
+        buffer.open_wq.notify_all(false);
+        Pipe {
+            readable,
+            writable,
+            buffer,
+            nonblocking: AtomicBool::new(false),
+        }
+    }
+
     pub const fn readable(&self) -> bool {
         self.readable
     }
 
     pub const fn writable(&self) -> bool {
-        !self.readable
+        self.writable
     }
 
+    /// Whether every end on the other side of this one is closed.
     pub fn closed(&self) -> bool {
-        Arc::strong_count(&self.buffer) == 1
+        self.buffer.lock().peer_closed(self.readable)
     }
 
     pub fn available_data(&self) -> usize {
         let ring_buffer = self.buffer.lock();
         ring_buffer.available_read()
     }
+
+    /// Copy up to `buf.len()` buffered bytes into `buf` without waiting, and
+    /// consume only the number of them `consume` returns.
+    ///
+    /// The pipe stays locked while `consume` runs, so other readers cannot
+    /// take the same bytes, and the ones it fails to use stay in the pipe.
+    pub fn read_with(
+        &self,
+        buf: &mut [u8],
+        consume: impl FnOnce(&[u8]) -> LinuxResult<usize>,
+    ) -> LinuxResult<usize> {
+        let mut ring_buffer = self.buffer.lock();
+        let size = ring_buffer.available_read().min(buf.len());
+        for (i, c) in buf[..size].iter_mut().enumerate() {
+            *c = ring_buffer.peek_byte(i);
+        }
+        let used = consume(&buf[..size])?.min(size);
+        for _ in 0..used {
+            ring_buffer.read_byte();
+        }
+        drop(ring_buffer);
+        if used > 0 {
+            self.buffer.poll_set.wake();
+        }
+        Ok(used)
+    }
+
+    /// Get the capacity of the pipe, for `F_GETPIPE_SZ`.
+    pub fn capacity(&self) -> usize {
+        self.buffer.lock().capacity()
+    }
+
+    /// Change the capacity of the pipe, for `F_SETPIPE_SZ`.
+    ///
+    /// The size is rounded up to a power of two number of pages. Returns the
+    /// new capacity, or `EBUSY` if the buffered data would not fit.
+    pub fn set_capacity(&self, size: usize, privileged: bool) -> LinuxResult<usize> {
+        if size > MAX_PIPE_SIZE && !privileged {
+            return Err(LinuxError::EPERM);
+        }
+        let pages = size.div_ceil(PAGE_SIZE_4K).max(1);
+        let capacity = pages
+            .checked_next_power_of_two()
+            .and_then(|pages| pages.checked_mul(PAGE_SIZE_4K))
+            .ok_or(LinuxError::EINVAL)?;
+        let mut ring_buffer = self.buffer.lock();
+        if capacity < ring_buffer.available_read() {
+            return Err(LinuxError::EBUSY);
+        }
+        if capacity != ring_buffer.capacity() {
+            ring_buffer.resize(capacity);
+            drop(ring_buffer);
+            self.buffer.poll_set.wake();
+        }
+        Ok(capacity)
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut ring_buffer = self.buffer.lock();
+        if self.readable {
+            ring_buffer.readers -= 1;
+        }
+        if self.writable {
+            ring_buffer.writers -= 1;
+        }
+        drop(ring_buffer);
+        // The other side may now be hung up.
+        self.buffer.poll_set.wake();
+    }
+}
+
+/// The error of a write with no reader left, which also raises `SIGPIPE`.
+fn broken_pipe() -> LinuxError {
+    send_sigpipe();
+    LinuxError::EPIPE
 }
 
 impl FileLike for Pipe {
@@ -121,20 +303,27 @@ impl FileLike for Pipe {
         }
 
         loop {
+            let waiter = self.buffer.waiter();
             let mut ring_buffer = self.buffer.lock();
             let read_size = ring_buffer.available_read().min(buf.len());
             if read_size == 0 {
-                if self.closed() {
+                if ring_buffer.peer_closed(true) {
                     return Ok(0);
                 }
+                if self.nonblocking() {
+                    return Err(LinuxError::EAGAIN);
+                }
                 drop(ring_buffer);
-                // Data not ready, wait for write end
-                axtask::yield_now(); // TODO: use synconize primitive
+                // Data not ready, wait for write end, or fail with `EINTR`
+                // on a signal.
+                waiter.wait(None)?;
                 continue;
             }
             for c in buf.iter_mut().take(read_size) {
                 *c = ring_buffer.read_byte();
             }
+            drop(ring_buffer);
+            self.buffer.poll_set.wake();
             return Ok(read_size);
         }
     }
@@ -143,8 +332,8 @@ impl FileLike for Pipe {
         if !self.writable() {
             return Err(LinuxError::EPERM);
         }
-        if self.closed() {
-            return Err(LinuxError::EPIPE);
+        if self.buffer.lock().peer_closed(false) {
+            return Err(broken_pipe());
         }
         if buf.is_empty() {
             return Ok(0);
@@ -153,24 +342,50 @@ impl FileLike for Pipe {
         let mut write_size = 0usize;
         let total_len = buf.len();
         loop {
+            let waiter = self.buffer.waiter();
             let mut ring_buffer = self.buffer.lock();
+            if ring_buffer.peer_closed(false) {
+                return if write_size > 0 {
+                    Ok(write_size)
+                } else {
+                    Err(broken_pipe())
+                };
+            }
             let loop_write = ring_buffer.available_write();
-            if loop_write == 0 {
-                if self.closed() {
-                    return Ok(write_size);
+            // Small writes wait until they fit in one piece.
+            let atomic = total_len <= PIPE_BUF.min(ring_buffer.capacity());
+            if loop_write == 0 || (atomic && loop_write < total_len) {
+                if self.nonblocking() {
+                    return if write_size > 0 {
+                        Ok(write_size)
+                    } else {
+                        Err(LinuxError::EAGAIN)
+                    };
                 }
                 drop(ring_buffer);
-                // Buffer is full, wait for read end to consume
-                axtask::yield_now(); // TODO: use synconize primitive
+                // Buffer is full, wait for read end to consume. A signal
+                // ends the write with what was written so far, if anything.
+                if let Err(err) = waiter.wait(None) {
+                    return if write_size > 0 {
+                        Ok(write_size)
+                    } else {
+                        Err(err)
+                    };
+                }
                 continue;
             }
             for _ in 0..loop_write {
                 if write_size == total_len {
-                    return Ok(write_size);
+                    break;
                 }
                 ring_buffer.write_byte(buf[write_size]);
                 write_size += 1;
             }
+            drop(ring_buffer);
+            self.buffer.poll_set.wake();
+            if write_size == total_len {
+                return Ok(write_size);
+            }
         }
     }
 
@@ -187,16 +402,41 @@ impl FileLike for Pipe {
 
     fn poll(&self) -> LinuxResult<PollState> {
         let buf = self.buffer.lock();
+        // Once the other side is closed, reads and writes return at once.
         Ok(PollState {
-            readable: self.readable() && buf.available_read() > 0,
-            writable: self.writable() && buf.available_write() > 0,
+            readable: self.readable() && (buf.available_read() > 0 || buf.peer_closed(true)),
+            writable: self.writable()
+                && (buf.available_write() >= PIPE_BUF || buf.peer_closed(false)),
         })
     }
 
-    fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
         Ok(())
     }
 
+    fn nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
+
+    fn register_waiter(&self, waiter: &PollWaiter) -> bool {
+        self.buffer.poll_set.register(waiter);
+        true
+    }
+
+    fn status_flags(&self) -> u32 {
+        let mode = match (self.readable(), self.writable()) {
+            (true, true) => O_RDWR,
+            (true, false) => O_RDONLY,
+            _ => O_WRONLY,
+        };
+        if self.nonblocking() {
+            mode | O_NONBLOCK
+        } else {
+            mode
+        }
+    }
+
     fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
         get_file_like(fd)?
             .into_any()
@@ -204,3 +444,99 @@ impl FileLike for Pipe {
             .map_err(|_| LinuxError::EINVAL)
     }
 }
+
+/// Named pipes created by `mknod`, keyed by the real path of their node.
+///
+/// The node on the file system is an empty file. The buffer is shared by the
+/// ends that are currently open and is dropped with the last of them, so
+/// data does not outlive the open ends, like on Linux.
+pub static FIFOS: FifoManager = FifoManager::new();
+
+struct Fifo {
+    /// The permission bits given to `mknod`.
+    mode: u32,
+    buffer: Weak<PipeBuffer>,
+}
+
+pub struct FifoManager {
+    fifos: RwLock<BTreeMap<String, Fifo>>,
+}
+
+impl FifoManager {
+    const fn new() -> Self {
+        Self {
+            fifos: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Mark the node at `path` as a named pipe.
+    pub fn create(&self, path: &str, mode: u32) {
+        self.fifos.write().insert(
+            path.into(),
+            Fifo {
+                mode,
+                buffer: Weak::new(),
+            },
+        );
+    }
+
+    /// Get the `st_mode` of the named pipe at `path`, if it is one.
+    pub fn mode(&self, path: &str) -> Option<u32> {
+        self.fifos
+            .read()
+            .get(path)
+            .map(|fifo| S_IFIFO | (fifo.mode & 0o7777))
+    }
+
+    /// Forget a removed named pipe.
+    pub fn remove(&self, path: &str) {
+        self.fifos.write().remove(path);
+    }
+
+    /// Move a renamed named pipe.
+    pub fn rename(&self, old_path: &str, new_path: &str) {
+        let mut fifos = self.fifos.write();
+        fifos.remove(new_path);
+        if let Some(fifo) = fifos.remove(old_path) {
+            fifos.insert(new_path.into(), fifo);
+        }
+    }
+
+    /// Open the read or write end of the named pipe at `path`, or both if
+    /// `readable` and `writable` are set, or return `None` if it is not one.
+    ///
+    /// Like on Linux, opening one end blocks until the other side is opened
+    /// as well, unless `nonblocking` is set. Then a reader does not wait, and
+    /// a writer fails with `ENXIO` if there is no reader. A signal interrupts
+    /// the wait with `EINTR`.
+    pub fn open(
+        &self,
+        path: &str,
+        readable: bool,
+        writable: bool,
+        nonblocking: bool,
+    ) -> Option<LinuxResult<Pipe>> {
+        let buffer = {
+            let mut fifos = self.fifos.write();
+            let fifo = fifos.get_mut(path)?;
+            fifo.buffer.upgrade().unwrap_or_else(|| {
+                let buffer = PipeBuffer::new();
+                fifo.buffer = Arc::downgrade(&buffer);
+                buffer
+            })
+        };
+        let pipe = Pipe::open_end(buffer.clone(), readable, writable);
+        pipe.nonblocking.store(nonblocking, Ordering::Release);
+        if readable && writable {
+            return Some(Ok(pipe));
+        }
+        if nonblocking {
+            if writable && pipe.closed() {
+                return Some(Err(LinuxError::ENXIO));
+            }
+        } else if let Err(err) = wait_interruptible(&buffer.open_wq, || !pipe.closed()) {
+            return Some(Err(err));
+        }
+        Some(Ok(pipe))
+    }
+}
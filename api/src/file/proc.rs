@@ -0,0 +1,186 @@
+//! The `/proc/self/fd` and `/proc/[pid]/fd` directories, and the other
+//! entries of `/proc/[pid]`.
+
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult,
+};
+use axprocess::Pid;
+use flatten_objects::FlattenObjects;
+use spin::RwLock;
+use starry_core::{
+    file::proc::{stat::ProcStat, sys::SysctlNode},
+    task::{ProcessData, get_process},
+};
+
+use super::{
+    AX_FILE_LIMIT, Directory, EventFd, FAIL_WRITEBACK, FD_TABLE, File, FileDescriptor, FileLike,
+    Inotify, Pipe, SignalFd, Socket,
+};
+use crate::imp::EpollInstance;
+
+/// Register `/proc/self/fd` and `/proc/sys/debug/fail_writeback`.
+pub fn init_procfs() {
+    let opts = axfs::fops::OpenOptions::new().set_read(true);
+    if let Ok(dir) = axfs::fops::Directory::open_dir("/proc/self", &opts) {
+        let _ = dir.add_node("fd", Arc::new(FdDir { pid: None }));
+    }
+    let _ = axfs::api::create_dir("/proc/sys/debug");
+    if let Ok(dir) = axfs::fops::Directory::open_dir("/proc/sys/debug", &opts) {
+        let _ = dir.add_node("fail_writeback", Arc::new(SysctlNode::new(&FAIL_WRITEBACK)));
+    }
+}
+
+/// Create `/proc/[pid]` with the `fd` directory and `stat` file of a new
+/// process.
+pub fn add_proc_pid_dir(pid: Pid) {
+    let path = format!("/proc/{}", pid);
+    if axfs::api::create_dir(&path).is_err() {
+        return;
+    }
+    let opts = axfs::fops::OpenOptions::new().set_read(true);
+    if let Ok(dir) = axfs::fops::Directory::open_dir(&path, &opts) {
+        let _ = dir.add_node("fd", Arc::new(FdDir { pid: Some(pid) }));
+        let _ = dir.add_node("stat", Arc::new(ProcStat::new(Some(pid))));
+    }
+}
+
+/// Remove `/proc/[pid]` once the process has been reaped.
+pub fn remove_proc_pid_dir(pid: Pid) {
+    let _ = axfs::api::remove_dir(&format!("/proc/{}/fd", pid));
+    let _ = axfs::api::remove_file(&format!("/proc/{}/stat", pid));
+    let _ = axfs::api::remove_dir(&format!("/proc/{}", pid));
+}
+
+/// Collect the open files of a descriptor table.
+fn snapshot(
+    table: &RwLock<FlattenObjects<FileDescriptor, AX_FILE_LIMIT>>,
+) -> Vec<(usize, Arc<dyn FileLike>)> {
+    let table = table.read();
+    table
+        .ids()
+        .filter_map(|fd| Some((fd, table.get(fd)?.file.clone())))
+        .collect()
+}
+
+/// Snapshot the open files of a process, or of the current one if `pid` is
+/// `None`.
+fn open_files(pid: Option<Pid>) -> VfsResult<Vec<(usize, Arc<dyn FileLike>)>> {
+    match pid {
+        None => Ok(snapshot(&FD_TABLE)),
+        Some(pid) => {
+            let process = get_process(pid).map_err(|_| VfsError::NotFound)?;
+            let data = process.data::<ProcessData>().ok_or(VfsError::NotFound)?;
+            Ok(snapshot(FD_TABLE.deref_from(&data.ns)))
+        }
+    }
+}
+
+/// What `readlink` shows for an open file: its path, or a pseudo name with a
+/// unique id for anonymous objects.
+fn link_target(file: Arc<dyn FileLike>) -> String {
+    let id = Arc::as_ptr(&file) as *const () as usize;
+    let file = file.into_any();
+    if let Some(file) = file.downcast_ref::<File>() {
+        file.path().into()
+    } else if let Some(dir) = file.downcast_ref::<Directory>() {
+        dir.path().into()
+    } else if file.is::<Pipe>() {
+        format!("pipe:[{}]", id)
+    } else if file.is::<Socket>() {
+        format!("socket:[{}]", id)
+    } else if file.is::<EventFd>() {
+        "anon_inode:[eventfd]".into()
+    } else if file.is::<SignalFd>() {
+        "anon_inode:[signalfd]".into()
+    } else if file.is::<EpollInstance>() {
+        "anon_inode:[eventpoll]".into()
+    } else if file.is::<Inotify>() {
+        "anon_inode:inotify".into()
+    } else {
+        format!("anon_inode:[{}]", id)
+    }
+}
+
+/// A directory with one symbolic link per open file descriptor.
+struct FdDir {
+    /// The process to list, or `None` for the current process.
+    pid: Option<Pid>,
+}
+
+impl VfsNodeOps for FdDir {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o500),
+            VfsNodeType::Dir,
+            0,
+            0,
+        ))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let name = path.trim_matches('/');
+        if name.is_empty() || name == "." {
+            return Ok(self);
+        }
+        let fd = name.parse::<usize>().map_err(|_| VfsError::NotFound)?;
+        if !open_files(self.pid)?.iter().any(|(id, _)| *id == fd) {
+            return Err(VfsError::NotFound);
+        }
+        Ok(Arc::new(FdLink { pid: self.pid, fd }))
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let files = open_files(self.pid)?;
+        let names = [".", ".."]
+            .into_iter()
+            .map(|name| VfsDirEntry::new(name, VfsNodeType::Dir))
+            .chain(
+                files
+                    .iter()
+                    .map(|(fd, _)| VfsDirEntry::new(&format!("{}", fd), VfsNodeType::SymLink)),
+            );
+        let mut count = 0;
+        for (dirent, entry) in dirents.iter_mut().zip(names.skip(start_idx)) {
+            *dirent = entry;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    axfs_vfs::impl_vfs_dir_default! {}
+}
+
+/// `/proc/[pid]/fd/[fd]`, linking to the open file.
+struct FdLink {
+    pid: Option<Pid>,
+    fd: usize,
+}
+
+impl VfsNodeOps for FdLink {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o700),
+            VfsNodeType::SymLink,
+            0,
+            0,
+        ))
+    }
+
+    fn readlink(&self, _path: &str, buf: &mut [u8]) -> VfsResult<usize> {
+        let (_, file) = open_files(self.pid)?
+            .into_iter()
+            .find(|(fd, _)| *fd == self.fd)
+            .ok_or(VfsError::NotFound)?;
+        let target = link_target(file);
+        let len = buf.len().min(target.len());
+        buf[..len].copy_from_slice(&target.as_bytes()[..len]);
+        Ok(len)
+    }
+
+    fn is_symlink(&self) -> bool {
+        true
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
@@ -0,0 +1,183 @@
+use core::{
+    any::Any,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use alloc::sync::Arc;
+use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
+use axsignal::{SignalInfo, SignalSet, Signo};
+use axsync::Mutex;
+use axtask::{TaskExtRef, current};
+
+use super::{FileLike, Kstat};
+use crate::signal::release_signal;
+
+/// The record returned by each `read` of a signalfd.
+#[repr(C)]
+#[derive(Default)]
+struct SignalfdSiginfo {
+    ssi_signo: u32,
+    ssi_errno: i32,
+    ssi_code: i32,
+    ssi_pid: u32,
+    ssi_uid: u32,
+    ssi_fd: i32,
+    ssi_tid: u32,
+    ssi_band: u32,
+    ssi_overrun: u32,
+    ssi_trapno: u32,
+    ssi_status: i32,
+    ssi_int: i32,
+    ssi_ptr: u64,
+    ssi_utime: u64,
+    ssi_stime: u64,
+    ssi_addr: u64,
+    ssi_addr_lsb: u16,
+    __pad2: u16,
+    ssi_syscall: i32,
+    ssi_call_addr: u64,
+    ssi_arch: u32,
+    __pad: [u8; 28],
+}
+
+impl From<&SignalInfo> for SignalfdSiginfo {
+    fn from(sig: &SignalInfo) -> Self {
+        // SAFETY: all the fields read here are plain integers, and the
+        // `_kill` prefix is shared by every layout that carries a sender.
+        unsafe {
+            let info = &sig.0.__bindgen_anon_1.__bindgen_anon_1;
+            let fields = &info._sifields;
+            let mut ssi = Self {
+                ssi_signo: info.si_signo as _,
+                ssi_errno: info.si_errno,
+                ssi_code: info.si_code,
+                ssi_pid: fields._kill._pid as _,
+                ssi_uid: fields._kill._uid,
+                ..Default::default()
+            };
+            if sig.signo() == Signo::SIGCHLD {
+                ssi.ssi_status = fields._sigchld._status;
+                ssi.ssi_utime = fields._sigchld._utime as _;
+                ssi.ssi_stime = fields._sigchld._stime as _;
+            } else {
+                ssi.ssi_int = fields._rt._sigval.sival_int;
+                ssi.ssi_ptr = fields._rt._sigval.sival_ptr as _;
+            }
+            ssi
+        }
+    }
+}
+
+/// A file that receives the signals in its mask, created by `signalfd4`.
+///
+/// Signals are taken from the thread that reads the file, like on Linux, so
+/// the mask should also be blocked to keep them from being delivered.
+pub struct SignalFd {
+    mask: Mutex<SignalSet>,
+    nonblocking: AtomicBool,
+}
+
+impl SignalFd {
+    pub fn new(mask: SignalSet) -> Self {
+        Self {
+            mask: Mutex::new(Self::sanitize(mask)),
+            nonblocking: AtomicBool::new(false),
+        }
+    }
+
+    /// Replace the set of signals the file receives.
+    pub fn set_mask(&self, mask: SignalSet) {
+        *self.mask.lock() = Self::sanitize(mask);
+    }
+
+    /// `SIGKILL` and `SIGSTOP` cannot be received through a signalfd.
+    fn sanitize(mut mask: SignalSet) -> SignalSet {
+        mask.remove(Signo::SIGKILL);
+        mask.remove(Signo::SIGSTOP);
+        mask
+    }
+
+    /// Take a signal in the mask off the current thread or its process,
+    /// waiting for one unless `nonblocking` is set.
+    fn dequeue(&self, nonblocking: bool) -> Option<SignalInfo> {
+        let mask = *self.mask.lock();
+        let curr = current();
+        let timeout = nonblocking.then_some(Duration::ZERO);
+        let sig = curr
+            .task_ext()
+            .thread_data()
+            .signal
+            .wait_timeout(mask, timeout)?;
+        release_signal(curr.task_ext().process_data(), &sig);
+        Some(sig)
+    }
+}
+
+impl FileLike for SignalFd {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        const SIZE: usize = size_of::<SignalfdSiginfo>();
+        if buf.len() < SIZE {
+            return Err(LinuxError::EINVAL);
+        }
+
+        let mut len = 0;
+        // Only wait for the first signal, then return what is pending.
+        while let Some(sig) = self.dequeue(self.nonblocking() || len > 0) {
+            let ssi = SignalfdSiginfo::from(&sig);
+            // SAFETY: `SignalfdSiginfo` is plain old data.
+            let bytes = unsafe {
+                core::slice::from_raw_parts(&ssi as *const SignalfdSiginfo as *const u8, SIZE)
+            };
+            buf[len..len + SIZE].copy_from_slice(bytes);
+            len += SIZE;
+            if buf.len() - len < SIZE {
+                break;
+            }
+        }
+
+        if len == 0 {
+            // Woken up by a signal outside the mask, or nothing is pending.
+            return Err(if self.nonblocking() {
+                LinuxError::EAGAIN
+            } else {
+                LinuxError::EINTR
+            });
+        }
+        Ok(len)
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn stat(&self) -> LinuxResult<Kstat> {
+        Ok(Kstat {
+            mode: 0o600u32, // rw-------
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        let mut pending = current().task_ext().thread_data().signal.pending();
+        pending &= *self.mask.lock();
+        Ok(PollState {
+            readable: pending != SignalSet::default(),
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
+}
@@ -0,0 +1,207 @@
+use core::{
+    any::Any,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use alloc::sync::Arc;
+use axerrno::{LinuxError, LinuxResult};
+use axhal::time::{monotonic_time, wall_time};
+use axio::PollState;
+use axtask::WaitQueue;
+use spin::Mutex;
+
+use super::{FileLike, Kstat, PollSet, PollWaiter};
+use crate::signal::{SIGNAL_CHECK_INTERVAL, unblocked_signal_pending};
+
+/// The clock a timerfd runs on.
+#[derive(Clone, Copy)]
+pub enum TimerClock {
+    /// `CLOCK_REALTIME`.
+    Realtime,
+    /// `CLOCK_MONOTONIC` and `CLOCK_BOOTTIME`, which are the same as the
+    /// system does not suspend.
+    Monotonic,
+}
+
+impl TimerClock {
+    pub fn now(self) -> Duration {
+        match self {
+            Self::Realtime => wall_time(),
+            Self::Monotonic => monotonic_time(),
+        }
+    }
+}
+
+struct TimerState {
+    /// When the timer expires next on its clock, or `None` if it is
+    /// disarmed.
+    deadline: Option<Duration>,
+    /// The period of the timer, zero for a one-shot timer.
+    interval: Duration,
+    /// The expirations that have not been read yet.
+    expirations: u64,
+}
+
+impl TimerState {
+    /// Count the expirations up to `now`.
+    fn update(&mut self, now: Duration) {
+        let Some(deadline) = self.deadline.filter(|&deadline| deadline <= now) else {
+            return;
+        };
+        if self.interval.is_zero() {
+            self.expirations = self.expirations.saturating_add(1);
+            self.deadline = None;
+        } else {
+            let periods = (now - deadline).as_nanos() / self.interval.as_nanos() + 1;
+            self.expirations = self.expirations.saturating_add(periods as u64);
+            let next = deadline.as_nanos() + periods * self.interval.as_nanos();
+            self.deadline = Some(Duration::from_nanos(next as u64));
+        }
+    }
+}
+
+/// A timer that is read as a file, created by `timerfd_create`.
+///
+/// The expirations are counted when the file is read or polled, so nothing
+/// runs while the timer is armed.
+pub struct TimerFd {
+    clock: TimerClock,
+    state: Mutex<TimerState>,
+    nonblocking: AtomicBool,
+    wq: WaitQueue,
+    poll_set: PollSet,
+}
+
+impl TimerFd {
+    pub fn new(clock: TimerClock) -> Self {
+        Self {
+            clock,
+            state: Mutex::new(TimerState {
+                deadline: None,
+                interval: Duration::ZERO,
+                expirations: 0,
+            }),
+            nonblocking: AtomicBool::new(false),
+            wq: WaitQueue::new(),
+            poll_set: PollSet::new(),
+        }
+    }
+
+    /// The current time on the clock of the timer.
+    pub fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    /// Get the time until the next expiration, zero if the timer is
+    /// disarmed, and the interval of the timer.
+    pub fn get_time(&self) -> (Duration, Duration) {
+        let now = self.now();
+        let mut state = self.state.lock();
+        state.update(now);
+        let left = state
+            .deadline
+            .map_or(Duration::ZERO, |deadline| deadline - now);
+        (left, state.interval)
+    }
+
+    /// Arm the timer to expire at `deadline` on its clock and then every
+    /// `interval` if it is not zero, or disarm it if `deadline` is `None`.
+    ///
+    /// The expirations that were not read are dropped, and the old setting
+    /// is returned like [`get_time`](Self::get_time).
+    pub fn set_time(&self, deadline: Option<Duration>, interval: Duration) -> (Duration, Duration) {
+        let old = self.get_time();
+        *self.state.lock() = TimerState {
+            deadline,
+            interval,
+            expirations: 0,
+        };
+        self.wq.notify_all(false);
+        self.poll_set.wake();
+        old
+    }
+
+    /// Take the expirations that have not been read yet, and get the time
+    /// until the next one if the timer is armed.
+    fn take_expirations(&self) -> (u64, Option<Duration>) {
+        let now = self.now();
+        let mut state = self.state.lock();
+        state.update(now);
+        let left = state.deadline.map(|deadline| deadline - now);
+        (core::mem::take(&mut state.expirations), left)
+    }
+}
+
+impl FileLike for TimerFd {
+    fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
+        if buf.len() < size_of::<u64>() {
+            return Err(LinuxError::EINVAL);
+        }
+
+        loop {
+            let (expirations, left) = self.take_expirations();
+            if expirations > 0 {
+                buf[..size_of::<u64>()].copy_from_slice(&expirations.to_ne_bytes());
+                return Ok(size_of::<u64>());
+            }
+
+            if self.nonblocking() {
+                return Err(LinuxError::EAGAIN);
+            }
+            if unblocked_signal_pending() {
+                return Err(LinuxError::EINTR);
+            }
+            // `set_time` wakes the readers up, so that they pick up the new
+            // setting.
+            let timeout = left.map_or(SIGNAL_CHECK_INTERVAL, |left| {
+                left.min(SIGNAL_CHECK_INTERVAL)
+            });
+            self.wq.wait_timeout(timeout);
+        }
+    }
+
+    fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+        Err(LinuxError::EINVAL)
+    }
+
+    fn stat(&self) -> LinuxResult<Kstat> {
+        Ok(Kstat {
+            mode: 0o600u32, // rw-------
+            ..Default::default()
+        })
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+
+    fn poll(&self) -> LinuxResult<PollState> {
+        let now = self.now();
+        let mut state = self.state.lock();
+        state.update(now);
+        Ok(PollState {
+            readable: state.expirations > 0,
+            writable: false,
+        })
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult {
+        self.nonblocking.store(nonblocking, Ordering::Release);
+        Ok(())
+    }
+
+    fn nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::Acquire)
+    }
+
+    fn register_waiter(&self, waiter: &PollWaiter) -> bool {
+        self.poll_set.register(waiter);
+        // Nothing happens when the timer expires, so the poller wakes up on
+        // its own by then.
+        if let Some(deadline) = self.state.lock().deadline {
+            waiter.wake_after(deadline.saturating_sub(self.now()));
+        }
+        true
+    }
+}
@@ -0,0 +1,125 @@
+//! Wakeups for tasks blocked in `poll`, `select` and `epoll_wait`.
+
+use core::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::Duration,
+};
+
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use axerrno::{LinuxError, LinuxResult};
+use axhal::time::wall_time;
+use axtask::WaitQueue;
+use spin::Mutex;
+
+use crate::signal::{SIGNAL_CHECK_INTERVAL, unblocked_signal_pending};
+
+/// The state shared by a [`PollWaiter`] and the [`PollSet`]s it is in.
+struct PollWaker {
+    woken: AtomicBool,
+    /// The wall time in nanoseconds by which a file becomes ready on its
+    /// own, or `u64::MAX`.
+    wake_by: AtomicU64,
+    wq: WaitQueue,
+}
+
+/// Blocks the current task until one of the files it is registered with may
+/// have become ready.
+///
+/// It must be registered before polling the files, so that it notices the
+/// changes made while they are polled.
+pub struct PollWaiter(Arc<PollWaker>);
+
+impl Default for PollWaiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PollWaiter {
+    pub fn new() -> Self {
+        Self(Arc::new(PollWaker {
+            woken: AtomicBool::new(false),
+            wake_by: AtomicU64::new(u64::MAX),
+            wq: WaitQueue::new(),
+        }))
+    }
+
+    /// Wake up after `timeout` at the latest, for a file that becomes ready
+    /// at a known time rather than when something happens to it.
+    pub fn wake_after(&self, timeout: Duration) {
+        let wake_by = (wall_time() + timeout).as_nanos() as u64;
+        self.0.wake_by.fetch_min(wake_by, Ordering::AcqRel);
+    }
+
+    /// Wait until a file may have become ready, or for at most `timeout`.
+    ///
+    /// Fails with `EINTR` once a signal that is not blocked is pending.
+    /// Sending a signal does not wake the waiter, so it checks for one every
+    /// [`SIGNAL_CHECK_INTERVAL`].
+    pub fn wait(&self, timeout: Option<Duration>) -> LinuxResult {
+        let woken = || self.0.woken.load(Ordering::Acquire);
+        let timeout = match self.0.wake_by.load(Ordering::Acquire) {
+            u64::MAX => timeout,
+            wake_by => {
+                let left = Duration::from_nanos(wake_by).saturating_sub(wall_time());
+                Some(timeout.map_or(left, |timeout| timeout.min(left)))
+            }
+        };
+        let deadline = timeout.map(|timeout| wall_time() + timeout);
+        loop {
+            if woken() {
+                return Ok(());
+            }
+            if unblocked_signal_pending() {
+                return Err(LinuxError::EINTR);
+            }
+            let left = deadline.map(|deadline| deadline.saturating_sub(wall_time()));
+            if left == Some(Duration::ZERO) {
+                return Ok(());
+            }
+            let timeout = left.map_or(SIGNAL_CHECK_INTERVAL, |left| {
+                left.min(SIGNAL_CHECK_INTERVAL)
+            });
+            self.0.wq.wait_timeout_until(timeout, woken);
+        }
+    }
+}
+
+/// The tasks waiting for one file to become ready.
+///
+/// A waiter is woken once, and is then forgotten, as the pollers register
+/// again each time they poll.
+pub struct PollSet(Mutex<Vec<Weak<PollWaker>>>);
+
+impl Default for PollSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PollSet {
+    pub const fn new() -> Self {
+        Self(Mutex::new(Vec::new()))
+    }
+
+    /// Add `waiter` to the tasks woken by [`wake`](Self::wake).
+    pub fn register(&self, waiter: &PollWaiter) {
+        let mut waiters = self.0.lock();
+        // The waiters of polls that are over are dropped.
+        waiters.retain(|waker| waker.strong_count() > 0);
+        waiters.push(Arc::downgrade(&waiter.0));
+    }
+
+    /// Wake up the tasks polling the file, after its readiness may have
+    /// changed.
+    pub fn wake(&self) {
+        let waiters = core::mem::take(&mut *self.0.lock());
+        for waker in waiters.iter().filter_map(Weak::upgrade) {
+            waker.woken.store(true, Ordering::Release);
+            waker.wq.notify_all(false);
+        }
+    }
+}
@@ -3,13 +3,18 @@ use core::{
     mem::offset_of,
 };
 
-use alloc::ffi::CString;
+use alloc::{ffi::CString, format, string::String};
 use axerrno::{LinuxError, LinuxResult};
 use axfs::fops::DirEntry;
 use axtask::{TaskExtRef, current};
-use linux_raw_sys::general::{
-    AT_FDCWD, AT_REMOVEDIR, DT_BLK, DT_CHR, DT_DIR, DT_FIFO, DT_LNK, DT_REG, DT_SOCK, DT_UNKNOWN,
-    linux_dirent64, termios,
+use linux_raw_sys::{
+    general::{
+        AT_EMPTY_PATH, AT_FDCWD, AT_REMOVEDIR, AT_SYMLINK_NOFOLLOW, DT_BLK, DT_CHR, DT_DIR,
+        DT_FIFO, DT_LNK, DT_REG, DT_SOCK, DT_UNKNOWN, FS_APPEND_FL, FS_IMMUTABLE_FL, IN_CREATE,
+        IN_DELETE, IN_ISDIR, S_IFBLK, S_IFCHR, S_IFIFO, S_IFMT, S_IFREG, S_IFSOCK, linux_dirent64,
+        termios,
+    },
+    ioctl::{FS_IOC_GETFLAGS, FS_IOC_SETFLAGS},
 };
 
 // Define ioctl constants directly since they're behind a feature flag
@@ -19,8 +24,13 @@ const TCGETS: u32 = 21505;
 const TCSETS: u32 = 21506;
 
 use crate::{
-    file::{Directory, FileLike},
-    path::{HARDLINK_MANAGER, handle_file_path},
+    file::{
+        Directory, FIFOS, File, FileLike, INODE_FLAGS, INODES, Inode, SUPPORTED_INODE_FLAGS,
+        get_file_like, inode_number, notify_fs_event,
+    },
+    path::{
+        FilePath, HARDLINK_MANAGER, handle_file_path, handle_file_path_resolve, handle_link_path,
+    },
     ptr::{UserConstPtr, UserPtr, nullable},
 };
 
@@ -78,6 +88,27 @@ pub fn sys_ioctl(fd: i32, op: usize, argp: UserPtr<c_void>) -> LinuxResult<isize
             debug!("TCSETS called - ignoring for now");
             Ok(0)
         }
+        FS_IOC_GETFLAGS => {
+            let path = inode_path(fd)?;
+            *UserPtr::<u32>::from(argp.address().as_usize()).get_as_mut()? = INODE_FLAGS.get(&path);
+            Ok(0)
+        }
+        FS_IOC_SETFLAGS => {
+            let path = inode_path(fd)?;
+            let flags = *UserConstPtr::<u32>::from(argp.address().as_usize()).get_as_ref()?;
+            if flags & !SUPPORTED_INODE_FLAGS != 0 {
+                return Err(LinuxError::EOPNOTSUPP);
+            }
+            // Only root may change the immutable and append-only flags.
+            let changed = flags ^ INODE_FLAGS.get(&path);
+            if changed & (FS_IMMUTABLE_FL | FS_APPEND_FL) != 0
+                && !current().task_ext().process_data().cred.read().is_root()
+            {
+                return Err(LinuxError::EPERM);
+            }
+            INODE_FLAGS.set(&path, flags);
+            Ok(0)
+        }
         _ => {
             warn!("Unimplemented ioctl operation: 0x{:x}", op);
             Ok(0)
@@ -85,6 +116,18 @@ pub fn sys_ioctl(fd: i32, op: usize, argp: UserPtr<c_void>) -> LinuxResult<isize
     }
 }
 
+/// Get the path of the regular file or directory referred to by `fd`.
+fn inode_path(fd: i32) -> LinuxResult<String> {
+    let file = get_file_like(fd)?.into_any();
+    if let Some(file) = file.downcast_ref::<File>() {
+        Ok(file.path().into())
+    } else if let Some(dir) = file.downcast_ref::<Directory>() {
+        Ok(dir.path().into())
+    } else {
+        Err(LinuxError::ENOTTY)
+    }
+}
+
 pub fn sys_chdir(path: UserConstPtr<c_char>) -> LinuxResult<isize> {
     let path = path.get_as_str()?;
     debug!("sys_chdir <= {:?}", path);
@@ -100,16 +143,63 @@ pub fn sys_mkdirat(dirfd: i32, path: UserConstPtr<c_char>, mode: u32) -> LinuxRe
         dirfd, path, mode
     );
 
-    if mode != 0 {
-        warn!("directory mode not supported.");
-    }
-
     let path = handle_file_path(dirfd, path)?;
     axfs::api::create_dir(path.as_str())?;
+    let cred = *current().task_ext().process_data().cred.read();
+    INODES.get(path.as_str()).init(cred.euid, cred.egid, mode);
+    notify_fs_event(path.as_str(), IN_CREATE | IN_ISDIR);
+
+    Ok(0)
+}
+
+/// Create a file system node.
+///
+/// Regular files are created empty, and FIFOs are backed by a [`Pipe`] that
+/// is shared by everyone who opens the path. Device and socket nodes cannot
+/// be created.
+///
+/// [`Pipe`]: crate::file::Pipe
+pub fn sys_mknodat(
+    dirfd: i32,
+    path: UserConstPtr<c_char>,
+    mode: u32,
+    dev: u64,
+) -> LinuxResult<isize> {
+    let path = path.get_as_str()?;
+    debug!(
+        "sys_mknodat <= dirfd: {}, path: {}, mode: {:#o}, dev: {}",
+        dirfd, path, mode, dev
+    );
 
+    let fifo = match mode & S_IFMT {
+        0 | S_IFREG => false,
+        S_IFIFO => true,
+        S_IFCHR | S_IFBLK | S_IFSOCK => return Err(LinuxError::EPERM),
+        _ => return Err(LinuxError::EINVAL),
+    };
+
+    let path = handle_file_path(dirfd, path)?;
+    if path.exists() {
+        return Err(LinuxError::EEXIST);
+    }
+    let mut opts = axfs::fops::OpenOptions::new();
+    opts.write(true);
+    opts.create(true);
+    axfs::fops::File::open(path.as_str(), &opts)?;
+    let cred = *current().task_ext().process_data().cred.read();
+    INODES.get(path.as_str()).init(cred.euid, cred.egid, mode);
+    if fifo {
+        FIFOS.create(path.as_str(), mode);
+    }
+    notify_fs_event(path.as_str(), IN_CREATE);
     Ok(0)
 }
 
+#[cfg(target_arch = "x86_64")]
+pub fn sys_mknod(path: UserConstPtr<c_char>, mode: u32, dev: u64) -> LinuxResult<isize> {
+    sys_mknodat(AT_FDCWD, path, mode, dev)
+}
+
 #[allow(dead_code)]
 #[repr(u8)]
 #[derive(Debug, Clone, Copy)]
@@ -154,7 +244,7 @@ impl<'a> DirBuffer<'a> {
         self.buf.len().saturating_sub(self.offset)
     }
 
-    fn write_entry(&mut self, d_type: FileType, name: &[u8]) -> bool {
+    fn write_entry(&mut self, d_ino: u64, d_type: FileType, name: &[u8]) -> bool {
         const NAME_OFFSET: usize = offset_of!(linux_dirent64, d_name);
 
         let len = NAME_OFFSET + name.len() + 1;
@@ -167,8 +257,7 @@ impl<'a> DirBuffer<'a> {
         unsafe {
             let entry_ptr = self.buf.as_mut_ptr().add(self.offset);
             entry_ptr.cast::<linux_dirent64>().write(linux_dirent64 {
-                // FIXME: real inode number
-                d_ino: 1,
+                d_ino,
                 d_off: 0,
                 d_reclen: len as _,
                 d_type: d_type as _,
@@ -197,10 +286,23 @@ pub fn sys_getdents64(fd: i32, buf: UserPtr<u8>, len: usize) -> LinuxResult<isiz
     let mut buffer = DirBuffer::new(buf);
 
     let dir = Directory::from_fd(fd)?;
+    // The same inode number as `stat` reports for the entry.
+    let entry_ino = |name: &[u8]| {
+        let path = format!(
+            "{}/{}",
+            dir.path().trim_end_matches('/'),
+            String::from_utf8_lossy(name)
+        );
+        FilePath::new(&path).map_or_else(|_| inode_number(&path), |path| inode_number(&path))
+    };
 
     let mut last_dirent = dir.last_dirent();
     if let Some(ent) = last_dirent.take()
-        && !buffer.write_entry(ent.entry_type().into(), ent.name_as_bytes())
+        && !buffer.write_entry(
+            entry_ino(ent.name_as_bytes()),
+            ent.entry_type().into(),
+            ent.name_as_bytes(),
+        )
     {
         *last_dirent = Some(ent);
         return Err(LinuxError::EINVAL);
@@ -215,7 +317,11 @@ pub fn sys_getdents64(fd: i32, buf: UserPtr<u8>, len: usize) -> LinuxResult<isiz
         }
 
         let [ent] = dirents;
-        if !buffer.write_entry(ent.entry_type().into(), ent.name_as_bytes()) {
+        if !buffer.write_entry(
+            entry_ino(ent.name_as_bytes()),
+            ent.entry_type().into(),
+            ent.name_as_bytes(),
+        ) {
             *last_dirent = Some(ent);
             break;
         }
@@ -253,9 +359,11 @@ pub fn sys_linkat(
     // handle old path
     let old_path = handle_file_path(old_dirfd, old_path)?;
     // handle new path
-    let new_path = handle_file_path(new_dirfd, new_path)?;
+    let new_path = handle_link_path(new_dirfd, new_path)?;
 
+    INODE_FLAGS.check_modify(old_path.as_str(), false)?;
     HARDLINK_MANAGER.create_link(&new_path, &old_path)?;
+    notify_fs_event(new_path.as_str(), IN_CREATE);
 
     Ok(0)
 }
@@ -279,20 +387,35 @@ pub fn sys_unlinkat(dirfd: c_int, path: UserConstPtr<c_char>, flags: u32) -> Lin
         dirfd, path, flags
     );
 
+    if flags & !AT_REMOVEDIR != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let link_path = handle_link_path(dirfd, path)?;
     let path = handle_file_path(dirfd, path)?;
+    INODE_FLAGS.check_modify(path.as_str(), false)?;
 
-    if flags == AT_REMOVEDIR {
+    let metadata = axfs::api::metadata(path.as_str())?;
+    if flags & AT_REMOVEDIR != 0 {
+        if !metadata.is_dir() {
+            return Err(LinuxError::ENOTDIR);
+        }
+        let not_empty = axfs::api::read_dir(path.as_str())?.any(|entry| {
+            entry.is_ok_and(|entry| !matches!(entry.file_name().as_str(), "." | ".."))
+        });
+        if not_empty {
+            return Err(LinuxError::ENOTEMPTY);
+        }
         axfs::api::remove_dir(path.as_str())?;
+        INODES.remove(path.as_str());
+        notify_fs_event(path.as_str(), IN_DELETE | IN_ISDIR);
+    } else if metadata.is_dir() {
+        return Err(LinuxError::EISDIR);
     } else {
-        let metadata = axfs::api::metadata(path.as_str())?;
-        if metadata.is_dir() {
-            return Err(LinuxError::EISDIR);
-        } else {
-            debug!("unlink file: {:?}", path);
-            HARDLINK_MANAGER
-                .remove_link(&path)
-                .ok_or(LinuxError::ENOENT)?;
-        }
+        debug!("unlink file: {:?}", link_path);
+        HARDLINK_MANAGER
+            .remove_link(&link_path)
+            .ok_or(LinuxError::ENOENT)?;
+        notify_fs_event(link_path.as_str(), IN_DELETE);
     }
     Ok(0)
 }
@@ -301,6 +424,91 @@ pub fn sys_unlink(path: UserConstPtr<c_char>) -> LinuxResult<isize> {
     sys_unlinkat(AT_FDCWD, path, 0)
 }
 
+/// Change the owner and group of a file, leaving the IDs that are `u32::MAX`
+/// (`-1`) unchanged.
+///
+/// Only root may give a file away. The owner of a file may change its group to
+/// their own effective group.
+fn chown_inode(inode: &Inode, path: &str, is_dir: bool, uid: u32, gid: u32) -> LinuxResult<isize> {
+    let (old_uid, old_gid) = inode.owner();
+    let uid = if uid == u32::MAX { old_uid } else { uid };
+    let gid = if gid == u32::MAX { old_gid } else { gid };
+
+    let cred = *current().task_ext().process_data().cred.read();
+    if !cred.is_root()
+        && (uid != old_uid || (gid != old_gid && (cred.euid != old_uid || gid != cred.egid)))
+    {
+        return Err(LinuxError::EPERM);
+    }
+    INODE_FLAGS.check_modify(path, false)?;
+
+    inode.chown(uid, gid, is_dir);
+    Ok(0)
+}
+
+/// Change the owner and group of the file at `path`.
+///
+/// With `AT_SYMLINK_NOFOLLOW`, a symbolic link itself is changed instead of
+/// the file it points to. With `AT_EMPTY_PATH` and an empty `path`, the file
+/// referred to by `dirfd` is changed.
+pub fn sys_fchownat(
+    dirfd: c_int,
+    path: UserConstPtr<c_char>,
+    uid: u32,
+    gid: u32,
+    flags: u32,
+) -> LinuxResult<isize> {
+    let path = nullable!(path.get_as_str())?.unwrap_or_default();
+    debug!(
+        "sys_fchownat <= dirfd: {}, path: {}, uid: {}, gid: {}, flags: {}",
+        dirfd, path, uid as i32, gid as i32, flags
+    );
+
+    if flags & !(AT_SYMLINK_NOFOLLOW | AT_EMPTY_PATH) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    if path.is_empty() {
+        if flags & AT_EMPTY_PATH == 0 {
+            return Err(LinuxError::ENOENT);
+        }
+        return sys_fchown(dirfd, uid, gid);
+    }
+
+    let path = if flags & AT_SYMLINK_NOFOLLOW != 0 {
+        handle_file_path(dirfd, path)?
+    } else {
+        handle_file_path_resolve(dirfd, path, 0)?
+    };
+    let is_dir = axfs::api::symlink_metadata(path.as_str())?.is_dir();
+    chown_inode(&INODES.get(path.as_str()), path.as_str(), is_dir, uid, gid)
+}
+
+pub fn sys_chown(path: UserConstPtr<c_char>, uid: u32, gid: u32) -> LinuxResult<isize> {
+    sys_fchownat(AT_FDCWD, path, uid, gid, 0)
+}
+
+pub fn sys_lchown(path: UserConstPtr<c_char>, uid: u32, gid: u32) -> LinuxResult<isize> {
+    sys_fchownat(AT_FDCWD, path, uid, gid, AT_SYMLINK_NOFOLLOW)
+}
+
+/// Change the owner and group of the file referred to by `fd`.
+pub fn sys_fchown(fd: c_int, uid: u32, gid: u32) -> LinuxResult<isize> {
+    debug!(
+        "sys_fchown <= fd: {}, uid: {}, gid: {}",
+        fd, uid as i32, gid as i32
+    );
+    let file = get_file_like(fd)?.into_any();
+    if let Some(file) = file.downcast_ref::<File>() {
+        chown_inode(file.inode(), file.path(), false, uid, gid)
+    } else if let Some(dir) = file.downcast_ref::<Directory>() {
+        chown_inode(dir.inode(), dir.path(), true, uid, gid)
+    } else {
+        // Pipes, sockets and other anonymous files have nowhere to keep an
+        // owner.
+        Ok(0)
+    }
+}
+
 pub fn sys_getcwd(buf: UserPtr<u8>, size: usize) -> LinuxResult<isize> {
     let buf = nullable!(buf.get_as_mut_slice(size))?;
 
@@ -334,6 +542,7 @@ pub fn sys_symlinkat(
 
     let new_path = handle_file_path(new_dirfd, new_path)?;
     axfs::api::create_symlink(target, &new_path)?;
+    notify_fs_event(new_path.as_str(), IN_CREATE);
 
     Ok(0)
 }
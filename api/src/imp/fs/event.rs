@@ -0,0 +1,25 @@
+use axerrno::{LinuxError, LinuxResult};
+use linux_raw_sys::general::{EFD_CLOEXEC, EFD_NONBLOCK, EFD_SEMAPHORE};
+
+use crate::file::{EventFd, FileLike};
+
+/// Create a file descriptor for event notification.
+///
+/// In `EFD_SEMAPHORE` mode every read returns 1 and decrements the counter by
+/// 1, otherwise a read returns the whole counter and resets it to zero.
+pub fn sys_eventfd2(initval: u32, flags: u32) -> LinuxResult<isize> {
+    debug!("sys_eventfd2 <= initval: {}, flags: {:#x}", initval, flags);
+    if flags & !(EFD_CLOEXEC | EFD_NONBLOCK | EFD_SEMAPHORE) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let event = EventFd::new(initval as _, flags & EFD_SEMAPHORE != 0);
+    event.set_nonblocking(flags & EFD_NONBLOCK != 0)?;
+    let fd = event.add_to_fd_table(flags & EFD_CLOEXEC != 0)?;
+    Ok(fd as _)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn sys_eventfd(initval: u32) -> LinuxResult<isize> {
+    sys_eventfd2(initval, 0)
+}
@@ -3,23 +3,35 @@ use core::{
     panic,
 };
 
-use alloc::string::ToString;
+use alloc::{string::ToString, sync::Arc};
 use axerrno::{AxError, LinuxError, LinuxResult};
 use axfs::fops::OpenOptions;
+use axhal::mem::PAGE_SIZE_4K;
+use axtask::{TaskExtRef, current};
 use linux_raw_sys::general::{
-    __kernel_mode_t, AT_FDCWD, F_DUPFD, F_DUPFD_CLOEXEC, F_SETFL, O_APPEND, O_CREAT, O_DIRECTORY,
-    O_NONBLOCK, O_PATH, O_RDONLY, O_TRUNC, O_WRONLY,
+    __kernel_mode_t, AT_FDCWD, F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD, F_GETFL, F_GETPIPE_SZ, F_SETFD,
+    F_SETFL, F_SETPIPE_SZ, FD_CLOEXEC, IN_CREATE, LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN, O_APPEND,
+    O_CLOEXEC, O_CREAT, O_DIRECTORY, O_NONBLOCK, O_PATH, O_RDONLY, O_TRUNC, O_WRONLY, R_OK,
+    RESOLVE_BENEATH, RESOLVE_CACHED, RESOLVE_IN_ROOT, RESOLVE_NO_MAGICLINKS, RESOLVE_NO_SYMLINKS,
+    RESOLVE_NO_XDEV, W_OK, open_how,
 };
 
+use super::stat::check_path_access;
 use crate::{
-    file::{Directory, FD_TABLE, File, FileLike, add_file_like, close_file_like, get_file_like},
-    path::handle_file_path,
+    file::{
+        Directory, FD_TABLE, FIFOS, FILE_LOCKS, File, FileDescriptor, FileLike, INODE_FLAGS, Pipe,
+        add_file_like, close_file_like, fd_limit, get_fd_cloexec, get_file_like, notify_fs_event,
+        set_fd_cloexec,
+    },
+    path::{handle_file_path, handle_file_path_resolve},
     ptr::UserConstPtr,
 };
 
 const O_EXEC: u32 = O_PATH;
 
 /// Convert open flags to [`OpenOptions`].
+///
+/// `O_APPEND` is left to [`File`], so that `F_SETFL` can change it.
 fn flags_to_options(flags: c_int, _mode: __kernel_mode_t) -> OpenOptions {
     let flags = flags as u32;
     let mut options = OpenOptions::new();
@@ -31,9 +43,6 @@ fn flags_to_options(flags: c_int, _mode: __kernel_mode_t) -> OpenOptions {
             options.write(true);
         }
     };
-    if flags & O_APPEND != 0 {
-        options.append(true);
-    }
     if flags & O_TRUNC != 0 {
         options.truncate(true);
     }
@@ -63,6 +72,10 @@ pub fn sys_openat(
     mode: __kernel_mode_t,
 ) -> LinuxResult<isize> {
     let path = path.get_as_str()?;
+    open_at(dirfd, path, flags, mode)
+}
+
+fn open_at(dirfd: c_int, path: &str, flags: i32, mode: __kernel_mode_t) -> LinuxResult<isize> {
     let opts = flags_to_options(flags, mode);
     debug!("sys_openat <= {} {} {:?}", dirfd, path, opts);
 
@@ -72,15 +85,48 @@ pub fn sys_openat(
         Some(Directory::from_fd(dirfd)?)
     };
     let real_path = handle_file_path(dirfd, path)?;
+    let cred = *current().task_ext().process_data().cred.read();
+    // Root may read and write any file. Files that do not exist yet are left
+    // to the open, which may create them.
+    if !cred.is_root() && flags as u32 & O_PATH == 0 {
+        let mut access = 0;
+        if flags as u32 & 0b11 != O_WRONLY {
+            access |= R_OK;
+        }
+        if flags as u32 & 0b11 != O_RDONLY || flags as u32 & O_TRUNC != 0 {
+            access |= W_OK;
+        }
+        if check_path_access(real_path.as_str(), access) == Err(LinuxError::EACCES) {
+            return Err(LinuxError::EACCES);
+        }
+    }
+    let cloexec = flags as u32 & O_CLOEXEC != 0;
+    let readable = flags as u32 & 0b11 != O_WRONLY;
+    let writable = flags as u32 & 0b11 != O_RDONLY;
+    let nonblocking = flags as u32 & O_NONBLOCK != 0;
+    if let Some(pipe) = FIFOS.open(real_path.as_str(), readable, writable, nonblocking) {
+        let fd = pipe?.add_to_fd_table(cloexec)?;
+        return Ok(fd as _);
+    }
+    if flags as u32 & 0b11 != O_RDONLY || flags as u32 & O_TRUNC != 0 {
+        let append = flags as u32 & O_APPEND != 0 && flags as u32 & O_TRUNC == 0;
+        INODE_FLAGS.check_modify(real_path.as_str(), append)?;
+    }
 
     if !opts.has_directory() {
+        let created = flags as u32 & O_CREAT != 0 && !real_path.exists();
         match dir.as_ref().map_or_else(
             || axfs::fops::File::open(path, &opts),
             |dir| dir.inner().open_file_at(path, &opts),
         ) {
             Err(AxError::IsADirectory) => {}
             r => {
-                let fd = File::new(r?, real_path.to_string()).add_to_fd_table()?;
+                let file = File::new(r?, real_path.to_string(), flags as u32);
+                if created {
+                    file.inode().init(cred.euid, cred.egid, mode as _);
+                    notify_fs_event(real_path.as_str(), IN_CREATE);
+                }
+                let fd = file.add_to_fd_table(cloexec)?;
                 return Ok(fd as _);
             }
         }
@@ -93,10 +139,61 @@ pub fn sys_openat(
         )?,
         real_path.to_string(),
     )
-    .add_to_fd_table()?;
+    .add_to_fd_table(cloexec)?;
     Ok(fd as _)
 }
 
+/// Open a file like `openat`, with the extensible `open_how` argument.
+///
+/// The `resolve` field restricts how the path is resolved, see
+/// [`handle_file_path_resolve`].
+pub fn sys_openat2(
+    dirfd: c_int,
+    path: UserConstPtr<c_char>,
+    how: UserConstPtr<u8>,
+    size: usize,
+) -> LinuxResult<isize> {
+    const SUPPORTED_RESOLVE: u32 = RESOLVE_NO_XDEV
+        | RESOLVE_NO_MAGICLINKS
+        | RESOLVE_NO_SYMLINKS
+        | RESOLVE_BENEATH
+        | RESOLVE_IN_ROOT
+        | RESOLVE_CACHED;
+
+    let path = path.get_as_str()?;
+    if size < size_of::<open_how>() {
+        return Err(LinuxError::EINVAL);
+    }
+    if size > PAGE_SIZE_4K {
+        return Err(LinuxError::E2BIG);
+    }
+    let bytes = how.get_as_slice(size)?;
+    let (known, extra) = bytes.split_at(size_of::<open_how>());
+    // Fields unknown to us must be zero.
+    if extra.iter().any(|&b| b != 0) {
+        return Err(LinuxError::E2BIG);
+    }
+    // SAFETY: `known` holds exactly one `open_how`, which is plain old data.
+    let how = unsafe { (known.as_ptr() as *const open_how).read_unaligned() };
+    debug!(
+        "sys_openat2 <= {} {} flags: {:#o}, mode: {:#o}, resolve: {:#x}",
+        dirfd, path, how.flags, how.mode, how.resolve
+    );
+
+    let flags = u32::try_from(how.flags).map_err(|_| LinuxError::EINVAL)?;
+    let resolve = u32::try_from(how.resolve).map_err(|_| LinuxError::EINVAL)?;
+    if how.mode & !0o7777 != 0
+        || (how.mode != 0 && flags & O_CREAT == 0)
+        || resolve & !SUPPORTED_RESOLVE != 0
+        || resolve & (RESOLVE_BENEATH | RESOLVE_IN_ROOT) == RESOLVE_BENEATH | RESOLVE_IN_ROOT
+    {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let path = handle_file_path_resolve(dirfd, path, resolve)?;
+    open_at(AT_FDCWD, path.as_str(), flags as _, how.mode as _)
+}
+
 /// Open a file by `filename` and insert it into the file descriptor table.
 ///
 /// Return its index in the file table (`fd`). Return `EMFILE` if it already
@@ -115,54 +212,124 @@ pub fn sys_close(fd: c_int) -> LinuxResult<isize> {
     Ok(0)
 }
 
-fn dup_fd(old_fd: c_int) -> LinuxResult<isize> {
+fn dup_fd(old_fd: c_int, cloexec: bool) -> LinuxResult<isize> {
     let f = get_file_like(old_fd)?;
-    let new_fd = add_file_like(f)?;
+    let new_fd = add_file_like(f, cloexec)?;
     Ok(new_fd as _)
 }
 
 pub fn sys_dup(old_fd: c_int) -> LinuxResult<isize> {
     debug!("sys_dup <= {}", old_fd);
-    dup_fd(old_fd)
+    dup_fd(old_fd, false)
 }
 
 pub fn sys_dup2(old_fd: c_int, new_fd: c_int) -> LinuxResult<isize> {
     debug!("sys_dup2 <= old_fd: {}, new_fd: {}", old_fd, new_fd);
+    if old_fd == new_fd {
+        get_file_like(old_fd)?;
+        return Ok(new_fd as _);
+    }
+    dup_fd_to(old_fd, new_fd, false)
+}
+
+/// Like `dup2`, but the close-on-exec flag of `new_fd` is set by `O_CLOEXEC`
+/// and `old_fd` and `new_fd` must differ.
+pub fn sys_dup3(old_fd: c_int, new_fd: c_int, flags: c_int) -> LinuxResult<isize> {
+    debug!(
+        "sys_dup3 <= old_fd: {}, new_fd: {}, flags: {:#x}",
+        old_fd, new_fd, flags
+    );
+    if flags as u32 & !O_CLOEXEC != 0 || old_fd == new_fd {
+        return Err(LinuxError::EINVAL);
+    }
+    dup_fd_to(old_fd, new_fd, flags as u32 & O_CLOEXEC != 0)
+}
+
+/// Duplicate `old_fd` onto `new_fd`, closing `new_fd` first if it is open.
+fn dup_fd_to(old_fd: c_int, new_fd: c_int, cloexec: bool) -> LinuxResult<isize> {
+    if new_fd < 0 || new_fd as usize >= fd_limit() {
+        return Err(LinuxError::EBADF);
+    }
     let mut fd_table = FD_TABLE.write();
     let f = fd_table
         .get(old_fd as _)
-        .cloned()
+        .map(|fd| fd.file.clone())
         .ok_or(LinuxError::EBADF)?;
 
-    if old_fd != new_fd {
-        fd_table.remove(new_fd as _);
-        fd_table
-            .add_at(new_fd as _, f)
-            .unwrap_or_else(|_| panic!("new_fd should be valid"));
-    }
+    fd_table.remove(new_fd as _);
+    fd_table
+        .add_at(new_fd as _, FileDescriptor { file: f, cloexec })
+        .unwrap_or_else(|_| panic!("new_fd should be valid"));
 
     Ok(new_fd as _)
 }
 
+/// Manipulate the file descriptor `fd`.
+///
+/// `F_GETFD` and `F_SETFD` read and change the close-on-exec flag of the
+/// descriptor alone, while `F_GETFL` and `F_SETFL` act on the open file shared
+/// with its duplicates.
 pub fn sys_fcntl(fd: c_int, cmd: c_int, arg: usize) -> LinuxResult<isize> {
     debug!("sys_fcntl <= fd: {} cmd: {} arg: {}", fd, cmd, arg);
 
     match cmd as u32 {
-        F_DUPFD => dup_fd(fd),
-        F_DUPFD_CLOEXEC => {
-            warn!("sys_fcntl: treat F_DUPFD_CLOEXEC as F_DUPFD");
-            dup_fd(fd)
+        F_DUPFD => dup_fd(fd, false),
+        F_DUPFD_CLOEXEC => dup_fd(fd, true),
+        F_GETFD => Ok(if get_fd_cloexec(fd)? {
+            FD_CLOEXEC as _
+        } else {
+            0
+        }),
+        F_SETFD => {
+            set_fd_cloexec(fd, arg & FD_CLOEXEC as usize != 0)?;
+            Ok(0)
         }
+        F_GETFL => Ok(get_file_like(fd)?.status_flags() as _),
         F_SETFL => {
             if fd == 0 || fd == 1 || fd == 2 {
                 return Ok(0);
             }
-            get_file_like(fd)?.set_nonblocking(arg & (O_NONBLOCK as usize) > 0)?;
+            get_file_like(fd)?.set_status_flags(arg as u32)?;
             Ok(0)
         }
+        F_GETPIPE_SZ => Ok(Pipe::from_fd(fd).map_err(|_| LinuxError::EBADF)?.capacity() as _),
+        F_SETPIPE_SZ => {
+            let pipe = Pipe::from_fd(fd).map_err(|_| LinuxError::EBADF)?;
+            let privileged = current().task_ext().process_data().cred.read().is_root();
+            Ok(pipe.set_capacity(arg, privileged)? as _)
+        }
         _ => {
             warn!("unsupported fcntl parameters: cmd: {}", cmd);
             Ok(0)
         }
     }
 }
+
+/// Apply or remove an advisory lock on the open file indicated by `fd`.
+///
+/// Only regular files and directories can be locked.
+pub fn sys_flock(fd: c_int, operation: c_int) -> LinuxResult<isize> {
+    debug!("sys_flock <= fd: {}, operation: {:#x}", fd, operation);
+
+    let file = get_file_like(fd)?;
+    // Locks belong to the open file, shared by duplicated descriptors.
+    let owner = Arc::as_ptr(&file) as *const () as usize;
+    let any = file.into_any();
+    let ino = if let Some(file) = any.downcast_ref::<File>() {
+        file.inode().ino()
+    } else if let Some(dir) = any.downcast_ref::<Directory>() {
+        dir.inode().ino()
+    } else {
+        return Err(LinuxError::EINVAL);
+    };
+
+    let operation = operation as u32;
+    let nonblocking = operation & LOCK_NB != 0;
+    match operation & !LOCK_NB {
+        LOCK_SH => FILE_LOCKS.lock(ino, owner, false, nonblocking)?,
+        LOCK_EX => FILE_LOCKS.lock(ino, owner, true, nonblocking)?,
+        LOCK_UN => FILE_LOCKS.unlock(ino, owner),
+        _ => return Err(LinuxError::EINVAL),
+    }
+    Ok(0)
+}
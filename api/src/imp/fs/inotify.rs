@@ -0,0 +1,84 @@
+use core::ffi::{c_char, c_int};
+
+use axerrno::{LinuxError, LinuxResult};
+use linux_raw_sys::general::{
+    AT_FDCWD, IN_ALL_EVENTS, IN_CLOEXEC, IN_DONT_FOLLOW, IN_EXCL_UNLINK, IN_MASK_ADD,
+    IN_MASK_CREATE, IN_NONBLOCK, IN_ONESHOT, IN_ONLYDIR,
+};
+use starry_core::file::resolve_symlink_path;
+
+use crate::{
+    file::{FileLike, Inotify, add_file_like},
+    path::{FilePath, handle_file_path},
+    ptr::UserConstPtr,
+};
+
+/// Create an inotify instance.
+pub fn sys_inotify_init1(flags: u32) -> LinuxResult<isize> {
+    debug!("sys_inotify_init1 <= flags: {:#x}", flags);
+    if flags & !(IN_CLOEXEC | IN_NONBLOCK) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let inotify = Inotify::new();
+    inotify.set_nonblocking(flags & IN_NONBLOCK != 0)?;
+    let fd = add_file_like(inotify, flags & IN_CLOEXEC != 0)?;
+    Ok(fd as _)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn sys_inotify_init() -> LinuxResult<isize> {
+    sys_inotify_init1(0)
+}
+
+/// Watch the file at `path` for the events in `mask` on the inotify instance
+/// `fd`, and return the watch descriptor.
+pub fn sys_inotify_add_watch(
+    fd: c_int,
+    path: UserConstPtr<c_char>,
+    mask: u32,
+) -> LinuxResult<isize> {
+    let path = path.get_as_str()?;
+    debug!(
+        "sys_inotify_add_watch <= fd: {}, path: {}, mask: {:#x}",
+        fd, path, mask
+    );
+
+    const VALID_FLAGS: u32 = IN_ALL_EVENTS
+        | IN_ONLYDIR
+        | IN_DONT_FOLLOW
+        | IN_EXCL_UNLINK
+        | IN_MASK_CREATE
+        | IN_MASK_ADD
+        | IN_ONESHOT;
+    if mask & IN_ALL_EVENTS == 0
+        || mask & !VALID_FLAGS != 0
+        || mask & IN_MASK_ADD != 0 && mask & IN_MASK_CREATE != 0
+    {
+        return Err(LinuxError::EINVAL);
+    }
+    let inotify = Inotify::from_fd(fd)?;
+
+    let path = handle_file_path(AT_FDCWD, path)?;
+    // A symbolic link is followed to the file it points to, unless
+    // `IN_DONT_FOLLOW` asks to watch the link itself.
+    let (path, metadata) = if mask & IN_DONT_FOLLOW != 0 {
+        let metadata = axfs::api::symlink_metadata(path.as_str())?;
+        (path, metadata)
+    } else {
+        let path = FilePath::new(resolve_symlink_path(path.as_str()))?;
+        let metadata = axfs::api::metadata(path.as_str())?;
+        (path, metadata)
+    };
+    if mask & IN_ONLYDIR != 0 && !metadata.is_dir() {
+        return Err(LinuxError::ENOTDIR);
+    }
+    Ok(inotify.add_watch(path.as_str(), mask)? as _)
+}
+
+/// Remove the watch `wd` from the inotify instance `fd`.
+pub fn sys_inotify_rm_watch(fd: c_int, wd: c_int) -> LinuxResult<isize> {
+    debug!("sys_inotify_rm_watch <= fd: {}, wd: {}", fd, wd);
+    Inotify::from_fd(fd)?.rm_watch(wd)?;
+    Ok(0)
+}
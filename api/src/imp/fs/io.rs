@@ -1,16 +1,85 @@
-use alloc::{sync::Arc, vec};
-use core::ffi::c_int;
+use alloc::{string::ToString, sync::Arc, vec, vec::Vec};
+use core::ffi::{c_char, c_int};
 
-use axerrno::{LinuxError, LinuxResult};
+use axerrno::{AxError, LinuxError, LinuxResult};
+use axfs::fops::OpenOptions;
 use axio::SeekFrom;
-use linux_raw_sys::general::{__kernel_off_t, iovec};
+use linux_raw_sys::general::{
+    __kernel_off_t, AT_FDCWD, FALLOC_FL_ALLOCATE_RANGE, FALLOC_FL_KEEP_SIZE, FALLOC_FL_PUNCH_HOLE,
+    O_WRONLY, POSIX_FADV_DONTNEED, POSIX_FADV_NOREUSE, POSIX_FADV_NORMAL, POSIX_FADV_RANDOM,
+    POSIX_FADV_SEQUENTIAL, POSIX_FADV_WILLNEED, SEEK_CUR, SEEK_DATA, SEEK_END, SEEK_HOLE, SEEK_SET,
+    iovec,
+};
 
 use crate::{
-    file::{FD_TABLE, File, FileLike, Pipe, get_file_like},
+    file::{Directory, FD_TABLE, File, FileLike, Pipe, get_file_like},
+    path::handle_file_path,
     ptr::{UserConstPtr, UserPtr},
 };
 
 const DEFAULT_BUFFER_SIZE: usize = 8192;
+/// The most bytes read ahead for one hint.
+const MAX_READAHEAD: usize = 2 * 1024 * 1024;
+
+/// Check that an I/O of `len` bytes at `offset` stays within the range of
+/// `off_t`.
+///
+/// Return `EINVAL` for offsets that are negative as an `off_t`, and `EOVERFLOW`
+/// if the end of the I/O cannot be represented.
+fn check_offset(offset: u64, len: usize) -> LinuxResult<u64> {
+    if offset > i64::MAX as u64 {
+        return Err(LinuxError::EINVAL);
+    }
+    if offset
+        .checked_add(len as u64)
+        .is_none_or(|end| end > i64::MAX as u64)
+    {
+        return Err(LinuxError::EOVERFLOW);
+    }
+    Ok(offset)
+}
+
+/// The total length of the buffers in `iovs`.
+///
+/// Return `EINVAL` if it does not fit in an `ssize_t`, like Linux, so the
+/// number of bytes transferred cannot overflow.
+fn iov_total_len(iovs: &[iovec]) -> LinuxResult<usize> {
+    iovs.iter()
+        .try_fold(0usize, |acc, iov| acc.checked_add(iov.iov_len as usize))
+        .filter(|&total| total <= isize::MAX as usize)
+        .ok_or(LinuxError::EINVAL)
+}
+
+/// The non-empty buffers in `iovs`.
+fn nonempty_iovs(iovs: &[iovec]) -> impl Iterator<Item = &iovec> {
+    iovs.iter().filter(|iov| iov.iov_len > 0)
+}
+
+/// Check the non-empty buffers in `iovs` to read into.
+///
+/// They are all checked before any I/O is done, so that an invalid one fails
+/// with `EFAULT` without a partial transfer, like Linux. The buffers may
+/// overlap, so each one is only borrowed while it is read into, by
+/// [`iov_buf_mut`].
+fn check_iov_bufs_mut(iovs: &[iovec]) -> LinuxResult {
+    for iov in nonempty_iovs(iovs) {
+        iov_buf_mut(iov)?;
+    }
+    Ok(())
+}
+
+/// The buffer of `iov` to read into.
+fn iov_buf_mut(iov: &iovec) -> LinuxResult<&'static mut [u8]> {
+    UserPtr::<u8>::from(iov.iov_base as usize).get_as_mut_slice(iov.iov_len as _)
+}
+
+/// The non-empty buffers in `iovs` to write from, checked like
+/// [`check_iov_bufs_mut`].
+fn iov_bufs(iovs: &[iovec]) -> LinuxResult<Vec<&'static [u8]>> {
+    nonempty_iovs(iovs)
+        .map(|iov| UserConstPtr::<u8>::from(iov.iov_base as usize).get_as_slice(iov.iov_len as _))
+        .collect()
+}
 
 /// Read data from the file indicated by `fd` at a specific offset.
 ///
@@ -27,6 +96,7 @@ pub fn sys_pread64(fd: c_int, buf: UserPtr<u8>, len: usize, offset: u64) -> Linu
         buf.len(),
         offset
     );
+    let offset = check_offset(offset, buf.len())?;
     Ok(get_file_like(fd)?.read_at(offset, buf)? as _)
 }
 
@@ -51,6 +121,7 @@ pub fn sys_pwrite64(
         buf.len(),
         offset
     );
+    let offset = check_offset(offset, buf.len())?;
     Ok(get_file_like(fd)?.write_at(offset, buf)? as _)
 }
 
@@ -68,6 +139,143 @@ pub fn sys_ftruncate(fd: c_int, len: u64) -> LinuxResult<isize> {
     Ok(0)
 }
 
+/// Truncate the file at `path` to `len` bytes, like [`sys_ftruncate`].
+pub fn sys_truncate(path: UserConstPtr<c_char>, len: i64) -> LinuxResult<isize> {
+    let path = path.get_as_str()?;
+    debug!("sys_truncate <= path: {}, len: {}", path, len);
+    if len < 0 {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let real_path = handle_file_path(AT_FDCWD, path)?;
+    let mut opts = OpenOptions::new();
+    opts.write(true);
+    let file = axfs::fops::File::open(real_path.as_str(), &opts).map_err(|err| match err {
+        AxError::IsADirectory => LinuxError::EISDIR,
+        err => err.into(),
+    })?;
+    File::new(file, real_path.to_string(), O_WRONLY).truncate(len as _)?;
+    Ok(0)
+}
+
+/// Manipulate the space allocated for the range of `len` bytes at `offset` in
+/// the file indicated by `fd`.
+///
+/// The default mode extends the file so that the range can be read, as zeros
+/// past the old end of file. `FALLOC_FL_PUNCH_HOLE` (which must be combined
+/// with `FALLOC_FL_KEEP_SIZE`) zeroes the range instead, as the file systems
+/// cannot deallocate it.
+///
+/// Return 0 on success.
+pub fn sys_fallocate(fd: c_int, mode: u32, offset: i64, len: i64) -> LinuxResult<isize> {
+    debug!(
+        "sys_fallocate <= fd: {}, mode: {:#x}, offset: {}, len: {}",
+        fd, mode, offset, len
+    );
+    if offset < 0 || len <= 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let end = offset.checked_add(len).ok_or(LinuxError::EFBIG)? as u64;
+    let file = match get_file_like(fd)?.into_any().downcast::<File>() {
+        Ok(file) => file,
+        Err(any) if any.is::<Directory>() => return Err(LinuxError::EISDIR),
+        Err(any) if any.is::<Pipe>() => return Err(LinuxError::ESPIPE),
+        Err(_) => return Err(LinuxError::ENODEV),
+    };
+    let size = file.inner().get_attr()?.size();
+
+    const PUNCH_HOLE: u32 = FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE;
+    match mode {
+        FALLOC_FL_ALLOCATE_RANGE => {
+            if end > size {
+                file.truncate(end)?;
+            }
+        }
+        // Space is allocated on the first write anyway.
+        FALLOC_FL_KEEP_SIZE => {}
+        PUNCH_HOLE => {
+            let zeros = vec![0u8; DEFAULT_BUFFER_SIZE];
+            let mut pos = offset as u64;
+            let end = end.min(size);
+            while pos < end {
+                let chunk_size = DEFAULT_BUFFER_SIZE.min((end - pos) as usize);
+                let written = file.write_at(pos, &zeros[..chunk_size])?;
+                if written == 0 {
+                    return Err(LinuxError::EIO);
+                }
+                pos += written as u64;
+            }
+        }
+        _ => return Err(LinuxError::EINVAL),
+    }
+    Ok(0)
+}
+
+/// Read up to `len` bytes of `file` from `offset` into a discard buffer, so
+/// that the file system caches them for the reads that follow.
+fn prefetch(file: &File, offset: u64, len: usize) -> LinuxResult<()> {
+    let len = len.min(MAX_READAHEAD);
+    let mut buffer = vec![0u8; DEFAULT_BUFFER_SIZE.min(len)];
+    let mut done = 0;
+    while done < len {
+        let chunk_size = DEFAULT_BUFFER_SIZE.min(len - done);
+        let read_bytes = file.read_at(offset + done as u64, &mut buffer[..chunk_size])?;
+        if read_bytes == 0 {
+            break;
+        }
+        done += read_bytes;
+    }
+    Ok(())
+}
+
+/// Read `count` bytes of a regular file from `offset` ahead of use.
+pub fn sys_readahead(fd: c_int, offset: i64, count: usize) -> LinuxResult<isize> {
+    debug!(
+        "sys_readahead <= fd: {}, offset: {}, count: {}",
+        fd, offset, count
+    );
+    if offset < 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let file = get_file_like(fd)?
+        .into_any()
+        .downcast::<File>()
+        .map_err(|_| LinuxError::EINVAL)?;
+    prefetch(&file, offset as u64, count)?;
+    Ok(0)
+}
+
+/// Announce how a range of a file will be accessed.
+///
+/// Sequential and will-need ranges are read ahead, like with
+/// [`sys_readahead`]. The other hints have nothing to act on.
+pub fn sys_fadvise64(fd: c_int, offset: i64, len: i64, advice: u32) -> LinuxResult<isize> {
+    debug!(
+        "sys_fadvise64 <= fd: {}, offset: {}, len: {}, advice: {}",
+        fd, offset, len, advice
+    );
+    let file = get_file_like(fd)?.into_any();
+    if file.is::<Pipe>() {
+        return Err(LinuxError::ESPIPE);
+    }
+    if offset < 0 || len < 0 {
+        return Err(LinuxError::EINVAL);
+    }
+
+    match advice {
+        POSIX_FADV_SEQUENTIAL | POSIX_FADV_WILLNEED => {
+            if let Ok(file) = file.downcast::<File>() {
+                // A length of 0 means up to the end of the file.
+                let len = if len == 0 { usize::MAX } else { len as usize };
+                prefetch(&file, offset as u64, len)?;
+            }
+        }
+        POSIX_FADV_NORMAL | POSIX_FADV_RANDOM | POSIX_FADV_DONTNEED | POSIX_FADV_NOREUSE => {}
+        _ => return Err(LinuxError::EINVAL),
+    }
+    Ok(0)
+}
+
 /// Synchronize a file's in-core state with storage device.
 ///
 /// This function transfers ("flushes") all modified in-core data of the file
@@ -81,16 +289,29 @@ pub fn sys_fsync(fd: c_int) -> LinuxResult<isize> {
     Ok(0)
 }
 
+/// Synchronize a file's data with storage device.
+///
+/// Same as `fsync`, except that metadata which is not needed for a subsequent
+/// data retrieval is not required to be flushed.
+///
+/// Return 0 on success.
+pub fn sys_fdatasync(fd: c_int) -> LinuxResult<isize> {
+    debug!("sys_fdatasync <= fd: {}", fd);
+    get_file_like(fd)?.fsync()?;
+    Ok(0)
+}
+
 /// Synchronize all file systems.
 ///
 /// This function causes all pending modifications to filesystem metadata and
-/// cached file data to be written to the underlying filesystems. It is equivalent
-/// to calling fsync() on every open file descriptor.
+/// cached file data to be written to the underlying filesystems, for every
+/// open file descriptor.
 ///
-/// Return 0 on success.
+/// Always returns 0, like Linux. Write-back errors are reported by the next
+/// `fsync` of each open file instead.
 pub fn sys_sync() -> LinuxResult<isize> {
     debug!("sys_sync");
-    FD_TABLE.sync_all()?;
+    FD_TABLE.sync_all();
     Ok(0)
 }
 
@@ -115,30 +336,33 @@ pub fn sys_read(fd: i32, buf: UserPtr<u8>, len: usize) -> LinuxResult<isize> {
 /// `iocnt` argument specifies the number of elements in the `iov` array.
 ///
 /// Return the total number of bytes read on success.
-pub fn sys_readv(fd: i32, iov: UserPtr<iovec>, iocnt: usize) -> LinuxResult<isize> {
+pub fn sys_readv(fd: i32, iov: UserConstPtr<iovec>, iocnt: usize) -> LinuxResult<isize> {
     if !(0..=1024).contains(&iocnt) {
         return Err(LinuxError::EINVAL);
     }
 
-    let iovs = iov.get_as_mut_slice(iocnt)?;
+    // Copied, as the buffers may overlap the vector itself.
+    let iovs = iov.get_as_slice(iocnt)?.to_vec();
+    iov_total_len(&iovs)?;
+    check_iov_bufs_mut(&iovs)?;
+    let file = get_file_like(fd)?;
     let mut ret = 0;
-    for iov in iovs {
-        if iov.iov_len == 0 {
-            continue;
-        }
-        let buf = UserPtr::<u8>::from(iov.iov_base as usize);
-        let buf = buf.get_as_mut_slice(iov.iov_len as _)?;
+    for iov in nonempty_iovs(&iovs) {
         debug!(
             "sys_readv <= fd: {}, buf: {:p}, len: {}",
-            fd,
-            buf.as_ptr(),
-            buf.len()
+            fd, iov.iov_base, iov.iov_len
         );
 
-        let read = get_file_like(fd)?.read(buf)?;
+        let len = iov.iov_len as usize;
+        let read = match iov_buf_mut(iov).and_then(|buf| file.read(buf)) {
+            Ok(read) => read,
+            // Report what was transferred before the error, like Linux.
+            Err(_) if ret > 0 => break,
+            Err(err) => return Err(err),
+        };
         ret += read as isize;
 
-        if read < buf.len() {
+        if read < len {
             break;
         }
     }
@@ -173,13 +397,10 @@ pub fn sys_writev(fd: i32, iov: UserConstPtr<iovec>, iocnt: usize) -> LinuxResul
     }
 
     let iovs = iov.get_as_slice(iocnt)?;
+    iov_total_len(iovs)?;
+    let file = get_file_like(fd)?;
     let mut ret = 0;
-    for iov in iovs {
-        if iov.iov_len == 0 {
-            continue;
-        }
-        let buf = UserConstPtr::<u8>::from(iov.iov_base as usize);
-        let buf = buf.get_as_slice(iov.iov_len as _)?;
+    for buf in iov_bufs(iovs)? {
         debug!(
             "sys_writev <= fd: {}, buf: {:p}, len: {}",
             fd,
@@ -187,7 +408,12 @@ pub fn sys_writev(fd: i32, iov: UserConstPtr<iovec>, iocnt: usize) -> LinuxResul
             buf.len()
         );
 
-        let written = get_file_like(fd)?.write(buf)?;
+        let written = match file.write(buf) {
+            Ok(written) => written,
+            // Report what was transferred before the error, like Linux.
+            Err(_) if ret > 0 => break,
+            Err(err) => return Err(err),
+        };
         ret += written as isize;
 
         if written < buf.len() {
@@ -198,25 +424,203 @@ pub fn sys_writev(fd: i32, iov: UserConstPtr<iovec>, iocnt: usize) -> LinuxResul
     Ok(ret)
 }
 
+/// Read data from the file at a specific offset using a vector of buffers.
+///
+/// This function combines the functionality of readv() and pread64(). The file
+/// offset is not changed.
+///
+/// Return the total number of bytes read on success.
+pub fn sys_preadv(
+    fd: i32,
+    iov: UserConstPtr<iovec>,
+    iocnt: usize,
+    offset: u64,
+) -> LinuxResult<isize> {
+    if !(0..=1024).contains(&iocnt) {
+        return Err(LinuxError::EINVAL);
+    }
+
+    // Copied, as the buffers may overlap the vector itself.
+    let iovs = iov.get_as_slice(iocnt)?.to_vec();
+    let total_len = iov_total_len(&iovs)?;
+    let mut offset = check_offset(offset, total_len)?;
+    check_iov_bufs_mut(&iovs)?;
+    let file = get_file_like(fd)?;
+    let mut ret = 0;
+    for iov in nonempty_iovs(&iovs) {
+        debug!(
+            "sys_preadv <= fd: {}, buf: {:p}, len: {}, offset: {}",
+            fd, iov.iov_base, iov.iov_len, offset
+        );
+
+        let len = iov.iov_len as usize;
+        let read = file.read_at(offset, iov_buf_mut(iov)?)?;
+        ret += read as isize;
+        offset += read as u64;
+
+        if read < len {
+            break;
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Write data to the file at a specific offset using a vector of buffers.
+///
+/// This function combines the functionality of writev() and pwrite64(). The
+/// file offset is not changed.
+///
+/// Return the total number of bytes written on success.
+pub fn sys_pwritev(
+    fd: i32,
+    iov: UserConstPtr<iovec>,
+    iocnt: usize,
+    offset: u64,
+) -> LinuxResult<isize> {
+    if !(0..=1024).contains(&iocnt) {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let iovs = iov.get_as_slice(iocnt)?;
+    let total_len = iov_total_len(iovs)?;
+    let mut offset = check_offset(offset, total_len)?;
+    let file = get_file_like(fd)?;
+    let mut ret = 0;
+    for buf in iov_bufs(iovs)? {
+        debug!(
+            "sys_pwritev <= fd: {}, buf: {:p}, len: {}, offset: {}",
+            fd,
+            buf.as_ptr(),
+            buf.len(),
+            offset
+        );
+
+        let written = file.write_at(offset, buf)?;
+        ret += written as isize;
+        offset += written as u64;
+
+        if written < buf.len() {
+            break;
+        }
+    }
+
+    Ok(ret)
+}
+
 /// Reposition read/write file offset.
 ///
 /// This function repositions the file offset of the open file description associated
 /// with the file descriptor `fd` to the argument `offset` according to the directive
-/// `whence`: SEEK_SET (0), SEEK_CUR (1), or SEEK_END (2).
+/// `whence`: SEEK_SET (0), SEEK_CUR (1), SEEK_END (2), SEEK_DATA (3) or
+/// SEEK_HOLE (4).
+///
+/// The file systems do not keep track of holes, so the whole file is treated
+/// as data followed by the hole at its end.
 ///
 /// Return the resulting offset location as measured in bytes from the beginning of the file.
 pub fn sys_lseek(fd: c_int, offset: __kernel_off_t, whence: c_int) -> LinuxResult<isize> {
     debug!("sys_lseek <= {} {} {}", fd, offset, whence);
-    let pos = match whence {
-        0 => SeekFrom::Start(offset as _),
-        1 => SeekFrom::Current(offset as _),
-        2 => SeekFrom::End(offset as _),
+    // Pipes, sockets and other streams have no file offset.
+    let file = match get_file_like(fd)?.into_any().downcast::<File>() {
+        Ok(file) => file,
+        Err(any) if any.is::<Directory>() => return Err(LinuxError::EINVAL),
+        Err(_) => return Err(LinuxError::ESPIPE),
+    };
+    let mut inner = file.inner();
+    let pos = match whence as u32 {
+        SEEK_SET | SEEK_CUR | SEEK_END => {
+            let base = match whence as u32 {
+                SEEK_SET => 0,
+                SEEK_CUR => inner.seek(SeekFrom::Current(0))?,
+                _ => inner.get_attr()?.size(),
+            };
+            // The result must be a non-negative `off_t`.
+            let pos = (base as i64)
+                .checked_add(offset as i64)
+                .ok_or(LinuxError::EOVERFLOW)?;
+            if pos < 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            pos as u64
+        }
+        SEEK_DATA | SEEK_HOLE => {
+            let size = inner.get_attr()?.size();
+            if offset < 0 || offset as u64 >= size {
+                return Err(LinuxError::ENXIO);
+            }
+            if whence as u32 == SEEK_DATA {
+                offset as u64
+            } else {
+                size
+            }
+        }
         _ => return Err(LinuxError::EINVAL),
     };
-    let off = File::from_fd(fd)?.inner().seek(pos)?;
+    let off = inner.seek(SeekFrom::Start(pos))?;
     Ok(off as _)
 }
 
+/// Copy up to `len` bytes from `read` to `write`, in chunks of at most
+/// [`DEFAULT_BUFFER_SIZE`] bytes.
+///
+/// `read` fills the buffer it is given and `write` consumes it, both
+/// returning how many bytes they transferred. The copy stops at the end of
+/// the source or at a short write, and an error after some bytes were copied
+/// returns what was copied so far, like Linux.
+fn copy_loop(
+    len: usize,
+    mut read: impl FnMut(&mut [u8]) -> LinuxResult<usize>,
+    mut write: impl FnMut(&[u8]) -> LinuxResult<usize>,
+) -> LinuxResult<isize> {
+    let mut buffer = vec![0u8; DEFAULT_BUFFER_SIZE.min(len)];
+    let mut total_copied = 0;
+
+    while total_copied < len {
+        let chunk_size = buffer.len().min(len - total_copied);
+        let result = read(&mut buffer[..chunk_size]).and_then(|read_bytes| {
+            let written_bytes = if read_bytes > 0 {
+                write(&buffer[..read_bytes])?
+            } else {
+                0
+            };
+            Ok((read_bytes, written_bytes))
+        });
+        let (read_bytes, written_bytes) = match result {
+            Ok(bytes) => bytes,
+            Err(_) if total_copied > 0 => break,
+            Err(err) => return Err(err),
+        };
+        total_copied += written_bytes;
+
+        if read_bytes == 0 || written_bytes < read_bytes {
+            break;
+        }
+    }
+
+    Ok(total_copied as isize)
+}
+
+/// The offset to copy at in `file`: the one `offset` points to, or the file
+/// offset if it is null.
+fn copy_offset(file: &File, offset: UserPtr<__kernel_off_t>) -> LinuxResult<u64> {
+    if offset.is_null() {
+        Ok(file.inner().seek(SeekFrom::Current(0))?)
+    } else {
+        Ok(*offset.get_as_mut()? as u64)
+    }
+}
+
+/// Move the offset returned by [`copy_offset`] past `len` copied bytes.
+fn advance_copy_offset(file: &File, offset: UserPtr<__kernel_off_t>, len: usize) -> LinuxResult {
+    if offset.is_null() {
+        file.inner().seek(SeekFrom::Current(len as i64))?;
+    } else {
+        *offset.get_as_mut()? += len as __kernel_off_t;
+    }
+    Ok(())
+}
+
 pub fn sys_copy_file_range(
     fd_in: c_int,
     off_in: UserPtr<__kernel_off_t>,
@@ -237,46 +641,18 @@ pub fn sys_copy_file_range(
     let file_in = File::from_fd(fd_in)?;
     let file_out = File::from_fd(fd_out)?;
 
-    let mut buffer = vec![0u8; DEFAULT_BUFFER_SIZE.min(len)];
-    let mut total_copied = 0;
-    let mut remaining = len;
-
-    while remaining > 0 {
-        let chunk_size = DEFAULT_BUFFER_SIZE.min(remaining);
-
-        let read_bytes = if off_in.is_null() {
-            file_in.read(&mut buffer[..chunk_size])?
-        } else {
-            let offset_ref = off_in.get_as_mut()?;
-            let current_offset = *offset_ref;
-            let bytes = file_in.read_at(current_offset as u64, &mut buffer[..chunk_size])?;
-            *offset_ref += bytes as __kernel_off_t;
-            bytes
-        };
-
-        if read_bytes == 0 {
-            break;
-        }
-
-        let written_bytes = if off_out.is_null() {
-            file_out.write(&buffer[..read_bytes])?
-        } else {
-            let offset_ref = off_out.get_as_mut()?;
-            let current_offset = *offset_ref;
-            let bytes = file_out.write_at(current_offset as u64, &buffer[..read_bytes])?;
-            *offset_ref += bytes as __kernel_off_t;
-            bytes
-        };
-
-        total_copied += written_bytes;
-        remaining -= written_bytes;
-
-        if written_bytes < read_bytes {
-            break;
-        }
-    }
-
-    Ok(total_copied as isize)
+    // The source is only consumed once the bytes are written, so that a
+    // short write does not skip any.
+    copy_loop(
+        len,
+        |buf| file_in.read_at(copy_offset(&file_in, off_in)?, buf),
+        |buf| {
+            let written_bytes = file_out.write_at(copy_offset(&file_out, off_out)?, buf)?;
+            advance_copy_offset(&file_in, off_in, written_bytes)?;
+            advance_copy_offset(&file_out, off_out, written_bytes)?;
+            Ok(written_bytes)
+        },
+    )
 }
 
 pub fn sys_splice(
@@ -345,35 +721,28 @@ fn splice_pipe_to_file(
     off_out: UserPtr<__kernel_off_t>,
     len: usize,
 ) -> LinuxResult<isize> {
+    // Only what is already in the pipe is moved, without waiting for more.
+    // The bytes are written from the pipe in place, and only the ones
+    // written are consumed, so a short or failed write loses none.
+    let len = len.min(pipe.available_data());
     let mut buffer = vec![0u8; DEFAULT_BUFFER_SIZE.min(len)];
     let mut total_copied = 0;
-    let mut remaining = len;
-
-    while remaining > 0 {
-        let available = pipe.available_data();
-        if available == 0 {
-            if pipe.closed() {
-                break;
-            }
-            break;
-        }
-
-        let chunk_size = DEFAULT_BUFFER_SIZE.min(remaining).min(available);
-        let bytes_read = pipe.read(&mut buffer[..chunk_size])?;
-
-        if bytes_read == 0 {
-            break;
-        }
-
-        let off_out_ref = off_out.get_as_mut()?;
-        let current_off_out = *off_out_ref;
-        let written_bytes = file.write_at(current_off_out as u64, &buffer[..bytes_read])?;
-        *off_out_ref += written_bytes as __kernel_off_t;
 
+    while total_copied < len {
+        let chunk_size = buffer.len().min(len - total_copied);
+        let result = pipe.read_with(&mut buffer[..chunk_size], |data| {
+            let written_bytes = file.write_at(copy_offset(&file, off_out)?, data)?;
+            advance_copy_offset(&file, off_out, written_bytes)?;
+            Ok(written_bytes)
+        });
+        let written_bytes = match result {
+            Ok(bytes) => bytes,
+            Err(_) if total_copied > 0 => break,
+            Err(err) => return Err(err),
+        };
         total_copied += written_bytes;
-        remaining -= written_bytes;
 
-        if written_bytes < bytes_read {
+        if written_bytes < chunk_size {
             break;
         }
     }
@@ -387,38 +756,14 @@ fn splice_file_to_pipe(
     off_in: UserPtr<__kernel_off_t>,
     len: usize,
 ) -> LinuxResult<isize> {
-    let mut buffer = vec![0u8; DEFAULT_BUFFER_SIZE.min(len)];
-    let mut total_copied = 0;
-    let mut remaining = len;
-
-    while remaining > 0 {
-        let chunk_size = DEFAULT_BUFFER_SIZE.min(remaining);
-
-        if !off_in.is_null() {
-            let current_off_in = *off_in.get_as_mut()?;
-            let file_stat = file.stat()?;
-            if current_off_in >= file_stat.size() as __kernel_off_t {
-                break;
-            }
-        }
-
-        let off_in_ref = off_in.get_as_mut()?;
-        let current_off_in = *off_in_ref;
-        let read_bytes = file.read_at(current_off_in as u64, &mut buffer[..chunk_size])?;
-        *off_in_ref += read_bytes as __kernel_off_t;
-        if read_bytes == 0 {
-            break;
-        }
-
-        let written_bytes = pipe.write(&buffer[..read_bytes])?;
-
-        total_copied += written_bytes;
-        remaining -= written_bytes;
-
-        if written_bytes < read_bytes {
-            break;
-        }
-    }
-
-    Ok(total_copied as isize)
+    // The pipe may fill up, so only what it took is consumed.
+    copy_loop(
+        len,
+        |buf| file.read_at(copy_offset(&file, off_in)?, buf),
+        |buf| {
+            let written_bytes = pipe.write(buf)?;
+            advance_copy_offset(&file, off_in, written_bytes)?;
+            Ok(written_bytes)
+        },
+    )
 }
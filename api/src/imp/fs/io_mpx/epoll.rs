@@ -2,16 +2,27 @@
 
 use core::{ffi::c_int, time::Duration};
 
-use crate::file::{FileLike, Kstat, add_file_like, get_file_like};
-use crate::ptr::UserPtr;
+use super::Watch;
+use crate::file::{
+    AX_FILE_LIMIT, FileLike, Kstat, PollSet, PollWaiter, add_file_like, get_file_like,
+};
+use crate::{
+    ptr::{UserConstPtr, UserPtr, nullable},
+    signal::with_sigmask,
+};
 use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
 use axerrno::{LinuxError, LinuxResult};
 use axhal::time::wall_time;
+use axsignal::SignalSet;
 use linux_raw_sys::general::{
-    EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD, EPOLLERR, EPOLLIN, EPOLLOUT, sigset_t,
+    EPOLL_CLOEXEC, EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD, EPOLLERR, EPOLLHUP, EPOLLIN,
+    EPOLLOUT, timespec,
 };
 use spin::Mutex;
 
+/// How deeply epoll instances may watch each other, like `EP_MAX_NESTS`.
+const EP_MAX_NESTS: usize = 4;
+
 /// Structure representing epoll_event for user space
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -26,12 +37,16 @@ unsafe impl Sync for EpollEvent {}
 /// Epoll instance structure
 pub struct EpollInstance {
     events: Mutex<BTreeMap<usize, EpollEvent>>,
+    /// The tasks waiting on this instance, woken when the interest list
+    /// changes.
+    poll_set: PollSet,
 }
 
 impl EpollInstance {
     fn new(_flags: usize) -> Self {
         Self {
             events: Mutex::new(BTreeMap::new()),
+            poll_set: PollSet::new(),
         }
     }
 
@@ -42,8 +57,40 @@ impl EpollInstance {
             .map_err(|_| LinuxError::EINVAL)
     }
 
+    /// Whether `target` can be reached by following the epoll instances this
+    /// one watches, giving up with `ELOOP` past `EP_MAX_NESTS` levels.
+    fn reaches(&self, target: &EpollInstance, depth: usize) -> LinuxResult<bool> {
+        let fds: Vec<usize> = self.events.lock().keys().copied().collect();
+        for fd in fds {
+            let Ok(inner) = Self::from_fd(fd as c_int) else {
+                continue;
+            };
+            if core::ptr::eq(&*inner, target) {
+                return Ok(true);
+            }
+            if depth >= EP_MAX_NESTS {
+                return Err(LinuxError::ELOOP);
+            }
+            if inner.reaches(target, depth + 1)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Add, modify or remove the watch on `fd`, as `epoll_ctl` does.
     fn control(&self, op: usize, fd: usize, event: &EpollEvent) -> LinuxResult<usize> {
-        get_file_like(fd as c_int)?;
+        let file = get_file_like(fd as c_int)?;
+        if let Ok(inner) = file.into_any().downcast::<EpollInstance>() {
+            if core::ptr::eq(&*inner, self) {
+                return Err(LinuxError::EINVAL);
+            }
+            // Watching an instance that already watches this one would make
+            // a cycle.
+            if op as u32 == EPOLL_CTL_ADD && inner.reaches(self, 1)? {
+                return Err(LinuxError::ELOOP);
+            }
+        }
 
         let mut events = self.events.lock();
         match op as u32 {
@@ -67,19 +114,50 @@ impl EpollInstance {
             }
             _ => return Err(LinuxError::EINVAL),
         }
+        drop(events);
+        // Tasks waiting on this instance must pick up the new interest list.
+        self.poll_set.wake();
         Ok(0)
     }
 
+    /// The number of watched file descriptors.
+    fn len(&self) -> usize {
+        self.events.lock().len()
+    }
+
+    /// How to wait for the watched file descriptors.
+    fn watch(&self) -> Watch {
+        let fds: Vec<usize> = self.events.lock().keys().copied().collect();
+        // Closed descriptors are reported right away, so they are skipped.
+        Watch::from_files(
+            fds.into_iter()
+                .filter_map(|fd| get_file_like(fd as c_int).ok())
+                .collect(),
+        )
+    }
+
+    /// Fill `events` with the ready file descriptors without blocking, and
+    /// return how many there are.
     fn poll_all(&self, events: &mut [EpollEvent]) -> LinuxResult<usize> {
-        let ready_list = self.events.lock();
+        let mut ready_list = self.events.lock();
         let mut events_num = 0;
+        let mut closed = Vec::new();
 
         for (&infd, ev) in ready_list.iter() {
             if events_num >= events.len() {
                 break;
             }
 
-            match get_file_like(infd as c_int).and_then(|f| f.poll()) {
+            let Ok(file) = get_file_like(infd as c_int) else {
+                // The file was closed, so report it as hung up once and stop
+                // watching it.
+                events[events_num].events = EPOLLHUP;
+                events[events_num].data = ev.data;
+                events_num += 1;
+                closed.push(infd);
+                continue;
+            };
+            match file.poll() {
                 Err(_) => {
                     if (ev.events & EPOLLERR) != 0 {
                         events[events_num].events = EPOLLERR;
@@ -100,8 +178,40 @@ impl EpollInstance {
                 }
             }
         }
+        for fd in closed {
+            ready_list.remove(&fd);
+        }
         Ok(events_num)
     }
+
+    /// Wait for the watched files to become ready, until `deadline` at most,
+    /// and get the events of at most `maxevents` of them.
+    ///
+    /// The events are empty if the deadline passed.
+    fn wait(&self, maxevents: usize, deadline: Option<Duration>) -> LinuxResult<Vec<EpollEvent>> {
+        let mut event_buffer = Vec::new();
+        loop {
+            // The watched files may change while waiting.
+            let watch = self.watch();
+            watch.poll_net();
+            let waiter = PollWaiter::new();
+            let sleep = self.register_waiter(&waiter);
+
+            event_buffer.resize(maxevents.min(self.len()), EpollEvent { events: 0, data: 0 });
+
+            let events_num = self.poll_all(&mut event_buffer)?;
+            if events_num > 0 {
+                event_buffer.truncate(events_num);
+                return Ok(event_buffer);
+            }
+
+            Watch::wait(sleep.then_some(&waiter), deadline)?;
+
+            if deadline.is_some_and(|ddl| wall_time() >= ddl) {
+                return Ok(Vec::new());
+            }
+        }
+    }
 }
 
 impl FileLike for EpollInstance {
@@ -131,6 +241,11 @@ impl FileLike for EpollInstance {
     fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
         Ok(())
     }
+
+    fn register_waiter(&self, waiter: &PollWaiter) -> bool {
+        self.poll_set.register(waiter);
+        self.watch().register(waiter)
+    }
 }
 
 /// Implementation of epoll_create system call
@@ -142,7 +257,7 @@ pub fn sys_epoll_create(size: c_int) -> LinuxResult<isize> {
     }
 
     let epoll_instance = Arc::new(EpollInstance::new(0));
-    let fd = add_file_like(epoll_instance)?;
+    let fd = add_file_like(epoll_instance, false)?;
     Ok(fd as isize)
 }
 
@@ -150,7 +265,14 @@ pub fn sys_epoll_create(size: c_int) -> LinuxResult<isize> {
 pub fn sys_epoll_create1(flags: c_int) -> LinuxResult<isize> {
     debug!("sys_epoll_create1 <= flags: {}", flags);
 
-    sys_epoll_create(1)
+    let flags = flags as u32;
+    if flags & !EPOLL_CLOEXEC != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let epoll_instance = Arc::new(EpollInstance::new(0));
+    let fd = add_file_like(epoll_instance, flags & EPOLL_CLOEXEC != 0)?;
+    Ok(fd as isize)
 }
 
 /// Implementation of epoll_ctl system call
@@ -188,47 +310,101 @@ pub fn sys_epoll_wait(
         epfd, maxevents, timeout
     );
 
+    let deadline =
+        (!timeout.is_negative()).then(|| wall_time() + Duration::from_millis(timeout as u64));
+    epoll_wait_until(epfd, events, maxevents, deadline)
+}
+
+/// Wait for events on an epoll instance until `deadline`, or forever if it
+/// is `None`.
+fn epoll_wait_until(
+    epfd: c_int,
+    events: UserPtr<EpollEvent>,
+    maxevents: c_int,
+    deadline: Option<Duration>,
+) -> LinuxResult<isize> {
     if maxevents <= 0 {
         return Err(LinuxError::EINVAL);
     }
 
-    let deadline =
-        (!timeout.is_negative()).then(|| wall_time() + Duration::from_millis(timeout as u64));
     let epoll_instance = EpollInstance::from_fd(epfd)?;
+    // No more events than file descriptors can be reported, and there can be
+    // no more of those than fit in the descriptor table.
+    let maxevents = (maxevents as usize).min(AX_FILE_LIMIT);
+    let ready = epoll_instance.wait(maxevents, deadline)?;
+    if !ready.is_empty() {
+        events
+            .get_as_mut_slice(ready.len())?
+            .copy_from_slice(&ready);
+    }
+    Ok(ready.len() as isize)
+}
 
-    loop {
-        axnet::poll_interfaces();
-
-        let mut event_buffer = Vec::with_capacity(maxevents as usize);
-        event_buffer.resize(maxevents as usize, EpollEvent { events: 0, data: 0 });
-
-        let events_num = epoll_instance.poll_all(&mut event_buffer)?;
-        if events_num > 0 {
-            let events_slice = events.get_as_mut_slice(events_num)?;
-            events_slice[..events_num].copy_from_slice(&event_buffer[..events_num]);
-            return Ok(events_num as isize);
-        }
-
-        axtask::yield_now();
-
-        if deadline.is_some_and(|ddl| wall_time() >= ddl) {
-            return Ok(0);
-        }
+/// Get the temporary signal mask of the `epoll_pwait` family, if any.
+fn parse_sigmask(
+    sigmask: UserConstPtr<SignalSet>,
+    sigsetsize: usize,
+) -> LinuxResult<Option<SignalSet>> {
+    let Some(mask) = nullable!(sigmask.get_as_ref())? else {
+        return Ok(None);
+    };
+    if sigsetsize != size_of::<SignalSet>() {
+        return Err(LinuxError::EINVAL);
     }
+    Ok(Some(*mask))
 }
 
-/// Implementation of epoll_pwait system call (simplified version without signal handling)
+/// Implementation of epoll_pwait system call, which blocks the signals in
+/// `sigmask` while waiting.
 pub fn sys_epoll_pwait(
     epfd: c_int,
     events: UserPtr<EpollEvent>,
     maxevents: c_int,
     timeout: c_int,
-    _sigmask: UserPtr<sigset_t>,
+    sigmask: UserConstPtr<SignalSet>,
+    sigsetsize: usize,
 ) -> LinuxResult<isize> {
     debug!(
         "sys_epoll_pwait <= epfd: {}, maxevents: {}, timeout: {}",
         epfd, maxevents, timeout
     );
 
-    sys_epoll_wait(epfd, events, maxevents, timeout)
+    match parse_sigmask(sigmask, sigsetsize)? {
+        Some(mask) => with_sigmask(mask, || sys_epoll_wait(epfd, events, maxevents, timeout)),
+        None => sys_epoll_wait(epfd, events, maxevents, timeout),
+    }
+}
+
+/// Implementation of epoll_pwait2 system call, which takes a `timespec`
+/// timeout and blocks the signals in `sigmask` while waiting.
+pub fn sys_epoll_pwait2(
+    epfd: c_int,
+    events: UserPtr<EpollEvent>,
+    maxevents: c_int,
+    timeout: UserPtr<timespec>,
+    sigmask: UserConstPtr<SignalSet>,
+    sigsetsize: usize,
+) -> LinuxResult<isize> {
+    debug!(
+        "sys_epoll_pwait2 <= epfd: {}, maxevents: {}",
+        epfd, maxevents
+    );
+
+    let deadline = if timeout.is_null() {
+        None
+    } else {
+        let ts = timeout.get_as_mut()?;
+        if ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) {
+            return Err(LinuxError::EINVAL);
+        }
+        Some(
+            wall_time()
+                + Duration::from_secs(ts.tv_sec as u64)
+                + Duration::from_nanos(ts.tv_nsec as u64),
+        )
+    };
+    match parse_sigmask(sigmask, sigsetsize)? {
+        Some(mask) => with_sigmask(mask, || epoll_wait_until(epfd, events, maxevents, deadline)),
+        None => epoll_wait_until(epfd, events, maxevents, deadline),
+    }
 }
@@ -8,10 +8,19 @@
 //! * [`epoll_ctl`](epoll::sys_epoll_ctl)
 //! * [`epoll_wait`](epoll::sys_epoll_wait)
 //! * [`epoll_pwait`](epoll::sys_epoll_pwait)
+//! * [`epoll_pwait2`](epoll::sys_epoll_pwait2)
 
-use axerrno::LinuxResult;
+use alloc::{sync::Arc, vec::Vec};
+use axerrno::{LinuxError, LinuxResult};
 use axhal::time::wall_time;
-use core::time::Duration;
+use axtask::{TaskExtRef, current};
+use core::{ffi::c_int, time::Duration};
+use linux_raw_sys::general::RLIMIT_NOFILE;
+
+use crate::{
+    file::{FileLike, PollWaiter, Socket, get_file_like},
+    signal::{sleep_interruptible, unblocked_signal_pending},
+};
 
 mod epoll;
 mod poll;
@@ -21,23 +30,90 @@ pub use self::epoll::*;
 pub use self::poll::*;
 pub use self::select::*;
 
-/// Common polling loop that handles network polling, yielding, and timeout checking
+/// How to wait for a set of watched files.
+pub(crate) struct Watch {
+    files: Vec<Arc<dyn FileLike>>,
+    /// Whether any of the files is a socket, whose state only changes when
+    /// the network interfaces are polled.
+    poll_net: bool,
+}
+
+impl Watch {
+    pub(crate) fn new(fds: impl IntoIterator<Item = c_int>) -> Self {
+        // Invalid descriptors are reported right away, so they are skipped.
+        Self::from_files(
+            fds.into_iter()
+                .filter_map(|fd| get_file_like(fd).ok())
+                .collect(),
+        )
+    }
+
+    fn from_files(files: Vec<Arc<dyn FileLike>>) -> Self {
+        let poll_net = files
+            .iter()
+            .any(|file| file.clone().into_any().is::<Socket>());
+        Self { files, poll_net }
+    }
+
+    /// Poll the network interfaces if any of the files is a socket.
+    fn poll_net(&self) {
+        if self.poll_net {
+            axnet::poll_interfaces();
+        }
+    }
+
+    /// Register `waiter` with all of the files, and return whether any of
+    /// them becoming ready wakes it.
+    fn register(&self, waiter: &PollWaiter) -> bool {
+        self.files.iter().all(|file| file.register_waiter(waiter))
+    }
+
+    /// Get a waiter woken when any of the files may become ready, or `None`
+    /// if the task must spin instead.
+    ///
+    /// It must be created before polling the files, so that it notices the
+    /// changes made while they are polled.
+    fn waiter(&self) -> Option<PollWaiter> {
+        let waiter = PollWaiter::new();
+        self.register(&waiter).then_some(waiter)
+    }
+
+    /// Wait for any of the files to become ready, until `deadline` at most.
+    ///
+    /// Fails with `EINTR` once a signal that is not blocked is pending.
+    fn wait(waiter: Option<&PollWaiter>, deadline: Option<Duration>) -> LinuxResult {
+        match waiter {
+            Some(waiter) => waiter.wait(deadline.map(|ddl| ddl.saturating_sub(wall_time()))),
+            None => {
+                if unblocked_signal_pending() {
+                    return Err(LinuxError::EINTR);
+                }
+                axtask::yield_now();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Common polling loop that handles network polling, waiting, and timeout checking
 /// Returns Ok(Some(result)) if polling function returns a result, Ok(None) if timeout occurred
 pub(crate) fn poll_with_timeout<F, R>(
     deadline: Option<Duration>,
+    watch: Watch,
     mut poll_fn: F,
 ) -> LinuxResult<Option<R>>
 where
     F: FnMut() -> LinuxResult<Option<R>>,
 {
     loop {
-        axnet::poll_interfaces();
+        watch.poll_net();
 
+        let waiter = watch.waiter();
         if let Some(result) = poll_fn()? {
             return Ok(Some(result));
         }
 
-        axtask::yield_now();
+        Watch::wait(waiter.as_ref(), deadline)?;
 
         if deadline.is_some_and(|ddl| wall_time() >= ddl) {
             return Ok(None);
@@ -45,10 +121,32 @@ where
     }
 }
 
-/// Handle empty nfds case with optional timeout sleep
+/// Handle empty nfds case: sleep for the timeout, if there is one, until a
+/// signal that is not blocked arrives.
 pub(crate) fn handle_empty_nfds(timeout: Option<Duration>) -> LinuxResult<isize> {
-    if let Some(duration) = timeout {
-        axtask::sleep(duration);
+    match timeout {
+        Some(duration) => sleep_interruptible(duration)?,
+        None => loop {
+            if unblocked_signal_pending() {
+                return Err(LinuxError::EINTR);
+            }
+            current().task_ext().process_data().signal.wait_signal();
+        },
     }
     Ok(0)
 }
+
+/// Check that `nfds` does not exceed the `RLIMIT_NOFILE` of the process.
+pub(crate) fn check_nfds(nfds: usize) -> LinuxResult {
+    let limit = current()
+        .task_ext()
+        .process_data()
+        .rlimits
+        .read()
+        .get(RLIMIT_NOFILE)
+        .soft;
+    if nfds as u64 > limit {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(())
+}
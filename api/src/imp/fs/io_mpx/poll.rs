@@ -2,7 +2,7 @@
 
 use core::{ffi::c_int, time::Duration};
 
-use super::{handle_empty_nfds, poll_with_timeout};
+use super::{Watch, check_nfds, handle_empty_nfds, poll_with_timeout};
 use crate::file::get_file_like;
 use crate::ptr::UserPtr;
 use axerrno::LinuxResult;
@@ -53,6 +53,14 @@ fn poll_fds(fds: UserPtr<pollfd>, nfds: usize) -> LinuxResult<Option<isize>> {
     }
 }
 
+/// How to wait for the file descriptors to poll.
+fn watch(fds: UserPtr<pollfd>, nfds: usize) -> LinuxResult<Watch> {
+    let pollfds = fds.get_as_mut_slice(nfds)?;
+    Ok(Watch::new(
+        pollfds.iter().map(|pollfd| pollfd.fd).filter(|&fd| fd >= 0),
+    ))
+}
+
 /// Implementation of poll system call
 pub fn sys_poll(fds: UserPtr<pollfd>, nfds: usize, timeout_ms: c_int) -> LinuxResult<isize> {
     debug!(
@@ -62,6 +70,7 @@ pub fn sys_poll(fds: UserPtr<pollfd>, nfds: usize, timeout_ms: c_int) -> LinuxRe
         timeout_ms
     );
 
+    check_nfds(nfds)?;
     if nfds == 0 {
         let timeout = (!timeout_ms.is_negative()).then(|| Duration::from_millis(timeout_ms as u64));
         return handle_empty_nfds(timeout);
@@ -70,7 +79,7 @@ pub fn sys_poll(fds: UserPtr<pollfd>, nfds: usize, timeout_ms: c_int) -> LinuxRe
     let deadline =
         (!timeout_ms.is_negative()).then(|| wall_time() + Duration::from_millis(timeout_ms as u64));
 
-    match poll_with_timeout(deadline, || poll_fds(fds, nfds))? {
+    match poll_with_timeout(deadline, watch(fds, nfds)?, || poll_fds(fds, nfds))? {
         Some(ready_count) => Ok(ready_count),
         None => Ok(0),
     }
@@ -85,6 +94,7 @@ pub fn sys_ppoll(
 ) -> LinuxResult<isize> {
     debug!("sys_ppoll <= fds: {:?}, nfds: {}", fds.address(), nfds);
 
+    check_nfds(nfds)?;
     if nfds == 0 {
         let timeout_duration = if timeout.is_null() {
             None
@@ -106,7 +116,7 @@ pub fn sys_ppoll(
         )
     };
 
-    match poll_with_timeout(deadline, || poll_fds(fds, nfds))? {
+    match poll_with_timeout(deadline, watch(fds, nfds)?, || poll_fds(fds, nfds))? {
         Some(ready_count) => Ok(ready_count),
         None => Ok(0),
     }
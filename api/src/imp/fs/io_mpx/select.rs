@@ -2,7 +2,7 @@
 
 use core::{ffi::c_int, time::Duration};
 
-use super::poll_with_timeout;
+use super::{Watch, check_nfds, poll_with_timeout};
 use crate::file::get_file_like;
 use crate::ptr::UserPtr;
 use axerrno::{LinuxError, LinuxResult};
@@ -59,6 +59,15 @@ impl FdSets {
         Ok(Self { nfds, bits })
     }
 
+    /// How to wait for the file descriptors in the sets.
+    fn watch(&self) -> Watch {
+        Watch::new((0..self.nfds).filter_map(|fd| {
+            let (idx, bit) = (fd / BITS_PER_USIZE, 1 << (fd % BITS_PER_USIZE));
+            let watched = (0..3).any(|set| self.bits[set * FD_SETSIZE_USIZES + idx] & bit != 0);
+            watched.then_some(fd as _)
+        }))
+    }
+
     fn poll_all(
         &self,
         res_read_fds: UserPtr<FdSet>,
@@ -173,6 +182,7 @@ pub fn sys_select(
     if nfds < 0 {
         return Err(LinuxError::EINVAL);
     }
+    check_nfds(nfds as usize)?;
 
     let nfds = (nfds as usize).min(FD_SETSIZE);
     let deadline = if timeout.is_null() {
@@ -192,7 +202,9 @@ pub fn sys_select(
     clear_fd_set(writefds)?;
     clear_fd_set(exceptfds)?;
 
-    match poll_with_timeout(deadline, || fd_sets.poll_all(readfds, writefds, exceptfds))? {
+    match poll_with_timeout(deadline, fd_sets.watch(), || {
+        fd_sets.poll_all(readfds, writefds, exceptfds)
+    })? {
         Some(res) => Ok(res as isize),
         None => Ok(0),
     }
@@ -212,6 +224,7 @@ pub fn sys_pselect6(
     if nfds < 0 {
         return Err(LinuxError::EINVAL);
     }
+    check_nfds(nfds as usize)?;
 
     let nfds = (nfds as usize).min(FD_SETSIZE);
     let deadline = if timeout.is_null() {
@@ -231,7 +244,9 @@ pub fn sys_pselect6(
     clear_fd_set(writefds)?;
     clear_fd_set(exceptfds)?;
 
-    match poll_with_timeout(deadline, || fd_sets.poll_all(readfds, writefds, exceptfds))? {
+    match poll_with_timeout(deadline, fd_sets.watch(), || {
+        fd_sets.poll_all(readfds, writefds, exceptfds)
+    })? {
         Some(res) => Ok(res as isize),
         None => Ok(0),
     }
@@ -1,15 +1,23 @@
 mod ctl;
+mod event;
 mod fd_ops;
+mod inotify;
 mod io;
 mod io_mpx;
 mod mount;
 mod pipe;
+mod signalfd;
 mod stat;
+mod timerfd;
 
 pub use self::ctl::*;
+pub use self::event::*;
 pub use self::fd_ops::*;
+pub use self::inotify::*;
 pub use self::io::*;
 pub use self::io_mpx::*;
 pub use self::mount::*;
 pub use self::pipe::*;
+pub use self::signalfd::*;
 pub use self::stat::*;
+pub use self::timerfd::*;
@@ -1,13 +1,26 @@
-use core::ffi::{c_char, c_void};
+use core::{
+    any::Any,
+    ffi::{c_char, c_int, c_void},
+};
 
-use alloc::vec::Vec;
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
 use axerrno::{LinuxError, LinuxResult};
+use axio::PollState;
 use axsync::Mutex;
-use linux_raw_sys::general::AT_FDCWD;
+use axtask::{TaskExtRef, current};
+use linux_raw_sys::general::{
+    AT_FDCWD, FSMOUNT_CLOEXEC, FSOPEN_CLOEXEC, MOVE_MOUNT_F_EMPTY_PATH, MOVE_MOUNT_T_EMPTY_PATH,
+    MSDOS_SUPER_MAGIC, RAMFS_MAGIC, TMPFS_MAGIC, fsconfig_command,
+};
 
 use crate::{
+    file::{Directory, FileLike, Kstat, get_file_like},
     path::{FilePath, handle_file_path},
-    ptr::UserConstPtr,
+    ptr::{UserConstPtr, nullable},
 };
 
 pub fn sys_mount(
@@ -76,24 +89,307 @@ pub fn sys_umount2(target: UserConstPtr<c_char>, flags: i32) -> LinuxResult<isiz
     Ok(0)
 }
 
+const FSCONFIG_SET_FLAG: u32 = fsconfig_command::FSCONFIG_SET_FLAG as _;
+const FSCONFIG_SET_STRING: u32 = fsconfig_command::FSCONFIG_SET_STRING as _;
+const FSCONFIG_CMD_CREATE: u32 = fsconfig_command::FSCONFIG_CMD_CREATE as _;
+
+/// The types of file system that can be mounted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FsType {
+    Vfat,
+    Tmpfs,
+    Ramfs,
+}
+
+impl FsType {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "vfat" => Some(Self::Vfat),
+            "tmpfs" => Some(Self::Tmpfs),
+            "ramfs" => Some(Self::Ramfs),
+            _ => None,
+        }
+    }
+
+    /// Whether the file system is stored on the device named by `source`,
+    /// rather than in memory.
+    fn needs_device(self) -> bool {
+        self == Self::Vfat
+    }
+
+    /// Whether `key` is a parameter of the file system other than `source`,
+    /// which is set with `FSCONFIG_SET_FLAG` if `flag` is set, or else with
+    /// a value.
+    ///
+    /// Values are not checked, as they do not change how the file system is
+    /// emulated.
+    fn has_param(self, key: &str, flag: bool) -> bool {
+        let (values, flags): (&[&str], &[&str]) = match self {
+            Self::Vfat => (
+                &[
+                    "uid",
+                    "gid",
+                    "umask",
+                    "dmask",
+                    "fmask",
+                    "codepage",
+                    "iocharset",
+                ],
+                &["ro", "rw", "quiet", "showexec"],
+            ),
+            Self::Tmpfs => (
+                &["size", "nr_blocks", "nr_inodes", "mode", "uid", "gid"],
+                &["ro", "rw"],
+            ),
+            Self::Ramfs => (&["mode"], &["ro", "rw"]),
+        };
+        if flag { flags } else { values }.contains(&key)
+    }
+
+    /// The magic number that `statfs` reports for the file system.
+    fn magic(self) -> u32 {
+        match self {
+            Self::Vfat => MSDOS_SUPER_MAGIC,
+            Self::Tmpfs => TMPFS_MAGIC,
+            Self::Ramfs => RAMFS_MAGIC,
+        }
+    }
+}
+
+/// A file system configuration context, created by `fsopen` and set up with
+/// `fsconfig`.
+struct FsContext {
+    fs_type: FsType,
+    state: Mutex<FsContextState>,
+}
+
+#[derive(Default)]
+struct FsContextState {
+    /// The `source` parameter.
+    source: Option<String>,
+    /// Whether `FSCONFIG_CMD_CREATE` has succeeded.
+    created: bool,
+    /// The device, for a file system that is stored on one.
+    device: Option<FilePath>,
+}
+
+/// A mount created by `fsmount`, which is detached until `move_mount` puts
+/// it in the tree.
+struct MountFd {
+    fs_type: FsType,
+    device: Option<FilePath>,
+    /// Where the mount is attached, if it is.
+    mnt_dir: Mutex<Option<FilePath>>,
+}
+
+macro_rules! impl_mount_api_file {
+    ($ty:ty) => {
+        impl FileLike for $ty {
+            fn read(&self, _buf: &mut [u8]) -> LinuxResult<usize> {
+                Err(LinuxError::EOPNOTSUPP)
+            }
+
+            fn write(&self, _buf: &[u8]) -> LinuxResult<usize> {
+                Err(LinuxError::EOPNOTSUPP)
+            }
+
+            fn stat(&self) -> LinuxResult<Kstat> {
+                Ok(Kstat::default())
+            }
+
+            fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+                self
+            }
+
+            fn poll(&self) -> LinuxResult<PollState> {
+                Ok(PollState {
+                    readable: false,
+                    writable: false,
+                })
+            }
+
+            fn set_nonblocking(&self, _nonblocking: bool) -> LinuxResult {
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_mount_api_file!(FsContext);
+impl_mount_api_file!(MountFd);
+
+/// Open a configuration context for a new file system of type `fs_name`,
+/// which can be `vfat`, `tmpfs` or `ramfs`.
+///
+/// Like on Linux, this needs root privileges.
+pub fn sys_fsopen(fs_name: UserConstPtr<c_char>, flags: u32) -> LinuxResult<isize> {
+    let fs_name = fs_name.get_as_str()?;
+    info!("sys_fsopen <= fs_name: {}, flags: {:#x}", fs_name, flags);
+    if !current().task_ext().process_data().cred.read().is_root() {
+        return Err(LinuxError::EPERM);
+    }
+    if flags & !FSOPEN_CLOEXEC != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let fs_type = FsType::from_name(fs_name).ok_or(LinuxError::ENODEV)?;
+
+    let context = FsContext {
+        fs_type,
+        state: Mutex::new(FsContextState::default()),
+    };
+    Ok(context.add_to_fd_table(flags & FSOPEN_CLOEXEC != 0)? as _)
+}
+
+/// Set a parameter of a file system context, or create the file system with
+/// `FSCONFIG_CMD_CREATE`.
+pub fn sys_fsconfig(
+    fd: c_int,
+    cmd: u32,
+    key: UserConstPtr<c_char>,
+    value: UserConstPtr<c_void>,
+    aux: c_int,
+) -> LinuxResult<isize> {
+    let key = nullable!(key.get_as_str())?;
+    info!(
+        "sys_fsconfig <= fd: {}, cmd: {}, key: {:?}, aux: {}",
+        fd, cmd, key, aux
+    );
+    let context = FsContext::from_fd(fd)?;
+    let mut state = context.state.lock();
+    if state.created {
+        return Err(LinuxError::EBUSY);
+    }
+
+    match cmd {
+        FSCONFIG_SET_STRING => {
+            let key = key.ok_or(LinuxError::EINVAL)?;
+            let value = UserConstPtr::<c_char>::from(value.address().as_usize()).get_as_str()?;
+            if key == "source" {
+                if state.source.is_some() {
+                    return Err(LinuxError::EINVAL);
+                }
+                state.source = Some(value.to_string());
+            } else if context.fs_type.has_param(key, false) {
+                debug!("ignored mount option {}={}", key, value);
+            } else {
+                return Err(LinuxError::EINVAL);
+            }
+        }
+        FSCONFIG_SET_FLAG => {
+            let key = key.ok_or(LinuxError::EINVAL)?;
+            if !value.is_null() || aux != 0 || !context.fs_type.has_param(key, true) {
+                return Err(LinuxError::EINVAL);
+            }
+            debug!("ignored mount flag {}", key);
+        }
+        FSCONFIG_CMD_CREATE => {
+            if key.is_some() || !value.is_null() || aux != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            // The source of an in-memory file system is only a name.
+            if context.fs_type.needs_device() {
+                let source = state.source.as_deref().ok_or(LinuxError::EINVAL)?;
+                state.device = Some(handle_file_path(AT_FDCWD, source)?);
+            }
+            state.created = true;
+        }
+        _ => return Err(LinuxError::EOPNOTSUPP),
+    }
+    Ok(0)
+}
+
+/// Create a detached mount from a file system context on which
+/// `FSCONFIG_CMD_CREATE` has been done.
+pub fn sys_fsmount(fs_fd: c_int, flags: u32, attr_flags: u32) -> LinuxResult<isize> {
+    info!(
+        "sys_fsmount <= fs_fd: {}, flags: {:#x}, attr_flags: {:#x}",
+        fs_fd, flags, attr_flags
+    );
+    if flags & !FSMOUNT_CLOEXEC != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let context = FsContext::from_fd(fs_fd)?;
+    let state = context.state.lock();
+    if !state.created {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let mount = MountFd {
+        fs_type: context.fs_type,
+        device: state.device.clone(),
+        mnt_dir: Mutex::new(None),
+    };
+    Ok(mount.add_to_fd_table(flags & FSMOUNT_CLOEXEC != 0)? as _)
+}
+
+/// Attach a mount created by `fsmount` to the tree.
+///
+/// The mount must be given by `from_dirfd` with `MOVE_MOUNT_F_EMPTY_PATH`,
+/// as moving mounts that are already attached is not supported.
+pub fn sys_move_mount(
+    from_dirfd: c_int,
+    from_path: UserConstPtr<c_char>,
+    to_dirfd: c_int,
+    to_path: UserConstPtr<c_char>,
+    flags: u32,
+) -> LinuxResult<isize> {
+    let from_path = nullable!(from_path.get_as_str())?.unwrap_or_default();
+    let to_path = nullable!(to_path.get_as_str())?.unwrap_or_default();
+    info!(
+        "sys_move_mount <= from: {} {:?}, to: {} {:?}, flags: {:#x}",
+        from_dirfd, from_path, to_dirfd, to_path, flags
+    );
+    if flags & MOVE_MOUNT_F_EMPTY_PATH == 0 || !from_path.is_empty() {
+        return Err(LinuxError::EINVAL);
+    }
+    let mount = get_file_like(from_dirfd)?
+        .into_any()
+        .downcast::<MountFd>()
+        .map_err(|_| LinuxError::EINVAL)?;
+
+    let mount_path = if flags & MOVE_MOUNT_T_EMPTY_PATH != 0 && to_path.is_empty() {
+        handle_file_path(AT_FDCWD, Directory::from_fd(to_dirfd)?.path())?
+    } else {
+        handle_file_path(to_dirfd, to_path)?
+    };
+
+    let mut mnt_dir = mount.mnt_dir.lock();
+    if mnt_dir.is_some() {
+        return Err(LinuxError::EINVAL);
+    }
+    if !mount_path.exists() {
+        return Err(LinuxError::ENOENT);
+    }
+    if check_mounted(&mount_path) {
+        return Err(LinuxError::EBUSY);
+    }
+    if !mount_fs(mount.fs_type, mount.device.as_ref(), &mount_path) {
+        return Err(LinuxError::EPERM);
+    }
+    *mnt_dir = Some(mount_path);
+    Ok(0)
+}
+
 /// Mounted File System
 /// "Mount" means read&write a file as a file system now
 struct MountedFs {
     //pub inner: Arc<Mutex<FATFileSystem>>,
-    pub device: FilePath,
+    fs_type: FsType,
+    pub device: Option<FilePath>,
     pub mnt_dir: FilePath,
 }
 
 impl MountedFs {
-    pub fn new(device: &FilePath, mnt_dir: &FilePath) -> Self {
+    fn new(fs_type: FsType, device: Option<&FilePath>, mnt_dir: &FilePath) -> Self {
         Self {
-            device: device.clone(),
+            fs_type,
+            device: device.cloned(),
             mnt_dir: mnt_dir.clone(),
         }
     }
 
     #[allow(unused)]
-    pub fn device(&self) -> FilePath {
+    pub fn device(&self) -> Option<FilePath> {
         self.device.clone()
     }
 
@@ -108,24 +404,30 @@ static MOUNTED: Mutex<Vec<MountedFs>> = Mutex::new(Vec::new());
 
 /// Mount a fatfs device
 pub fn mount_fat_fs(device_path: &FilePath, mount_path: &FilePath) -> bool {
+    mount_fs(FsType::Vfat, Some(device_path), mount_path)
+}
+
+/// Mount a file system of type `fs_type`, stored on `device_path` if it is
+/// not in memory.
+fn mount_fs(fs_type: FsType, device_path: Option<&FilePath>, mount_path: &FilePath) -> bool {
     // device_path needs symlink lookup, but mount_path does not
     // only opened files will be added to the symlink table for now, so do not convert now
     // debug!("mounting {} to {}", device_path.path(), mount_path.path());
     // if let Some(true_device_path) = real_path(device_path) {
+    let device = device_path.map_or("none", |path| path.as_str());
     if mount_path.exists() {
-        MOUNTED.lock().push(MountedFs::new(device_path, mount_path));
+        MOUNTED
+            .lock()
+            .push(MountedFs::new(fs_type, device_path, mount_path));
         info!(
-            "mounted {} to {}",
-            device_path.as_str(),
+            "mounted {} ({:?}) to {}",
+            device,
+            fs_type,
             mount_path.as_str()
         );
         return true;
     }
-    info!(
-        "mount failed: {} to {}",
-        device_path.as_str(),
-        mount_path.as_str()
-    );
+    info!("mount failed: {} to {}", device, mount_path.as_str());
     false
 }
 
@@ -142,3 +444,15 @@ pub fn check_mounted(path: &FilePath) -> bool {
     let mounted = MOUNTED.lock();
     mounted.iter().any(|m| path.starts_with(&m.mnt_dir()))
 }
+
+/// Get the magic number of the mounted file system holding `path`, if it is
+/// on one.
+pub fn mounted_fs_magic(path: &FilePath) -> Option<u32> {
+    let mounted = MOUNTED.lock();
+    // The last mount on a path hides the earlier ones.
+    mounted
+        .iter()
+        .rev()
+        .find(|m| path.starts_with(&m.mnt_dir()))
+        .map(|m| m.fs_type.magic())
+}
@@ -1,23 +1,29 @@
 use core::ffi::c_int;
 
-use axerrno::LinuxResult;
+use axerrno::{LinuxError, LinuxResult};
+use linux_raw_sys::general::{O_CLOEXEC, O_NONBLOCK};
 
 use crate::{
     file::{FileLike, Pipe, close_file_like},
     ptr::UserPtr,
 };
 
+/// Create a pipe, with `O_NONBLOCK` and `O_CLOEXEC` applied to both ends.
 pub fn sys_pipe2(fds: UserPtr<[c_int; 2]>, flags: i32) -> LinuxResult<isize> {
-    if flags != 0 {
-        warn!("sys_pipe2: unsupported flags: {}", flags);
+    let flags = flags as u32;
+    if flags & !(O_NONBLOCK | O_CLOEXEC) != 0 {
+        return Err(LinuxError::EINVAL);
     }
+    let cloexec = flags & O_CLOEXEC != 0;
 
     let fds = fds.get_as_mut()?;
 
     let (read_end, write_end) = Pipe::new();
-    let read_fd = read_end.add_to_fd_table()?;
+    read_end.set_nonblocking(flags & O_NONBLOCK != 0)?;
+    write_end.set_nonblocking(flags & O_NONBLOCK != 0)?;
+    let read_fd = read_end.add_to_fd_table(cloexec)?;
     let write_fd = write_end
-        .add_to_fd_table()
+        .add_to_fd_table(cloexec)
         .inspect_err(|_| close_file_like(read_fd).unwrap())?;
 
     fds[0] = read_fd;
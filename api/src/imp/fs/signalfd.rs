@@ -0,0 +1,50 @@
+use core::ffi::c_int;
+
+use axerrno::{LinuxError, LinuxResult};
+use axsignal::SignalSet;
+use linux_raw_sys::general::{O_CLOEXEC, O_NONBLOCK};
+
+use crate::{
+    file::{FileLike, SignalFd},
+    ptr::UserConstPtr,
+};
+
+const SFD_CLOEXEC: u32 = O_CLOEXEC;
+const SFD_NONBLOCK: u32 = O_NONBLOCK;
+
+/// Create a file descriptor that receives the signals in `mask`, or change
+/// the mask of the signalfd `fd` if it is not -1.
+pub fn sys_signalfd4(
+    fd: c_int,
+    mask: UserConstPtr<SignalSet>,
+    sizemask: usize,
+    flags: u32,
+) -> LinuxResult<isize> {
+    debug!(
+        "sys_signalfd4 <= fd: {}, sizemask: {}, flags: {:#x}",
+        fd, sizemask, flags
+    );
+    if sizemask != size_of::<SignalSet>() || flags & !(SFD_CLOEXEC | SFD_NONBLOCK) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let mask = *mask.get_as_ref()?;
+
+    if fd != -1 {
+        SignalFd::from_fd(fd)?.set_mask(mask);
+        return Ok(fd as _);
+    }
+
+    let signalfd = SignalFd::new(mask);
+    signalfd.set_nonblocking(flags & SFD_NONBLOCK != 0)?;
+    let fd = signalfd.add_to_fd_table(flags & SFD_CLOEXEC != 0)?;
+    Ok(fd as _)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn sys_signalfd(
+    fd: c_int,
+    mask: UserConstPtr<SignalSet>,
+    sizemask: usize,
+) -> LinuxResult<isize> {
+    sys_signalfd4(fd, mask, sizemask, 0)
+}
@@ -2,18 +2,57 @@ use core::ffi::{c_char, c_int};
 
 use axerrno::{AxError, LinuxError, LinuxResult};
 use axfs::fops::OpenOptions;
-use linux_raw_sys::general::{AT_EMPTY_PATH, AT_SYMLINK_NOFOLLOW, stat, statx};
+use axtask::{TaskExtRef, current};
+use linux_raw_sys::general::{
+    ANON_INODE_FS_MAGIC, AT_EACCESS, AT_EMPTY_PATH, AT_FDCWD, AT_SYMLINK_NOFOLLOW, O_RDONLY,
+    PIPEFS_MAGIC, PROC_SUPER_MAGIC, R_OK, SOCKFS_MAGIC, SYSFS_MAGIC, TMPFS_MAGIC, W_OK, X_OK, stat,
+    statfs, statx,
+};
 
+use super::mount::mounted_fs_magic;
 use crate::{
-    file::{Directory, File, FileLike, Kstat, get_file_like},
-    path::handle_file_path,
+    file::{
+        BLOCK_SIZE, Directory, FIFOS, File, FileLike, INODES, Kstat, Pipe, Socket, get_file_like,
+    },
+    path::{FilePath, handle_file_path, handle_file_path_resolve},
     ptr::{UserConstPtr, UserPtr, nullable},
 };
 
+/// The magic number of the root file system.
+#[cfg(feature = "lwext4_rs")]
+const ROOT_FS_MAGIC: u32 = linux_raw_sys::general::EXT4_SUPER_MAGIC;
+#[cfg(not(feature = "lwext4_rs"))]
+const ROOT_FS_MAGIC: u32 = linux_raw_sys::general::MSDOS_SUPER_MAGIC;
+
+/// The file systems that `axfs` mounts over the root one, with the magic
+/// number of their Linux counterparts.
+const MOUNT_FS_MAGICS: [(&str, u32); 4] = [
+    // Like devtmpfs, which reports the tmpfs magic.
+    ("/dev", TMPFS_MAGIC),
+    ("/tmp", TMPFS_MAGIC),
+    ("/proc", PROC_SUPER_MAGIC),
+    ("/sys", SYSFS_MAGIC),
+];
+
+fn fifo_stat(path: &str, mode: u32) -> Kstat {
+    let inode = INODES.get(path);
+    let mut kstat = Kstat::new(mode, 0, 0, BLOCK_SIZE, 1);
+    kstat.set_ino(inode.ino());
+    inode.fill(&mut kstat);
+    kstat
+}
+
 fn stat_at_path(path: &str) -> LinuxResult<Kstat> {
+    // Stat the file that symbolic links point to, so that it reports the same
+    // inode and owner as when it is reached directly.
+    let path = handle_file_path_resolve(AT_FDCWD, path, 0)?;
+    let path = path.as_str();
+    if let Some(mode) = FIFOS.mode(path) {
+        return Ok(fifo_stat(path, mode));
+    }
     let opts = OpenOptions::new().set_read(true);
     match axfs::fops::File::open(path, &opts) {
-        Ok(file) => File::new(file, path.into()).stat(),
+        Ok(file) => File::new(file, path.into(), O_RDONLY).stat(),
         Err(AxError::IsADirectory) => {
             let dir = axfs::fops::Directory::open_dir(path, &opts)?;
             Directory::new(dir, path.into()).stat()
@@ -23,18 +62,35 @@ fn stat_at_path(path: &str) -> LinuxResult<Kstat> {
 }
 
 fn lstat_at_path(path: &str) -> LinuxResult<Kstat> {
+    let path = handle_file_path(AT_FDCWD, path)?;
+    let path = path.as_str();
+    if let Some(mode) = FIFOS.mode(path) {
+        return Ok(fifo_stat(path, mode));
+    }
     // Use symlink_metadata API that doesn't follow symlinks
     let metadata = axfs::api::symlink_metadata(path)?;
     let ty = metadata.file_type() as u8;
     let perm = metadata.permissions().mode() as u32;
 
-    Ok(Kstat::new(
+    let mut kstat = Kstat::new(
         ((ty as u32) << 12) | perm,
         metadata.len(),
-        metadata.len() / 512 + 1,
-        512,
+        Kstat::blocks_of(metadata.len()),
+        BLOCK_SIZE,
         1,
-    ))
+    );
+    let inode = INODES.get(path);
+    kstat.set_ino(inode.ino());
+    inode.fill(&mut kstat);
+    Ok(kstat)
+}
+
+/// Check whether the current process may access the file at `path` as in
+/// `access`, a mask of `R_OK`, `W_OK` and `X_OK`, with its effective user and
+/// group ID.
+pub fn check_path_access(path: &str, access: u32) -> LinuxResult {
+    let cred = *current().task_ext().process_data().cred.read();
+    stat_at_path(path)?.check_access(access, cred.euid, cred.egid)
 }
 
 /// Get the file metadata by `path` and write into `statbuf`.
@@ -157,53 +213,113 @@ pub fn sys_statx(
 }
 
 /// Check whether the calling process can access the file pathname.
-/// Since the current system doesn't implement permission groups,
-/// we only check if the file exists and return 0.
+///
+/// The check is made with the real user and group ID of the process, or with
+/// the effective ones if `flags` has `AT_EACCESS`.
 pub fn sys_faccessat(
     dirfd: c_int,
     pathname: UserConstPtr<c_char>,
     mode: u32,
-    _flags: u32,
+    flags: u32,
 ) -> LinuxResult<isize> {
     let path = pathname.get_as_str()?;
     debug!(
-        "sys_faccessat <= dirfd: {}, pathname: {}, mode: {:#x}",
-        dirfd, path, mode
+        "sys_faccessat <= dirfd: {}, pathname: {}, mode: {:#x}, flags: {:#x}",
+        dirfd, path, mode, flags
     );
 
+    if mode & !(R_OK | W_OK | X_OK) != 0 || flags & !(AT_EACCESS | AT_SYMLINK_NOFOLLOW) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+
     // Handle the path resolution using the same logic as fstatat
     let resolved_path = handle_file_path(dirfd, path)?;
+    let kstat = if flags & AT_SYMLINK_NOFOLLOW != 0 {
+        lstat_at_path(resolved_path.as_str())?
+    } else {
+        stat_at_path(resolved_path.as_str())?
+    };
 
-    // Try to check if the file exists by attempting to stat it
-    match stat_at_path(resolved_path.as_str()) {
-        Ok(_) => {
-            // File exists, since we don't implement permissions,
-            // we assume all access types are allowed
-            debug!("sys_faccessat: file exists, allowing access");
-            Ok(0)
-        }
-        Err(LinuxError::ENOENT) => {
-            debug!("sys_faccessat: file does not exist");
-            Err(LinuxError::ENOENT)
-        }
-        Err(e) => {
-            debug!("sys_faccessat: error accessing file: {:?}", e);
-            Err(e)
-        }
-    }
+    let cred = *current().task_ext().process_data().cred.read();
+    let (uid, gid) = if flags & AT_EACCESS != 0 {
+        (cred.euid, cred.egid)
+    } else {
+        (cred.uid, cred.gid)
+    };
+    kstat.check_access(mode, uid, gid)?;
+    Ok(0)
 }
 
 /// Check whether the calling process can access the file pathname.
 /// This is the legacy access() syscall for x86_64.
-/// Since the current system doesn't implement permission groups,
-/// we only check if the file exists and return 0.
 pub fn sys_access(pathname: UserConstPtr<c_char>, mode: u32) -> LinuxResult<isize> {
     let path = pathname.get_as_str()?;
     debug!("sys_access <= pathname: {}, mode: {:#x}", path, mode);
 
-    // Use AT_FDCWD (-100) to indicate current working directory
-    const AT_FDCWD: c_int = -100;
-
     // Call faccessat with AT_FDCWD and no flags
     sys_faccessat(AT_FDCWD, pathname, mode, 0)
 }
+
+/// The magic number of the file system holding `path`, as in `f_type`.
+fn fs_magic(path: &FilePath) -> u32 {
+    if let Some(magic) = mounted_fs_magic(path) {
+        return magic;
+    }
+    let path = path.as_str();
+    MOUNT_FS_MAGICS
+        .iter()
+        .find(|(mount_point, _)| {
+            path.strip_prefix(mount_point)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+        .map_or(ROOT_FS_MAGIC, |&(_, magic)| magic)
+}
+
+/// Fill in `buf` for a file system of type `magic`.
+///
+/// The backends do not report their usage, so only the type and the sizes
+/// are known.
+fn fill_statfs(buf: UserPtr<statfs>, magic: u32) -> LinuxResult<isize> {
+    // SAFETY: `statfs` is plain old data.
+    let mut fs: statfs = unsafe { core::mem::zeroed() };
+    fs.f_type = magic as _;
+    fs.f_bsize = BLOCK_SIZE as _;
+    fs.f_frsize = BLOCK_SIZE as _;
+    fs.f_namelen = 255;
+    *buf.get_as_mut()? = fs;
+    Ok(0)
+}
+
+/// Get information about the file system holding the file at `path`.
+pub fn sys_statfs(path: UserConstPtr<c_char>, buf: UserPtr<statfs>) -> LinuxResult<isize> {
+    let path = path.get_as_str()?;
+    debug!("sys_statfs <= path: {}", path);
+
+    let path = handle_file_path_resolve(AT_FDCWD, path, 0)?;
+    if !path.exists() {
+        return Err(LinuxError::ENOENT);
+    }
+    fill_statfs(buf, fs_magic(&path))
+}
+
+/// Get information about the file system holding the file `fd`.
+///
+/// Pipes, sockets and the other files without a path report the magic
+/// number of the pseudo file system Linux keeps them in.
+pub fn sys_fstatfs(fd: c_int, buf: UserPtr<statfs>) -> LinuxResult<isize> {
+    debug!("sys_fstatfs <= fd: {}", fd);
+
+    let file = get_file_like(fd)?.into_any();
+    let path = if let Some(file) = file.downcast_ref::<File>() {
+        Some(file.path())
+    } else {
+        file.downcast_ref::<Directory>().map(|dir| dir.path())
+    };
+    let magic = match path {
+        Some(path) => fs_magic(&FilePath::new(path)?),
+        None if file.is::<Pipe>() => PIPEFS_MAGIC,
+        None if file.is::<Socket>() => SOCKFS_MAGIC,
+        None => ANON_INODE_FS_MAGIC,
+    };
+    fill_statfs(buf, magic)
+}
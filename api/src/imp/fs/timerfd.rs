@@ -0,0 +1,95 @@
+use core::{ffi::c_int, time::Duration};
+
+use axerrno::{LinuxError, LinuxResult};
+use linux_raw_sys::general::{
+    CLOCK_BOOTTIME, CLOCK_MONOTONIC, CLOCK_REALTIME, O_CLOEXEC, O_NONBLOCK, itimerspec, timespec,
+};
+
+use crate::{
+    file::{FileLike, TimerClock, TimerFd},
+    ptr::{UserConstPtr, UserPtr, nullable},
+    time::TimeValueLike,
+};
+
+const TFD_CLOEXEC: u32 = O_CLOEXEC;
+const TFD_NONBLOCK: u32 = O_NONBLOCK;
+const TFD_TIMER_ABSTIME: u32 = 1 << 0;
+
+/// Convert a time of a timer setting, checking that it is valid.
+fn timer_value(ts: &timespec) -> LinuxResult<Duration> {
+    if ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(ts.to_time_value())
+}
+
+fn itimerspec_of(left: Duration, interval: Duration) -> itimerspec {
+    itimerspec {
+        it_interval: timespec::from_time_value(interval),
+        it_value: timespec::from_time_value(left),
+    }
+}
+
+/// Create a timer that is read as a file, running on the clock `clockid`.
+pub fn sys_timerfd_create(clockid: c_int, flags: u32) -> LinuxResult<isize> {
+    debug!(
+        "sys_timerfd_create <= clockid: {}, flags: {:#x}",
+        clockid, flags
+    );
+    let clock = match clockid as u32 {
+        CLOCK_REALTIME => TimerClock::Realtime,
+        CLOCK_MONOTONIC | CLOCK_BOOTTIME => TimerClock::Monotonic,
+        _ => return Err(LinuxError::EINVAL),
+    };
+    if flags & !(TFD_CLOEXEC | TFD_NONBLOCK) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let timerfd = TimerFd::new(clock);
+    timerfd.set_nonblocking(flags & TFD_NONBLOCK != 0)?;
+    let fd = timerfd.add_to_fd_table(flags & TFD_CLOEXEC != 0)?;
+    Ok(fd as _)
+}
+
+/// Arm or disarm the timerfd `fd`, and get its old setting in `old_value`.
+///
+/// The time of `new_value` is absolute on the clock of the timer with
+/// `TFD_TIMER_ABSTIME`, and relative to now otherwise. A zero time disarms
+/// the timer.
+pub fn sys_timerfd_settime(
+    fd: c_int,
+    flags: u32,
+    new_value: UserConstPtr<itimerspec>,
+    old_value: UserPtr<itimerspec>,
+) -> LinuxResult<isize> {
+    debug!("sys_timerfd_settime <= fd: {}, flags: {:#x}", fd, flags);
+    if flags & !TFD_TIMER_ABSTIME != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let timerfd = TimerFd::from_fd(fd)?;
+    let new_value = new_value.get_as_ref()?;
+    let value = timer_value(&new_value.it_value)?;
+    let interval = timer_value(&new_value.it_interval)?;
+
+    let deadline = if value.is_zero() {
+        None
+    } else if flags & TFD_TIMER_ABSTIME != 0 {
+        Some(value)
+    } else {
+        Some(timerfd.now() + value)
+    };
+    let (left, old_interval) = timerfd.set_time(deadline, interval);
+    if let Some(old_value) = nullable!(old_value.get_as_mut())? {
+        *old_value = itimerspec_of(left, old_interval);
+    }
+    Ok(0)
+}
+
+/// Get the time until the next expiration of the timerfd `fd`, and its
+/// interval.
+pub fn sys_timerfd_gettime(fd: c_int, curr_value: UserPtr<itimerspec>) -> LinuxResult<isize> {
+    debug!("sys_timerfd_gettime <= fd: {}", fd);
+    let (left, interval) = TimerFd::from_fd(fd)?.get_time();
+    *curr_value.get_as_mut()? = itimerspec_of(left, interval);
+    Ok(0)
+}
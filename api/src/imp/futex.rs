@@ -1,4 +1,5 @@
 use axerrno::{LinuxError, LinuxResult};
+use axhal::time::wall_time;
 use axtask::{TaskExtRef, current};
 use linux_raw_sys::general::{
     FUTEX_CMD_MASK, FUTEX_CMP_REQUEUE, FUTEX_REQUEUE, FUTEX_WAIT, FUTEX_WAKE, timespec,
@@ -6,6 +7,7 @@ use linux_raw_sys::general::{
 
 use crate::{
     ptr::{UserConstPtr, UserPtr, nullable},
+    signal::{SIGNAL_CHECK_INTERVAL, unblocked_signal_pending},
     time::TimeValueLike,
 };
 
@@ -30,14 +32,30 @@ pub fn sys_futex(
                 return Err(LinuxError::EAGAIN);
             }
             let wq = futex_table.get_or_insert(addr);
+            let deadline = nullable!(timeout.get_as_ref())?
+                .map(|timeout| wall_time() + timeout.to_time_value());
 
-            if let Some(timeout) = nullable!(timeout.get_as_ref())? {
-                wq.wait_timeout(timeout.to_time_value());
-            } else {
-                wq.wait();
+            loop {
+                if unblocked_signal_pending() {
+                    return Err(LinuxError::EINTR);
+                }
+                let left = deadline.map(|deadline| deadline.saturating_sub(wall_time()));
+                if left.is_some_and(|left| left.is_zero()) {
+                    return Ok(0);
+                }
+                let timeout = left.map_or(SIGNAL_CHECK_INTERVAL, |left| {
+                    left.min(SIGNAL_CHECK_INTERVAL)
+                });
+                if !wq.wait_timeout(timeout) {
+                    return Ok(0);
+                }
+                // A wake sent while this task was between two waits is
+                // missed, so give up once the value changed, as the waker
+                // changes it first.
+                if *uaddr.get_as_ref()? != value {
+                    return Ok(0);
+                }
             }
-
-            Ok(0)
         }
         FUTEX_WAKE => {
             let wq = futex_table.get(addr);
@@ -1,11 +1,16 @@
-use alloc::vec;
+use alloc::{sync::Arc, vec, vec::Vec};
 use axerrno::{LinuxError, LinuxResult};
 use axhal::paging::{MappingFlags, PageSize};
 use axtask::{TaskExtRef, current};
-use linux_raw_sys::general::*;
-use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange, align_up_4k};
+use core::{any::Any, sync::atomic::Ordering};
+use linux_raw_sys::{general::*, prctl::PR_MCE_KILL_EARLY};
+use memory_addr::{MemoryAddr, PAGE_SIZE_4K, VirtAddr, VirtAddrRange, align_up_4k};
+use starry_core::{cow, mm::MmapBacking};
 
-use crate::file::{File, FileLike};
+use crate::{
+    file::{File, FileLike},
+    signal::send_memory_failure_signal,
+};
 
 bitflags::bitflags! {
     /// `PROT_*` flags for use with [`sys_mmap`].
@@ -64,6 +69,9 @@ bitflags::bitflags! {
         const HUGE = MAP_HUGETLB;
         /// Huge page 1g size
         const HUGE_1GB = MAP_HUGETLB | MAP_HUGE_1GB;
+        /// Put the mapping into the first 4GB of the address space.
+        #[cfg(target_arch = "x86_64")]
+        const BIT32 = MAP_32BIT;
     }
 }
 
@@ -112,22 +120,29 @@ pub fn sys_mmap(
             return Err(LinuxError::EINVAL);
         }
         let dst_addr = VirtAddr::from(start);
+        cow::release(&mut aspace, dst_addr, aligned_length);
         aspace.unmap(dst_addr, aligned_length)?;
+        process_data
+            .mmap_backings
+            .lock()
+            .remove(dst_addr, aligned_length);
         dst_addr
     } else {
+        let limit = VirtAddrRange::new(aspace.base(), aspace.end());
+        #[cfg(target_arch = "x86_64")]
+        let limit = if map_flags.contains(MmapFlags::BIT32) {
+            let end = limit.end.min(VirtAddr::from(1usize << 32));
+            if limit.start >= end {
+                return Err(LinuxError::ENOMEM);
+            }
+            VirtAddrRange::new(limit.start, end)
+        } else {
+            limit
+        };
+        // Prefer the area at or above the hint, then anywhere in the limit.
         aspace
-            .find_free_area(
-                VirtAddr::from(start),
-                aligned_length,
-                VirtAddrRange::new(aspace.base(), aspace.end()),
-                page_size,
-            )
-            .or(aspace.find_free_area(
-                aspace.base(),
-                aligned_length,
-                VirtAddrRange::new(aspace.base(), aspace.end()),
-                page_size,
-            ))
+            .find_free_area(VirtAddr::from(start), aligned_length, limit, page_size)
+            .or_else(|| aspace.find_free_area(limit.start, aligned_length, limit, page_size))
             .ok_or(LinuxError::ENOMEM)?
     };
 
@@ -144,19 +159,37 @@ pub fn sys_mmap(
         populate,
         page_size,
     )?;
+    // The new pages replace any that were hit by a memory error.
+    process_data.clear_poisoned(start_addr, aligned_length);
 
-    if populate {
+    let file = if populate {
         let file = File::from_fd(fd)?;
-        let file = file.inner();
-        let file_size = file.get_attr()?.size() as usize;
+        let inner = file.inner();
+        let file_size = inner.get_attr()?.size() as usize;
         if offset < 0 || offset as usize >= file_size {
             return Err(LinuxError::EINVAL);
         }
         let offset = offset as usize;
         let length = core::cmp::min(length, file_size - offset);
         let mut buf = vec![0u8; length];
-        file.read_at(offset as u64, &mut buf)?;
+        inner.read_at(offset as u64, &mut buf)?;
         aspace.write(start_addr, page_size, &buf)?;
+        drop(inner);
+        Some((file as Arc<dyn Any + Send + Sync>, offset as u64))
+    } else {
+        None
+    };
+    // Private anonymous memory needs no record.
+    if file.is_some() || map_flags.contains(MmapFlags::SHARED) {
+        let backing = MmapBacking {
+            size: aligned_length,
+            shared: map_flags.contains(MmapFlags::SHARED),
+            file,
+        };
+        process_data
+            .mmap_backings
+            .lock()
+            .insert(start_addr, backing);
     }
     Ok(start_addr.as_usize() as _)
 }
@@ -167,8 +200,11 @@ pub fn sys_munmap(addr: usize, length: usize) -> LinuxResult<isize> {
     let mut aspace = process_data.aspace.lock();
     let length = align_up_4k(length);
     let start_addr = VirtAddr::from(addr);
+    cow::release(&mut aspace, start_addr, length);
     aspace.unmap(start_addr, length)?;
     axhal::arch::flush_tlb(None);
+    process_data.clear_poisoned(start_addr, length);
+    process_data.mmap_backings.lock().remove(start_addr, length);
     Ok(0)
 }
 
@@ -186,7 +222,130 @@ pub fn sys_mprotect(addr: usize, length: usize, prot: u32) -> LinuxResult<isize>
     let mut aspace = process_data.aspace.lock();
     let length = align_up_4k(length);
     let start_addr = VirtAddr::from(addr);
-    aspace.protect(start_addr, length, permission_flags.into())?;
+    let flags = permission_flags.into();
+    cow::protect(&mut aspace, start_addr, length, flags)?;
+
+    Ok(0)
+}
+
+pub fn sys_madvise(addr: usize, length: usize, advice: u32) -> LinuxResult<isize> {
+    debug!(
+        "sys_madvise: addr: {:#x}, length: {:#x}, advice: {}",
+        addr, length, advice
+    );
+    if !addr.is_aligned_4k() {
+        return Err(LinuxError::EINVAL);
+    }
+    let length = align_up_4k(length);
+    if length == 0 {
+        return Ok(0);
+    }
 
+    let curr = current();
+    let process_data = curr.task_ext().process_data();
+    let mut aspace = process_data.aspace.lock();
+    let start_addr = VirtAddr::from(addr);
+    if !aspace.check_region_access(
+        VirtAddrRange::from_start_size(start_addr, length),
+        MappingFlags::empty(),
+    ) {
+        return Err(LinuxError::ENOMEM);
+    }
+
+    match advice {
+        MADV_NORMAL | MADV_RANDOM | MADV_SEQUENTIAL | MADV_WILLNEED => {}
+        MADV_DONTNEED | MADV_FREE => {
+            // The populated pages are unmapped, so that the next access faults
+            // in zeroes, or the file contents for a private file mapping.
+            // Shared memory keeps its contents.
+            let shm_data = process_data.shm_data.lock();
+            let backings = process_data.mmap_backings.lock();
+            let is_shared = |vaddr: VirtAddr| {
+                let in_segment = shm_data.attached.values().any(|attach| {
+                    (attach.addr..attach.addr + attach.segment.size).contains(&vaddr)
+                });
+                in_segment
+                    || backings
+                        .overlapping(vaddr, PAGE_SIZE_4K)
+                        .any(|(_, backing)| backing.shared)
+            };
+
+            // Runs of pages with the same flags are remapped together. They
+            // are all found first, so that nothing changes on an error.
+            let end = start_addr + length;
+            let mut runs: Vec<(VirtAddr, usize, MappingFlags, PageSize)> = Vec::new();
+            let mut vaddr = start_addr;
+            while vaddr < end {
+                // Pages that have not been populated yet are already zero.
+                let Ok((_, flags, page_size)) = aspace.page_table().query(vaddr) else {
+                    vaddr += PAGE_SIZE_4K;
+                    continue;
+                };
+                // A copy-on-write page is mapped read-only until it is copied.
+                let flags = cow::cow_flags(&aspace, vaddr).unwrap_or(flags);
+                if is_shared(vaddr) {
+                    vaddr += PAGE_SIZE_4K;
+                    continue;
+                }
+                let size = page_size as usize;
+                // A huge page cannot be split.
+                if !vaddr.is_aligned(page_size) || vaddr + size > end {
+                    return Err(LinuxError::EINVAL);
+                }
+                match runs.last_mut() {
+                    Some((start, run_size, run_flags, run_page_size))
+                        if *start + *run_size == vaddr
+                            && *run_flags == flags
+                            && *run_page_size == page_size =>
+                    {
+                        *run_size += size
+                    }
+                    _ => runs.push((vaddr, size, flags, page_size)),
+                }
+                vaddr += size;
+            }
+
+            for (start, size, flags, page_size) in runs {
+                let files = backings
+                    .overlapping(start, size)
+                    .filter_map(|(addr, backing)| {
+                        let (file, offset) = backing.file.clone()?;
+                        Some((addr, backing.size, file, offset))
+                    })
+                    .collect::<Vec<_>>();
+                cow::release(&mut aspace, start, size);
+                aspace.unmap(start, size)?;
+                aspace.map_alloc(start, size, flags, !files.is_empty(), page_size)?;
+                for (addr, backing_size, file, offset) in files {
+                    let from = addr.max(start);
+                    let to = (addr + backing_size).min(start + size);
+                    let file = file.downcast::<File>().map_err(|_| LinuxError::EINVAL)?;
+                    // Past the end of the file the pages stay zero.
+                    let mut buf = vec![0u8; to - from];
+                    file.inner()
+                        .read_at(offset + (from - addr) as u64, &mut buf)?;
+                    aspace.write(from, page_size, &buf)?;
+                }
+            }
+            axhal::arch::flush_tlb(None);
+        }
+        MADV_HWPOISON => {
+            // Simulate an uncorrectable memory error on the pages. They become
+            // inaccessible and any later access gets `SIGBUS`.
+            aspace.protect(start_addr, length, MappingFlags::empty())?;
+            axhal::arch::flush_tlb(None);
+            drop(aspace);
+            process_data.poisoned_pages.lock().extend(
+                (addr..addr + length)
+                    .step_by(PAGE_SIZE_4K)
+                    .map(VirtAddr::from),
+            );
+            // Processes asking for early kill are told before any access.
+            if process_data.mce_kill_policy.load(Ordering::Acquire) == PR_MCE_KILL_EARLY {
+                send_memory_failure_signal(&curr.task_ext().thread, start_addr, false)?;
+            }
+        }
+        _ => return Err(LinuxError::EINVAL),
+    }
     Ok(0)
 }
@@ -1,7 +1,9 @@
 mod brk;
 mod mmap;
+mod process_vm;
 mod shm;
 
 pub use self::brk::*;
 pub use self::mmap::*;
+pub use self::process_vm::*;
 pub use self::shm::*;
@@ -0,0 +1,187 @@
+//! Cross-process memory access system calls.
+
+use alloc::{sync::Arc, vec::Vec};
+use axerrno::{LinuxError, LinuxResult};
+use axhal::paging::{MappingFlags, PageSize};
+use axprocess::{Pid, Process};
+use axtask::{TaskExtRef, current};
+use linux_raw_sys::general::iovec;
+use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange};
+use starry_core::{
+    cow,
+    task::{ProcessData, get_process},
+};
+
+use crate::ptr::{UserConstPtr, UserPtr};
+
+/// The most `iovec`s in one call, like `UIO_MAXIOV`.
+const UIO_MAXIOV: usize = 1024;
+
+/// Get the process `pid`, if the caller may access its memory.
+///
+/// Like the `ptrace` access checks, the target must be a child of the caller
+/// or run with the caller's user ID, unless the caller is root.
+fn target_process(pid: Pid) -> LinuxResult<Arc<Process>> {
+    let curr = current();
+    let process = get_process(pid)?;
+    let data = process.data::<ProcessData>().ok_or(LinuxError::ESRCH)?;
+    let cred = *curr.task_ext().process_data().cred.read();
+    let target_cred = *data.cred.read();
+    let is_child = process
+        .parent()
+        .is_some_and(|parent| Arc::ptr_eq(&parent, curr.task_ext().thread.process()));
+    let same_user = target_cred.uid == cred.euid && target_cred.euid == cred.euid;
+    if !cred.is_root() && !is_child && !same_user {
+        return Err(LinuxError::EPERM);
+    }
+    Ok(process)
+}
+
+/// Copy between the local buffers in `local_iov` and the ranges of the
+/// address space of process `pid` in `remote_iov`, into the latter if
+/// `write` is set.
+///
+/// The local buffers are all checked first. A remote range that is not
+/// accessible ends the copy, and the bytes copied before it are returned,
+/// like Linux.
+fn process_vm_copy(
+    pid: Pid,
+    local_iov: UserConstPtr<iovec>,
+    liovcnt: usize,
+    remote_iov: UserConstPtr<iovec>,
+    riovcnt: usize,
+    flags: usize,
+    write: bool,
+) -> LinuxResult<isize> {
+    if flags != 0 || liovcnt > UIO_MAXIOV || riovcnt > UIO_MAXIOV {
+        return Err(LinuxError::EINVAL);
+    }
+    let local_iov = local_iov.get_as_slice(liovcnt)?;
+    let remote_iov = remote_iov.get_as_slice(riovcnt)?;
+    for iovs in [local_iov, remote_iov] {
+        iovs.iter()
+            .try_fold(0usize, |acc, iov| acc.checked_add(iov.iov_len as usize))
+            .filter(|&total| total <= isize::MAX as usize)
+            .ok_or(LinuxError::EINVAL)?;
+    }
+
+    // The local buffers are checked before the address space of the target
+    // is locked, as it may be that of the caller.
+    let local = local_iov.iter().filter(|iov| iov.iov_len > 0);
+    let mut local_read: Vec<&'static mut [u8]> = Vec::new();
+    let mut local_write: Vec<&'static [u8]> = Vec::new();
+    for iov in local {
+        let (base, len) = (iov.iov_base as usize, iov.iov_len as usize);
+        if write {
+            local_write.push(UserConstPtr::<u8>::from(base).get_as_slice(len)?);
+        } else {
+            local_read.push(UserPtr::<u8>::from(base).get_as_mut_slice(len)?);
+        }
+    }
+    let local_lens: Vec<usize> = if write {
+        local_write.iter().map(|buf| buf.len()).collect()
+    } else {
+        local_read.iter().map(|buf| buf.len()).collect()
+    };
+
+    let process = target_process(pid)?;
+    let data = process.data::<ProcessData>().ok_or(LinuxError::ESRCH)?;
+    let mut aspace = data.aspace.lock();
+    let access_flags = if write {
+        MappingFlags::WRITE
+    } else {
+        MappingFlags::READ
+    };
+
+    let mut copied = 0;
+    let mut faulted = false;
+    let (mut index, mut offset) = (0, 0);
+    'remote: for iov in remote_iov.iter().filter(|iov| iov.iov_len > 0) {
+        let start = VirtAddr::from(iov.iov_base as usize);
+        let len = iov.iov_len as usize;
+        faulted = start.as_usize().checked_add(len).is_none()
+            // Copy-on-write pages get their own frame before they are
+            // written.
+            || (write && cow::unshare(&mut aspace, data.user_cpus(), start, len).is_err())
+            || !aspace
+                .check_region_access(VirtAddrRange::from_start_size(start, len), access_flags)
+            || aspace
+                .populate_area(
+                    start.align_down_4k(),
+                    (start + len).align_up_4k() - start.align_down_4k(),
+                    access_flags,
+                )
+                .is_err();
+        if faulted {
+            break;
+        }
+
+        let mut done = 0;
+        while done < len {
+            let Some(&local_len) = local_lens.get(index) else {
+                break 'remote;
+            };
+            let size = (len - done).min(local_len - offset);
+            let local = offset..offset + size;
+            let result = if write {
+                aspace.write(start + done, PageSize::Size4K, &local_write[index][local])
+            } else {
+                aspace.read(
+                    start + done,
+                    PageSize::Size4K,
+                    &mut local_read[index][local],
+                )
+            };
+            if result.is_err() {
+                faulted = true;
+                break 'remote;
+            }
+            done += size;
+            copied += size;
+            offset += size;
+            if offset == local_len {
+                index += 1;
+                offset = 0;
+            }
+        }
+    }
+
+    if copied == 0 && faulted {
+        return Err(LinuxError::EFAULT);
+    }
+    Ok(copied as _)
+}
+
+/// Read the memory of process `pid` at the ranges in `remote_iov` into the
+/// buffers in `local_iov`.
+pub fn sys_process_vm_readv(
+    pid: Pid,
+    local_iov: UserConstPtr<iovec>,
+    liovcnt: usize,
+    remote_iov: UserConstPtr<iovec>,
+    riovcnt: usize,
+    flags: usize,
+) -> LinuxResult<isize> {
+    debug!(
+        "sys_process_vm_readv <= pid: {}, liovcnt: {}, riovcnt: {}",
+        pid, liovcnt, riovcnt
+    );
+    process_vm_copy(pid, local_iov, liovcnt, remote_iov, riovcnt, flags, false)
+}
+
+/// Write the buffers in `local_iov` to the memory of process `pid` at the
+/// ranges in `remote_iov`.
+pub fn sys_process_vm_writev(
+    pid: Pid,
+    local_iov: UserConstPtr<iovec>,
+    liovcnt: usize,
+    remote_iov: UserConstPtr<iovec>,
+    riovcnt: usize,
+    flags: usize,
+) -> LinuxResult<isize> {
+    debug!(
+        "sys_process_vm_writev <= pid: {}, liovcnt: {}, riovcnt: {}",
+        pid, liovcnt, riovcnt
+    );
+    process_vm_copy(pid, local_iov, liovcnt, remote_iov, riovcnt, flags, true)
+}
@@ -2,46 +2,74 @@
 
 use alloc::sync::Arc;
 use axerrno::{LinuxError, LinuxResult};
-use axhal::paging::{MappingFlags, PageSize};
+use axhal::paging::MappingFlags;
 use axtask::{TaskExtRef, current};
-use memory_addr::VirtAddr;
-use starry_core::shm::{ShmId, ShmKey, ShmSegment, ShmidDs, shm_manager};
+use core::sync::atomic::Ordering;
+use linux_raw_sys::general::RLIMIT_MEMLOCK;
+use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange};
+use starry_core::{
+    cow,
+    shm::{
+        SHM_LIMITS, SHMMIN, SHMSEG, ShmBacking, ShmId, ShmInfo, ShmKey, ShmSegment, ShmUsage,
+        ShmidDs, shm_manager,
+    },
+};
 
 use crate::ptr::UserPtr;
 
 const IPC_RMID: i32 = 0;
 const IPC_STAT: i32 = 2;
 const IPC_SET: i32 = 1;
+const IPC_INFO: i32 = 3;
+const SHM_LOCK: i32 = 11;
+const SHM_UNLOCK: i32 = 12;
+const SHM_STAT: i32 = 13;
+const SHM_INFO: i32 = 14;
+const SHM_STAT_ANY: i32 = 15;
 
 const SHM_RND: i32 = 0o020000;
 const SHM_RDONLY: i32 = 0o010000;
-
-const MAX_SHM_SIZE: usize = 1 << 30; // 1GB
+const SHM_REMAP: i32 = 0o040000;
 
 /// Validates segment consistency and permissions.
 fn validate_segment(segment: &Arc<ShmSegment>, shmflg: i32) -> LinuxResult<()> {
-    if segment
-        .marked_for_deletion
-        .load(core::sync::atomic::Ordering::SeqCst)
-    {
+    if segment.marked_for_deletion.load(Ordering::SeqCst) {
         return Err(LinuxError::EIDRM);
     }
     if !segment.validate() {
         return Err(LinuxError::EINVAL);
     }
     let access = if (shmflg & SHM_RDONLY) != 0 { 0o4 } else { 0o6 };
-    if !segment.check_permissions(0, 0, access) {
+    let cred = *current().task_ext().process_data().cred.read();
+    if !segment.check_permissions(cred.euid, cred.egid, access) {
         return Err(LinuxError::EACCES);
     }
     Ok(())
 }
 
+/// Checks that the caller may read the attributes of the segment.
+fn check_read(segment: &ShmSegment) -> LinuxResult<()> {
+    let cred = *current().task_ext().process_data().cred.read();
+    if !segment.check_permissions(cred.euid, cred.egid, 0o4) {
+        return Err(LinuxError::EACCES);
+    }
+    Ok(())
+}
+
+/// Checks that the caller owns or created the segment, or is root, as
+/// changing or removing it requires.
+fn check_owner(segment: &ShmSegment) -> LinuxResult<()> {
+    let cred = *current().task_ext().process_data().cred.read();
+    let perm = segment.get_stat().shm_perm;
+    if !cred.is_root() && cred.euid != perm.uid && cred.euid != perm.cuid {
+        return Err(LinuxError::EPERM);
+    }
+    Ok(())
+}
+
 /// shmget system call - get shared memory segment.
 pub fn sys_shmget(key: ShmKey, size: usize, flags: i32) -> LinuxResult<isize> {
     info!("sys_shmget: key={}, size={}, flags={:#x}", key, size, flags);
-    if size > MAX_SHM_SIZE || (size == 0 && key != starry_core::shm::IPC_PRIVATE) {
-        return Err(LinuxError::EINVAL);
-    }
     let segment = shm_manager().lock().get_or_create(key, size, flags)?;
     Ok(segment.id as isize)
 }
@@ -58,53 +86,76 @@ pub fn sys_shmat(shmid: ShmId, shmaddr: usize, shmflg: i32) -> LinuxResult<isize
     if (shmflg & SHM_RND) == 0 && shmaddr != 0 && (shmaddr & (axhal::mem::PAGE_SIZE_4K - 1)) != 0 {
         return Err(LinuxError::EINVAL);
     }
+    if (shmflg & SHM_REMAP) != 0 && shmaddr == 0 {
+        return Err(LinuxError::EINVAL);
+    }
     let curr = current();
     let process_data = curr.task_ext().process_data();
     let mut aspace = process_data.aspace.lock();
-    let segment = {
-        let manager = shm_manager().lock();
-        let segment = manager.get_by_id(shmid)?;
-        validate_segment(&segment, shmflg)?;
-        segment.inc_attach();
-        segment
-    };
+    let segment = shm_manager().lock().get_by_id(shmid)?;
+    validate_segment(&segment, shmflg)?;
+    if !segment.try_attach() {
+        return Err(LinuxError::EIDRM);
+    }
     let size = segment.size;
-    let vaddr = if (shmflg & SHM_RND) != 0 {
-        if shmaddr == 0 {
-            return Err(LinuxError::EINVAL);
+    let page_size = segment.page_size;
+    // The address space stays locked from choosing the address until the
+    // segment is mapped, so concurrent attaches cannot pick the same range.
+    let mut attach = || {
+        let limit = VirtAddrRange::new(aspace.base(), aspace.end());
+        let vaddr = if shmaddr == 0 {
+            aspace
+                .find_free_area(aspace.base(), size, limit, page_size)
+                .ok_or(LinuxError::ENOMEM)?
+        } else {
+            let vaddr = if (shmflg & SHM_RND) != 0 {
+                VirtAddr::from(shmaddr).align_down(page_size)
+            } else if VirtAddr::from(shmaddr).is_aligned(page_size) {
+                VirtAddr::from(shmaddr)
+            } else {
+                return Err(LinuxError::EINVAL);
+            };
+            if (shmflg & SHM_REMAP) != 0 {
+                // A segment only partly covered by the range cannot be
+                // detached, so check for one before unmapping anything, and
+                // detach the segments only once their pages are gone, so a
+                // failed unmap leaves them attached.
+                let mut shm_data = process_data.shm_data.lock();
+                shm_data.check_detach_range(vaddr, size)?;
+                cow::release(&mut aspace, vaddr, size);
+                aspace.unmap(vaddr, size)?;
+                let pid = curr.task_ext().thread.process().pid() as i32;
+                shm_data.detach_range(vaddr, size, pid)?;
+                drop(shm_data);
+                process_data.mmap_backings.lock().remove(vaddr, size);
+                process_data.clear_poisoned(vaddr, size);
+            } else if aspace.find_free_area(vaddr, size, limit, page_size) != Some(vaddr) {
+                // An explicit address must not overlap existing mappings.
+                return Err(LinuxError::EINVAL);
+            }
+            vaddr
+        };
+        let mut flags = MappingFlags::USER | MappingFlags::READ;
+        if (shmflg & SHM_RDONLY) == 0 {
+            flags |= MappingFlags::WRITE;
         }
-        VirtAddr::from(shmaddr & !(axhal::mem::PAGE_SIZE_4K - 1))
-    } else if aspace.contains_range(VirtAddr::from(shmaddr), size) {
-        return Err(LinuxError::EINVAL);
-    } else {
-        aspace
-            .find_free_area(
-                VirtAddr::from(shmaddr),
-                size,
-                memory_addr::VirtAddrRange::new(aspace.base(), aspace.end()),
-                PageSize::Size4K,
-            )
-            .or_else(|| {
-                aspace.find_free_area(
-                    aspace.base(),
-                    size,
-                    memory_addr::VirtAddrRange::new(aspace.base(), aspace.end()),
-                    PageSize::Size4K,
-                )
-            })
-            .ok_or(LinuxError::ENOMEM)?
+        match segment.backing {
+            ShmBacking::Contiguous(paddr) => {
+                aspace.map_linear(vaddr, paddr, size, flags, page_size)?
+            }
+            // Reserve the range, the pages are mapped on first touch.
+            ShmBacking::Lazy(_) => aspace.map_alloc(vaddr, size, flags, false, page_size)?,
+        }
+        Ok((vaddr, flags))
     };
-    let mut flags = MappingFlags::USER | MappingFlags::READ;
-    if (shmflg & SHM_RDONLY) == 0 {
-        flags |= MappingFlags::WRITE;
-    }
-    let map_result = aspace.map_linear(vaddr, segment.paddr, size, flags, PageSize::Size4K);
-    if let Err(e) = map_result {
-        segment.dec_attach();
-        return Err(LinuxError::from(e));
-    }
+    // Undo the attach count taken above if the segment cannot be mapped.
+    let (vaddr, flags) = attach().inspect_err(|_| {
+        if segment.dec_attach() {
+            let _ = shm_manager().lock().remove(shmid);
+        }
+    })?;
     let mut shm_data = process_data.shm_data.lock();
-    shm_data.attach(shmid, vaddr, segment);
+    shm_data.attach(shmid, vaddr, segment, flags);
     Ok(vaddr.as_usize() as isize)
 }
 
@@ -117,18 +168,12 @@ pub fn sys_shmdt(shmaddr: usize) -> LinuxResult<isize> {
     let mut shm_data = process_data.shm_data.lock();
     let vaddr = VirtAddr::from(shmaddr);
     let attach = shm_data.detach(vaddr).ok_or(LinuxError::EINVAL)?;
+    cow::release(&mut aspace, vaddr, attach.segment.size);
     aspace.unmap(vaddr, attach.segment.size)?;
-    attach.segment.dec_attach();
     let pid = curr.task_ext().thread.process().pid() as i32;
     attach.segment.set_last_pid(pid);
-    let mut manager = shm_manager().lock();
-    if attach
-        .segment
-        .marked_for_deletion
-        .load(core::sync::atomic::Ordering::SeqCst)
-        && attach.segment.get_attach_count() == 0
-    {
-        manager.remove(attach.id)?;
+    if attach.segment.dec_attach() {
+        shm_manager().lock().remove(attach.id)?;
     }
     Ok(0)
 }
@@ -136,15 +181,46 @@ pub fn sys_shmdt(shmaddr: usize) -> LinuxResult<isize> {
 /// shmctl system call - control shared memory segment.
 pub fn sys_shmctl(shmid: ShmId, cmd: i32, buf: UserPtr<ShmidDs>) -> LinuxResult<isize> {
     info!("sys_shmctl: shmid={}, cmd={}", shmid, cmd);
-    let mut manager = shm_manager().lock();
-    let segment = manager.get_by_id(shmid)?;
+    match cmd {
+        IPC_INFO => {
+            let info = UserPtr::<ShmInfo>::from(buf.address().as_usize());
+            *info.get_as_mut()? = ShmInfo {
+                shmmax: SHM_LIMITS.shmmax.load(Ordering::Relaxed),
+                shmmin: SHMMIN,
+                shmmni: SHM_LIMITS.shmmni.load(Ordering::Relaxed),
+                shmseg: SHMSEG,
+                shmall: SHM_LIMITS.shmall.load(Ordering::Relaxed),
+                __unused: [0; 4],
+            };
+            return Ok(shm_manager().lock().max_index() as isize);
+        }
+        SHM_INFO => {
+            let usage = UserPtr::<ShmUsage>::from(buf.address().as_usize());
+            let manager = shm_manager().lock();
+            *usage.get_as_mut()? = manager.usage();
+            return Ok(manager.max_index() as isize);
+        }
+        SHM_STAT | SHM_STAT_ANY => {
+            if shmid < 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            let segment = shm_manager().lock().get_by_index(shmid as usize)?;
+            // Only `SHM_STAT_ANY` may read a segment without read permission.
+            if cmd == SHM_STAT {
+                check_read(&segment)?;
+            }
+            *buf.get_as_mut()? = segment.get_stat();
+            return Ok(segment.id as isize);
+        }
+        _ => {}
+    }
+
+    let segment = shm_manager().lock().get_by_id(shmid)?;
     match cmd {
         IPC_RMID => {
-            segment
-                .marked_for_deletion
-                .store(true, core::sync::atomic::Ordering::SeqCst);
-            if segment.get_attach_count() == 0 {
-                manager.remove(shmid)?;
+            check_owner(&segment)?;
+            if segment.mark_for_deletion() {
+                shm_manager().lock().remove(shmid)?;
             }
             Ok(0)
         }
@@ -152,6 +228,7 @@ pub fn sys_shmctl(shmid: ShmId, cmd: i32, buf: UserPtr<ShmidDs>) -> LinuxResult<
             if buf.is_null() {
                 return Err(LinuxError::EFAULT);
             }
+            check_read(&segment)?;
             let stat = segment.get_stat();
             let user_stat = buf.get_as_mut()?;
             *user_stat = stat;
@@ -161,6 +238,7 @@ pub fn sys_shmctl(shmid: ShmId, cmd: i32, buf: UserPtr<ShmidDs>) -> LinuxResult<
             if buf.is_null() {
                 return Err(LinuxError::EFAULT);
             }
+            check_owner(&segment)?;
             let user_stat = buf.get_as_mut()?;
             segment.set_perm(
                 user_stat.shm_perm.uid,
@@ -169,6 +247,31 @@ pub fn sys_shmctl(shmid: ShmId, cmd: i32, buf: UserPtr<ShmidDs>) -> LinuxResult<
             );
             Ok(0)
         }
+        SHM_LOCK | SHM_UNLOCK => {
+            let lock = cmd == SHM_LOCK;
+            if lock == segment.is_locked() {
+                return Ok(0);
+            }
+            check_owner(&segment)?;
+            let curr = current();
+            let process_data = curr.task_ext().process_data();
+            let cred = *process_data.cred.read();
+            if !cred.is_root() {
+                let perm = segment.get_stat().shm_perm;
+                if lock {
+                    let limit = process_data.rlimits.read().get(RLIMIT_MEMLOCK).soft;
+                    if limit == 0 {
+                        return Err(LinuxError::EPERM);
+                    }
+                    let locked = shm_manager().lock().locked_size(perm.uid);
+                    if (locked + segment.size) as u64 > limit {
+                        return Err(LinuxError::ENOMEM);
+                    }
+                }
+            }
+            segment.set_locked(lock);
+            Ok(0)
+        }
         _ => {
             warn!("sys_shmctl: unsupported command {}", cmd);
             Err(LinuxError::EINVAL)
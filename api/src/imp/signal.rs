@@ -3,7 +3,7 @@ use core::{mem, time::Duration};
 use alloc::sync::Arc;
 use axerrno::{LinuxError, LinuxResult};
 use axhal::arch::TrapFrame;
-use axprocess::{Pid, Thread};
+use axprocess::{Pid, ProcessGroup, Thread};
 use axsignal::{SignalInfo, SignalSet, SignalStack, Signo};
 use axtask::{TaskExtRef, current};
 use linux_raw_sys::general::{
@@ -14,7 +14,10 @@ use starry_core::task::{get_process, get_process_group, get_thread, processes};
 
 use crate::{
     ptr::{UserConstPtr, UserPtr, nullable},
-    signal::{check_signals, send_signal_process, send_signal_process_group, send_signal_thread},
+    signal::{
+        check_signals, release_signal, send_signal_process, send_signal_process_group,
+        send_signal_thread,
+    },
     time::TimeValueLike,
 };
 
@@ -84,9 +87,16 @@ pub fn sys_rt_sigaction(
     Ok(0)
 }
 
+/// Get the signals that are pending on the calling thread or its process
+/// while blocked.
 pub fn sys_rt_sigpending(set: UserPtr<SignalSet>, sigsetsize: usize) -> LinuxResult<isize> {
     check_sigset_size(sigsetsize)?;
-    *set.get_as_mut()? = current().task_ext().thread_data().signal.pending();
+    let curr = current();
+    let signal = &curr.task_ext().thread_data().signal;
+    let mut pending = signal.pending();
+    // Unblocked signals are about to be delivered, so Linux leaves them out.
+    pending &= signal.with_blocked_mut(|blocked| *blocked);
+    *set.get_as_mut()? = pending;
     Ok(0)
 }
 
@@ -98,40 +108,60 @@ fn make_siginfo(signo: u32, code: i32) -> LinuxResult<Option<SignalInfo>> {
     Ok(Some(SignalInfo::new(signo, code)))
 }
 
+/// Send `sig` to every process in `pg`. Signal 0 (`None`) only checks that
+/// the group has members.
+fn kill_process_group(pg: &ProcessGroup, sig: Option<SignalInfo>) -> usize {
+    match sig {
+        Some(sig) => send_signal_process_group(pg, sig),
+        None => pg.processes().len(),
+    }
+}
+
+/// Send a signal to a process (`pid > 0`), the caller's process group
+/// (`pid == 0`), every process but init and the caller (`pid == -1`), or
+/// process group `-pid` (`pid < -1`).
+///
+/// Returns `ESRCH` if no process was signalled.
 pub fn sys_kill(pid: i32, signo: u32) -> LinuxResult<isize> {
-    let Some(sig) = make_siginfo(signo, SI_USER as _)? else {
-        // TODO: should also check permissions
-        return Ok(0);
-    };
+    // TODO: should also check permissions
+    let sig = make_siginfo(signo, SI_USER as _)?;
 
     let curr = current();
-    match pid {
+    let count = match pid {
         1.. => {
             let proc = get_process(pid as Pid)?;
-            send_signal_process(&proc, sig)?;
-            Ok(1)
+            if let Some(sig) = sig {
+                send_signal_process(&proc, sig)?;
+            }
+            1
         }
         0 => {
             let pg = curr.task_ext().thread.process().group();
-            Ok(send_signal_process_group(&pg, sig) as _)
+            kill_process_group(&pg, sig)
         }
         -1 => {
+            let curr_pid = curr.task_ext().thread.process().pid();
             let mut count = 0;
             for proc in processes() {
-                if proc.is_init() {
-                    // init process
+                if proc.is_init() || proc.pid() == curr_pid {
                     continue;
                 }
-                send_signal_process(&proc, sig.clone())?;
-                count += 1;
+                count += match &sig {
+                    Some(sig) => send_signal_process(&proc, sig.clone()).is_ok() as usize,
+                    None => 1,
+                };
             }
-            Ok(count)
+            count
         }
         ..-1 => {
             let pg = get_process_group((-pid) as Pid)?;
-            Ok(send_signal_process_group(&pg, sig) as _)
+            kill_process_group(&pg, sig)
         }
+    };
+    if count == 0 {
+        return Err(LinuxError::ESRCH);
     }
+    Ok(0)
 }
 
 pub fn sys_tkill(tid: Pid, signo: u32) -> LinuxResult<isize> {
@@ -231,6 +261,7 @@ pub fn sys_rt_sigtimedwait(
     else {
         return Err(LinuxError::EAGAIN);
     };
+    release_signal(current().task_ext().process_data(), &sig);
 
     if let Some(info) = nullable!(info.get_as_mut())? {
         *info = sig.0;
@@ -1,24 +1,141 @@
 use core::ffi::c_char;
 
-use axerrno::LinuxResult;
-use linux_raw_sys::system::new_utsname;
+use axerrno::{LinuxError, LinuxResult};
+use axhal::time::monotonic_time;
+use axprocess::Pid;
+use axtask::{TaskExtRef, current};
+use linux_raw_sys::{
+    general::{GRND_INSECURE, GRND_NONBLOCK, GRND_RANDOM, RLIM_NLIMITS, RLIMIT_NOFILE, rlimit64},
+    system::{new_utsname, sysinfo},
+};
+use spin::Mutex;
+use starry_core::{
+    mm::system_memory,
+    resource::Rlimit,
+    task::{ProcessData, get_process, processes},
+};
 
-use crate::ptr::UserPtr;
+use crate::{
+    file::AX_FILE_LIMIT,
+    ptr::{UserConstPtr, UserPtr, nullable},
+};
 
 pub fn sys_getuid() -> LinuxResult<isize> {
-    Ok(0)
+    Ok(current().task_ext().process_data().cred.read().uid as _)
 }
 
 pub fn sys_geteuid() -> LinuxResult<isize> {
-    Ok(1)
+    Ok(current().task_ext().process_data().cred.read().euid as _)
 }
 
 pub fn sys_getgid() -> LinuxResult<isize> {
-    Ok(0)
+    Ok(current().task_ext().process_data().cred.read().gid as _)
 }
 
 pub fn sys_getegid() -> LinuxResult<isize> {
-    Ok(1)
+    Ok(current().task_ext().process_data().cred.read().egid as _)
+}
+
+/// Set the user identity of the calling process.
+///
+/// Root sets both the real and the effective user ID, other users can only
+/// set the effective user ID back to the real one.
+pub fn sys_setuid(uid: u32) -> LinuxResult<isize> {
+    let curr = current();
+    let mut cred = curr.task_ext().process_data().cred.write();
+    if cred.is_root() {
+        cred.uid = uid;
+        cred.euid = uid;
+    } else if uid == cred.uid {
+        cred.euid = uid;
+    } else {
+        return Err(LinuxError::EPERM);
+    }
+    Ok(0)
+}
+
+/// Set the group identity of the calling process.
+pub fn sys_setgid(gid: u32) -> LinuxResult<isize> {
+    let curr = current();
+    let mut cred = curr.task_ext().process_data().cred.write();
+    if cred.is_root() {
+        cred.gid = gid;
+        cred.egid = gid;
+    } else if gid == cred.gid {
+        cred.egid = gid;
+    } else {
+        return Err(LinuxError::EPERM);
+    }
+    Ok(0)
+}
+
+/// Get and set the resource limits of a process, or of the calling process
+/// if `pid` is 0.
+///
+/// The old limit is written to `old_limit` before `new_limit` is applied.
+/// Only root may raise a hard limit or change the limits of a process owned
+/// by another user.
+pub fn sys_prlimit64(
+    pid: Pid,
+    resource: u32,
+    new_limit: UserConstPtr<rlimit64>,
+    old_limit: UserPtr<rlimit64>,
+) -> LinuxResult<isize> {
+    debug!("sys_prlimit64 <= pid: {}, resource: {}", pid, resource);
+    if resource >= RLIM_NLIMITS {
+        return Err(LinuxError::EINVAL);
+    }
+    let new_limit = nullable!(new_limit.get_as_ref())?.map(|limit| Rlimit {
+        soft: limit.rlim_cur,
+        hard: limit.rlim_max,
+    });
+    let old_limit = nullable!(old_limit.get_as_mut())?;
+
+    let curr = current();
+    let caller = *curr.task_ext().process_data().cred.read();
+    let process = if pid == 0 {
+        curr.task_ext().thread.process().clone()
+    } else {
+        get_process(pid)?
+    };
+    let data = process.data::<ProcessData>().ok_or(LinuxError::ESRCH)?;
+    if !caller.is_root() {
+        let target = *data.cred.read();
+        if target.uid != caller.uid || target.euid != caller.uid {
+            return Err(LinuxError::EPERM);
+        }
+    }
+
+    let mut rlimits = data.rlimits.write();
+    let old = rlimits.get(resource);
+    if let Some(new) = new_limit {
+        if new.soft > new.hard {
+            return Err(LinuxError::EINVAL);
+        }
+        if new.hard > old.hard && !caller.is_root() {
+            return Err(LinuxError::EPERM);
+        }
+        // The file descriptor table cannot grow past its fixed size.
+        if resource == RLIMIT_NOFILE && new.hard > AX_FILE_LIMIT as u64 {
+            return Err(LinuxError::EPERM);
+        }
+        rlimits.set(resource, new);
+    }
+    if let Some(old_limit) = old_limit {
+        *old_limit = rlimit64 {
+            rlim_cur: old.soft,
+            rlim_max: old.hard,
+        };
+    }
+    Ok(0)
+}
+
+pub fn sys_getrlimit(resource: u32, old_limit: UserPtr<rlimit64>) -> LinuxResult<isize> {
+    sys_prlimit64(0, resource, 0usize.into(), old_limit)
+}
+
+pub fn sys_setrlimit(resource: u32, new_limit: UserConstPtr<rlimit64>) -> LinuxResult<isize> {
+    sys_prlimit64(0, resource, new_limit, 0usize.into())
 }
 
 const fn pad_str(info: &str) -> [c_char; 65] {
@@ -44,3 +161,58 @@ pub fn sys_uname(name: UserPtr<new_utsname>) -> LinuxResult<isize> {
     *name.get_as_mut()? = UTSNAME;
     Ok(0)
 }
+
+/// State of the kernel pseudo-random generator, 0 until it is seeded.
+static RNG_STATE: Mutex<u64> = Mutex::new(0);
+
+/// Get the next 64 random bits from a splitmix64 generator.
+///
+/// It is seeded from the clocks on first use and the current time is mixed
+/// into every call, so outputs differ across boots and between callers.
+fn next_random() -> u64 {
+    let mut state = RNG_STATE.lock();
+    if *state == 0 {
+        *state = axhal::time::wall_time().as_nanos() as u64
+            ^ axhal::time::monotonic_time_nanos().rotate_left(32);
+    }
+    *state = state
+        .wrapping_add(0x9e37_79b9_7f4a_7c15)
+        .wrapping_add(axhal::time::monotonic_time_nanos());
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Fill a buffer with random bytes.
+///
+/// The generator never runs out of entropy, so `GRND_NONBLOCK` and
+/// `GRND_RANDOM` never make a difference and the whole buffer is filled.
+pub fn sys_getrandom(buf: UserPtr<u8>, len: usize, flags: u32) -> LinuxResult<isize> {
+    if flags & !(GRND_NONBLOCK | GRND_RANDOM | GRND_INSECURE) != 0
+        || flags & (GRND_RANDOM | GRND_INSECURE) == GRND_RANDOM | GRND_INSECURE
+    {
+        return Err(LinuxError::EINVAL);
+    }
+    let buf = buf.get_as_mut_slice(len)?;
+    for chunk in buf.chunks_mut(size_of::<u64>()) {
+        chunk.copy_from_slice(&next_random().to_ne_bytes()[..chunk.len()]);
+    }
+    Ok(len as _)
+}
+
+/// Get overall system statistics.
+///
+/// Load averages are not tracked, so they are reported as zero.
+pub fn sys_sysinfo(info: UserPtr<sysinfo>) -> LinuxResult<isize> {
+    let memory = system_memory();
+    let info = info.get_as_mut()?;
+    // SAFETY: `sysinfo` is plain old data.
+    *info = unsafe { core::mem::zeroed() };
+    info.uptime = monotonic_time().as_secs() as _;
+    info.totalram = memory.total as _;
+    info.freeram = memory.free as _;
+    info.procs = processes().len().min(u16::MAX as usize) as _;
+    info.mem_unit = 1;
+    Ok(0)
+}
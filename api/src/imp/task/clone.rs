@@ -1,7 +1,10 @@
 use alloc::sync::Arc;
 use axerrno::{LinuxError, LinuxResult};
 use axfs::{CURRENT_DIR, CURRENT_DIR_PATH};
-use axhal::arch::{TrapFrame, UspaceContext};
+use axhal::{
+    arch::{TrapFrame, UspaceContext},
+    mem::PAGE_SIZE_4K,
+};
 use axprocess::Pid;
 use axsignal::Signo;
 use axsync::Mutex;
@@ -9,11 +12,18 @@ use axtask::{TaskExtRef, current};
 use bitflags::bitflags;
 use linux_raw_sys::general::*;
 use starry_core::{
+    cow,
     mm::copy_from_kernel,
-    task::{ProcessData, TaskExt, ThreadData, add_thread_to_table, new_user_task},
+    task::{
+        PID_MAX, ProcessData, TaskExt, ThreadData, add_thread_to_table, alloc_pid, new_user_task,
+        reserve_pid,
+    },
 };
 
-use crate::{file::FD_TABLE, ptr::UserPtr};
+use crate::{
+    file::{FD_TABLE, add_proc_pid_dir},
+    ptr::{UserConstPtr, UserPtr},
+};
 
 bitflags! {
     /// Options for use with [`sys_clone`].
@@ -78,6 +88,18 @@ bitflags! {
     }
 }
 
+/// Arguments of a clone, shared by [`sys_clone`] and [`sys_clone3`].
+struct CloneArgs {
+    flags: CloneFlags,
+    exit_signal: u32,
+    stack: usize,
+    parent_tid: usize,
+    child_tid: usize,
+    tls: usize,
+    /// The TID requested through `clone3`'s `set_tid`.
+    set_tid: Option<Pid>,
+}
+
 pub fn sys_clone(
     tf: &TrapFrame,
     flags: u32,
@@ -96,6 +118,98 @@ pub fn sys_clone(
         flags, exit_signal, stack, parent_tid, child_tid, tls
     );
 
+    do_clone(
+        tf,
+        CloneArgs {
+            flags,
+            exit_signal,
+            stack,
+            parent_tid,
+            child_tid,
+            tls,
+            set_tid: None,
+        },
+    )
+}
+
+/// Create a child process or thread, with the arguments passed in a
+/// `struct clone_args`.
+pub fn sys_clone3(tf: &TrapFrame, args: UserConstPtr<u8>, size: usize) -> LinuxResult<isize> {
+    if size < CLONE_ARGS_SIZE_VER0 as usize {
+        return Err(LinuxError::EINVAL);
+    }
+    let bytes = args.get_as_slice(size)?;
+    let (known, extra) = bytes.split_at(size.min(size_of::<clone_args>()));
+    // Fields unknown to us must be zero.
+    if extra.iter().any(|&b| b != 0) {
+        return Err(LinuxError::E2BIG);
+    }
+    // SAFETY: `clone_args` is made of integers only, so all zeroes is a
+    // valid value.
+    let mut args: clone_args = unsafe { core::mem::zeroed() };
+    // SAFETY: `known` is at most `size_of::<clone_args>()` bytes long, any
+    // bytes are valid for the integer fields, and the two do not overlap as
+    // `args` is on the stack.
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            known.as_ptr(),
+            &mut args as *mut clone_args as *mut u8,
+            known.len(),
+        )
+    };
+
+    info!("sys_clone3 <= args: {:?}", args);
+
+    if args.flags > u32::MAX as u64 || args.flags & 0xff != 0 || args.exit_signal > 0xff {
+        return Err(LinuxError::EINVAL);
+    }
+    let flags = CloneFlags::from_bits_truncate(args.flags as u32);
+    if (args.stack == 0) != (args.stack_size == 0) {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let set_tid = match args.set_tid_size {
+        0 if args.set_tid == 0 => None,
+        // There are no nested PID namespaces, so only one TID can be given.
+        1 if args.set_tid != 0 => {
+            if !current().task_ext().process_data().cred.read().is_root() {
+                return Err(LinuxError::EPERM);
+            }
+            let tid = *UserConstPtr::<Pid>::from(args.set_tid as usize).get_as_ref()?;
+            if tid == 0 || tid > PID_MAX {
+                return Err(LinuxError::EINVAL);
+            }
+            Some(tid)
+        }
+        _ => return Err(LinuxError::EINVAL),
+    };
+
+    do_clone(
+        tf,
+        CloneArgs {
+            flags,
+            exit_signal: args.exit_signal as u32,
+            // The stack grows down from the end of the given region.
+            stack: (args.stack + args.stack_size) as usize,
+            parent_tid: args.parent_tid as usize,
+            child_tid: args.child_tid as usize,
+            tls: args.tls as usize,
+            set_tid,
+        },
+    )
+}
+
+fn do_clone(tf: &TrapFrame, args: CloneArgs) -> LinuxResult<isize> {
+    let CloneArgs {
+        flags,
+        exit_signal,
+        stack,
+        parent_tid,
+        child_tid,
+        tls,
+        set_tid,
+    } = args;
+
     if exit_signal != 0 && flags.contains(CloneFlags::THREAD | CloneFlags::PARENT) {
         return Err(LinuxError::EINVAL);
     }
@@ -121,22 +235,16 @@ pub fn sys_clone(
 
     let curr = current();
     let mut new_task = new_user_task(curr.name(), new_uctx, set_child_tid);
+    let parent_tid = if flags.contains(CloneFlags::PARENT_SETTID) {
+        Some(UserPtr::<Pid>::from(parent_tid).get_as_mut()?)
+    } else {
+        None
+    };
 
-    let tid = new_task.id().as_u64() as Pid;
-    if flags.contains(CloneFlags::PARENT_SETTID) {
-        *UserPtr::<Pid>::from(parent_tid).get_as_mut()? = tid;
-    }
-
-    let process = if flags.contains(CloneFlags::THREAD) {
-        new_task.ctx_mut().set_page_table_root(
-            curr.task_ext()
-                .process_data()
-                .aspace
-                .lock()
-                .page_table_root(),
-        );
-
-        curr.task_ext().thread.process()
+    // What a new process is created from is found before its PID is
+    // reserved, so that nothing fails while it is held.
+    let fork = if flags.contains(CloneFlags::THREAD) {
+        None
     } else {
         let parent = if flags.contains(CloneFlags::PARENT) {
             curr.task_ext()
@@ -147,69 +255,120 @@ pub fn sys_clone(
         } else {
             curr.task_ext().thread.process().clone()
         };
-        let builder = parent.fork(tid);
-
         let aspace = if flags.contains(CloneFlags::VM) {
             curr.task_ext().process_data().aspace.clone()
         } else {
-            let mut aspace = curr.task_ext().process_data().aspace.lock();
-            let mut aspace = aspace.try_clone()?;
-            copy_from_kernel(&mut aspace)?;
+            let process_data = curr.task_ext().process_data();
+            let mut aspace = process_data.aspace.lock();
+            // The pages of `MAP_SHARED` mappings stay shared with the child,
+            // the others are copied on write.
+            let backings = process_data.mmap_backings.lock();
+            let mut aspace = cow::fork(&mut aspace, process_data.user_cpus(), |page| {
+                backings
+                    .overlapping(page, PAGE_SIZE_4K)
+                    .any(|(_, backing)| backing.shared)
+            })?;
+            if let Err(err) = copy_from_kernel(&mut aspace) {
+                cow::release_all(&mut aspace);
+                return Err(err.into());
+            }
             Arc::new(Mutex::new(aspace))
         };
-        new_task
-            .ctx_mut()
-            .set_page_table_root(aspace.lock().page_table_root());
-
-        let signal_actions = if flags.contains(CloneFlags::SIGHAND) {
-            parent
-                .data::<ProcessData>()
-                .map_or_else(Arc::default, |it| it.signal.actions.clone())
-        } else {
-            Arc::default()
-        };
-        let process_data = ProcessData::new(
-            curr.task_ext().process_data().exe_path.read().clone(),
-            aspace,
-            signal_actions,
-            exit_signal,
-        );
+        Some((parent, aspace))
+    };
 
-        if flags.contains(CloneFlags::FILES) {
-            FD_TABLE
-                .deref_from(&process_data.ns)
-                .init_shared(FD_TABLE.share());
-        } else {
-            FD_TABLE
-                .deref_from(&process_data.ns)
-                .init_new(FD_TABLE.copy_inner());
+    let tid = match set_tid {
+        Some(tid) if !reserve_pid(tid) => {
+            // The pages shared with the child are not its anymore.
+            if let Some((_, aspace)) = &fork {
+                cow::release_all(&mut aspace.lock());
+            }
+            return Err(LinuxError::EEXIST);
+        }
+        Some(tid) => tid,
+        None => alloc_pid(new_task.id().as_u64() as Pid),
+    };
+    if let Some(parent_tid) = parent_tid {
+        *parent_tid = tid;
+    }
+
+    let process = match fork {
+        None => {
+            new_task.ctx_mut().set_page_table_root(
+                curr.task_ext()
+                    .process_data()
+                    .aspace
+                    .lock()
+                    .page_table_root(),
+            );
+            curr.task_ext().thread.process()
         }
+        Some((parent, aspace)) => {
+            new_task
+                .ctx_mut()
+                .set_page_table_root(aspace.lock().page_table_root());
 
-        if flags.contains(CloneFlags::FS) {
-            CURRENT_DIR
-                .deref_from(&process_data.ns)
-                .init_shared(CURRENT_DIR.share());
-            CURRENT_DIR_PATH
-                .deref_from(&process_data.ns)
-                .init_shared(CURRENT_DIR_PATH.share());
-        } else {
-            CURRENT_DIR
-                .deref_from(&process_data.ns)
-                .init_new(CURRENT_DIR.copy_inner());
-            CURRENT_DIR_PATH
-                .deref_from(&process_data.ns)
-                .init_new(CURRENT_DIR_PATH.copy_inner());
+            let signal_actions = if flags.contains(CloneFlags::SIGHAND) {
+                parent
+                    .data::<ProcessData>()
+                    .map_or_else(Arc::default, |it| it.signal.actions.clone())
+            } else {
+                Arc::default()
+            };
+            let process_data = ProcessData::new(
+                curr.task_ext().process_data().exe_path.read().clone(),
+                aspace,
+                signal_actions,
+                exit_signal,
+            );
+            *process_data.cred.write() = *curr.task_ext().process_data().cred.read();
+            *process_data.rlimits.write() = curr.task_ext().process_data().rlimits.read().clone();
+            process_data.set_stack_bottom(curr.task_ext().process_data().get_stack_bottom());
+            *process_data.shm_data.lock() = curr.task_ext().process_data().shm_data.lock().fork();
+            *process_data.mmap_backings.lock() =
+                curr.task_ext().process_data().mmap_backings.lock().clone();
+
+            if flags.contains(CloneFlags::FILES) {
+                FD_TABLE
+                    .deref_from(&process_data.ns)
+                    .init_shared(FD_TABLE.share());
+            } else {
+                FD_TABLE
+                    .deref_from(&process_data.ns)
+                    .init_new(FD_TABLE.copy_inner());
+            }
+
+            if flags.contains(CloneFlags::FS) {
+                CURRENT_DIR
+                    .deref_from(&process_data.ns)
+                    .init_shared(CURRENT_DIR.share());
+                CURRENT_DIR_PATH
+                    .deref_from(&process_data.ns)
+                    .init_shared(CURRENT_DIR_PATH.share());
+            } else {
+                CURRENT_DIR
+                    .deref_from(&process_data.ns)
+                    .init_new(CURRENT_DIR.copy_inner());
+                CURRENT_DIR_PATH
+                    .deref_from(&process_data.ns)
+                    .init_new(CURRENT_DIR_PATH.copy_inner());
+            }
+            &parent.fork(tid).data(process_data).build()
         }
-        &builder.data(process_data).build()
     };
 
     let thread_data = ThreadData::new(process.data().unwrap());
+    thread_data.set_cpu_affinity(curr.task_ext().thread_data().cpu_affinity());
+    thread_data.set_comm(&curr.task_ext().thread_data().comm());
     if flags.contains(CloneFlags::CHILD_CLEARTID) {
         thread_data.set_clear_child_tid(child_tid);
     }
 
     let thread = process.new_thread(tid).data(thread_data).build();
     add_thread_to_table(&thread);
+    if !flags.contains(CloneFlags::THREAD) {
+        add_proc_pid_dir(process.pid());
+    }
     new_task.init_task_ext(TaskExt::new(thread));
     axtask::spawn_task(new_task);
 
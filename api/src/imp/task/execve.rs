@@ -1,13 +1,27 @@
-use core::ffi::c_char;
+use core::{ffi::c_char, sync::atomic::Ordering};
 
-use alloc::{string::ToString, vec::Vec};
-use axerrno::{LinuxError, LinuxResult};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use axerrno::{AxError, LinuxError, LinuxResult};
 use axhal::arch::TrapFrame;
+use axsignal::{SignalInfo, Signo};
 use axtask::{TaskExtRef, current};
-use starry_core::mm::{load_user_app, map_trampoline};
+use linux_raw_sys::general::{RLIMIT_STACK, SI_KERNEL, X_OK};
+use starry_core::{
+    cow,
+    mm::{load_user_app, map_trampoline},
+    task::{ProcessData, exe_name, take_over_leader_tid},
+};
 use xmas_elf::ElfFile;
 
-use crate::ptr::UserConstPtr;
+use crate::{
+    check_path_access,
+    file::FD_TABLE,
+    ptr::UserConstPtr,
+    signal::{SIGNAL_CHECK_INTERVAL, fatal_signal_pending, send_signal_thread},
+};
 
 /// Validate if the file is a valid executable format
 fn validate_executable(data: &[u8]) -> LinuxResult<()> {
@@ -18,6 +32,78 @@ fn validate_executable(data: &[u8]) -> LinuxResult<()> {
     }
 }
 
+/// Maximum total size of the arguments and environment passed to `execve`.
+pub const ARG_MAX: usize = 128 * 1024;
+
+/// Copy a null-terminated array of strings from user space.
+///
+/// `total_size` accumulates the size taken by the strings, their null
+/// terminators and the pointer array, and `E2BIG` is returned once it exceeds
+/// [`ARG_MAX`].
+fn copy_strings(
+    array: UserConstPtr<UserConstPtr<c_char>>,
+    total_size: &mut usize,
+) -> LinuxResult<Vec<String>> {
+    let ptrs = array.get_as_null_terminated()?;
+    // The pointer array, including its null terminator.
+    *total_size += (ptrs.len() + 1) * size_of::<usize>();
+    if *total_size > ARG_MAX {
+        return Err(LinuxError::E2BIG);
+    }
+    ptrs.iter()
+        .map(|ptr| {
+            let s = ptr.get_as_str()?;
+            *total_size += s.len() + 1;
+            if *total_size > ARG_MAX {
+                return Err(LinuxError::E2BIG);
+            }
+            Ok(s.into())
+        })
+        .collect()
+}
+
+/// Terminate all the other threads of the current process and wait for them
+/// to exit, so that the calling thread is the only one left. It then takes
+/// over the TID of the leader, if it was another thread.
+fn kill_other_threads() -> LinuxResult<()> {
+    // Nothing here may borrow from the task, whose thread is replaced below.
+    let thread = current().task_ext().thread.clone();
+    let process = thread.process().clone();
+    if process.threads().len() == 1 {
+        return Ok(());
+    }
+
+    let process_data = process.data::<ProcessData>().ok_or(LinuxError::ESRCH)?;
+    if !process_data.begin_exec(thread.tid()) {
+        // Another thread is already running `execve` and we are going to be
+        // killed by it.
+        return Err(LinuxError::EAGAIN);
+    }
+    // The signal only wakes up threads blocked in the kernel, they exit on
+    // their way back to user space.
+    let sig = SignalInfo::new(Signo::SIGKILL, SI_KERNEL as _);
+    for thr in process.threads() {
+        if thr.tid() != thread.tid() {
+            let _ = send_signal_thread(&thr, sig.clone());
+        }
+    }
+    // The other threads leave any wait they are in, but the exec gives up if
+    // this thread is killed too, rather than wait for them.
+    let alone = || process.threads().len() == 1;
+    while !alone() {
+        if fatal_signal_pending() {
+            process_data.end_exec();
+            return Err(LinuxError::EAGAIN);
+        }
+        process_data
+            .thread_exit_wq
+            .wait_timeout_until(SIGNAL_CHECK_INTERVAL, alone);
+    }
+    take_over_leader_tid();
+    process_data.end_exec();
+    Ok(())
+}
+
 pub fn sys_execve(
     tf: &mut TrapFrame,
     path: UserConstPtr<c_char>,
@@ -26,16 +112,9 @@ pub fn sys_execve(
 ) -> LinuxResult<isize> {
     let path = path.get_as_str()?.to_string();
 
-    let args = argv
-        .get_as_null_terminated()?
-        .iter()
-        .map(|arg| arg.get_as_str().map(Into::into))
-        .collect::<Result<Vec<_>, _>>()?;
-    let envs = envp
-        .get_as_null_terminated()?
-        .iter()
-        .map(|env| env.get_as_str().map(Into::into))
-        .collect::<Result<Vec<_>, _>>()?;
+    let mut total_size = 0;
+    let args = copy_strings(argv, &mut total_size)?;
+    let envs = copy_strings(envp, &mut total_size)?;
 
     info!(
         "sys_execve: path: {:?}, args: {:?}, envs: {:?}",
@@ -45,39 +124,67 @@ pub fn sys_execve(
     let curr = current();
     let curr_ext = curr.task_ext();
 
-    if curr_ext.thread.process().threads().len() > 1 {
-        // TODO: handle multi-thread case
-        error!("sys_execve: multi-thread not supported");
-        return Err(LinuxError::EAGAIN);
-    }
-
     // Validate the executable without modifying the address space
+    check_path_access(&path, X_OK)?;
     let file_data = axfs::api::read(&path).map_err(|_| LinuxError::ENOENT)?;
     validate_executable(&file_data)?;
 
+    // Fail while the old image is still intact if the arguments cannot fit
+    // on a stack of `RLIMIT_STACK`, `load_user_app` checks the exact size.
+    let stack_size = curr_ext
+        .process_data()
+        .rlimits
+        .read()
+        .get(RLIMIT_STACK)
+        .soft as usize;
+    if total_size >= stack_size {
+        return Err(LinuxError::ENOMEM);
+    }
+
+    kill_other_threads()?;
+    // The thread may have taken over the TID of the leader.
+    let curr_ext = curr.task_ext();
+
     // Proceed with execve
     let mut aspace = curr_ext.process_data().aspace.lock();
+    cow::release_all(&mut aspace);
     aspace.unmap_user_areas()?;
+    curr_ext
+        .process_data()
+        .shm_data
+        .lock()
+        .detach_all(&mut aspace, curr_ext.thread.process().pid() as _);
+    curr_ext
+        .process_data()
+        .membarrier_registrations
+        .store(0, Ordering::Release);
+    curr_ext.process_data().poisoned_pages.lock().clear();
+    curr_ext.process_data().mmap_backings.lock().clear();
     map_trampoline(&mut aspace)?;
     axhal::arch::flush_tlb(None);
 
-    let (entry_point, user_stack_base) =
-        load_user_app(&mut aspace, &path, &args, &envs).map_err(|e| {
+    let (entry_point, user_stack_base, user_stack_bottom, thread_pointer) =
+        load_user_app(&mut aspace, &path, &args, &envs, Some(stack_size)).map_err(|e| {
             error!("Failed to load app {}: {:?}", path, e);
-            LinuxError::ENOENT
+            match e {
+                AxError::NoMemory => LinuxError::ENOMEM,
+                _ => LinuxError::ENOENT,
+            }
         })?;
     drop(aspace);
+    curr_ext
+        .process_data()
+        .set_stack_bottom(user_stack_bottom.as_usize());
 
     // Set process name and executable path
-    let name = path
-        .rsplit_once('/')
-        .map_or(path.as_str(), |(_, name)| name);
-    curr.set_name(name);
+    curr_ext.thread_data().set_comm(exe_name(&path));
+    curr.set_name(&curr_ext.thread_data().comm());
     *curr_ext.process_data().exe_path.write() = path;
 
-    // TODO: fd close-on-exec
+    FD_TABLE.close_on_exec();
 
     tf.set_ip(entry_point.as_usize());
     tf.set_sp(user_stack_base.as_usize());
+    tf.set_tls(thread_pointer.map_or(0, |tp| tp.as_usize()));
     Ok(0)
 }
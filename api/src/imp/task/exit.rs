@@ -1,9 +1,10 @@
 use axprocess::Pid;
-use axsignal::{SignalInfo, Signo};
+use axsignal::{SignalDisposition, SignalInfo, Signo};
 use axtask::{TaskExtRef, current};
 use linux_raw_sys::general::SI_KERNEL;
 use starry_core::task::ProcessData;
 
+use super::reap;
 use crate::{
     file::FD_TABLE,
     ptr::UserPtr,
@@ -32,10 +33,32 @@ pub fn do_exit(exit_code: i32, group_exit: bool) -> ! {
     }
 
     let process = thread.process();
-    if thread.exit(exit_code) {
+    let last_thread = thread.exit(exit_code);
+    if let Some(data) = process.data::<ProcessData>() {
+        data.thread_exit_wq.notify_all(false);
+    }
+    if last_thread {
+        // The children are handed to init on exit; those that are zombies
+        // already will never be waited for, so they are reaped here.
+        let children = process.children();
         process.exit();
+        for child in children.iter().filter(|child| child.is_zombie()) {
+            reap(child);
+        }
         if let Some(parent) = process.parent() {
-            if let Some(signo) = process.data::<ProcessData>().and_then(|it| it.exit_signal) {
+            let exit_signal = process.data::<ProcessData>().and_then(|it| it.exit_signal);
+            // Like Linux, a child is reaped on exit if its parent ignores
+            // SIGCHLD, and nobody is notified.
+            let autoreap = exit_signal == Some(Signo::SIGCHLD)
+                && parent.data::<ProcessData>().is_some_and(|data| {
+                    matches!(
+                        data.signal.actions.lock()[Signo::SIGCHLD].disposition,
+                        SignalDisposition::Ignore
+                    )
+                });
+            if autoreap {
+                reap(process);
+            } else if let Some(signo) = exit_signal {
                 let _ = send_signal_process(&parent, SignalInfo::new(signo, SI_KERNEL as _));
             }
             if let Some(data) = parent.data::<ProcessData>() {
@@ -44,6 +67,12 @@ pub fn do_exit(exit_code: i32, group_exit: bool) -> ! {
         }
 
         process.exit();
+        if let Some(data) = process.data::<ProcessData>() {
+            let mut aspace = data.aspace.lock();
+            data.shm_data
+                .lock()
+                .detach_all(&mut aspace, process.pid() as _);
+        }
         // TODO: clear namespace resources
         // FIXME: axns should drop all the resources
         FD_TABLE.clear();
@@ -0,0 +1,72 @@
+use core::{ffi::c_char, sync::atomic::Ordering};
+
+use axerrno::{LinuxError, LinuxResult};
+use axtask::{TaskExtRef, current};
+use linux_raw_sys::prctl::{
+    PR_GET_NAME, PR_MCE_KILL, PR_MCE_KILL_CLEAR, PR_MCE_KILL_DEFAULT, PR_MCE_KILL_EARLY,
+    PR_MCE_KILL_GET, PR_MCE_KILL_LATE, PR_MCE_KILL_SET, PR_SET_NAME,
+};
+use starry_core::task::TASK_COMM_LEN;
+
+use crate::ptr::{UserConstPtr, UserPtr};
+
+/// Operations on a process or thread.
+pub fn sys_prctl(
+    option: u32,
+    arg2: usize,
+    arg3: usize,
+    arg4: usize,
+    arg5: usize,
+) -> LinuxResult<isize> {
+    debug!(
+        "sys_prctl <= option: {}, args: {:#x}, {:#x}, {:#x}, {:#x}",
+        option, arg2, arg3, arg4, arg5
+    );
+
+    let curr = current();
+    let process_data = curr.task_ext().process_data();
+    match option {
+        PR_SET_NAME => {
+            let name = UserConstPtr::<c_char>::from(arg2).get_as_str()?;
+            let thread_data = curr.task_ext().thread_data();
+            thread_data.set_comm(name);
+            curr.set_name(&thread_data.comm());
+            Ok(0)
+        }
+        PR_GET_NAME => {
+            let buf = UserPtr::<u8>::from(arg2).get_as_mut_slice(TASK_COMM_LEN)?;
+            let name = curr.task_ext().thread_data().comm();
+            let len = name.len();
+            buf[..len].copy_from_slice(&name.as_bytes()[..len]);
+            buf[len] = 0;
+            Ok(0)
+        }
+        PR_MCE_KILL => {
+            if arg4 != 0 || arg5 != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            let policy = match arg2 as u32 {
+                PR_MCE_KILL_CLEAR if arg3 == 0 => PR_MCE_KILL_DEFAULT,
+                PR_MCE_KILL_SET => match arg3 as u32 {
+                    policy @ (PR_MCE_KILL_LATE | PR_MCE_KILL_EARLY | PR_MCE_KILL_DEFAULT) => policy,
+                    _ => return Err(LinuxError::EINVAL),
+                },
+                _ => return Err(LinuxError::EINVAL),
+            };
+            process_data
+                .mce_kill_policy
+                .store(policy, Ordering::Release);
+            Ok(0)
+        }
+        PR_MCE_KILL_GET => {
+            if arg2 != 0 || arg3 != 0 || arg4 != 0 || arg5 != 0 {
+                return Err(LinuxError::EINVAL);
+            }
+            Ok(process_data.mce_kill_policy.load(Ordering::Acquire) as _)
+        }
+        _ => {
+            warn!("sys_prctl: unsupported option {}", option);
+            Err(LinuxError::EINVAL)
+        }
+    }
+}
@@ -1,8 +1,17 @@
+use core::sync::atomic::{Ordering, fence};
+
+use alloc::sync::Arc;
 use axerrno::{LinuxError, LinuxResult};
-use linux_raw_sys::general::timespec;
+use axprocess::{Pid, Thread};
+use axtask::{TaskExtRef, current};
+use linux_raw_sys::general::{membarrier_cmd, timespec};
+use starry_core::task::{
+    ThreadData, get_thread, on_each_cpu, online_cpus_mask, set_current_affinity_mask,
+};
 
 use crate::{
     ptr::{UserConstPtr, UserPtr, nullable},
+    signal::sleep_interruptible,
     time::TimeValueLike,
 };
 
@@ -11,9 +20,10 @@ pub fn sys_sched_yield() -> LinuxResult<isize> {
     Ok(0)
 }
 
-/// Sleep some nanoseconds
+/// Sleep some nanoseconds, or until a signal that is not blocked arrives.
 ///
-/// TODO: should be woken by signals, and set errno
+/// A sleep cut short by a signal writes the time left to `rem` and fails with
+/// `EINTR`.
 pub fn sys_nanosleep(req: UserConstPtr<timespec>, rem: UserPtr<timespec>) -> LinuxResult<isize> {
     let req = req.get_as_ref()?;
 
@@ -26,17 +36,144 @@ pub fn sys_nanosleep(req: UserConstPtr<timespec>, rem: UserPtr<timespec>) -> Lin
 
     let now = axhal::time::monotonic_time();
 
-    axtask::sleep(dur);
+    if let Err(err) = sleep_interruptible(dur) {
+        let actual = axhal::time::monotonic_time() - now;
+        if let Some(rem) = nullable!(rem.get_as_mut())? {
+            *rem = timespec::from_time_value(dur.saturating_sub(actual));
+        }
+        return Err(err);
+    }
+    Ok(0)
+}
+
+/// Get the thread `tid`, or the calling thread if it is 0.
+fn thread_or_current(tid: Pid) -> LinuxResult<Arc<Thread>> {
+    if tid == 0 {
+        Ok(current().task_ext().thread.clone())
+    } else {
+        get_thread(tid)
+    }
+}
 
-    let after = axhal::time::monotonic_time();
-    let actual = after - now;
+/// Get the CPU affinity mask of a thread.
+///
+/// Returns the number of bytes written to `mask`.
+pub fn sys_sched_getaffinity(tid: Pid, cpusetsize: usize, mask: UserPtr<u8>) -> LinuxResult<isize> {
+    const SIZE: usize = size_of::<usize>();
+    if cpusetsize < SIZE || cpusetsize % SIZE != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let thread = thread_or_current(tid)?;
+    let thread_data = thread.data::<ThreadData>().ok_or(LinuxError::ESRCH)?;
+    let affinity = thread_data.cpu_affinity();
+    mask.get_as_mut_slice(SIZE)?
+        .copy_from_slice(&affinity.to_ne_bytes());
+    Ok(SIZE as _)
+}
 
-    if let Some(diff) = dur.checked_sub(actual) {
-        if let Some(rem) = nullable!(rem.get_as_mut())? {
-            *rem = timespec::from_time_value(diff);
+/// Set the CPU affinity mask of a thread.
+///
+/// CPUs that are not online are ignored, and `EINVAL` is returned if no CPU
+/// is left, or if the mask has CPUs user tasks may not run on. Another thread
+/// applies its new mask the next time it returns to user space.
+pub fn sys_sched_setaffinity(
+    tid: Pid,
+    cpusetsize: usize,
+    mask: UserConstPtr<u8>,
+) -> LinuxResult<isize> {
+    let mut bytes = [0; size_of::<usize>()];
+    let len = cpusetsize.min(bytes.len());
+    bytes[..len].copy_from_slice(mask.get_as_slice(len)?);
+    let affinity = usize::from_ne_bytes(bytes) & online_cpus_mask();
+    debug!(
+        "sys_sched_setaffinity <= tid: {}, mask: {:#x}",
+        tid, affinity
+    );
+    if affinity == 0 {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let thread = thread_or_current(tid)?;
+    let thread_data = thread.data::<ThreadData>().ok_or(LinuxError::ESRCH)?;
+    if Arc::ptr_eq(&thread, &current().task_ext().thread) {
+        if !set_current_affinity_mask(affinity) {
+            return Err(LinuxError::EINVAL);
         }
-        Err(LinuxError::EINTR)
+        thread_data.set_cpu_affinity(affinity);
     } else {
-        Ok(0)
+        thread_data.change_cpu_affinity(affinity);
+    }
+    Ok(0)
+}
+
+const MEMBARRIER_CMD_QUERY: u32 = membarrier_cmd::MEMBARRIER_CMD_QUERY as _;
+const MEMBARRIER_CMD_GLOBAL: u32 = membarrier_cmd::MEMBARRIER_CMD_GLOBAL as _;
+const MEMBARRIER_CMD_GLOBAL_EXPEDITED: u32 = membarrier_cmd::MEMBARRIER_CMD_GLOBAL_EXPEDITED as _;
+const MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED: u32 =
+    membarrier_cmd::MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED as _;
+const MEMBARRIER_CMD_PRIVATE_EXPEDITED: u32 = membarrier_cmd::MEMBARRIER_CMD_PRIVATE_EXPEDITED as _;
+const MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED: u32 =
+    membarrier_cmd::MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED as _;
+const MEMBARRIER_CMD_GET_REGISTRATIONS: u32 = membarrier_cmd::MEMBARRIER_CMD_GET_REGISTRATIONS as _;
+
+/// The commands reported by `MEMBARRIER_CMD_QUERY`.
+const MEMBARRIER_SUPPORTED_CMDS: u32 = MEMBARRIER_CMD_GLOBAL
+    | MEMBARRIER_CMD_GLOBAL_EXPEDITED
+    | MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED
+    | MEMBARRIER_CMD_PRIVATE_EXPEDITED
+    | MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED
+    | MEMBARRIER_CMD_GET_REGISTRATIONS;
+
+/// Make the CPUs in `cpus` go through a full memory barrier, ordering the
+/// memory accesses of the tasks running on them with those of the caller.
+///
+/// The caller moves to each of the other CPUs in turn. To run it, the
+/// scheduler of that CPU must switch away from the task it was running, which
+/// orders that task's accesses as an IPI would.
+fn barrier_on_cpus(cpus: usize) {
+    fence(Ordering::SeqCst);
+    on_each_cpu(cpus & !(1 << axhal::cpu::this_cpu_id()), || {});
+    fence(Ordering::SeqCst);
+}
+
+/// Issue memory barriers on the threads of a set of processes.
+///
+/// The global commands reach every CPU, and the private ones the CPUs that
+/// have run the threads of the caller's process.
+/// `MEMBARRIER_CMD_PRIVATE_EXPEDITED` needs the address space to be
+/// registered with `MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED` first, and
+/// fails with `EPERM` otherwise.
+pub fn sys_membarrier(cmd: u32, flags: u32, cpu_id: i32) -> LinuxResult<isize> {
+    debug!(
+        "sys_membarrier <= cmd: {}, flags: {}, cpu_id: {}",
+        cmd, flags, cpu_id
+    );
+    if flags != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+
+    let curr = current();
+    let registrations = &curr.task_ext().process_data().membarrier_registrations;
+    match cmd {
+        MEMBARRIER_CMD_QUERY => Ok(MEMBARRIER_SUPPORTED_CMDS as _),
+        MEMBARRIER_CMD_GLOBAL | MEMBARRIER_CMD_GLOBAL_EXPEDITED => {
+            barrier_on_cpus(online_cpus_mask());
+            Ok(0)
+        }
+        MEMBARRIER_CMD_PRIVATE_EXPEDITED => {
+            if registrations.load(Ordering::Acquire) & MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED
+                == 0
+            {
+                return Err(LinuxError::EPERM);
+            }
+            barrier_on_cpus(curr.task_ext().process_data().user_cpus());
+            Ok(0)
+        }
+        MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED | MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED => {
+            registrations.fetch_or(cmd, Ordering::AcqRel);
+            Ok(0)
+        }
+        MEMBARRIER_CMD_GET_REGISTRATIONS => Ok(registrations.load(Ordering::Acquire) as _),
+        _ => Err(LinuxError::EINVAL),
     }
 }
@@ -2,7 +2,7 @@ use axerrno::{LinuxError, LinuxResult};
 use axprocess::Pid;
 use axtask::{TaskExtRef, current};
 use num_enum::TryFromPrimitive;
-use starry_core::task::{get_process, get_process_group};
+use starry_core::task::{add_process_group_to_table, get_process, get_process_group};
 
 pub fn sys_getpid() -> LinuxResult<isize> {
     Ok(axtask::current().task_ext().thread.process().pid() as _)
@@ -18,8 +18,10 @@ pub fn sys_getppid() -> LinuxResult<isize> {
         .pid() as _)
 }
 
+/// Get the Linux thread id of the calling thread, which is the pid for the
+/// main thread of a process.
 pub fn sys_gettid() -> LinuxResult<isize> {
-    Ok(axtask::current().id().as_u64() as _)
+    Ok(axtask::current().task_ext().thread.tid() as _)
 }
 
 pub fn sys_getpgid(pid: Pid) -> LinuxResult<isize> {
@@ -65,9 +67,10 @@ pub fn sys_setpgid(pid: Pid, pgid: Pid) -> LinuxResult<isize> {
     }
 
     if target_pgid == target_process.pid() {
-        if target_process.create_group().is_none() {
+        let Some(group) = target_process.create_group() else {
             return Err(LinuxError::EPERM);
-        }
+        };
+        add_process_group_to_table(&group);
     } else {
         let target_group = get_process_group(target_pgid);
         if target_group.is_err() {
@@ -87,6 +90,29 @@ pub fn sys_setpgid(pid: Pid, pgid: Pid) -> LinuxResult<isize> {
     Ok(0)
 }
 
+pub fn sys_getsid(pid: Pid) -> LinuxResult<isize> {
+    let process = if pid == 0 {
+        current().task_ext().thread.process().clone()
+    } else {
+        get_process(pid)?
+    };
+    Ok(process.group().session().sid() as _)
+}
+
+/// Create a new session led by the calling process, in a new process group
+/// of which it is the leader as well.
+///
+/// Fails with `EPERM` if the process already leads a process group.
+pub fn sys_setsid() -> LinuxResult<isize> {
+    let curr = current();
+    let process = curr.task_ext().thread.process();
+    let Some((session, group)) = process.create_session() else {
+        return Err(LinuxError::EPERM);
+    };
+    add_process_group_to_table(&group);
+    Ok(session.sid() as _)
+}
+
 /// ARCH_PRCTL codes
 ///
 /// It is only avaliable on x86_64, and is not convenient
@@ -116,9 +142,15 @@ pub fn sys_set_tid_address(clear_child_tid: usize) -> LinuxResult<isize> {
     curr.task_ext()
         .thread_data()
         .set_clear_child_tid(clear_child_tid);
-    Ok(curr.id().as_u64() as isize)
+    Ok(curr.task_ext().thread.tid() as isize)
 }
 
+/// Set or get architecture-specific thread state.
+///
+/// Only x86_64 has this syscall. On aarch64 the thread pointer is
+/// `TPIDR_EL0`, which user space writes itself and which is kept in the trap
+/// frame across context switches, so setting up TLS needs no syscall besides
+/// `CLONE_SETTLS` for new threads.
 #[cfg(target_arch = "x86_64")]
 pub fn sys_arch_prctl(
     tf: &mut axhal::arch::TrapFrame,
@@ -8,7 +8,11 @@ use linux_raw_sys::general::{
 };
 use starry_core::task::ProcessData;
 
-use crate::ptr::{UserPtr, nullable};
+use crate::{
+    file::remove_proc_pid_dir,
+    ptr::{UserPtr, nullable},
+    signal::{SIGNAL_CHECK_INTERVAL, unblocked_signal_pending},
+};
 
 bitflags! {
     #[derive(Debug)]
@@ -55,6 +59,12 @@ impl WaitPid {
     }
 }
 
+/// Release a zombie process, and remove its `/proc/[pid]` directory.
+pub(crate) fn reap(process: &Process) {
+    remove_proc_pid_dir(process.pid());
+    process.free();
+}
+
 pub fn sys_waitpid(pid: i32, exit_code_ptr: UserPtr<i32>, options: u32) -> LinuxResult<isize> {
     let options = WaitOptions::from_bits_truncate(options);
     info!("sys_waitpid <= pid: {:?}, options: {:?}", pid, options);
@@ -73,25 +83,27 @@ pub fn sys_waitpid(pid: i32, exit_code_ptr: UserPtr<i32>, options: u32) -> Linux
         WaitPid::Pgid(-pid as _)
     };
 
-    let children = process
-        .children()
-        .into_iter()
-        .filter(|child| pid.apply(child))
-        .filter(|child| {
-            options.contains(WaitOptions::WALL)
-                || (options.contains(WaitOptions::WCLONE)
-                    == child.data::<ProcessData>().unwrap().is_clone_child())
-        })
-        .collect::<Vec<_>>();
-    if children.is_empty() {
-        return Err(LinuxError::ECHILD);
-    }
-
     let exit_code = nullable!(exit_code_ptr.get_as_mut())?;
     loop {
+        // Children reaped on exit disappear while we wait, so the list is
+        // taken again every time.
+        let children = process
+            .children()
+            .into_iter()
+            .filter(|child| pid.apply(child))
+            .filter(|child| {
+                options.contains(WaitOptions::WALL)
+                    || (options.contains(WaitOptions::WCLONE)
+                        == child.data::<ProcessData>().unwrap().is_clone_child())
+            })
+            .collect::<Vec<_>>();
+        if children.is_empty() {
+            return Err(LinuxError::ECHILD);
+        }
+
         if let Some(child) = children.iter().find(|child| child.is_zombie()) {
             if !options.contains(WaitOptions::WNOWAIT) {
-                child.free();
+                reap(child);
             }
             if let Some(exit_code) = exit_code {
                 *exit_code = child.exit_code();
@@ -100,7 +112,10 @@ pub fn sys_waitpid(pid: i32, exit_code_ptr: UserPtr<i32>, options: u32) -> Linux
         } else if options.contains(WaitOptions::WNOHANG) {
             return Ok(0);
         } else {
-            proc_data.child_exit_wq.wait();
+            if unblocked_signal_pending() {
+                return Err(LinuxError::EINTR);
+            }
+            proc_data.child_exit_wq.wait_timeout(SIGNAL_CHECK_INTERVAL);
         }
     }
 }
@@ -1,19 +1,37 @@
 use axerrno::{LinuxError, LinuxResult};
 use axhal::time::{monotonic_time, monotonic_time_nanos, nanos_to_ticks, wall_time};
+use core::time::Duration;
 use linux_raw_sys::general::{
-    __kernel_clockid_t, CLOCK_MONOTONIC, CLOCK_REALTIME, timespec, timeval,
+    __kernel_clockid_t, CLOCK_BOOTTIME, CLOCK_MONOTONIC, CLOCK_MONOTONIC_COARSE,
+    CLOCK_MONOTONIC_RAW, CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME, CLOCK_REALTIME_COARSE,
+    CLOCK_THREAD_CPUTIME_ID, TIMER_ABSTIME, timespec, timeval,
 };
 use starry_core::task::time_stat_output;
 
-use crate::{ptr::UserPtr, time::TimeValueLike};
+use crate::{
+    ptr::{UserConstPtr, UserPtr, nullable},
+    signal::sleep_interruptible,
+    time::TimeValueLike,
+};
 
+/// Get the time of the clock `clock_id`.
+///
+/// The CPU time clocks only count the calling thread, as the times of other
+/// threads are not accessible.
 pub fn sys_clock_gettime(
     clock_id: __kernel_clockid_t,
     ts: UserPtr<timespec>,
 ) -> LinuxResult<isize> {
     let now = match clock_id as u32 {
-        CLOCK_REALTIME => wall_time(),
-        CLOCK_MONOTONIC => monotonic_time(),
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE => wall_time(),
+        // The system does not suspend, so the boot time is monotonic.
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => {
+            monotonic_time()
+        }
+        CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID => {
+            let (_, utime_us, _, stime_us) = time_stat_output();
+            Duration::from_micros((utime_us + stime_us) as u64)
+        }
         _ => {
             warn!(
                 "Called sys_clock_gettime for unsupported clock {}",
@@ -26,6 +44,51 @@ pub fn sys_clock_gettime(
     Ok(0)
 }
 
+/// Sleep until the clock `clock_id` reaches `request` if `TIMER_ABSTIME` is
+/// set in `flags`, or for the duration `request` otherwise.
+///
+/// Like `nanosleep`, a relative sleep that ends early writes the time left to
+/// `remain` and fails with `EINTR`.
+pub fn sys_clock_nanosleep(
+    clock_id: __kernel_clockid_t,
+    flags: u32,
+    request: UserConstPtr<timespec>,
+    remain: UserPtr<timespec>,
+) -> LinuxResult<isize> {
+    let now = || match clock_id as u32 {
+        CLOCK_REALTIME => Ok(wall_time()),
+        CLOCK_MONOTONIC | CLOCK_BOOTTIME => Ok(monotonic_time()),
+        _ => Err(LinuxError::EINVAL),
+    };
+    if flags & !TIMER_ABSTIME != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let request = request.get_as_ref()?;
+    if request.tv_nsec < 0 || request.tv_nsec > 999_999_999 || request.tv_sec < 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let request = request.to_time_value();
+    let start = now()?;
+
+    if flags & TIMER_ABSTIME != 0 {
+        // The realtime clock may be set while sleeping, so sleep again until
+        // the clock itself reaches the deadline.
+        while let Some(left) = request.checked_sub(now()?).filter(|left| !left.is_zero()) {
+            sleep_interruptible(left)?;
+        }
+        return Ok(0);
+    }
+
+    if let Err(err) = sleep_interruptible(request) {
+        let left = request.saturating_sub(now()?.saturating_sub(start));
+        if let Some(remain) = nullable!(remain.get_as_mut())? {
+            *remain = timespec::from_time_value(left);
+        }
+        return Err(err);
+    }
+    Ok(0)
+}
+
 pub fn sys_gettimeofday(ts: UserPtr<timeval>) -> LinuxResult<isize> {
     *ts.get_as_mut()? = timeval::from_time_value(wall_time());
     Ok(0)
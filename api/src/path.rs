@@ -1,15 +1,20 @@
 use core::{ffi::c_int, fmt, ops::Deref};
 
 use alloc::{
-    collections::btree_map::BTreeMap,
+    collections::{btree_map::BTreeMap, vec_deque::VecDeque},
     string::{String, ToString},
+    vec,
+    vec::Vec,
 };
 use axerrno::{AxError, AxResult, LinuxError, LinuxResult};
 use axfs::api::canonicalize;
-use linux_raw_sys::general::AT_FDCWD;
+use linux_raw_sys::general::{
+    AT_FDCWD, PATH_MAX, RESOLVE_BENEATH, RESOLVE_IN_ROOT, RESOLVE_NO_MAGICLINKS,
+    RESOLVE_NO_SYMLINKS,
+};
 use spin::RwLock;
 
-use crate::file::{Directory, File, FileLike};
+use crate::file::{Directory, FIFOS, File, FileLike, INODE_FLAGS, INODES};
 
 /// 一个规范化的文件路径表示
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
@@ -19,6 +24,12 @@ impl FilePath {
     /// 从路径字符串创建一个新的 `FilePath`，路径将被规范化。
     /// 输入路径可以是绝对路径或相对路径。
     pub fn new<P: AsRef<str>>(path: P) -> AxResult<Self> {
+        let path = Self::new_link(path)?;
+        Ok(Self(HARDLINK_MANAGER.real_path(&path.0)))
+    }
+
+    /// 创建一个不解析硬链接的 `FilePath`，用于对链接本身进行操作（如 `unlink`）。
+    pub fn new_link<P: AsRef<str>>(path: P) -> AxResult<Self> {
         let path = path.as_ref();
         let canonical = canonicalize(path).map_err(|_| AxError::NotFound)?;
         let mut new_path = canonical.trim().to_string();
@@ -33,7 +44,7 @@ impl FilePath {
             "canonical path should start with /"
         );
 
-        Ok(Self(HARDLINK_MANAGER.real_path(&new_path)))
+        Ok(Self(new_path))
     }
 
     /// 返回底层路径的字符串切片
@@ -179,11 +190,18 @@ impl From<LinkError> for LinuxError {
 pub static HARDLINK_MANAGER: HardlinkManager = HardlinkManager::new();
 
 /// A manager for hardlinks
+///
+/// 所有对 `inner` 的修改（包括对底层文件系统的删除与重命名）都在同一个写锁内完成，
+/// 因此并发的 `create_link`/`remove_link` 不会导致引用计数竞争。
 pub struct HardlinkManager {
     inner: RwLock<LinkManagerInner>,
 }
 struct LinkManagerInner {
+    /// 链接路径 -> 真实文件路径
     links: BTreeMap<String, String>,
+    /// 真实文件路径 -> 名字总数（包括真实路径本身）
+    ///
+    /// 只有存在额外链接（计数至少为 2）的文件才会出现在这里。
     ref_counts: BTreeMap<String, usize>,
 }
 
@@ -201,30 +219,40 @@ impl HardlinkManager {
     /// 创建链接
     /// 如果目标路径不存在，则返回 `LinkError::NotFound`
     /// 如果目标路径不是文件，则返回 `LinkError::NotFile`
+    /// 如果链接路径已存在，则返回 `LinkError::LinkExists`
     pub fn create_link(&self, src: &FilePath, dst: &FilePath) -> Result<(), LinkError> {
+        let mut inner = self.inner.write();
         if !dst.exists() {
             return Err(LinkError::NotFound);
         }
-        if !dst.is_dir() {
+        if dst.is_dir() || axfs::api::metadata(dst.as_str()).is_ok_and(|m| m.is_dir()) {
             return Err(LinkError::NotFile);
         }
+        if inner.links.contains_key(src.as_str()) || src.exists() {
+            return Err(LinkError::LinkExists);
+        }
 
-        let mut inner = self.inner.write();
-        self.atomic_link_update(&mut inner, src, dst);
+        self.atomic_link_add(&mut inner, src, dst);
         Ok(())
     }
 
     /// 移除链接
-    /// 链接数量为零 或 没有链接时， 删除文件
+    /// 最后一个名字被移除时，删除文件
     /// 如果路径对应的链接不存在 或 路径对应的文件不存在，则返回 `None`
-    /// 否则返回链接的目标路径
+    /// 否则返回被移除名字所对应的真实路径
     pub fn remove_link(&self, src: &FilePath) -> Option<String> {
         let mut inner = self.inner.write();
-        self.atomic_link_remove(&mut inner, src).or_else(|| {
-            axfs::api::remove_file(src.as_str())
-                .ok()
-                .map(|_| src.to_string())
-        })
+        if let Some(dst) = self.atomic_link_remove(&mut inner, src) {
+            return Some(dst);
+        }
+        if inner.ref_counts.contains_key(src.as_str()) {
+            return self.atomic_real_path_remove(&mut inner, src);
+        }
+        axfs::api::remove_file(src.as_str()).ok()?;
+        INODES.remove(src.as_str());
+        INODE_FLAGS.remove(src.as_str());
+        FIFOS.remove(src.as_str());
+        Some(src.to_string())
     }
 
     pub fn real_path(&self, path: &str) -> String {
@@ -238,27 +266,23 @@ impl HardlinkManager {
 
     pub fn link_count(&self, path: &FilePath) -> usize {
         let inner = self.inner.read();
+        let real_path = inner
+            .links
+            .get(path.as_str())
+            .map_or(path.as_str(), |p| p.as_str());
         inner
             .ref_counts
-            .get(path.as_str())
+            .get(real_path)
             .copied()
             .unwrap_or_else(|| if path.exists() { 1 } else { 0 })
     }
 
     // 原子操作helpers
 
-    /// 创建或更新链接
-    /// 如果链接已存在，则更新目标路径
-    /// 如果目标路径不存在，则返回 `LinkError::NotFound`
-    fn atomic_link_update(&self, inner: &mut LinkManagerInner, src: &FilePath, dst: &FilePath) {
-        if let Some(old_dst) = inner.links.get(src.as_str()) {
-            if old_dst == dst.as_str() {
-                return;
-            }
-            self.decrease_ref_count(inner, &old_dst.to_string());
-        }
+    /// 添加链接，并增加目标路径的引用计数
+    fn atomic_link_add(&self, inner: &mut LinkManagerInner, src: &FilePath, dst: &FilePath) {
         inner.links.insert(src.to_string(), dst.to_string());
-        *inner.ref_counts.entry(dst.to_string()).or_insert(0) += 1;
+        *inner.ref_counts.entry(dst.to_string()).or_insert(1) += 1;
     }
 
     /// 移除链接
@@ -269,16 +293,45 @@ impl HardlinkManager {
         })
     }
 
+    /// 移除仍有其他链接的真实路径
+    /// 文件会被重命名到其中一个链接上，其余链接改为指向新的真实路径
+    fn atomic_real_path_remove(
+        &self,
+        inner: &mut LinkManagerInner,
+        src: &FilePath,
+    ) -> Option<String> {
+        let new_real = inner
+            .links
+            .iter()
+            .find(|(_, dst)| dst.as_str() == src.as_str())
+            .map(|(link, _)| link.clone())?;
+        axfs::api::rename(src.as_str(), &new_real).ok()?;
+        INODES.rename(src.as_str(), &new_real);
+        INODE_FLAGS.rename(src.as_str(), &new_real);
+        FIFOS.rename(src.as_str(), &new_real);
+
+        inner.links.remove(&new_real);
+        for dst in inner.links.values_mut() {
+            if dst.as_str() == src.as_str() {
+                *dst = new_real.clone();
+            }
+        }
+        let count = inner.ref_counts.remove(src.as_str())?;
+        if count > 2 {
+            inner.ref_counts.insert(new_real, count - 1);
+        }
+        Some(src.to_string())
+    }
+
     /// 减少引用计数
-    /// 如果引用计数为零，则删除链接，并删除文件，如果删除文件失败，则返回 `None`
-    /// 如果链接不存在，则返回 `None`
+    /// 只剩真实路径一个名字时，移除计数项
+    /// 如果计数项不存在，则返回 `None`
     fn decrease_ref_count(&self, inner: &mut LinkManagerInner, path: &str) -> Option<()> {
         match inner.ref_counts.get_mut(path) {
             Some(count) => {
                 *count -= 1;
-                if *count == 0 {
+                if *count <= 1 {
                     inner.ref_counts.remove(path);
-                    axfs::api::remove_file(path).ok()?
                 }
                 Some(())
             }
@@ -290,17 +343,129 @@ impl HardlinkManager {
     }
 }
 
-pub fn handle_file_path(dirfd: c_int, path: &str) -> LinuxResult<FilePath> {
+/// 将 `dirfd` 与 `path` 拼接为待规范化的路径
+fn dirfd_join(dirfd: c_int, path: &str) -> LinuxResult<String> {
     if path.starts_with('/') {
-        Ok(FilePath::new(path)?)
+        Ok(path.to_string())
     } else if path.is_empty() {
-        Ok(FilePath::new(File::from_fd(dirfd)?.path())?)
+        Ok(File::from_fd(dirfd)?.path().to_string())
     } else {
         let base = if dirfd == AT_FDCWD {
             FilePath::new("")?
         } else {
             FilePath::new(Directory::from_fd(dirfd)?.path())?
         };
-        Ok(base.join(path)?)
+        let mut new_path = base.0;
+        if !new_path.ends_with('/') {
+            new_path.push('/');
+        }
+        new_path.push_str(path);
+        Ok(new_path)
+    }
+}
+
+pub fn handle_file_path(dirfd: c_int, path: &str) -> LinuxResult<FilePath> {
+    Ok(FilePath::new(dirfd_join(dirfd, path)?)?)
+}
+
+/// Like [`handle_file_path`], but does not resolve the final hardlink, so that
+/// the link itself can be operated on.
+pub fn handle_link_path(dirfd: c_int, path: &str) -> LinuxResult<FilePath> {
+    Ok(FilePath::new_link(dirfd_join(dirfd, path)?)?)
+}
+
+/// The most symbolic links followed while resolving one path, like
+/// `MAXSYMLINKS`.
+const MAX_SYMLINKS: usize = 40;
+
+/// Whether the path made of `components` is a `/proc/[pid]/fd/[fd]` link.
+fn is_magic_link(components: &[String]) -> bool {
+    matches!(components, [proc, _, fd, _] if proc == "proc" && fd == "fd")
+}
+
+fn join_components(components: &[String]) -> String {
+    let mut path = String::from("/");
+    path.push_str(&components.join("/"));
+    path
+}
+
+/// Like [`handle_file_path`], but resolves `path` one component at a time
+/// to enforce the `RESOLVE_*` flags of `openat2`.
+///
+/// With `RESOLVE_BENEATH`, absolute paths, symbolic links and `..` must not
+/// leave `dirfd` (`EXDEV`). With `RESOLVE_IN_ROOT`, they are resolved as if
+/// `dirfd` was the root directory.
+pub fn handle_file_path_resolve(dirfd: c_int, path: &str, resolve: u32) -> LinuxResult<FilePath> {
+    let beneath = resolve & RESOLVE_BENEATH != 0;
+    let mut current: Vec<String> = FilePath::new(dirfd_join(dirfd, ".")?)?
+        .split('/')
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect();
+    let root_len = if resolve & (RESOLVE_BENEATH | RESOLVE_IN_ROOT) != 0 {
+        current.len()
+    } else {
+        0
+    };
+    if path.starts_with('/') {
+        if beneath {
+            return Err(LinuxError::EXDEV);
+        }
+        current.truncate(root_len);
+    }
+
+    let mut pending: VecDeque<String> = path
+        .split('/')
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect();
+    let mut links = 0;
+    let mut buf = vec![0; PATH_MAX as usize];
+    while let Some(name) = pending.pop_front() {
+        match name.as_str() {
+            "." => continue,
+            ".." => {
+                if current.len() > root_len {
+                    current.pop();
+                } else if beneath {
+                    return Err(LinuxError::EXDEV);
+                }
+                continue;
+            }
+            _ => current.push(name),
+        }
+
+        let full_path = join_components(&current);
+        if !axfs::api::symlink_metadata(&full_path).is_ok_and(|m| m.file_type().is_symlink()) {
+            continue;
+        }
+        if resolve & RESOLVE_NO_SYMLINKS != 0
+            || (resolve & RESOLVE_NO_MAGICLINKS != 0 && is_magic_link(&current))
+        {
+            return Err(LinuxError::ELOOP);
+        }
+        links += 1;
+        if links > MAX_SYMLINKS {
+            return Err(LinuxError::ELOOP);
+        }
+
+        let len = axfs::api::read_link(&full_path, &mut buf)?;
+        let target = core::str::from_utf8(&buf[..len]).map_err(|_| LinuxError::EINVAL)?;
+        current.pop();
+        if target.starts_with('/') {
+            if beneath {
+                return Err(LinuxError::EXDEV);
+            }
+            current.truncate(root_len);
+        }
+        for name in target.split('/').filter(|name| !name.is_empty()).rev() {
+            pending.push_front(name.into());
+        }
+    }
+
+    let mut resolved = join_components(&current);
+    if path.ends_with('/') && !resolved.ends_with('/') {
+        resolved.push('/');
     }
+    Ok(FilePath::new(resolved)?)
 }
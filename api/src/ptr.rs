@@ -4,7 +4,7 @@ use axerrno::{LinuxError, LinuxResult};
 use axhal::paging::MappingFlags;
 use axtask::{TaskExtRef, current};
 use memory_addr::{MemoryAddr, PAGE_SIZE_4K, VirtAddr, VirtAddrRange};
-use starry_core::mm::access_user_memory;
+use starry_core::{cow, mm::access_user_memory};
 
 fn check_region(start: VirtAddr, layout: Layout, access_flags: MappingFlags) -> LinuxResult<()> {
     let align = layout.align();
@@ -13,8 +13,24 @@ fn check_region(start: VirtAddr, layout: Layout, access_flags: MappingFlags) ->
     }
 
     let task = current();
+    // Pages hit by a memory error fail the access instead of faulting in the
+    // kernel.
+    if task
+        .task_ext()
+        .process_data()
+        .is_range_poisoned(start, layout.size())
+    {
+        return Err(LinuxError::EFAULT);
+    }
     let mut aspace = task.task_ext().process_data().aspace.lock();
 
+    // Copy-on-write pages get their own frame before the kernel writes to
+    // them.
+    if access_flags.contains(MappingFlags::WRITE) {
+        let cpus = task.task_ext().process_data().user_cpus();
+        cow::unshare(&mut aspace, cpus, start, layout.size())?;
+    }
+
     if !aspace.check_region_access(
         VirtAddrRange::from_start_size(start, layout.size()),
         access_flags,
@@ -59,6 +75,9 @@ fn check_null_terminated<T: PartialEq + Default>(
                 // querying the page table since the page might has not been
                 // allocated yet.
                 let task = current();
+                if task.task_ext().process_data().is_poisoned(page) {
+                    return Err(LinuxError::EFAULT);
+                }
                 let aspace = task.task_ext().process_data().aspace.lock();
                 if !aspace.check_region_access(
                     VirtAddrRange::from_start_size(page, PAGE_SIZE_4K),
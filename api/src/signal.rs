@@ -1,17 +1,35 @@
+use core::{mem, sync::atomic::Ordering, time::Duration};
+
 use axerrno::{LinuxError, LinuxResult};
 use axhal::{
     arch::TrapFrame,
+    time::wall_time,
     trap::{POST_TRAP, register_trap_handler},
 };
 use axprocess::{Process, ProcessGroup, Thread};
-use axsignal::{SignalInfo, SignalOSAction, SignalSet};
-use axtask::{TaskExtRef, current};
-use starry_core::task::{ProcessData, ThreadData};
+use axsignal::{SignalInfo, SignalOSAction, SignalSet, Signo};
+use axtask::{TaskExtRef, WaitQueue, current};
+use linux_raw_sys::general::{BUS_MCEERR_AO, BUS_MCEERR_AR, RLIMIT_SIGPENDING, SI_USER, SIGRTMIN};
+use memory_addr::VirtAddr;
+use starry_core::task::{ProcessData, ThreadData, set_current_affinity_mask};
 
 use crate::do_exit;
 
 pub fn check_signals(tf: &mut TrapFrame, restore_blocked: Option<SignalSet>) -> bool {
-    let Some((sig, os_action)) = current()
+    // Another thread is running `execve`, so this one must go away before
+    // handling any signal.
+    let curr = current();
+    let thread = &curr.task_ext().thread;
+    if curr
+        .task_ext()
+        .process_data()
+        .exec_tid()
+        .is_some_and(|tid| tid != thread.tid())
+    {
+        do_exit(0, false);
+    }
+
+    let Some((sig, os_action)) = curr
         .task_ext()
         .thread_data()
         .signal
@@ -20,6 +38,7 @@ pub fn check_signals(tf: &mut TrapFrame, restore_blocked: Option<SignalSet>) ->
         return false;
     };
 
+    release_signal(curr.task_ext().process_data(), &sig);
     let signo = sig.signo();
     match os_action {
         SignalOSAction::Terminate => {
@@ -49,15 +68,154 @@ fn post_trap_callback(tf: &mut TrapFrame, from_user: bool) {
         return;
     }
 
-    check_signals(tf, None);
+    let curr = current();
+    let thr_data = curr.task_ext().thread_data();
+    // Another thread changed our CPU affinity with `sched_setaffinity`.
+    if let Some(affinity) = thr_data.take_changed_affinity()
+        && !set_current_affinity_mask(affinity)
+    {
+        warn!("Failed to restrict user task to CPUs {:#x}", affinity);
+    }
+    curr.task_ext().process_data().mark_user_cpu();
+
+    // A system call that waited with a temporary signal mask was interrupted,
+    // its handlers run with that mask and the old one is restored afterwards.
+    let saved = thr_data.saved_sigmask.lock().take();
+    if !check_signals(tf, saved)
+        && let Some(saved) = saved
+    {
+        thr_data.signal.with_blocked_mut(|blocked| *blocked = saved);
+    }
+}
+
+/// How often a task in an interruptible wait checks for signals, as sending
+/// a signal only wakes the tasks waiting for one.
+pub(crate) const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Whether the current thread has a pending signal that it does not block.
+pub fn unblocked_signal_pending() -> bool {
+    let curr = current();
+    let signal = &curr.task_ext().thread_data().signal;
+    let mut pending = signal.pending();
+    pending &= !signal.with_blocked_mut(|blocked| *blocked);
+    pending != SignalSet::default()
+}
+
+/// Whether the current thread is being killed, with a `SIGKILL` pending.
+pub fn fatal_signal_pending() -> bool {
+    let curr = current();
+    curr.task_ext()
+        .thread_data()
+        .signal
+        .pending()
+        .has(Signo::SIGKILL)
+}
+
+/// Sleep for `dur`, or fail with `EINTR` once a signal that is not blocked
+/// is pending.
+pub fn sleep_interruptible(dur: Duration) -> LinuxResult {
+    let deadline = wall_time() + dur;
+    loop {
+        let left = deadline.saturating_sub(wall_time());
+        if left.is_zero() {
+            return Ok(());
+        }
+        if unblocked_signal_pending() {
+            return Err(LinuxError::EINTR);
+        }
+        axtask::sleep(left.min(SIGNAL_CHECK_INTERVAL));
+    }
+}
+
+/// Block on `wq` until `condition` holds, or fail with `EINTR` once a signal
+/// that is not blocked is pending.
+pub fn wait_interruptible(wq: &WaitQueue, condition: impl Fn() -> bool) -> LinuxResult {
+    loop {
+        if condition() {
+            return Ok(());
+        }
+        if unblocked_signal_pending() {
+            return Err(LinuxError::EINTR);
+        }
+        wq.wait_timeout_until(SIGNAL_CHECK_INTERVAL, &condition);
+    }
+}
+
+/// Run `f` with the signals in `mask` blocked instead of the current ones,
+/// like the `epoll_pwait` family does.
+///
+/// If `f` is interrupted by a signal, the old mask is only restored once the
+/// signal is handled, so that the handler runs with `mask` in effect.
+pub fn with_sigmask<R>(mut mask: SignalSet, f: impl FnOnce() -> LinuxResult<R>) -> LinuxResult<R> {
+    mask.remove(Signo::SIGKILL);
+    mask.remove(Signo::SIGSTOP);
+
+    let curr = current();
+    let thr_data = curr.task_ext().thread_data();
+    let old = thr_data
+        .signal
+        .with_blocked_mut(|blocked| mem::replace(blocked, mask));
+    let result = f();
+    if matches!(result, Err(LinuxError::EINTR)) {
+        *thr_data.saved_sigmask.lock() = Some(old);
+    } else {
+        thr_data.signal.with_blocked_mut(|blocked| *blocked = old);
+    }
+    result
+}
+
+/// Real-time signals are queued once per send, while standard signals are
+/// coalesced into one pending instance.
+fn is_realtime(sig: &SignalInfo) -> bool {
+    sig.signo() as u32 >= SIGRTMIN
+}
+
+/// Account for a signal queued to a process or one of its threads. The
+/// charge is released if the signal is dropped instead, see
+/// [`release_signal`].
+///
+/// Returns `EAGAIN` if it is a real-time signal and the process already has
+/// `RLIMIT_SIGPENDING` of them queued.
+fn charge_signal(proc_data: &ProcessData, sig: &SignalInfo) -> LinuxResult<()> {
+    if !is_realtime(sig) {
+        return Ok(());
+    }
+    let limit = proc_data.rlimits.read().get(RLIMIT_SIGPENDING).soft;
+    proc_data
+        .queued_rt_signals
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |queued| {
+            ((queued as u64) < limit).then_some(queued + 1)
+        })
+        .map_err(|_| LinuxError::EAGAIN)?;
+    Ok(())
+}
+
+/// Release the charge of a signal taken off the queue of the current process
+/// or thread.
+pub fn release_signal(proc_data: &ProcessData, sig: &SignalInfo) {
+    if is_realtime(sig) {
+        let _ = proc_data.queued_rt_signals.fetch_update(
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            |queued| queued.checked_sub(1),
+        );
+    }
 }
 
 pub fn send_signal_thread(thr: &Thread, sig: SignalInfo) -> LinuxResult<()> {
     info!("Send signal {:?} to thread {}", sig.signo(), thr.tid());
-    let Some(thr) = thr.data::<ThreadData>() else {
+    let (Some(thr_data), Some(proc_data)) = (
+        thr.data::<ThreadData>(),
+        thr.process().data::<ProcessData>(),
+    ) else {
         return Err(LinuxError::EPERM);
     };
-    thr.signal.send_signal(sig);
+    charge_signal(proc_data, &sig)?;
+    let charge = sig.clone();
+    // An ignored signal is dropped without being queued.
+    if !thr_data.signal.send_signal(sig) {
+        release_signal(proc_data, &charge);
+    }
     Ok(())
 }
 
@@ -66,10 +224,48 @@ pub fn send_signal_process(proc: &Process, sig: SignalInfo) -> LinuxResult<()> {
     let Some(proc) = proc.data::<ProcessData>() else {
         return Err(LinuxError::EPERM);
     };
-    proc.signal.send_signal(sig);
+    charge_signal(proc, &sig)?;
+    let charge = sig.clone();
+    if !proc.signal.send_signal(sig) {
+        release_signal(proc, &charge);
+    }
     Ok(())
 }
 
+/// Raise `SIGPIPE` on the current thread, after it wrote to a pipe that has
+/// no reader left.
+pub fn send_sigpipe() {
+    let curr = current();
+    let sig = SignalInfo::new(Signo::SIGPIPE, SI_USER as _);
+    let _ = send_signal_thread(&curr.task_ext().thread, sig);
+}
+
+/// Report an uncorrectable memory error at `vaddr` with `SIGBUS`.
+///
+/// If `action_required` is set, the error was consumed by `thr` and it gets
+/// `BUS_MCEERR_AR`. Otherwise the page was found bad before any access and
+/// `BUS_MCEERR_AO` is sent.
+pub fn send_memory_failure_signal(
+    thr: &Thread,
+    vaddr: VirtAddr,
+    action_required: bool,
+) -> LinuxResult<()> {
+    let code = if action_required {
+        BUS_MCEERR_AR
+    } else {
+        BUS_MCEERR_AO
+    };
+    let mut sig = SignalInfo::new(Signo::SIGBUS, code as _);
+    // SAFETY: `SIGBUS` carries the `_sigfault` fields.
+    unsafe {
+        let fault = &mut sig.0.__bindgen_anon_1.__bindgen_anon_1._sifields._sigfault;
+        fault._addr = vaddr.as_mut_ptr() as _;
+        // The whole page is affected.
+        fault.__bindgen_anon_1._addr_lsb = 12;
+    }
+    send_signal_thread(thr, sig)
+}
+
 pub fn send_signal_process_group(pg: &ProcessGroup, sig: SignalInfo) -> usize {
     info!(
         "Send signal {:?} to process group {}",
@@ -0,0 +1,24 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <unistd.h>
+
+void test_access_x() {
+  close(open("access_x_file", O_CREAT | O_WRONLY | O_TRUNC, 0644));
+  if (access("access_x_file", X_OK) == -1 && errno == EACCES &&
+      access("access_x_file", R_OK | W_OK) == 0) {
+    puts("test_access_x ok");
+  }
+  close(open("access_x_exec", O_CREAT | O_WRONLY | O_TRUNC, 0755));
+  if (access("access_x_exec", X_OK) == 0 &&
+      faccessat(AT_FDCWD, "access_x_exec", X_OK, 0) == 0) {
+    puts("test_access_x ok2");
+  }
+  unlink("access_x_file");
+  unlink("access_x_exec");
+}
+
+int main() {
+  test_access_x();
+  return 0;
+}
@@ -0,0 +1,45 @@
+#include <errno.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define ARGS 128
+#define ARG_LEN (32 * 1024)
+
+void test_arg_max(char *self) {
+  // 4 MiB of arguments is over the limit of Linux as well.
+  static char *argv[ARGS + 2];
+  argv[0] = self;
+  for (int i = 1; i <= ARGS; i++) {
+    argv[i] = malloc(ARG_LEN);
+    memset(argv[i], 'a', ARG_LEN - 1);
+    argv[i][ARG_LEN - 1] = '\0';
+  }
+  argv[ARGS + 1] = NULL;
+  if (execv(self, argv) == -1 && errno == E2BIG) {
+    puts("test_arg_max ok");
+  }
+
+  // Arguments within the limit still work.
+  pid_t pid = fork();
+  if (pid == 0) {
+    argv[2] = NULL;
+    execv(self, argv);
+    _exit(1);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_arg_max ok2");
+  }
+}
+
+int main(int argc, char *argv[]) {
+  if (argc == 2) {
+    return strlen(argv[1]) != ARG_LEN - 1;
+  }
+  test_arg_max(argv[0]);
+  return 0;
+}
@@ -0,0 +1,92 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <sys/stat.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+// Run `fn` in a child with the user ID `uid`, returning its exit status.
+static int as_user(uid_t uid, int (*fn)(void)) {
+  pid_t pid = fork();
+  if (pid == 0) {
+    if (setuid(uid) != 0) {
+      _exit(2);
+    }
+    _exit(fn());
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  return WIFEXITED(status) ? WEXITSTATUS(status) : -1;
+}
+
+static int can_open(void) {
+  int fd = open("chown_private", O_RDONLY);
+  if (fd < 0) {
+    return errno == EACCES ? 1 : 2;
+  }
+  close(fd);
+  return 0;
+}
+
+static int can_access(void) {
+  return access("chown_private", R_OK) == 0 ? 0 : 1;
+}
+
+void test_owner() {
+  int fd = open("chown_owner", O_CREAT | O_WRONLY | O_TRUNC, 0644);
+  close(fd);
+  struct stat st;
+  if (chown("chown_owner", 1000, 1000) == 0 && stat("chown_owner", &st) == 0 &&
+      st.st_uid == 1000 && st.st_gid == 1000) {
+    puts("test_owner ok");
+  }
+  // A file is owned by the user who creates it.
+  pid_t pid = fork();
+  if (pid == 0) {
+    setuid(1000);
+    close(open("chown_mine", O_CREAT | O_WRONLY | O_TRUNC, 0600));
+    _exit(0);
+  }
+  waitpid(pid, NULL, 0);
+  if (stat("chown_mine", &st) == 0 && st.st_uid == 1000) {
+    puts("test_owner ok2");
+  }
+  unlink("chown_owner");
+  unlink("chown_mine");
+}
+
+void test_access() {
+  int fd = open("chown_private", O_CREAT | O_WRONLY | O_TRUNC, 0600);
+  close(fd);
+  chown("chown_private", 1000, 1000);
+  if (as_user(1000, can_open) == 0 && as_user(1000, can_access) == 0) {
+    puts("test_access ok");
+  }
+  if (as_user(2000, can_open) == 1 && as_user(2000, can_access) == 1) {
+    puts("test_access ok2");
+  }
+  unlink("chown_private");
+}
+
+void test_setid() {
+  int fd = open("chown_setid", O_CREAT | O_WRONLY | O_TRUNC, 06755);
+  close(fd);
+  struct stat st;
+  stat("chown_setid", &st);
+  if ((st.st_mode & 07777) == 06755) {
+    puts("test_setid ok");
+  }
+  chown("chown_setid", 1000, 1000);
+  stat("chown_setid", &st);
+  if ((st.st_mode & 07777) == 0755) {
+    puts("test_setid ok2");
+  }
+  unlink("chown_setid");
+}
+
+int main() {
+  test_owner();
+  test_access();
+  test_setid();
+  return 0;
+}
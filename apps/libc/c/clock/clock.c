@@ -0,0 +1,94 @@
+#include <errno.h>
+#include <stdio.h>
+#include <time.h>
+
+static long long ns_of(const struct timespec *ts) {
+  return ts->tv_sec * 1000000000LL + ts->tv_nsec;
+}
+
+void test_monotonic() {
+  struct timespec prev, now;
+  clock_gettime(CLOCK_MONOTONIC, &prev);
+  int ok = 1;
+  for (int i = 0; i < 1000; i++) {
+    clock_gettime(CLOCK_MONOTONIC, &now);
+    ok &= ns_of(&now) >= ns_of(&prev);
+    prev = now;
+  }
+  if (ok) {
+    puts("test_monotonic ok");
+  }
+}
+
+void test_clocks() {
+  const clockid_t clocks[] = {CLOCK_REALTIME,         CLOCK_MONOTONIC,
+                              CLOCK_MONOTONIC_RAW,    CLOCK_MONOTONIC_COARSE,
+                              CLOCK_BOOTTIME,         CLOCK_PROCESS_CPUTIME_ID,
+                              CLOCK_THREAD_CPUTIME_ID};
+  struct timespec ts;
+  int ok = 1;
+  for (int i = 0; i < sizeof(clocks) / sizeof(clocks[0]); i++) {
+    ok &= clock_gettime(clocks[i], &ts) == 0 && ts.tv_nsec >= 0 &&
+          ts.tv_nsec < 1000000000;
+  }
+  if (ok) {
+    puts("test_clocks ok");
+  }
+
+  // After spinning for 50ms, the process has run for a while, but not for
+  // longer than the system.
+  struct timespec start, mono;
+  clock_gettime(CLOCK_MONOTONIC, &start);
+  do {
+    clock_gettime(CLOCK_MONOTONIC, &mono);
+  } while (ns_of(&mono) - ns_of(&start) < 50000000);
+  clock_gettime(CLOCK_PROCESS_CPUTIME_ID, &ts);
+  clock_gettime(CLOCK_MONOTONIC, &mono);
+  if (ns_of(&ts) > 0 && ns_of(&ts) <= ns_of(&mono)) {
+    puts("test_clocks ok2");
+  }
+  if (clock_gettime(100, &ts) == -1 && errno == EINVAL) {
+    puts("test_clocks ok3");
+  }
+}
+
+// Sleeping to an absolute deadline wakes up at it, not a relative time
+// from the call.
+void test_abs_sleep() {
+  struct timespec start, deadline, now;
+  clock_gettime(CLOCK_MONOTONIC, &start);
+  deadline = start;
+  deadline.tv_nsec += 100000000;
+  if (deadline.tv_nsec >= 1000000000) {
+    deadline.tv_sec++;
+    deadline.tv_nsec -= 1000000000;
+  }
+  int ret = clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, &deadline, NULL);
+  clock_gettime(CLOCK_MONOTONIC, &now);
+  long long late = ns_of(&now) - ns_of(&deadline);
+  if (ret == 0 && late >= 0 && late < 50000000) {
+    puts("test_abs_sleep ok");
+  }
+
+  // A deadline in the past returns right away.
+  clock_gettime(CLOCK_MONOTONIC, &start);
+  ret = clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, &deadline, NULL);
+  clock_gettime(CLOCK_MONOTONIC, &now);
+  if (ret == 0 && ns_of(&now) - ns_of(&start) < 50000000) {
+    puts("test_abs_sleep ok2");
+  }
+
+  // clock_nanosleep returns the error instead of setting errno.
+  struct timespec bad = {0, 1000000000};
+  if (clock_nanosleep(CLOCK_MONOTONIC, 0, &bad, NULL) == EINVAL &&
+      clock_nanosleep(100, 0, &start, NULL) == EINVAL) {
+    puts("test_abs_sleep ok3");
+  }
+}
+
+int main() {
+  test_monotonic();
+  test_clocks();
+  test_abs_sleep();
+  return 0;
+}
@@ -0,0 +1,105 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/epoll.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static char *self;
+
+// Run this program again with `fd`, and return whether `fd` is still open
+// in the new image.
+static int open_after_exec(int fd) {
+  pid_t pid = fork();
+  if (pid == 0) {
+    char arg[16];
+    snprintf(arg, sizeof(arg), "%d", fd);
+    char *argv[] = {self, "check", arg, NULL};
+    execv(self, argv);
+    _exit(2);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  return WIFEXITED(status) && WEXITSTATUS(status) == 0;
+}
+
+void test_getfd() {
+  int fd = open("/dev/null", O_RDONLY);
+  if (fcntl(fd, F_GETFD) == 0 && fcntl(fd, F_SETFD, FD_CLOEXEC) == 0 &&
+      fcntl(fd, F_GETFD) == FD_CLOEXEC) {
+    puts("test_getfd ok");
+  }
+  if (!open_after_exec(fd)) {
+    puts("test_getfd ok2");
+  }
+  close(fd);
+}
+
+void test_exec_close() {
+  int kept = open("/dev/null", O_RDONLY);
+  int closed = open("/dev/null", O_RDONLY | O_CLOEXEC);
+  if (open_after_exec(kept) && !open_after_exec(closed)) {
+    puts("test_exec_close ok");
+  }
+  close(kept);
+  close(closed);
+}
+
+void test_dup3() {
+  int fd = open("/dev/null", O_RDONLY);
+  if (dup3(fd, 100, O_CLOEXEC) == 100 && fcntl(100, F_GETFD) == FD_CLOEXEC &&
+      dup3(fd, 101, 0) == 101 && fcntl(101, F_GETFD) == 0) {
+    puts("test_dup3 ok");
+  }
+  if (dup3(fd, fd, 0) == -1 && errno == EINVAL &&
+      dup3(fd, 102, O_NONBLOCK) == -1 && errno == EINVAL) {
+    puts("test_dup3 ok2");
+  }
+  if (!open_after_exec(100) && open_after_exec(101)) {
+    puts("test_dup3 ok3");
+  }
+  close(fd);
+  close(100);
+  close(101);
+}
+
+void test_epoll_cloexec() {
+  int closed = epoll_create1(EPOLL_CLOEXEC);
+  int kept = epoll_create1(0);
+  if (fcntl(closed, F_GETFD) == FD_CLOEXEC && !open_after_exec(closed) &&
+      open_after_exec(kept)) {
+    puts("test_epoll_cloexec ok");
+  }
+  if (epoll_create1(1) == -1 && errno == EINVAL) {
+    puts("test_epoll_cloexec ok2");
+  }
+  close(closed);
+  close(kept);
+}
+
+// Clearing FD_CLOEXEC keeps the fd open across exec again.
+void test_setfd_clear() {
+  int fd = open("/dev/null", O_RDONLY | O_CLOEXEC);
+  if (fcntl(fd, F_SETFD, 0) == 0 && fcntl(fd, F_GETFD) == 0 &&
+      open_after_exec(fd)) {
+    puts("test_setfd_clear ok");
+  }
+  close(fd);
+}
+
+int main(int argc, char *argv[]) {
+  if (argc == 3 && strcmp(argv[1], "check") == 0) {
+    // Succeed if the descriptor was kept.
+    return fcntl(atoi(argv[2]), F_GETFD) == -1 && errno == EBADF;
+  }
+  self = argv[0];
+  test_getfd();
+  test_exec_close();
+  test_dup3();
+  test_epoll_cloexec();
+  test_setfd_clear();
+  return 0;
+}
@@ -0,0 +1,80 @@
+#include <errno.h>
+#include <linux/sched.h>
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+// Fork a child with PID `tid` that waits for a byte on `fd`, or exits at
+// once if `fd` is -1.
+static pid_t fork_with_tid(pid_t tid, int fd) {
+  struct clone_args args;
+  memset(&args, 0, sizeof(args));
+  args.exit_signal = SIGCHLD;
+  args.set_tid = (uint64_t)(uintptr_t)&tid;
+  args.set_tid_size = 1;
+  pid_t pid = syscall(SYS_clone3, &args, sizeof(args));
+  if (pid == 0) {
+    char c;
+    if (fd != -1) {
+      read(fd, &c, 1);
+    }
+    _exit(getpid() == tid ? 0 : 1);
+  }
+  return pid;
+}
+
+void test_set_tid() {
+  pid_t tid = getpid() + 1000;
+  int status;
+  if (fork_with_tid(tid, -1) == tid && waitpid(tid, &status, 0) == tid &&
+      WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_set_tid ok");
+  }
+  if (fork_with_tid(getpid(), -1) == -1 && errno == EEXIST) {
+    puts("test_set_tid ok2");
+  }
+}
+
+void test_skip() {
+  int pipefd[2];
+  pipe(pipefd);
+  // Take the PIDs the next children would most likely get.
+  pid_t last = fork();
+  if (last == 0) {
+    _exit(0);
+  }
+  waitpid(last, NULL, 0);
+  pid_t taken[4];
+  for (int i = 0; i < 4; i++) {
+    taken[i] = fork_with_tid(last + 1 + i, pipefd[0]);
+  }
+
+  pid_t pid = fork();
+  if (pid == 0) {
+    _exit(0);
+  }
+  int clash = 0;
+  for (int i = 0; i < 4; i++) {
+    clash |= pid == taken[i];
+  }
+  if (pid > 0 && !clash) {
+    puts("test_skip ok");
+  }
+  waitpid(pid, NULL, 0);
+  write(pipefd[1], "xxxx", 4);
+  for (int i = 0; i < 4; i++) {
+    waitpid(taken[i], NULL, 0);
+  }
+  close(pipefd[0]);
+  close(pipefd[1]);
+}
+
+int main() {
+  test_set_tid();
+  test_skip();
+  return 0;
+}
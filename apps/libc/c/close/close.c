@@ -0,0 +1,60 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <unistd.h>
+
+void test_close() {
+  int fd = open("close.tmp", O_CREAT | O_WRONLY | O_TRUNC, 0644);
+  write(fd, "data", 4);
+  int dup_fd = dup(fd);
+  // Nothing failed to be written, so close succeeds and releases the fd.
+  if (close(fd) == 0 && close(fd) == -1 && errno == EBADF) {
+    puts("test_close ok");
+  }
+  if (close(dup_fd) == 0) {
+    puts("test_close ok2");
+  }
+
+  int ro = open("close.tmp", O_RDONLY);
+  if (close(ro) == 0 && fcntl(ro, F_GETFD) == -1 && errno == EBADF) {
+    puts("test_close ok3");
+  }
+  unlink("close.tmp");
+}
+
+// Make the next `n` write-backs fail with EIO.
+static int fail_writeback(int n) {
+  int fd = open("/proc/sys/debug/fail_writeback", O_WRONLY);
+  if (fd < 0) {
+    return -1;
+  }
+  dprintf(fd, "%d\n", n);
+  close(fd);
+  return 0;
+}
+
+void test_close_error() {
+  int fd = open("close.tmp", O_CREAT | O_WRONLY | O_TRUNC, 0644);
+  int other = open("close.tmp", O_RDWR);
+  write(fd, "data", 4);
+  if (fail_writeback(1) != 0) {
+    return;
+  }
+  // sync cannot fail, the error is left for the open files to report.
+  sync();
+  if (close(fd) == -1 && errno == EIO && fcntl(fd, F_GETFD) == -1 &&
+      errno == EBADF) {
+    puts("test_close_error ok");
+  }
+  if (fsync(other) == -1 && errno == EIO && fsync(other) == 0 &&
+      close(other) == 0) {
+    puts("test_close_error ok2");
+  }
+  unlink("close.tmp");
+}
+
+int main() {
+  test_close();
+  test_close_error();
+  return 0;
+}
@@ -0,0 +1,159 @@
+#include <pthread.h>
+#include <sched.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+// Run `child` in a forked child and return its exit status.
+static int run_child(int (*child)(char *), char *page) {
+  pid_t pid = fork();
+  if (pid == 0) {
+    _exit(child(page));
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  return WIFEXITED(status) ? WEXITSTATUS(status) : -1;
+}
+
+static int write_page(char *page) {
+  if (strcmp(page, "parent") != 0) {
+    return 1;
+  }
+  strcpy(page, "child");
+  return strcmp(page, "child") != 0;
+}
+
+// The kernel writes to the page on behalf of the child.
+static int read_into_page(char *page) {
+  int fds[2];
+  pipe(fds);
+  write(fds[1], "child", 6);
+  if (read(fds[0], page, 6) != 6) {
+    return 1;
+  }
+  return strcmp(page, "child") != 0;
+}
+
+void test_anon() {
+  char *page = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+                    MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  strcpy(page, "parent");
+  if (run_child(write_page, page) == 0 && strcmp(page, "parent") == 0) {
+    puts("test_anon ok");
+  }
+  if (run_child(read_into_page, page) == 0 && strcmp(page, "parent") == 0) {
+    puts("test_anon ok2");
+  }
+
+  // The parent writes while the child still shares the page.
+  int fds[2];
+  pipe(fds);
+  pid_t pid = fork();
+  if (pid == 0) {
+    char c;
+    read(fds[0], &c, 1);
+    _exit(strcmp(page, "parent") != 0);
+  }
+  strcpy(page, "again");
+  write(fds[1], "x", 1);
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0 &&
+      strcmp(page, "again") == 0) {
+    puts("test_anon ok3");
+  }
+  munmap(page, 4096);
+}
+
+void test_heap() {
+  char *buf = malloc(64);
+  strcpy(buf, "parent");
+  if (run_child(write_page, buf) == 0 && strcmp(buf, "parent") == 0) {
+    puts("test_heap ok");
+  }
+  free(buf);
+}
+
+void test_readonly() {
+  char *page = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+                    MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  strcpy(page, "parent");
+  mprotect(page, 4096, PROT_READ);
+  pid_t pid = fork();
+  if (pid == 0) {
+    // Made writable again, the page is still copied on write.
+    mprotect(page, 4096, PROT_READ | PROT_WRITE);
+    strcpy(page, "child");
+    _exit(0);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0 &&
+      strcmp(page, "parent") == 0) {
+    puts("test_readonly ok");
+  }
+  munmap(page, 4096);
+}
+
+void test_shared_mapping() {
+  char *page = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+                    MAP_SHARED | MAP_ANONYMOUS, -1, 0);
+  strcpy(page, "parent");
+  if (run_child(write_page, page) == 0 && strcmp(page, "child") == 0) {
+    puts("test_shared_mapping ok");
+  }
+  munmap(page, 4096);
+}
+
+static volatile int stop_writer;
+
+static void *writer(void *arg) {
+  volatile long *counter = arg;
+  while (!stop_writer) {
+    (*counter)++;
+    sched_yield();
+  }
+  return NULL;
+}
+
+// Another thread of the parent keeps writing to the page across the fork.
+void test_threads() {
+  volatile long *page = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+                             MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  pthread_t thread;
+  pthread_create(&thread, NULL, writer, (void *)page);
+  while (*page == 0) {
+    sched_yield();
+  }
+  pid_t pid = fork();
+  if (pid == 0) {
+    long seen = *page;
+    usleep(20000);
+    _exit(*page != seen);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  long before = *page;
+  usleep(20000);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_threads ok");
+  }
+  if (*page > before) {
+    puts("test_threads ok2");
+  }
+  stop_writer = 1;
+  pthread_join(thread, NULL);
+  munmap((void *)page, 4096);
+}
+
+int main() {
+  test_anon();
+  test_heap();
+  test_readonly();
+  test_shared_mapping();
+  test_threads();
+  return 0;
+}
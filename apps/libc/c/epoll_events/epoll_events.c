@@ -0,0 +1,74 @@
+#include <errno.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <sys/epoll.h>
+#include <unistd.h>
+
+#define MAX_EVENTS 100000
+
+// A maxevents far above the number of watched fds still reports just the
+// ready ones.
+void test_maxevents() {
+  int epfd = epoll_create1(0);
+  int fds[3][2];
+  for (int i = 0; i < 3; i++) {
+    pipe(fds[i]);
+    struct epoll_event ev = {.events = EPOLLIN, .data.fd = fds[i][0]};
+    epoll_ctl(epfd, EPOLL_CTL_ADD, fds[i][0], &ev);
+  }
+  write(fds[0][1], "x", 1);
+  write(fds[2][1], "x", 1);
+
+  struct epoll_event *events = calloc(MAX_EVENTS, sizeof(*events));
+  int n = epoll_wait(epfd, events, MAX_EVENTS, 0);
+  if (n == 2 && events[0].data.fd != events[1].data.fd &&
+      (events[0].data.fd == fds[0][0] || events[0].data.fd == fds[2][0]) &&
+      (events[1].data.fd == fds[0][0] || events[1].data.fd == fds[2][0])) {
+    puts("test_maxevents ok");
+  }
+  // The same goes for a wait that blocks until the timeout.
+  char c;
+  read(fds[0][0], &c, 1);
+  read(fds[2][0], &c, 1);
+  if (epoll_wait(epfd, events, MAX_EVENTS, 10) == 0) {
+    puts("test_maxevents ok2");
+  }
+  if (epoll_wait(epfd, events, 0, 0) == -1 && errno == EINVAL) {
+    puts("test_maxevents ok3");
+  }
+
+  free(events);
+  for (int i = 0; i < 3; i++) {
+    close(fds[i][0]);
+    close(fds[i][1]);
+  }
+  close(epfd);
+}
+
+// A watched fd that is closed is reported as hung up once, and then no
+// longer watched.
+void test_closed_hup() {
+  int epfd = epoll_create1(0);
+  int fds[2];
+  pipe(fds);
+  struct epoll_event ev = {.events = EPOLLIN, .data.u64 = 42};
+  epoll_ctl(epfd, EPOLL_CTL_ADD, fds[0], &ev);
+  close(fds[0]);
+  close(fds[1]);
+
+  struct epoll_event events[4];
+  if (epoll_wait(epfd, events, 4, 0) == 1 && events[0].events == EPOLLHUP &&
+      events[0].data.u64 == 42) {
+    puts("test_closed_hup ok");
+  }
+  if (epoll_wait(epfd, events, 4, 10) == 0) {
+    puts("test_closed_hup ok2");
+  }
+  close(epfd);
+}
+
+int main() {
+  test_maxevents();
+  test_closed_hup();
+  return 0;
+}
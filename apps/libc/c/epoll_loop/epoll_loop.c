@@ -0,0 +1,39 @@
+#include <errno.h>
+#include <stdio.h>
+#include <sys/epoll.h>
+#include <unistd.h>
+
+void test_epoll_self() {
+  int epfd = epoll_create1(0);
+  struct epoll_event ev = {.events = EPOLLIN};
+  if (epoll_ctl(epfd, EPOLL_CTL_ADD, epfd, &ev) == -1 && errno == EINVAL) {
+    puts("test_epoll_self ok");
+  }
+  close(epfd);
+}
+
+void test_epoll_cycle() {
+  int a = epoll_create1(0);
+  int b = epoll_create1(0);
+  struct epoll_event ev = {.events = EPOLLIN};
+  if (epoll_ctl(a, EPOLL_CTL_ADD, b, &ev) == 0) {
+    puts("test_epoll_cycle ok");
+  }
+  if (epoll_ctl(b, EPOLL_CTL_ADD, a, &ev) == -1 && errno == ELOOP) {
+    puts("test_epoll_cycle ok2");
+  }
+  // Nesting without a cycle is still allowed.
+  int c = epoll_create1(0);
+  if (epoll_ctl(b, EPOLL_CTL_ADD, c, &ev) == 0) {
+    puts("test_epoll_cycle ok3");
+  }
+  close(c);
+  close(b);
+  close(a);
+}
+
+int main() {
+  test_epoll_self();
+  test_epoll_cycle();
+  return 0;
+}
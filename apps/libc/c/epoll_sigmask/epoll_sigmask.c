@@ -0,0 +1,91 @@
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <sys/epoll.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+#ifndef SYS_epoll_pwait2
+#define SYS_epoll_pwait2 441
+#endif
+
+static volatile int handled;
+
+static void handler(int signum) {
+  (void)signum;
+  handled = 1;
+}
+
+// Send SIGUSR1 to the caller a little later, from a child.
+static pid_t signal_later(void) {
+  pid_t parent = getpid();
+  pid_t pid = fork();
+  if (pid == 0) {
+    usleep(100000);
+    kill(parent, SIGUSR1);
+    _exit(0);
+  }
+  return pid;
+}
+
+static int still_blocked(void) {
+  sigset_t cur;
+  sigprocmask(SIG_BLOCK, NULL, &cur);
+  return sigismember(&cur, SIGUSR1);
+}
+
+void test_pwait(int pwait2) {
+  const char *name = pwait2 ? "test_pwait2" : "test_pwait";
+  signal(SIGUSR1, handler);
+  sigset_t block, empty;
+  sigemptyset(&block);
+  sigaddset(&block, SIGUSR1);
+  sigemptyset(&empty);
+  sigprocmask(SIG_BLOCK, &block, NULL);
+
+  int epfd = epoll_create1(0);
+  struct epoll_event ev;
+  handled = 0;
+  pid_t pid = signal_later();
+  int ret;
+  if (pwait2) {
+    ret = syscall(SYS_epoll_pwait2, epfd, &ev, 1, NULL, &empty, _NSIG / 8);
+  } else {
+    ret = epoll_pwait(epfd, &ev, 1, -1, &empty);
+  }
+  if (ret == -1 && errno == EINTR && handled) {
+    printf("%s ok\n", name);
+  }
+  // The old mask is back once the handler ran.
+  if (still_blocked()) {
+    printf("%s ok2\n", name);
+  }
+  waitpid(pid, NULL, 0);
+  close(epfd);
+  sigprocmask(SIG_UNBLOCK, &block, NULL);
+}
+
+void test_timeout() {
+  int epfd = epoll_create1(0);
+  struct epoll_event ev;
+  struct timespec ts = {0, 10000000};
+  sigset_t empty;
+  sigemptyset(&empty);
+  if (syscall(SYS_epoll_pwait2, epfd, &ev, 1, &ts, &empty, _NSIG / 8) == 0) {
+    puts("test_timeout ok");
+  }
+  if (syscall(SYS_epoll_pwait2, epfd, &ev, 1, &ts, &empty, 1) == -1 &&
+      errno == EINVAL) {
+    puts("test_timeout ok2");
+  }
+  close(epfd);
+}
+
+int main() {
+  test_pwait(0);
+  test_pwait(1);
+  test_timeout();
+  return 0;
+}
@@ -0,0 +1,47 @@
+#include <errno.h>
+#include <poll.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <sys/eventfd.h>
+#include <unistd.h>
+
+static int readable(int fd) {
+  struct pollfd pfd = {.fd = fd, .events = POLLIN};
+  return poll(&pfd, 1, 0) == 1 && pfd.revents & POLLIN;
+}
+
+void test_eventfd_counter() {
+  int fd = eventfd(5, EFD_NONBLOCK);
+  uint64_t value = 0;
+  // The whole counter is read at once.
+  if (readable(fd) && read(fd, &value, 8) == 8 && value == 5 &&
+      !readable(fd) && read(fd, &value, 8) == -1 && errno == EAGAIN) {
+    puts("test_eventfd_counter ok");
+  }
+  close(fd);
+}
+
+void test_eventfd_sem() {
+  int fd = eventfd(5, EFD_NONBLOCK | EFD_SEMAPHORE);
+  uint64_t value = 0;
+  int reads = 0;
+  // Each read takes one off the counter.
+  while (readable(fd) && read(fd, &value, 8) == 8 && value == 1) {
+    reads++;
+  }
+  if (reads == 5 && read(fd, &value, 8) == -1 && errno == EAGAIN) {
+    puts("test_eventfd_sem ok");
+  }
+  value = 2;
+  write(fd, &value, 8);
+  if (read(fd, &value, 8) == 8 && value == 1 && readable(fd)) {
+    puts("test_eventfd_sem ok2");
+  }
+  close(fd);
+}
+
+int main() {
+  test_eventfd_counter();
+  test_eventfd_sem();
+  return 0;
+}
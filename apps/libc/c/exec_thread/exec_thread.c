@@ -0,0 +1,98 @@
+#include <pthread.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static char *self;
+
+static void *exec_from_thread(void *arg) {
+  char pid[16];
+  snprintf(pid, sizeof(pid), "%d", getpid());
+  char *argv[] = {self, "exec", pid, NULL};
+  execv(self, argv);
+  perror("execv");
+  return arg;
+}
+
+void test_exec_thread() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    pthread_t thread;
+    pthread_create(&thread, NULL, exec_from_thread, NULL);
+    // The main thread is killed by the exec.
+    for (;;) {
+      pause();
+    }
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_exec_thread ok2");
+  }
+}
+
+static int pipe_fds[2];
+
+static void *sleep_long(void *arg) {
+  sleep(1000);
+  return arg;
+}
+
+static void *read_empty_pipe(void *arg) {
+  char c;
+  read(pipe_fds[0], &c, 1);
+  return arg;
+}
+
+static void *exec_later(void *arg) {
+  usleep(100000);
+  char *argv[] = {self, "blocked", NULL};
+  execv(self, argv);
+  perror("execv");
+  return arg;
+}
+
+void test_exec_blocked() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    // The other threads are blocked in the kernel when the exec kills
+    // them: in a sleep, a pipe read and, for the main thread, a futex wait.
+    pipe(pipe_fds);
+    pthread_t sleeper, reader, thread;
+    pthread_create(&sleeper, NULL, sleep_long, NULL);
+    pthread_create(&reader, NULL, read_empty_pipe, NULL);
+    pthread_create(&thread, NULL, exec_later, NULL);
+    pthread_join(sleeper, NULL);
+    _exit(1);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_exec_blocked ok2");
+  }
+}
+
+int main(int argc, char *argv[]) {
+  if (argc == 2 && strcmp(argv[1], "blocked") == 0) {
+    puts("test_exec_blocked ok");
+    fflush(stdout);
+    return 0;
+  }
+  if (argc == 3 && strcmp(argv[1], "exec") == 0) {
+    // The new image runs on the thread that called exec, with the TID of
+    // the leader.
+    if (getpid() == atoi(argv[2]) && syscall(SYS_gettid) == getpid()) {
+      puts("test_exec_thread ok");
+      fflush(stdout);
+      return 0;
+    }
+    return 1;
+  }
+  self = argv[0];
+  test_exec_thread();
+  test_exec_blocked();
+  return 0;
+}
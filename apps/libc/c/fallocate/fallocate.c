@@ -0,0 +1,67 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+#define PATH "/tmp/fallocate_test"
+
+static int all_zero(const char *buf, int len) {
+  for (int i = 0; i < len; i++) {
+    if (buf[i] != 0) {
+      return 0;
+    }
+  }
+  return 1;
+}
+
+// Preallocating past the end of file extends it with zeros.
+void test_fallocate() {
+  int fd = open(PATH, O_CREAT | O_TRUNC | O_RDWR, 0644);
+  write(fd, "data", 4);
+  struct stat st;
+  char buf[64];
+  if (fallocate(fd, 0, 0, 8192) == 0 && fstat(fd, &st) == 0 &&
+      st.st_size == 8192 && pread(fd, buf, 4, 0) == 4 &&
+      memcmp(buf, "data", 4) == 0 && pread(fd, buf, 64, 4096) == 64 &&
+      all_zero(buf, 64)) {
+    puts("test_fallocate ok");
+  }
+  // With FALLOC_FL_KEEP_SIZE, the size does not change.
+  if (fallocate(fd, FALLOC_FL_KEEP_SIZE, 0, 16384) == 0 &&
+      fstat(fd, &st) == 0 && st.st_size == 8192) {
+    puts("test_fallocate ok2");
+  }
+  if (fallocate(fd, 0, 0, 0) == -1 && errno == EINVAL &&
+      fallocate(fd, FALLOC_FL_PUNCH_HOLE, 0, 4096) == -1) {
+    puts("test_fallocate ok3");
+  }
+  close(fd);
+  unlink(PATH);
+}
+
+// Punching a hole in the middle zeroes it and keeps the rest.
+void test_punch_hole() {
+  int fd = open(PATH, O_CREAT | O_TRUNC | O_RDWR, 0644);
+  char buf[12288];
+  memset(buf, 'x', sizeof(buf));
+  write(fd, buf, sizeof(buf));
+  int mode = FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE;
+  struct stat st;
+  if (fallocate(fd, mode, 4096, 4096) == 0 && fstat(fd, &st) == 0 &&
+      st.st_size == sizeof(buf) &&
+      pread(fd, buf, sizeof(buf), 0) == sizeof(buf) && buf[4095] == 'x' &&
+      all_zero(buf + 4096, 4096) && buf[8192] == 'x') {
+    puts("test_punch_hole ok");
+  }
+  close(fd);
+  unlink(PATH);
+}
+
+int main() {
+  test_fallocate();
+  test_punch_hole();
+  return 0;
+}
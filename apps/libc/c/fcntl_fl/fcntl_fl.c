@@ -0,0 +1,67 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <unistd.h>
+
+void test_file() {
+  int fd = open("fcntl_fl_file", O_CREAT | O_WRONLY | O_TRUNC, 0644);
+  if ((fcntl(fd, F_GETFL) & (O_ACCMODE | O_APPEND)) == O_WRONLY) {
+    puts("test_file ok");
+  }
+  fcntl(fd, F_SETFL, O_APPEND | O_NONBLOCK);
+  int flags = fcntl(fd, F_GETFL);
+  if ((flags & (O_ACCMODE | O_APPEND | O_NONBLOCK)) ==
+      (O_WRONLY | O_APPEND | O_NONBLOCK)) {
+    puts("test_file ok2");
+  }
+  // The access mode cannot be changed.
+  fcntl(fd, F_SETFL, O_RDWR);
+  if ((fcntl(fd, F_GETFL) & (O_ACCMODE | O_APPEND | O_NONBLOCK)) == O_WRONLY) {
+    puts("test_file ok3");
+  }
+  close(fd);
+  unlink("fcntl_fl_file");
+}
+
+void test_dir() {
+  int fd = open(".", O_RDONLY | O_DIRECTORY);
+  int mask = O_ACCMODE | O_DIRECTORY | O_NONBLOCK;
+  if ((fcntl(fd, F_GETFL) & mask) == (O_RDONLY | O_DIRECTORY)) {
+    puts("test_dir ok");
+  }
+  fcntl(fd, F_SETFL, O_NONBLOCK);
+  if ((fcntl(fd, F_GETFL) & mask) == (O_RDONLY | O_DIRECTORY | O_NONBLOCK)) {
+    puts("test_dir ok2");
+  }
+  close(fd);
+}
+
+void test_dup() {
+  int fds[2];
+  pipe(fds);
+  int dup_fd = dup(fds[0]);
+  // The status flags belong to the open file, which the duplicate shares.
+  fcntl(fds[0], F_SETFL, O_NONBLOCK);
+  if (fcntl(dup_fd, F_GETFL) & O_NONBLOCK) {
+    puts("test_dup ok");
+  }
+  char c;
+  if (read(dup_fd, &c, 1) == -1 && errno == EAGAIN) {
+    puts("test_dup ok2");
+  }
+  // The close-on-exec flag belongs to each descriptor.
+  fcntl(fds[0], F_SETFD, FD_CLOEXEC);
+  if (fcntl(fds[0], F_GETFD) == FD_CLOEXEC && fcntl(dup_fd, F_GETFD) == 0) {
+    puts("test_dup ok3");
+  }
+  close(dup_fd);
+  close(fds[0]);
+  close(fds[1]);
+}
+
+int main() {
+  test_file();
+  test_dir();
+  test_dup();
+  return 0;
+}
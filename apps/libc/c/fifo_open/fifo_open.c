@@ -0,0 +1,77 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static const char *path = "fifo_open.fifo";
+
+static void usr1_handler(int signum) { (void)signum; }
+
+void test_interrupt() {
+  struct sigaction sa;
+  memset(&sa, 0, sizeof(sa));
+  sa.sa_handler = usr1_handler;
+  sigaction(SIGUSR1, &sa, NULL);
+
+  pid_t pid = fork();
+  if (pid == 0) {
+    // No writer ever comes, so only the signal ends the open.
+    int fd = open(path, O_RDONLY);
+    _exit(fd == -1 && errno == EINTR ? 0 : 1);
+  }
+  // Signal until the child is woken, in case it was not in the open yet.
+  int status;
+  while (waitpid(pid, &status, WNOHANG) == 0) {
+    usleep(100000);
+    kill(pid, SIGUSR1);
+  }
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_interrupt ok");
+  }
+
+  // A nonblocking writer without a reader fails instead.
+  if (open(path, O_WRONLY | O_NONBLOCK) == -1 && errno == ENXIO) {
+    puts("test_interrupt ok2");
+  }
+}
+
+void test_rdwr() {
+  // Both ends are opened at once, so this does not block.
+  int fd = open(path, O_RDWR);
+  if (fd < 0) {
+    perror("open");
+    return;
+  }
+  char buf[8] = {0};
+  if (write(fd, "fifo", 4) == 4 && read(fd, buf, sizeof(buf)) == 4 &&
+      strcmp(buf, "fifo") == 0) {
+    puts("test_rdwr ok");
+  }
+  if ((fcntl(fd, F_GETFL) & O_ACCMODE) == O_RDWR) {
+    puts("test_rdwr ok2");
+  }
+
+  // A reader opened now finds the writer already there.
+  int reader = open(path, O_RDONLY);
+  if (reader >= 0) {
+    puts("test_rdwr ok3");
+    close(reader);
+  }
+  close(fd);
+}
+
+int main() {
+  unlink(path);
+  if (mkfifo(path, 0644) != 0) {
+    perror("mkfifo");
+    return 1;
+  }
+  test_interrupt();
+  test_rdwr();
+  unlink(path);
+  return 0;
+}
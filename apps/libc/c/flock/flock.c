@@ -0,0 +1,81 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/file.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static void usr1_handler(int signum) { (void)signum; }
+
+void test_exclusive() {
+  int fd1 = open("flock.tmp", O_CREAT | O_RDWR, 0644);
+  int fd2 = open("flock.tmp", O_RDWR);
+  if (flock(fd1, LOCK_EX) == 0 &&
+      flock(fd2, LOCK_EX | LOCK_NB) == -1 && errno == EWOULDBLOCK) {
+    puts("test_exclusive ok");
+  }
+  // The lock goes away with the last descriptor of the open file.
+  int dup1 = dup(fd1);
+  close(fd1);
+  if (flock(fd2, LOCK_EX | LOCK_NB) == -1) {
+    close(dup1);
+    if (flock(fd2, LOCK_EX | LOCK_NB) == 0) {
+      puts("test_exclusive ok2");
+    }
+  }
+  close(fd2);
+  unlink("flock.tmp");
+}
+
+void test_relinked() {
+  int fd1 = open("flock.tmp", O_CREAT | O_RDWR, 0644);
+  int fd2 = open("flock.tmp", O_RDWR);
+  flock(fd1, LOCK_EX);
+  // The file moves to the remaining name, the lock stays with it.
+  link("flock.tmp", "flock.link");
+  unlink("flock.tmp");
+  if (flock(fd2, LOCK_EX | LOCK_NB) == -1 && errno == EWOULDBLOCK) {
+    puts("test_relinked ok");
+  }
+  flock(fd1, LOCK_UN);
+  if (flock(fd2, LOCK_EX | LOCK_NB) == 0) {
+    puts("test_relinked ok2");
+  }
+  close(fd1);
+  close(fd2);
+  unlink("flock.link");
+}
+
+void test_interrupted() {
+  int fd1 = open("flock.tmp", O_CREAT | O_RDWR, 0644);
+  int fd2 = open("flock.tmp", O_RDWR);
+  struct sigaction sa;
+  memset(&sa, 0, sizeof(sa));
+  sa.sa_handler = usr1_handler;
+  sigaction(SIGUSR1, &sa, NULL);
+
+  flock(fd1, LOCK_EX);
+  pid_t parent = getpid();
+  pid_t pid = fork();
+  if (pid == 0) {
+    sleep(1);
+    kill(parent, SIGUSR1);
+    _exit(0);
+  }
+  if (flock(fd2, LOCK_EX) == -1 && errno == EINTR) {
+    puts("test_interrupted ok");
+  }
+  waitpid(pid, NULL, 0);
+  close(fd1);
+  close(fd2);
+  unlink("flock.tmp");
+}
+
+int main() {
+  test_exclusive();
+  test_relinked();
+  test_interrupted();
+  return 0;
+}
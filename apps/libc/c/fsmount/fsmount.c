@@ -0,0 +1,64 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <linux/magic.h>
+#include <linux/mount.h>
+#include <stdio.h>
+#include <sys/stat.h>
+#include <sys/statfs.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static const char *dir = "fsmount_dir";
+
+void test_tmpfs() {
+  int fsfd = syscall(SYS_fsopen, "tmpfs", FSOPEN_CLOEXEC);
+  if (fsfd < 0) {
+    perror("fsopen");
+    return;
+  }
+  // Parameters the file system does not have are rejected.
+  if (syscall(SYS_fsconfig, fsfd, FSCONFIG_SET_STRING, "bogus", "1", 0) == -1 &&
+      errno == EINVAL) {
+    puts("test_tmpfs ok");
+  }
+  if (syscall(SYS_fsconfig, fsfd, FSCONFIG_SET_STRING, "source", "none", 0) ==
+          0 &&
+      syscall(SYS_fsconfig, fsfd, FSCONFIG_SET_STRING, "size", "1m", 0) == 0 &&
+      syscall(SYS_fsconfig, fsfd, FSCONFIG_CMD_CREATE, NULL, NULL, 0) == 0) {
+    puts("test_tmpfs ok2");
+  }
+  int mfd = syscall(SYS_fsmount, fsfd, FSMOUNT_CLOEXEC, 0);
+  struct statfs st;
+  if (mfd >= 0 &&
+      syscall(SYS_move_mount, mfd, "", AT_FDCWD, dir,
+              MOVE_MOUNT_F_EMPTY_PATH) == 0 &&
+      statfs(dir, &st) == 0 && st.f_type == TMPFS_MAGIC) {
+    puts("test_tmpfs ok3");
+  }
+  syscall(SYS_umount2, dir, 0);
+  close(mfd);
+  close(fsfd);
+}
+
+void test_unprivileged() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    setuid(1000);
+    int fsfd = syscall(SYS_fsopen, "tmpfs", 0);
+    _exit(fsfd == -1 && errno == EPERM ? 0 : 1);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_unprivileged ok");
+  }
+}
+
+int main() {
+  mkdir(dir, 0755);
+  test_tmpfs();
+  test_unprivileged();
+  rmdir(dir);
+  return 0;
+}
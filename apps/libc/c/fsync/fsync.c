@@ -0,0 +1,67 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <unistd.h>
+
+void test_fsync() {
+  int fd1 = open("fsync.tmp", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  int fd2 = open("fsync.tmp", O_RDWR);
+  write(fd1, "data", 4);
+  // Without a failed write-back, every open file syncs cleanly, also after
+  // sync has written the data out.
+  sync();
+  if (fsync(fd1) == 0 && fsync(fd2) == 0 && fdatasync(fd1) == 0) {
+    puts("test_fsync ok");
+  }
+  close(fd1);
+  close(fd2);
+  unlink("fsync.tmp");
+
+  int fds[2];
+  pipe(fds);
+  if (fsync(fds[0]) == -1 && errno == EINVAL) {
+    puts("test_fsync ok2");
+  }
+  close(fds[0]);
+  close(fds[1]);
+}
+
+// Make the next `n` write-backs fail with EIO.
+static int fail_writeback(int n) {
+  int fd = open("/proc/sys/debug/fail_writeback", O_WRONLY);
+  if (fd < 0) {
+    return -1;
+  }
+  dprintf(fd, "%d\n", n);
+  close(fd);
+  return 0;
+}
+
+void test_fsync_error() {
+  int fd1 = open("fsync.tmp", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  int fd2 = open("fsync.tmp", O_RDWR);
+  write(fd1, "data", 4);
+  if (fail_writeback(1) != 0) {
+    return;
+  }
+  // The failure is reported once by the fsync that hit it.
+  if (fsync(fd1) == -1 && errno == EIO && fsync(fd1) == 0) {
+    puts("test_fsync_error ok");
+  }
+  // It is also reported once on the other file open at the time, but not on
+  // one opened after it.
+  int fd3 = open("fsync.tmp", O_RDWR);
+  if (fsync(fd2) == -1 && errno == EIO && fsync(fd2) == 0 && fsync(fd3) == 0) {
+    puts("test_fsync_error ok2");
+  }
+  close(fd1);
+  close(fd2);
+  close(fd3);
+  unlink("fsync.tmp");
+}
+
+int main() {
+  test_fsync();
+  test_fsync_error();
+  return 0;
+}
@@ -0,0 +1,47 @@
+#include <fcntl.h>
+#include <stdio.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+// `ftok` derives keys from the inode and device numbers reported by `stat`,
+// so they must be stable for a file and differ between files.
+void test_key() {
+  close(open("ftok_a", O_CREAT | O_WRONLY | O_TRUNC, 0644));
+  close(open("ftok_b", O_CREAT | O_WRONLY | O_TRUNC, 0644));
+  key_t a = ftok("ftok_a", 1);
+  if (a != -1 && a == ftok("ftok_a", 1)) {
+    puts("test_key ok");
+  }
+  if (a != ftok("ftok_b", 1) && a != ftok("ftok_a", 2)) {
+    puts("test_key ok2");
+  }
+}
+
+// Another process finds the segment from the same path and project id.
+void test_shared() {
+  int id = shmget(ftok("ftok_a", 1), 4096, IPC_CREAT | 0600);
+  char *mem = shmat(id, NULL, 0);
+  mem[0] = 42;
+  if (fork() == 0) {
+    int child_id = shmget(ftok("ftok_a", 1), 4096, 0600);
+    char *child_mem = shmat(child_id, NULL, 0);
+    _exit(child_id == id && child_mem[0] == 42 ? 0 : 1);
+  }
+  int status;
+  wait(&status);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_shared ok");
+  }
+  shmdt(mem);
+  shmctl(id, IPC_RMID, NULL);
+  unlink("ftok_a");
+  unlink("ftok_b");
+}
+
+int main() {
+  test_key();
+  test_shared();
+  return 0;
+}
@@ -0,0 +1,21 @@
+#include <errno.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/random.h>
+
+void test_getrandom() {
+  char a[64], b[64];
+  if (getrandom(a, sizeof(a), 0) == sizeof(a) &&
+      getrandom(b, sizeof(b), GRND_NONBLOCK) == sizeof(b) &&
+      memcmp(a, b, sizeof(a)) != 0) {
+    puts("test_getrandom ok");
+  }
+  if (getrandom(a, sizeof(a), ~0u) == -1 && errno == EINVAL) {
+    puts("test_getrandom ok2");
+  }
+}
+
+int main() {
+  test_getrandom();
+  return 0;
+}
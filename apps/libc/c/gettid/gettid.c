@@ -0,0 +1,51 @@
+#include <pthread.h>
+#include <stdio.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+#define THREADS 3
+
+static pid_t tids[THREADS];
+static pthread_barrier_t barrier;
+
+// The threads wait for each other, so that their tids are not reused.
+static void *worker(void *arg) {
+  *(pid_t *)arg = syscall(SYS_gettid);
+  pthread_barrier_wait(&barrier);
+  return NULL;
+}
+
+void test_gettid() {
+  pid_t pid = getpid();
+  if (syscall(SYS_gettid) == pid) {
+    puts("test_gettid ok");
+  }
+
+  pthread_t threads[THREADS];
+  pthread_barrier_init(&barrier, NULL, THREADS);
+  for (int i = 0; i < THREADS; i++) {
+    pthread_create(&threads[i], NULL, worker, &tids[i]);
+  }
+  for (int i = 0; i < THREADS; i++) {
+    pthread_join(threads[i], NULL);
+  }
+  int distinct = 1;
+  for (int i = 0; i < THREADS; i++) {
+    if (tids[i] <= 0 || tids[i] == pid) {
+      distinct = 0;
+    }
+    for (int j = 0; j < i; j++) {
+      if (tids[i] == tids[j]) {
+        distinct = 0;
+      }
+    }
+  }
+  if (distinct) {
+    puts("test_gettid ok2");
+  }
+}
+
+int main() {
+  test_gettid();
+  return 0;
+}
@@ -0,0 +1,70 @@
+#include <fcntl.h>
+#include <pthread.h>
+#include <stdio.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+#define THREADS 4
+#define ITERS 50
+
+static int failed;
+
+static void *link_and_unlink(void *arg) {
+  long t = (long)arg;
+  char name[32];
+  for (int i = 0; i < ITERS; i++) {
+    snprintf(name, sizeof(name), "hardlink_%ld_%d", t, i);
+    if (link("hardlink_file", name) != 0) {
+      failed = 1;
+    }
+    // Every other link is kept.
+    if (i % 2 == 0 && unlink(name) != 0) {
+      failed = 1;
+    }
+  }
+  return NULL;
+}
+
+void test_link_threads() {
+  int fd = open("hardlink_file", O_CREAT | O_WRONLY | O_TRUNC, 0644);
+  write(fd, "data", 4);
+  close(fd);
+
+  pthread_t threads[THREADS];
+  for (long t = 0; t < THREADS; t++) {
+    pthread_create(&threads[t], NULL, link_and_unlink, (void *)t);
+  }
+  for (int t = 0; t < THREADS; t++) {
+    pthread_join(threads[t], NULL);
+  }
+  struct stat st;
+  if (!failed && stat("hardlink_file", &st) == 0 &&
+      st.st_nlink == 1 + THREADS * ITERS / 2) {
+    puts("test_link_threads ok");
+  }
+
+  // The file stays until its last name is removed.
+  unlink("hardlink_file");
+  char name[32];
+  int left = 1;
+  for (int t = 0; t < THREADS; t++) {
+    for (int i = 1; i < ITERS; i += 2) {
+      snprintf(name, sizeof(name), "hardlink_%d_%d", t, i);
+      char buf[4];
+      fd = open(name, O_RDONLY);
+      if (fd < 0 || read(fd, buf, 4) != 4) {
+        left = 0;
+      }
+      close(fd);
+      unlink(name);
+    }
+  }
+  if (left && access(name, F_OK) != 0) {
+    puts("test_link_threads ok2");
+  }
+}
+
+int main() {
+  test_link_threads();
+  return 0;
+}
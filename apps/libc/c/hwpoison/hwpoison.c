@@ -0,0 +1,77 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <setjmp.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/prctl.h>
+#include <unistd.h>
+
+static sigjmp_buf env;
+static volatile int bus_code;
+static void *volatile bus_addr;
+
+static void bus_handler(int signum, siginfo_t *info, void *ctx) {
+  (void)signum;
+  (void)ctx;
+  bus_code = info->si_code;
+  bus_addr = info->si_addr;
+  siglongjmp(env, 1);
+}
+
+void test_sigbus() {
+  struct sigaction sa;
+  memset(&sa, 0, sizeof(sa));
+  sa.sa_sigaction = bus_handler;
+  sa.sa_flags = SA_SIGINFO;
+  sigaction(SIGBUS, &sa, NULL);
+
+  char *page = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+                    MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  page[0] = 1;
+  if (madvise(page, 4096, MADV_HWPOISON) != 0) {
+    perror("madvise");
+    return;
+  }
+  if (sigsetjmp(env, 1) == 0) {
+    volatile char c = page[0];
+    (void)c;
+    puts("test_sigbus failed: no signal");
+    return;
+  }
+  if (bus_code == BUS_MCEERR_AR && bus_addr == page) {
+    puts("test_sigbus ok");
+  }
+
+  // A system call given the poisoned page fails instead of killing us.
+  int fd = open("/dev/zero", O_RDONLY);
+  if (read(fd, page, 16) == -1 && errno == EFAULT) {
+    puts("test_sigbus ok2");
+  }
+  close(fd);
+
+  // The poison goes away with the mapping.
+  munmap(page, 4096);
+  char *again = mmap(page, 4096, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED, -1, 0);
+  again[0] = 2;
+  if (again == page && again[0] == 2) {
+    puts("test_sigbus ok3");
+  }
+  munmap(again, 4096);
+}
+
+void test_mce_kill() {
+  if (prctl(PR_MCE_KILL, PR_MCE_KILL_SET, PR_MCE_KILL_EARLY, 0, 0) == 0 &&
+      prctl(PR_MCE_KILL_GET, 0, 0, 0, 0) == PR_MCE_KILL_EARLY) {
+    puts("test_mce_kill ok");
+  }
+  prctl(PR_MCE_KILL, PR_MCE_KILL_CLEAR, 0, 0, 0);
+}
+
+int main() {
+  test_sigbus();
+  test_mce_kill();
+  return 0;
+}
@@ -0,0 +1,71 @@
+#include <dirent.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+static ino_t ino_of(const char *path) {
+  struct stat st;
+  if (stat(path, &st) != 0) {
+    return 0;
+  }
+  return st.st_ino;
+}
+
+static ino_t dirent_ino_of(const char *name) {
+  ino_t ino = 0;
+  DIR *dir = opendir(".");
+  struct dirent *ent;
+  while (dir && (ent = readdir(dir)) != NULL) {
+    if (strcmp(ent->d_name, name) == 0) {
+      ino = ent->d_ino;
+    }
+  }
+  if (dir) {
+    closedir(dir);
+  }
+  return ino;
+}
+
+void test_distinct() {
+  close(open("ino_a", O_CREAT | O_WRONLY, 0644));
+  close(open("ino_b", O_CREAT | O_WRONLY, 0644));
+  if (ino_of("ino_a") != ino_of("ino_b")) {
+    puts("test_distinct ok");
+  }
+  if (dirent_ino_of("ino_a") == ino_of("ino_a")) {
+    puts("test_distinct ok2");
+  }
+  unlink("ino_b");
+}
+
+void test_hardlink() {
+  ino_t ino = ino_of("ino_a");
+  link("ino_a", "ino_link");
+  if (ino_of("ino_link") == ino) {
+    puts("test_hardlink ok");
+  }
+  // The file keeps its number when its first name goes away.
+  int fd = open("ino_a", O_RDONLY);
+  unlink("ino_a");
+  struct stat st;
+  fstat(fd, &st);
+  if (ino_of("ino_link") == ino && st.st_ino == ino) {
+    puts("test_hardlink ok2");
+  }
+  close(fd);
+  // A new file at the same path is another file.
+  close(open("ino_a", O_CREAT | O_WRONLY, 0644));
+  if (ino_of("ino_a") != ino) {
+    puts("test_hardlink ok3");
+  }
+  unlink("ino_a");
+  unlink("ino_link");
+}
+
+int main() {
+  test_distinct();
+  test_hardlink();
+  return 0;
+}
@@ -0,0 +1,81 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <linux/fs.h>
+#include <linux/stat.h>
+#include <stdio.h>
+#include <sys/ioctl.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+static int set_flags(int fd, int flags) {
+  return ioctl(fd, FS_IOC_SETFLAGS, &flags);
+}
+
+void test_statx_attr() {
+  int fd = open("inode_attr_file", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  int flags = -1;
+  if (set_flags(fd, FS_IMMUTABLE_FL) == 0 &&
+      ioctl(fd, FS_IOC_GETFLAGS, &flags) == 0 && flags == FS_IMMUTABLE_FL) {
+    puts("test_statx_attr ok");
+  }
+  struct statx stx;
+  if (syscall(SYS_statx, AT_FDCWD, "inode_attr_file", 0, STATX_BASIC_STATS,
+              &stx) == 0 &&
+      stx.stx_attributes_mask & STATX_ATTR_IMMUTABLE &&
+      stx.stx_attributes & STATX_ATTR_IMMUTABLE &&
+      !(stx.stx_attributes & STATX_ATTR_APPEND)) {
+    puts("test_statx_attr ok2");
+  }
+  if (write(fd, "x", 1) == -1 && errno == EPERM) {
+    puts("test_statx_attr ok3");
+  }
+  set_flags(fd, 0);
+  close(fd);
+  unlink("inode_attr_file");
+}
+
+void test_immutable() {
+  int fd = open("inode_attr_file", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  write(fd, "data", 4);
+  set_flags(fd, FS_IMMUTABLE_FL);
+  if (open("inode_attr_file", O_WRONLY) == -1 && errno == EPERM &&
+      truncate("inode_attr_file", 0) == -1 && errno == EPERM &&
+      unlink("inode_attr_file") == -1 && errno == EPERM) {
+    puts("test_immutable ok");
+  }
+  set_flags(fd, 0);
+  close(fd);
+  unlink("inode_attr_file");
+}
+
+void test_append_only() {
+  int fd = open("inode_attr_file", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  write(fd, "data", 4);
+  set_flags(fd, FS_APPEND_FL);
+  if (open("inode_attr_file", O_WRONLY) == -1 && errno == EPERM &&
+      ftruncate(fd, 0) == -1 && errno == EPERM) {
+    puts("test_append_only ok");
+  }
+  // Writes go to the end of the file, whatever the offset.
+  int afd = open("inode_attr_file", O_WRONLY | O_APPEND);
+  lseek(afd, 0, SEEK_SET);
+  char buf[8] = {0};
+  if (write(afd, "more", 4) == 4 && pread(fd, buf, 8, 0) == 8 &&
+      buf[0] == 'd' && buf[4] == 'm') {
+    puts("test_append_only ok2");
+  }
+  if (fcntl(afd, F_SETFL, 0) == -1 && errno == EPERM) {
+    puts("test_append_only ok3");
+  }
+  close(afd);
+  set_flags(fd, 0);
+  close(fd);
+  unlink("inode_attr_file");
+}
+
+int main() {
+  test_statx_attr();
+  test_immutable();
+  test_append_only();
+  return 0;
+}
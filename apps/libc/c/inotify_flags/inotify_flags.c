@@ -0,0 +1,70 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <sys/inotify.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+// Whether an event is waiting on the inotify instance `fd`.
+static int has_event(int fd) {
+  char buf[256];
+  if (read(fd, buf, sizeof(buf)) > 0) {
+    return 1;
+  }
+  return errno != EAGAIN;
+}
+
+void test_dont_follow() {
+  close(open("inotify_target", O_CREAT | O_WRONLY | O_TRUNC, 0644));
+  unlink("inotify_link");
+  symlink("inotify_target", "inotify_link");
+
+  int follow = inotify_init1(IN_NONBLOCK);
+  int nofollow = inotify_init1(IN_NONBLOCK);
+  inotify_add_watch(follow, "inotify_link", IN_MODIFY);
+  inotify_add_watch(nofollow, "inotify_link", IN_MODIFY | IN_DONT_FOLLOW);
+
+  int fd = open("inotify_target", O_WRONLY);
+  write(fd, "x", 1);
+  close(fd);
+  if (has_event(follow)) {
+    puts("test_dont_follow ok");
+  }
+  if (!has_event(nofollow)) {
+    puts("test_dont_follow ok2");
+  }
+  close(follow);
+  close(nofollow);
+  unlink("inotify_link");
+  unlink("inotify_target");
+}
+
+void test_excl_unlink() {
+  mkdir("inotify_dir", 0755);
+  int fd = open("inotify_dir/file", O_CREAT | O_WRONLY | O_TRUNC, 0644);
+  unlink("inotify_dir/file");
+
+  int all = inotify_init1(IN_NONBLOCK);
+  int excl = inotify_init1(IN_NONBLOCK);
+  inotify_add_watch(all, "inotify_dir", IN_MODIFY);
+  inotify_add_watch(excl, "inotify_dir", IN_MODIFY | IN_EXCL_UNLINK);
+
+  // The unlinked file is still written through its open descriptor.
+  write(fd, "x", 1);
+  if (has_event(all)) {
+    puts("test_excl_unlink ok");
+  }
+  if (!has_event(excl)) {
+    puts("test_excl_unlink ok2");
+  }
+  close(fd);
+  close(all);
+  close(excl);
+  rmdir("inotify_dir");
+}
+
+int main() {
+  test_dont_follow();
+  test_excl_unlink();
+  return 0;
+}
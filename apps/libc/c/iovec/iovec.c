@@ -0,0 +1,75 @@
+#include <errno.h>
+#include <limits.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/uio.h>
+#include <unistd.h>
+
+// A readv that runs out of data in the second of three buffers fills the
+// first, part of the second, and counts both.
+void test_readv_partial() {
+  int fds[2];
+  pipe(fds);
+  write(fds[1], "0123456789", 10);
+
+  char a[4], b[8], c[4];
+  memset(b, '-', sizeof(b));
+  memset(c, '-', sizeof(c));
+  struct iovec iov[3] = {{a, sizeof(a)}, {b, sizeof(b)}, {c, sizeof(c)}};
+  if (readv(fds[0], iov, 3) == 10 && memcmp(a, "0123", 4) == 0 &&
+      memcmp(b, "456789--", 8) == 0 && memcmp(c, "----", 4) == 0) {
+    puts("test_readv_partial ok");
+  }
+  close(fds[0]);
+  close(fds[1]);
+}
+
+// The bytes of a writev of three buffers are read back in order into
+// buffers of other sizes.
+void test_writev_partial() {
+  int fds[2];
+  pipe(fds);
+  struct iovec out[3] = {{"ab", 2}, {"cdef", 4}, {"g", 1}};
+  if (writev(fds[1], out, 3) == 7) {
+    puts("test_writev_partial ok");
+  }
+  close(fds[1]);
+
+  char a[3], b[6];
+  memset(b, '-', sizeof(b));
+  struct iovec in[3] = {{a, sizeof(a)}, {b, sizeof(b)}, {NULL, 0}};
+  if (readv(fds[0], in, 3) == 7 && memcmp(a, "abc", 3) == 0 &&
+      memcmp(b, "defg--", 6) == 0 && readv(fds[0], in, 3) == 0) {
+    puts("test_writev_partial ok2");
+  }
+  close(fds[0]);
+}
+
+// The total length of the buffers must fit in an ssize_t.
+void test_iov_limit() {
+  int fds[2];
+  pipe(fds);
+  write(fds[1], "abc", 3);
+
+  char buf[4];
+  struct iovec huge[1] = {{buf, (size_t)SSIZE_MAX + 1}};
+  if (readv(fds[0], huge, 1) == -1 && errno == EINVAL &&
+      writev(fds[1], huge, 1) == -1 && errno == EINVAL) {
+    puts("test_iov_limit ok");
+  }
+  // Buffers that only add up past the limit fail too, before any byte is
+  // read.
+  struct iovec sum[2] = {{buf, SSIZE_MAX / 2 + 1}, {buf, SSIZE_MAX / 2 + 1}};
+  if (readv(fds[0], sum, 2) == -1 && read(fds[0], buf, 4) == 3) {
+    puts("test_iov_limit ok2");
+  }
+  close(fds[0]);
+  close(fds[1]);
+}
+
+int main() {
+  test_readv_partial();
+  test_writev_partial();
+  test_iov_limit();
+  return 0;
+}
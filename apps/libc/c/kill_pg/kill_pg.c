@@ -0,0 +1,61 @@
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static int killed_by(pid_t pid, int sig) {
+  int status;
+  return waitpid(pid, &status, 0) == pid && WIFSIGNALED(status) &&
+         WTERMSIG(status) == sig;
+}
+
+// A process that moves to its own group signals it with kill(0), which
+// reaches the other member of the group.
+void test_kill_group() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    setpgid(0, 0);
+    pid_t member = fork();
+    if (member == 0) {
+      for (;;) {
+        pause();
+      }
+    }
+    signal(SIGTERM, SIG_IGN);
+    if (kill(0, SIGTERM) != 0) {
+      _exit(1);
+    }
+    _exit(killed_by(member, SIGTERM) ? 0 : 1);
+  }
+  int status;
+  if (waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+      WEXITSTATUS(status) == 0) {
+    puts("test_kill_group ok");
+  }
+}
+
+// kill(-pgid) signals the group `pgid`, and not the caller.
+void test_kill_pgid() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    setpgid(0, 0);
+    for (;;) {
+      pause();
+    }
+  }
+  // Set the group here too, so that it exists before the child runs.
+  setpgid(pid, pid);
+  if (kill(-pid, SIGUSR1) == 0 && killed_by(pid, SIGUSR1)) {
+    puts("test_kill_pgid ok");
+  }
+  if (kill(-pid, SIGUSR1) == -1 && errno == ESRCH) {
+    puts("test_kill_pgid ok2");
+  }
+}
+
+int main() {
+  test_kill_group();
+  test_kill_pgid();
+  return 0;
+}
@@ -0,0 +1,85 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <limits.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+void test_lseek_pipe() {
+  int fds[2];
+  pipe(fds);
+  if (lseek(fds[0], 0, SEEK_CUR) == -1 && errno == ESPIPE &&
+      lseek(fds[1], 0, SEEK_SET) == -1 && errno == ESPIPE) {
+    puts("test_lseek_pipe ok");
+  }
+  close(fds[0]);
+  close(fds[1]);
+
+  int fd = open("lseek_file", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  write(fd, "hello", 5);
+  if (lseek(fd, 1, SEEK_SET) == 1 && lseek(fd, 1, SEEK_CUR) == 2 &&
+      lseek(fd, -1, SEEK_END) == 4) {
+    puts("test_lseek_pipe ok2");
+  }
+  close(fd);
+  unlink("lseek_file");
+}
+
+#define HOLE_END (1 << 20)
+
+// A file with data, a hole and data again. The file system may report all
+// of it as data, but never a hole before the first data or past the end.
+void test_seek_hole() {
+  int fd = open("lseek_hole", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  char buf[4096];
+  memset(buf, 'd', sizeof(buf));
+  write(fd, buf, sizeof(buf));
+  pwrite(fd, buf, sizeof(buf), HOLE_END);
+  off_t size = HOLE_END + sizeof(buf);
+
+  off_t hole = lseek(fd, 0, SEEK_HOLE);
+  if (lseek(fd, 0, SEEK_DATA) == 0 && hole >= (off_t)sizeof(buf) &&
+      hole <= size && lseek(fd, size - 1, SEEK_HOLE) == size) {
+    puts("test_seek_hole ok");
+  }
+  off_t data = lseek(fd, 8192, SEEK_DATA);
+  if (data >= 8192 && data <= HOLE_END && pread(fd, buf, 16, 8192) == 16 &&
+      buf[0] == 0 && buf[15] == 0) {
+    puts("test_seek_hole ok2");
+  }
+  if (lseek(fd, size, SEEK_DATA) == -1 && errno == ENXIO &&
+      lseek(fd, size, SEEK_HOLE) == -1 && errno == ENXIO &&
+      lseek(fd, -1, SEEK_DATA) == -1 && errno == ENXIO) {
+    puts("test_seek_hole ok3");
+  }
+  close(fd);
+  unlink("lseek_hole");
+}
+
+void test_seek_range() {
+  int fd = open("lseek_range", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  write(fd, "hello", 5);
+  if (lseek(fd, -1, SEEK_SET) == -1 && errno == EINVAL &&
+      lseek(fd, -6, SEEK_END) == -1 && errno == EINVAL &&
+      lseek(fd, -6, SEEK_CUR) == -1 && errno == EINVAL) {
+    puts("test_seek_range ok");
+  }
+  // A position past the end of `off_t` cannot be represented.
+  if (lseek(fd, LLONG_MAX, SEEK_END) == -1 && errno == EOVERFLOW) {
+    puts("test_seek_range ok2");
+  }
+  // The failed seeks left the position alone.
+  if (lseek(fd, 0, SEEK_CUR) == 5) {
+    puts("test_seek_range ok3");
+  }
+  close(fd);
+  unlink("lseek_range");
+}
+
+int main() {
+  test_lseek_pipe();
+  test_seek_hole();
+  test_seek_range();
+  return 0;
+}
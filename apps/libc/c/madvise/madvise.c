@@ -0,0 +1,54 @@
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+void test_dontneed_anon() {
+  char *page = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+                    MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  memset(page, 0x5a, 4096);
+  if (madvise(page, 4096, MADV_DONTNEED) != 0) {
+    perror("madvise");
+    return;
+  }
+  if (page[0] == 0 && page[4095] == 0) {
+    puts("test_dontneed_anon ok");
+  }
+  munmap(page, 4096);
+}
+
+void test_dontneed_file() {
+  const char *path = "madvise.tmp";
+  int fd = open(path, O_RDWR | O_CREAT | O_TRUNC, 0644);
+  write(fd, "file contents", 13);
+  char *page = mmap(NULL, 4096, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, 0);
+  memcpy(page, "changed", 7);
+  madvise(page, 4096, MADV_DONTNEED);
+  // A private file mapping reads the file again.
+  if (memcmp(page, "file contents", 13) == 0 && page[13] == 0) {
+    puts("test_dontneed_file ok");
+  }
+  munmap(page, 4096);
+  close(fd);
+  unlink(path);
+}
+
+void test_dontneed_shared() {
+  char *page = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+                    MAP_SHARED | MAP_ANONYMOUS, -1, 0);
+  memset(page, 0x5a, 4096);
+  madvise(page, 4096, MADV_DONTNEED);
+  // Shared memory keeps its contents.
+  if (page[0] == 0x5a) {
+    puts("test_dontneed_shared ok");
+  }
+  munmap(page, 4096);
+}
+
+int main() {
+  test_dontneed_anon();
+  test_dontneed_file();
+  test_dontneed_shared();
+  return 0;
+}
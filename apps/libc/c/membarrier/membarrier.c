@@ -0,0 +1,58 @@
+#include <errno.h>
+#include <linux/membarrier.h>
+#include <pthread.h>
+#include <stdatomic.h>
+#include <stdio.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+static int membarrier(int cmd, unsigned int flags) {
+  return syscall(SYS_membarrier, cmd, flags, 0);
+}
+
+static atomic_int stop;
+static atomic_long spins;
+
+static void *spin(void *arg) {
+  (void)arg;
+  while (!atomic_load_explicit(&stop, memory_order_relaxed)) {
+    atomic_fetch_add_explicit(&spins, 1, memory_order_relaxed);
+  }
+  return NULL;
+}
+
+void test_private() {
+  if (membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED, 0) == -1 &&
+      errno == EPERM) {
+    puts("test_private ok");
+  }
+  // The barrier reaches the CPUs running the other threads, even while
+  // they never enter the kernel.
+  pthread_t threads[2];
+  for (int i = 0; i < 2; i++) {
+    pthread_create(&threads[i], NULL, spin, NULL);
+  }
+  if (membarrier(MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED, 0) == 0 &&
+      membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED, 0) == 0 &&
+      membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED, 0) == 0) {
+    puts("test_private ok2");
+  }
+  atomic_store(&stop, 1);
+  for (int i = 0; i < 2; i++) {
+    pthread_join(threads[i], NULL);
+  }
+}
+
+void test_global() {
+  int cmds = membarrier(MEMBARRIER_CMD_QUERY, 0);
+  if (cmds & MEMBARRIER_CMD_GLOBAL &&
+      membarrier(MEMBARRIER_CMD_GLOBAL, 0) == 0) {
+    puts("test_global ok");
+  }
+}
+
+int main() {
+  test_private();
+  test_global();
+  return 0;
+}
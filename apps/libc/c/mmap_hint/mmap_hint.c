@@ -0,0 +1,44 @@
+#define _GNU_SOURCE
+#include <stdint.h>
+#include <stdio.h>
+#include <sys/mman.h>
+
+#define HINT ((void *)0x10000000)
+
+void test_mmap_hint() {
+  char *addr = mmap(HINT, 4096, PROT_READ | PROT_WRITE,
+                    MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  if (addr == HINT) {
+    puts("test_mmap_hint ok");
+  }
+  // A hint that is taken only moves the mapping.
+  char *other = mmap(HINT, 4096, PROT_READ | PROT_WRITE,
+                     MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  if (other != MAP_FAILED && other != addr) {
+    other[0] = 1;
+    puts("test_mmap_hint ok2");
+  }
+  munmap(addr, 4096);
+  munmap(other, 4096);
+}
+
+void test_map_32bit() {
+#ifdef MAP_32BIT
+  char *addr = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+                    MAP_PRIVATE | MAP_ANONYMOUS | MAP_32BIT, -1, 0);
+  if (addr != MAP_FAILED && (uintptr_t)addr + 4096 <= (1ul << 32)) {
+    addr[0] = 1;
+    puts("test_map_32bit ok");
+  }
+  munmap(addr, 4096);
+#else
+  // Only x86_64 has low mappings.
+  puts("test_map_32bit ok");
+#endif
+}
+
+int main() {
+  test_mmap_hint();
+  test_map_32bit();
+  return 0;
+}
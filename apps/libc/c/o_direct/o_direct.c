@@ -0,0 +1,41 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+
+#define PATH "o_direct_file"
+#define BLOCK 4096
+
+void test_o_direct() {
+  char *buf = aligned_alloc(BLOCK, 2 * BLOCK);
+  memset(buf, 'd', 2 * BLOCK);
+  int fd = open(PATH, O_CREAT | O_TRUNC | O_WRONLY, 0644);
+  write(fd, buf, 2 * BLOCK);
+  close(fd);
+
+  fd = open(PATH, O_RDONLY | O_DIRECT);
+  if (fd < 0) {
+    return;
+  }
+  if (read(fd, buf + 1, BLOCK) == -1 && errno == EINVAL &&
+      read(fd, buf, BLOCK - 1) == -1 && errno == EINVAL &&
+      pread(fd, buf, BLOCK, 1) == -1 && errno == EINVAL) {
+    puts("test_o_direct ok");
+  }
+  memset(buf, 0, 2 * BLOCK);
+  if (pread(fd, buf, BLOCK, BLOCK) == BLOCK && buf[0] == 'd' &&
+      buf[BLOCK - 1] == 'd') {
+    puts("test_o_direct ok2");
+  }
+  close(fd);
+  free(buf);
+  unlink(PATH);
+}
+
+int main() {
+  test_o_direct();
+  return 0;
+}
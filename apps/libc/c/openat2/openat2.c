@@ -0,0 +1,60 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <linux/openat2.h>
+#include <stdio.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+#define DIR "/tmp/openat2_dir"
+
+static int openat2(int dirfd, const char *path, unsigned long long resolve) {
+  struct open_how how = {.flags = O_RDONLY, .resolve = resolve};
+  return syscall(SYS_openat2, dirfd, path, &how, sizeof(how));
+}
+
+static int fails_with(int fd, int err) { return fd == -1 && errno == err; }
+
+void test_openat2() {
+  mkdir(DIR, 0755);
+  close(open(DIR "/file", O_CREAT | O_WRONLY, 0644));
+  symlink("file", DIR "/link");
+  symlink("/tmp", DIR "/abs_link");
+  int dirfd = open(DIR, O_RDONLY | O_DIRECTORY);
+
+  int fd = openat2(dirfd, "file", 0);
+  if (fd >= 0) {
+    puts("test_openat2 ok");
+    close(fd);
+  }
+
+  // RESOLVE_BENEATH keeps the path under `dirfd`.
+  fd = openat2(dirfd, "./link", RESOLVE_BENEATH);
+  if (fd >= 0) {
+    close(fd);
+    if (fails_with(openat2(dirfd, "../openat2_dir/file", RESOLVE_BENEATH),
+                   EXDEV) &&
+        fails_with(openat2(dirfd, "/tmp", RESOLVE_BENEATH), EXDEV) &&
+        fails_with(openat2(dirfd, "abs_link", RESOLVE_BENEATH), EXDEV)) {
+      puts("test_openat2 ok2");
+    }
+  }
+
+  // RESOLVE_NO_SYMLINKS rejects every symbolic link on the way.
+  if (fails_with(openat2(dirfd, "link", RESOLVE_NO_SYMLINKS), ELOOP) &&
+      fails_with(openat2(dirfd, "abs_link/openat2_dir", RESOLVE_NO_SYMLINKS),
+                 ELOOP)) {
+    puts("test_openat2 ok3");
+  }
+
+  close(dirfd);
+  unlink(DIR "/abs_link");
+  unlink(DIR "/link");
+  unlink(DIR "/file");
+  rmdir(DIR);
+}
+
+int main() {
+  test_openat2();
+  return 0;
+}
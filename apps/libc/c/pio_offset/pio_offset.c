@@ -0,0 +1,42 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <sys/stat.h>
+#include <sys/uio.h>
+#include <unistd.h>
+
+void test_pio_offset() {
+  int fd = open("pio_offset_file", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  write(fd, "data", 4);
+  char buf[2] = "xy";
+  struct iovec iov = {.iov_base = buf, .iov_len = sizeof(buf)};
+
+  // An offset of u64::MAX is negative as an off_t.
+  if (pread(fd, buf, 2, -1) == -1 && errno == EINVAL &&
+      pwrite(fd, buf, 2, -1) == -1 && errno == EINVAL &&
+      preadv(fd, &iov, 1, -1) == -1 && errno == EINVAL &&
+      pwritev(fd, &iov, 1, -1) == -1 && errno == EINVAL) {
+    puts("test_pio_offset ok");
+  }
+  // The end of the write is past the largest off_t.
+  if (pwrite(fd, buf, 2, INT64_MAX - 1) == -1 && errno == EOVERFLOW &&
+      pwritev(fd, &iov, 1, INT64_MAX - 1) == -1 && errno == EOVERFLOW) {
+    puts("test_pio_offset ok2");
+  }
+  // Nothing was written anywhere.
+  struct stat st;
+  char data[4];
+  if (fstat(fd, &st) == 0 && st.st_size == 4 && pread(fd, data, 4, 0) == 4 &&
+      data[0] == 'd') {
+    puts("test_pio_offset ok3");
+  }
+  close(fd);
+  unlink("pio_offset_file");
+}
+
+int main() {
+  test_pio_offset();
+  return 0;
+}
@@ -0,0 +1,52 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <unistd.h>
+
+void test_pipe2() {
+  int fds[2];
+  char c;
+  if (pipe2(fds, O_NONBLOCK | O_CLOEXEC) == 0 &&
+      read(fds[0], &c, 1) == -1 && errno == EAGAIN) {
+    puts("test_pipe2 ok");
+  }
+  if (fcntl(fds[0], F_GETFD) == FD_CLOEXEC &&
+      fcntl(fds[1], F_GETFD) == FD_CLOEXEC &&
+      fcntl(fds[1], F_GETFL) & O_NONBLOCK) {
+    puts("test_pipe2 ok2");
+  }
+  close(fds[0]);
+  close(fds[1]);
+  if (pipe2(fds, O_APPEND) == -1 && errno == EINVAL) {
+    puts("test_pipe2 ok3");
+  }
+}
+
+void test_pipe_size() {
+  int fds[2];
+  pipe(fds);
+  if (fcntl(fds[0], F_SETPIPE_SZ, 65536) == 65536 &&
+      fcntl(fds[1], F_GETPIPE_SZ) == 65536) {
+    puts("test_pipe_size ok");
+  }
+  // Sizes are rounded up to a power of two number of pages.
+  if (fcntl(fds[1], F_SETPIPE_SZ, 5000) == 8192 &&
+      fcntl(fds[0], F_GETPIPE_SZ) == 8192) {
+    puts("test_pipe_size ok2");
+  }
+  // The buffered data must fit.
+  char buf[8192] = {0};
+  write(fds[1], buf, sizeof(buf));
+  if (fcntl(fds[1], F_SETPIPE_SZ, 4096) == -1 && errno == EBUSY) {
+    puts("test_pipe_size ok3");
+  }
+  close(fds[0]);
+  close(fds[1]);
+}
+
+int main() {
+  test_pipe2();
+  test_pipe_size();
+  return 0;
+}
@@ -0,0 +1,117 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <poll.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+#define SIZE 4096
+
+static char buf[SIZE];
+
+static long now_ms() {
+  struct timespec ts;
+  clock_gettime(CLOCK_MONOTONIC, &ts);
+  return ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}
+
+// Make a pipe of `SIZE` bytes and fill it.
+static void full_pipe(int fds[2]) {
+  pipe(fds);
+  fcntl(fds[1], F_SETPIPE_SZ, SIZE);
+  fcntl(fds[1], F_SETFL, O_NONBLOCK);
+  write(fds[1], buf, SIZE);
+}
+
+void test_full_nonblock() {
+  int fds[2];
+  full_pipe(fds);
+  if (write(fds[1], buf, 1) == -1 && errno == EAGAIN) {
+    puts("test_full_nonblock ok");
+  }
+  struct pollfd pfd = {.fd = fds[1], .events = POLLOUT};
+  if (poll(&pfd, 1, 0) == 0) {
+    puts("test_full_nonblock ok2");
+  }
+  close(fds[0]);
+  close(fds[1]);
+}
+
+void test_full_block() {
+  int fds[2];
+  full_pipe(fds);
+  fcntl(fds[1], F_SETFL, 0);
+  if (fork() == 0) {
+    usleep(100000);
+    read(fds[0], buf, SIZE);
+    _exit(0);
+  }
+  // The write waits for the child to make room.
+  long start = now_ms();
+  if (write(fds[1], "x", 1) == 1 && now_ms() - start >= 50) {
+    puts("test_full_block ok");
+  }
+  wait(NULL);
+  char c;
+  if (read(fds[0], &c, 1) == 1 && c == 'x') {
+    puts("test_full_block ok2");
+  }
+  close(fds[0]);
+  close(fds[1]);
+}
+
+static void on_signal(int sig) { (void)sig; }
+
+// Send SIGUSR1 to the caller after a while, from a child process.
+static void signal_later() {
+  pid_t parent = getpid();
+  if (fork() == 0) {
+    usleep(100000);
+    kill(parent, SIGUSR1);
+    _exit(0);
+  }
+}
+
+void test_full_signal() {
+  // Without SA_RESTART, the interrupted calls fail with EINTR.
+  struct sigaction sa = {.sa_handler = on_signal};
+  sigaction(SIGUSR1, &sa, NULL);
+  int fds[2];
+  full_pipe(fds);
+  fcntl(fds[1], F_SETFL, 0);
+  signal_later();
+  if (write(fds[1], "x", 1) == -1 && errno == EINTR) {
+    puts("test_full_signal ok");
+  }
+  wait(NULL);
+
+  // A write interrupted after it took some bytes returns their count.
+  static char big[2 * SIZE];
+  read(fds[0], buf, SIZE);
+  signal_later();
+  if (write(fds[1], big, sizeof(big)) == SIZE) {
+    puts("test_full_signal ok2");
+  }
+  wait(NULL);
+
+  read(fds[0], buf, SIZE);
+  signal_later();
+  if (read(fds[0], buf, SIZE) == -1 && errno == EINTR) {
+    puts("test_full_signal ok3");
+  }
+  wait(NULL);
+  close(fds[0]);
+  close(fds[1]);
+}
+
+int main() {
+  memset(buf, 'a', SIZE);
+  test_full_nonblock();
+  test_full_block();
+  test_full_signal();
+  return 0;
+}
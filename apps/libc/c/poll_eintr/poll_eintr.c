@@ -0,0 +1,78 @@
+#include <errno.h>
+#include <poll.h>
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <sys/epoll.h>
+#include <sys/eventfd.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static void on_signal(int sig) { (void)sig; }
+
+// Send SIGUSR1 to the caller after a while, from a child process, and
+// return the child.
+static pid_t signal_later() {
+  pid_t parent = getpid();
+  pid_t pid = fork();
+  if (pid == 0) {
+    usleep(100000);
+    kill(parent, SIGUSR1);
+    _exit(0);
+  }
+  return pid;
+}
+
+void test_poll_eintr() {
+  int fds[2];
+  pipe(fds);
+  pid_t pid = signal_later();
+  // Nothing is ever written, so only the signal ends the wait.
+  struct pollfd pfd = {.fd = fds[0], .events = POLLIN};
+  if (poll(&pfd, 1, -1) == -1 && errno == EINTR) {
+    puts("test_poll_eintr ok");
+  }
+  waitpid(pid, NULL, 0);
+
+  int epfd = epoll_create1(0);
+  struct epoll_event ev = {.events = EPOLLIN, .data.fd = fds[0]};
+  epoll_ctl(epfd, EPOLL_CTL_ADD, fds[0], &ev);
+  pid = signal_later();
+  if (epoll_wait(epfd, &ev, 1, -1) == -1 && errno == EINTR) {
+    puts("test_poll_eintr ok2");
+  }
+  waitpid(pid, NULL, 0);
+  close(epfd);
+  close(fds[0]);
+  close(fds[1]);
+}
+
+void test_eventfd_eintr() {
+  int efd = eventfd(0, 0);
+  pid_t pid = signal_later();
+  uint64_t value;
+  if (read(efd, &value, sizeof(value)) == -1 && errno == EINTR) {
+    puts("test_eventfd_eintr ok");
+  }
+  waitpid(pid, NULL, 0);
+
+  // A write that would overflow the counter blocks too.
+  value = 0xfffffffffffffffe;
+  write(efd, &value, sizeof(value));
+  pid = signal_later();
+  value = 1;
+  if (write(efd, &value, sizeof(value)) == -1 && errno == EINTR) {
+    puts("test_eventfd_eintr ok2");
+  }
+  waitpid(pid, NULL, 0);
+  close(efd);
+}
+
+int main() {
+  // Without SA_RESTART, the interrupted calls fail with EINTR.
+  struct sigaction sa = {.sa_handler = on_signal};
+  sigaction(SIGUSR1, &sa, NULL);
+  test_poll_eintr();
+  test_eventfd_eintr();
+  return 0;
+}
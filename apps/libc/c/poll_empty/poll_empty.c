@@ -0,0 +1,47 @@
+#include <errno.h>
+#include <poll.h>
+#include <signal.h>
+#include <stdio.h>
+#include <sys/wait.h>
+#include <time.h>
+#include <unistd.h>
+
+static volatile sig_atomic_t received;
+
+static void handler(int signum) { received = signum; }
+
+static long now_ms() {
+  struct timespec ts;
+  clock_gettime(CLOCK_MONOTONIC, &ts);
+  return ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}
+
+void test_poll_empty() {
+  long start = now_ms();
+  if (poll(NULL, 0, 50) == 0 && now_ms() - start >= 40) {
+    puts("test_poll_empty ok");
+  }
+}
+
+void test_poll_empty_forever() {
+  signal(SIGUSR1, handler);
+  pid_t parent = getpid();
+  if (fork() == 0) {
+    usleep(100000);
+    kill(parent, SIGUSR1);
+    _exit(0);
+  }
+  // Only the signal ends the wait.
+  long start = now_ms();
+  if (poll(NULL, 0, -1) == -1 && errno == EINTR && received == SIGUSR1 &&
+      now_ms() - start >= 50) {
+    puts("test_poll_empty_forever ok");
+  }
+  wait(NULL);
+}
+
+int main() {
+  test_poll_empty();
+  test_poll_empty_forever();
+  return 0;
+}
@@ -0,0 +1,69 @@
+#include <poll.h>
+#include <pthread.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <sys/epoll.h>
+#include <sys/eventfd.h>
+#include <time.h>
+#include <unistd.h>
+
+static struct timespec written;
+
+static long elapsed_ms(const struct timespec *since) {
+  struct timespec now;
+  clock_gettime(CLOCK_MONOTONIC, &now);
+  return (now.tv_sec - since->tv_sec) * 1000 +
+         (now.tv_nsec - since->tv_nsec) / 1000000;
+}
+
+static void *write_later(void *arg) {
+  int fd = *(int *)arg;
+  usleep(200000);
+  clock_gettime(CLOCK_MONOTONIC, &written);
+  uint64_t value = 1;
+  write(fd, &value, sizeof(value));
+  return NULL;
+}
+
+void test_pipe() {
+  int fds[2];
+  pipe(fds);
+  pthread_t thread;
+  pthread_create(&thread, NULL, write_later, &fds[1]);
+  // The poll blocks until the other thread writes, and wakes right away.
+  struct pollfd pfd = {.fd = fds[0], .events = POLLIN};
+  if (poll(&pfd, 1, -1) == 1 && pfd.revents & POLLIN) {
+    puts("test_pipe ok");
+  }
+  if (elapsed_ms(&written) < 100) {
+    puts("test_pipe ok2");
+  }
+  pthread_join(thread, NULL);
+  close(fds[0]);
+  close(fds[1]);
+}
+
+void test_eventfd() {
+  int efd = eventfd(0, 0);
+  int epfd = epoll_create1(0);
+  struct epoll_event ev = {.events = EPOLLIN, .data.fd = efd};
+  epoll_ctl(epfd, EPOLL_CTL_ADD, efd, &ev);
+  pthread_t thread;
+  pthread_create(&thread, NULL, write_later, &efd);
+  struct epoll_event out;
+  if (epoll_wait(epfd, &out, 1, 5000) == 1 && out.data.fd == efd) {
+    puts("test_eventfd ok");
+  }
+  if (elapsed_ms(&written) < 100) {
+    puts("test_eventfd ok2");
+  }
+  pthread_join(thread, NULL);
+  close(epfd);
+  close(efd);
+}
+
+int main() {
+  test_pipe();
+  test_eventfd();
+  return 0;
+}
@@ -0,0 +1,38 @@
+#include <pthread.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/prctl.h>
+
+static char thread_name[16];
+
+static void *worker(void *arg) {
+  prctl(PR_GET_NAME, thread_name);
+  return NULL;
+}
+
+void test_prctl_name() {
+  char name[16];
+  if (prctl(PR_SET_NAME, "worker") == 0 && prctl(PR_GET_NAME, name) == 0 &&
+      strcmp(name, "worker") == 0) {
+    puts("test_prctl_name ok");
+  }
+
+  // Names are cut to 15 bytes, like on Linux.
+  if (prctl(PR_SET_NAME, "a_name_longer_than_16") == 0 &&
+      prctl(PR_GET_NAME, name) == 0 && strcmp(name, "a_name_longer_t") == 0) {
+    puts("test_prctl_name ok2");
+  }
+
+  // A new thread starts with the name of its creator.
+  pthread_t thread;
+  pthread_create(&thread, NULL, worker, NULL);
+  pthread_join(thread, NULL);
+  if (strcmp(thread_name, "a_name_longer_t") == 0) {
+    puts("test_prctl_name ok3");
+  }
+}
+
+int main() {
+  test_prctl_name();
+  return 0;
+}
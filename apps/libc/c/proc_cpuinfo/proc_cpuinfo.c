@@ -0,0 +1,40 @@
+#define _GNU_SOURCE
+#include <sched.h>
+#include <stdio.h>
+#include <string.h>
+
+// /proc/cpuinfo has one block per online CPU, numbered from 0, which a new
+// process may all run on.
+void test_cpuinfo() {
+  FILE *f = fopen("/proc/cpuinfo", "r");
+  if (!f) {
+    return;
+  }
+  char line[256];
+  int count = 0, ordered = 1, named = 0;
+  while (fgets(line, sizeof(line), f)) {
+    int id;
+    if (sscanf(line, "processor : %d", &id) == 1) {
+      ordered &= id == count;
+      count++;
+    } else if (strncmp(line, "model name", 10) == 0) {
+      named++;
+    }
+  }
+  fclose(f);
+
+  cpu_set_t set;
+  CPU_ZERO(&set);
+  sched_getaffinity(0, sizeof(set), &set);
+  if (count > 0 && ordered && named == count) {
+    puts("test_cpuinfo ok");
+  }
+  if (count == CPU_COUNT(&set)) {
+    puts("test_cpuinfo ok2");
+  }
+}
+
+int main() {
+  test_cpuinfo();
+  return 0;
+}
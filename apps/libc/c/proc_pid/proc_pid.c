@@ -0,0 +1,85 @@
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/epoll.h>
+#include <sys/inotify.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static int proc_dir_exists(pid_t pid) {
+  char path[64];
+  snprintf(path, sizeof(path), "/proc/%d", pid);
+  return access(path, F_OK) == 0;
+}
+
+void test_autoreap() {
+  signal(SIGCHLD, SIG_IGN);
+  pid_t pid = fork();
+  if (pid == 0) {
+    _exit(0);
+  }
+  // The child is reaped on exit, so there is nothing to wait for.
+  if (waitpid(pid, NULL, 0) == -1 && errno == ECHILD) {
+    puts("test_autoreap ok");
+  }
+  if (!proc_dir_exists(pid)) {
+    puts("test_autoreap ok2");
+  }
+  signal(SIGCHLD, SIG_DFL);
+}
+
+void test_orphan() {
+  int pipefd[2];
+  pipe(pipefd);
+  pid_t pid = fork();
+  if (pid == 0) {
+    pid_t grandchild = fork();
+    if (grandchild == 0) {
+      _exit(0);
+    }
+    // Exit with a zombie child that is never waited for.
+    usleep(50000);
+    write(pipefd[1], &grandchild, sizeof(grandchild));
+    _exit(0);
+  }
+  pid_t grandchild;
+  read(pipefd[0], &grandchild, sizeof(grandchild));
+  waitpid(pid, NULL, 0);
+  if (!proc_dir_exists(pid) && !proc_dir_exists(grandchild)) {
+    puts("test_orphan ok");
+  }
+  close(pipefd[0]);
+  close(pipefd[1]);
+}
+
+static int fd_link_is(int fd, const char *expected) {
+  char path[64], target[64];
+  snprintf(path, sizeof(path), "/proc/self/fd/%d", fd);
+  ssize_t len = readlink(path, target, sizeof(target) - 1);
+  if (len < 0) {
+    return 0;
+  }
+  target[len] = 0;
+  return strcmp(target, expected) == 0;
+}
+
+void test_link() {
+  int epfd = epoll_create1(0);
+  if (fd_link_is(epfd, "anon_inode:[eventpoll]")) {
+    puts("test_link ok");
+  }
+  int infd = inotify_init1(0);
+  if (fd_link_is(infd, "anon_inode:inotify")) {
+    puts("test_link ok2");
+  }
+  close(epfd);
+  close(infd);
+}
+
+int main() {
+  test_autoreap();
+  test_orphan();
+  test_link();
+  return 0;
+}
@@ -0,0 +1,126 @@
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/prctl.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+// Read the comm and the starttime (field 22) of process `pid`.
+static int read_stat(pid_t pid, char *comm, unsigned long long *start) {
+  char path[64], buf[1024];
+  snprintf(path, sizeof(path), "/proc/%d/stat", pid);
+  FILE *f = fopen(path, "r");
+  if (!f) {
+    return -1;
+  }
+  size_t len = fread(buf, 1, sizeof(buf) - 1, f);
+  fclose(f);
+  buf[len] = 0;
+  char *open = strchr(buf, '('), *close = strrchr(buf, ')');
+  if (!open || !close) {
+    return -1;
+  }
+  memcpy(comm, open + 1, close - open - 1);
+  comm[close - open - 1] = 0;
+  // Fields 3 to 21 come before the start time.
+  char *p = close + 2;
+  for (int field = 3; field < 22; field++) {
+    p = strchr(p, ' ') + 1;
+  }
+  sscanf(p, "%llu", start);
+  return 0;
+}
+
+void test_comm() {
+  char comm[32];
+  unsigned long long start;
+  prctl(PR_SET_NAME, "stat-worker");
+  if (read_stat(getpid(), comm, &start) == 0 &&
+      strcmp(comm, "stat-worker") == 0) {
+    puts("test_comm ok");
+  }
+  prctl(PR_SET_NAME, "a-very-long-thread-name");
+  if (read_stat(getpid(), comm, &start) == 0 &&
+      strcmp(comm, "a-very-long-thr") == 0) {
+    puts("test_comm ok2");
+  }
+}
+
+void test_starttime() {
+  char comm[32];
+  unsigned long long parent, child;
+  read_stat(getpid(), comm, &parent);
+  usleep(50000);
+  int pipefd[2];
+  pipe(pipefd);
+  pid_t pid = fork();
+  if (pid == 0) {
+    char c;
+    read(pipefd[0], &c, 1);
+    _exit(0);
+  }
+  // The child started at least 50ms, 5 ticks, after its parent.
+  if (read_stat(pid, comm, &child) == 0 && child >= parent + 5) {
+    puts("test_starttime ok");
+  }
+  write(pipefd[1], "x", 1);
+  waitpid(pid, NULL, 0);
+}
+
+// Read the value of the line `key` of /proc/self/status into `value`.
+static int read_status(const char *key, char *value, size_t size) {
+  char line[256];
+  FILE *f = fopen("/proc/self/status", "r");
+  if (!f) {
+    return -1;
+  }
+  int found = -1;
+  size_t len = strlen(key);
+  while (fgets(line, sizeof(line), f)) {
+    if (strncmp(line, key, len) == 0 && line[len] == ':') {
+      snprintf(value, size, "%s", line + len + 2);
+      value[strcspn(value, "\n")] = 0;
+      found = 0;
+      break;
+    }
+  }
+  fclose(f);
+  return found;
+}
+
+void test_status() {
+  char value[64];
+  prctl(PR_SET_NAME, "status-worker");
+  if (read_status("Name", value, sizeof(value)) == 0 &&
+      strcmp(value, "status-worker") == 0 &&
+      read_status("State", value, sizeof(value)) == 0 &&
+      strcmp(value, "R (running)") == 0) {
+    puts("test_status ok");
+  }
+
+  // A new mapping counts towards VmSize, and its pages towards VmRSS once
+  // they are touched.
+  unsigned long size, rss, new_size, new_rss;
+  read_status("VmSize", value, sizeof(value));
+  sscanf(value, "%lu", &size);
+  read_status("VmRSS", value, sizeof(value));
+  sscanf(value, "%lu", &rss);
+  char *buf = mmap(NULL, 1 << 20, PROT_READ | PROT_WRITE,
+                   MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  memset(buf, 1, 1 << 20);
+  read_status("VmSize", value, sizeof(value));
+  sscanf(value, "%lu", &new_size);
+  read_status("VmRSS", value, sizeof(value));
+  sscanf(value, "%lu", &new_rss);
+  if (new_size >= size + 1024 && new_rss >= rss + 1024) {
+    puts("test_status ok2");
+  }
+  munmap(buf, 1 << 20);
+}
+
+int main() {
+  test_comm();
+  test_starttime();
+  test_status();
+  return 0;
+}
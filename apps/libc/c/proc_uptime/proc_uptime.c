@@ -0,0 +1,57 @@
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+static double read_uptime(double *idle) {
+  FILE *f = fopen("/proc/uptime", "r");
+  if (!f) {
+    return -1;
+  }
+  double uptime = -1;
+  if (fscanf(f, "%lf %lf", &uptime, idle) != 2) {
+    uptime = -1;
+  }
+  fclose(f);
+  return uptime;
+}
+
+void test_uptime() {
+  double idle;
+  double first = read_uptime(&idle);
+  if (first > 0 && idle >= 0) {
+    puts("test_uptime ok");
+  }
+  usleep(50000);
+  if (read_uptime(&idle) > first) {
+    puts("test_uptime ok2");
+  }
+}
+
+// /proc/stat starts with the aggregate cpu line, and has the boot time.
+void test_proc_stat() {
+  FILE *f = fopen("/proc/stat", "r");
+  if (!f) {
+    return;
+  }
+  char line[256];
+  int cpu = 0, btime = 0;
+  unsigned long long value;
+  if (fgets(line, sizeof(line), f) && strncmp(line, "cpu ", 4) == 0) {
+    cpu = 1;
+  }
+  while (fgets(line, sizeof(line), f)) {
+    if (sscanf(line, "btime %llu", &value) == 1 && value > 0) {
+      btime = 1;
+    }
+  }
+  fclose(f);
+  if (cpu && btime) {
+    puts("test_proc_stat ok");
+  }
+}
+
+int main() {
+  test_uptime();
+  test_proc_stat();
+  return 0;
+}
@@ -0,0 +1,68 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <sys/uio.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static long value = 1;
+
+void test_vm() {
+  int fds[2];
+  pipe(fds);
+  pid_t pid = fork();
+  if (pid == 0) {
+    value = 0x1234;
+    write(fds[1], "x", 1);
+    while (1)
+      pause();
+  }
+  char c;
+  read(fds[0], &c, 1);
+
+  // The child has the same layout, so `value` is at the same address there.
+  long out = 0;
+  struct iovec local = {.iov_base = &out, .iov_len = sizeof(out)};
+  struct iovec remote = {.iov_base = &value, .iov_len = sizeof(value)};
+  if (process_vm_readv(pid, &local, 1, &remote, 1, 0) == sizeof(out) &&
+      out == 0x1234 && value == 1) {
+    puts("test_vm ok");
+  }
+
+  long in = 0x5678;
+  local.iov_base = &in;
+  out = 0;
+  if (process_vm_writev(pid, &local, 1, &remote, 1, 0) == sizeof(in)) {
+    local.iov_base = &out;
+    if (process_vm_readv(pid, &local, 1, &remote, 1, 0) == sizeof(out) &&
+        out == 0x5678 && value == 1) {
+      puts("test_vm ok2");
+    }
+  }
+
+  // A fault in the second remote range ends the transfer with what was
+  // copied before it.
+  long outs[2];
+  struct iovec local2 = {.iov_base = outs, .iov_len = sizeof(outs)};
+  struct iovec remotes[2] = {
+      {.iov_base = &value, .iov_len = sizeof(value)},
+      {.iov_base = NULL, .iov_len = sizeof(value)},
+  };
+  if (process_vm_readv(pid, &local2, 1, remotes, 2, 0) == sizeof(long)) {
+    puts("test_vm ok3");
+  }
+  // The first range faults, so nothing is copied.
+  if (process_vm_readv(pid, &local2, 1, &remotes[1], 1, 0) == -1 &&
+      errno == EFAULT) {
+    puts("test_vm ok4");
+  }
+
+  kill(pid, SIGKILL);
+  waitpid(pid, NULL, 0);
+}
+
+int main() {
+  test_vm();
+  return 0;
+}
@@ -0,0 +1,65 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+#define PATH "/tmp/readahead_test"
+#define SIZE (1 << 20)
+
+static char buf[SIZE];
+
+void test_readahead() {
+  memset(buf, 'r', SIZE);
+  int fd = open(PATH, O_CREAT | O_TRUNC | O_RDWR, 0644);
+  write(fd, buf, SIZE);
+  lseek(fd, 0, SEEK_SET);
+
+  // Reading ahead neither fails nor moves the offset.
+  if (readahead(fd, 0, SIZE) == 0 && lseek(fd, 0, SEEK_CUR) == 0) {
+    puts("test_readahead ok");
+  }
+  memset(buf, 0, SIZE);
+  if (read(fd, buf, SIZE) == SIZE && buf[0] == 'r' && buf[SIZE - 1] == 'r') {
+    puts("test_readahead ok2");
+  }
+
+  int pipefd[2];
+  pipe(pipefd);
+  if (readahead(pipefd[0], 0, 16) == -1 && errno == EINVAL) {
+    puts("test_readahead ok3");
+  }
+  close(pipefd[0]);
+  close(pipefd[1]);
+  close(fd);
+  unlink(PATH);
+}
+
+void test_fadvise() {
+  int fd = open(PATH, O_CREAT | O_TRUNC | O_RDWR, 0644);
+  write(fd, buf, SIZE);
+  if (posix_fadvise(fd, 0, 0, POSIX_FADV_SEQUENTIAL) == 0 &&
+      posix_fadvise(fd, 0, SIZE, POSIX_FADV_WILLNEED) == 0 &&
+      posix_fadvise(fd, 0, SIZE, POSIX_FADV_RANDOM) == 0 &&
+      posix_fadvise(fd, 0, SIZE, POSIX_FADV_DONTNEED) == 0) {
+    puts("test_fadvise ok");
+  }
+
+  int pipefd[2];
+  pipe(pipefd);
+  if (posix_fadvise(pipefd[0], 0, 0, POSIX_FADV_NORMAL) == ESPIPE &&
+      posix_fadvise(fd, 0, -1, POSIX_FADV_NORMAL) == EINVAL) {
+    puts("test_fadvise ok2");
+  }
+  close(pipefd[0]);
+  close(pipefd[1]);
+  close(fd);
+  unlink(PATH);
+}
+
+int main() {
+  test_readahead();
+  test_fadvise();
+  return 0;
+}
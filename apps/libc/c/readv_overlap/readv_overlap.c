@@ -0,0 +1,42 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/uio.h>
+#include <unistd.h>
+
+void test_overlap() {
+  int fd = open("readv_overlap_file", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  write(fd, "0123456789", 10);
+
+  // The buffers overlap, so the second read overwrites part of the first.
+  char buf[8] = {0};
+  struct iovec iov[2] = {{buf, 4}, {buf + 2, 4}};
+  lseek(fd, 0, SEEK_SET);
+  if (readv(fd, iov, 2) == 8 && memcmp(buf, "014567", 6) == 0) {
+    puts("test_overlap ok");
+  }
+  memset(buf, 0, sizeof(buf));
+  if (preadv(fd, iov, 2, 2) == 8 && memcmp(buf, "236789", 6) == 0) {
+    puts("test_overlap ok2");
+  }
+  close(fd);
+  unlink("readv_overlap_file");
+}
+
+void test_fault() {
+  int fd = open("/dev/zero", O_RDONLY);
+  char buf[4] = {1, 1, 1, 1};
+  struct iovec iov[2] = {{buf, 4}, {(void *)8, 4}};
+  // A bad buffer fails the call before anything is read.
+  if (readv(fd, iov, 2) == -1 && errno == EFAULT && buf[0] == 1) {
+    puts("test_fault ok");
+  }
+  close(fd);
+}
+
+int main() {
+  test_overlap();
+  test_fault();
+  return 0;
+}
@@ -0,0 +1,66 @@
+#include <errno.h>
+#include <poll.h>
+#include <stdio.h>
+#include <sys/resource.h>
+#include <unistd.h>
+
+#define LIMIT 32
+
+// poll rejects more entries than the process may have open files.
+void test_poll_nfds() {
+  struct rlimit old, rl;
+  getrlimit(RLIMIT_NOFILE, &old);
+  rl.rlim_cur = LIMIT;
+  rl.rlim_max = old.rlim_max;
+  setrlimit(RLIMIT_NOFILE, &rl);
+
+  struct pollfd fds[LIMIT + 1];
+  for (int i = 0; i <= LIMIT; i++) {
+    fds[i].fd = -1;
+    fds[i].events = POLLIN;
+  }
+  if (poll(fds, LIMIT, 0) == 0) {
+    puts("test_poll_nfds ok");
+  }
+  if (poll(fds, LIMIT + 1, 0) == -1 && errno == EINVAL) {
+    puts("test_poll_nfds ok2");
+  }
+  setrlimit(RLIMIT_NOFILE, &old);
+}
+
+void test_rlimit_nofile() {
+  struct rlimit old, rl;
+  getrlimit(RLIMIT_NOFILE, &old);
+  rl.rlim_cur = 16;
+  rl.rlim_max = old.rlim_max;
+  if (setrlimit(RLIMIT_NOFILE, &rl) == 0 &&
+      getrlimit(RLIMIT_NOFILE, &rl) == 0 && rl.rlim_cur == 16 &&
+      rl.rlim_max == old.rlim_max) {
+    puts("test_rlimit_nofile ok");
+  }
+
+  // New file descriptors stay below the soft limit.
+  int fd, last = -1;
+  while ((fd = dup(0)) >= 0) {
+    last = fd;
+  }
+  if (errno == EMFILE && last == 15 && dup2(0, 16) == -1 && errno == EBADF) {
+    puts("test_rlimit_nofile ok2");
+  }
+  for (fd = 3; fd <= last; fd++) {
+    close(fd);
+  }
+
+  rl.rlim_cur = old.rlim_max + 1;
+  if (old.rlim_max != RLIM_INFINITY && setrlimit(RLIMIT_NOFILE, &rl) == -1 &&
+      errno == EINVAL) {
+    puts("test_rlimit_nofile ok3");
+  }
+  setrlimit(RLIMIT_NOFILE, &old);
+}
+
+int main() {
+  test_poll_nfds();
+  test_rlimit_nofile();
+  return 0;
+}
@@ -0,0 +1,50 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <sched.h>
+#include <stdio.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+void test_other() {
+  int pipefd[2];
+  pipe(pipefd);
+  pid_t pid = fork();
+  if (pid == 0) {
+    char c;
+    read(pipefd[0], &c, 1);
+    // The new mask is applied once we are back in user space.
+    cpu_set_t set;
+    sched_getaffinity(0, sizeof(set), &set);
+    _exit(CPU_COUNT(&set) == 1 && CPU_ISSET(0, &set) ? 0 : 1);
+  }
+
+  cpu_set_t set;
+  CPU_ZERO(&set);
+  CPU_SET(0, &set);
+  if (sched_setaffinity(pid, sizeof(set), &set) == 0) {
+    puts("test_other ok");
+  }
+  write(pipefd[1], "x", 1);
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_other ok2");
+  }
+}
+
+void test_invalid() {
+  cpu_set_t set;
+  CPU_ZERO(&set);
+  if (sched_setaffinity(0, sizeof(set), &set) == -1 && errno == EINVAL) {
+    puts("test_invalid ok");
+  }
+  if (sched_setaffinity(1 << 22, sizeof(set), &set) == -1) {
+    puts("test_invalid ok2");
+  }
+}
+
+int main() {
+  test_other();
+  test_invalid();
+  return 0;
+}
@@ -0,0 +1,51 @@
+#include <fcntl.h>
+#include <poll.h>
+#include <stdio.h>
+#include <sys/select.h>
+#include <time.h>
+#include <unistd.h>
+
+static long elapsed_ms(const struct timespec *since) {
+  struct timespec now;
+  clock_gettime(CLOCK_MONOTONIC, &now);
+  return (now.tv_sec - since->tv_sec) * 1000 +
+         (now.tv_nsec - since->tv_nsec) / 1000000;
+}
+
+// Regular files are always ready, so a select on one returns right away
+// instead of waiting for the timeout.
+void test_select_file() {
+  int fd = open("select_file", O_CREAT | O_RDWR | O_TRUNC, 0644);
+  fd_set rfds, wfds;
+  FD_ZERO(&rfds);
+  FD_ZERO(&wfds);
+  FD_SET(fd, &rfds);
+  FD_SET(fd, &wfds);
+  struct timeval tv = {1, 0};
+  struct timespec start;
+  clock_gettime(CLOCK_MONOTONIC, &start);
+  if (select(fd + 1, &rfds, &wfds, NULL, &tv) == 2 && FD_ISSET(fd, &rfds) &&
+      FD_ISSET(fd, &wfds)) {
+    puts("test_select_file ok");
+  }
+  if (elapsed_ms(&start) < 500) {
+    puts("test_select_file ok2");
+  }
+  close(fd);
+  unlink("select_file");
+}
+
+void test_poll_dir() {
+  int fd = open(".", O_RDONLY | O_DIRECTORY);
+  struct pollfd pfd = {.fd = fd, .events = POLLIN};
+  if (poll(&pfd, 1, 1000) == 1 && pfd.revents & POLLIN) {
+    puts("test_poll_dir ok");
+  }
+  close(fd);
+}
+
+int main() {
+  test_select_file();
+  test_poll_dir();
+  return 0;
+}
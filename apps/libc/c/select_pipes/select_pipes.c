@@ -0,0 +1,74 @@
+#include <poll.h>
+#include <stdio.h>
+#include <sys/select.h>
+#include <unistd.h>
+
+#define PIPES 4
+
+static int pipes[PIPES][2];
+
+// Only pipes are watched, so no network interface is polled: the results
+// must be the same as with sockets around.
+void test_select_pipes() {
+  for (int i = 0; i < PIPES; i++) {
+    pipe(pipes[i]);
+  }
+  write(pipes[2][1], "x", 1);
+
+  fd_set rfds, wfds;
+  FD_ZERO(&rfds);
+  FD_ZERO(&wfds);
+  int nfds = 0;
+  for (int i = 0; i < PIPES; i++) {
+    FD_SET(pipes[i][0], &rfds);
+    nfds = pipes[i][0] + 1 > nfds ? pipes[i][0] + 1 : nfds;
+  }
+  FD_SET(pipes[0][1], &wfds);
+  nfds = pipes[0][1] + 1 > nfds ? pipes[0][1] + 1 : nfds;
+  struct timeval tv = {0, 0};
+  if (select(nfds, &rfds, &wfds, NULL, &tv) == 2 &&
+      FD_ISSET(pipes[2][0], &rfds) && !FD_ISSET(pipes[0][0], &rfds) &&
+      FD_ISSET(pipes[0][1], &wfds)) {
+    puts("test_select_pipes ok");
+  }
+
+  FD_ZERO(&rfds);
+  FD_SET(pipes[1][0], &rfds);
+  struct timespec ts = {0, 10000000};
+  if (pselect(pipes[1][0] + 1, &rfds, NULL, NULL, &ts, NULL) == 0) {
+    puts("test_select_pipes ok2");
+  }
+}
+
+void test_poll_pipes() {
+  struct pollfd pfds[PIPES];
+  for (int i = 0; i < PIPES; i++) {
+    pfds[i].fd = pipes[i][0];
+    pfds[i].events = POLLIN;
+  }
+  if (poll(pfds, PIPES, 10) == 1 && pfds[2].revents == POLLIN &&
+      pfds[0].revents == 0) {
+    puts("test_poll_pipes ok");
+  }
+
+  // The readiness follows the pipe as it is drained and closed.
+  char c;
+  read(pipes[2][0], &c, 1);
+  close(pipes[3][1]);
+  if (poll(pfds, PIPES, 0) == 1 && pfds[2].revents == 0 &&
+      pfds[3].revents != 0) {
+    puts("test_poll_pipes ok2");
+  }
+  for (int i = 0; i < PIPES; i++) {
+    close(pipes[i][0]);
+    if (i != 3) {
+      close(pipes[i][1]);
+    }
+  }
+}
+
+int main() {
+  test_select_pipes();
+  test_poll_pipes();
+  return 0;
+}
@@ -0,0 +1,35 @@
+#include <errno.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+void test_setsid() {
+  pid_t sid = getsid(0);
+  if (sid > 0 && getsid(getpid()) == sid) {
+    puts("test_setsid ok");
+  }
+  fflush(stdout);
+
+  pid_t pid = fork();
+  if (pid == 0) {
+    pid_t self = getpid();
+    if (setsid() == self && getsid(0) == self && getpgid(0) == self) {
+      puts("test_setsid ok2");
+    }
+    // The process now leads its group.
+    if (setsid() == -1 && errno == EPERM) {
+      puts("test_setsid ok3");
+    }
+    exit(0);
+  }
+  waitpid(pid, NULL, 0);
+  if (getsid(0) == sid) {
+    puts("test_setsid ok4");
+  }
+}
+
+int main() {
+  test_setsid();
+  return 0;
+}
@@ -0,0 +1,91 @@
+#include <stdio.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define WORKERS 4
+#define ITERS 2000
+
+static int ids[WORKERS];
+static int shared_id;
+
+// Attach, write to, detach and stat the segment `id` `ITERS` times, counting
+// the writes in the byte `slot` of the segment. Exits with 1 on failure.
+static void worker(int id, int slot) {
+  struct shmid_ds ds;
+  for (int i = 0; i < ITERS; i++) {
+    unsigned short *addr = shmat(id, NULL, 0);
+    if (addr == (void *)-1) {
+      _exit(1);
+    }
+    addr[slot]++;
+    if (shmdt(addr) != 0 || shmctl(id, IPC_STAT, &ds) != 0) {
+      _exit(1);
+    }
+  }
+  _exit(0);
+}
+
+// Run the workers in parallel processes, each on the segment `ids[i]`, or
+// all on `shared_id` if `shared` is set, and return whether they all
+// succeeded.
+static int run(int shared) {
+  for (int i = 0; i < WORKERS; i++) {
+    if (fork() == 0) {
+      worker(shared ? shared_id : ids[i], i);
+    }
+  }
+  int ok = 1;
+  for (int i = 0; i < WORKERS; i++) {
+    int status;
+    wait(&status);
+    ok &= WIFEXITED(status) && WEXITSTATUS(status) == 0;
+  }
+  return ok;
+}
+
+// Whether the segment `id` is detached everywhere else, and holds `ITERS`
+// writes in each of the slots `first` to `last`.
+static int check(int id, int first, int last) {
+  struct shmid_ds ds;
+  unsigned short *addr = shmat(id, NULL, SHM_RDONLY);
+  if (shmctl(id, IPC_STAT, &ds) != 0 || ds.shm_nattch != 1) {
+    return 0;
+  }
+  int ok = 1;
+  for (int i = first; i <= last; i++) {
+    ok &= addr[i] == ITERS;
+  }
+  shmdt(addr);
+  return ok;
+}
+
+// Workers in separate processes attach and detach segments concurrently,
+// each in its own address space: first one segment each, then all the same
+// segment. No operation may fail or lose a write.
+void test_shm_bench() {
+  for (int i = 0; i < WORKERS; i++) {
+    ids[i] = shmget(IPC_PRIVATE, 4096, IPC_CREAT | 0600);
+  }
+  shared_id = shmget(IPC_PRIVATE, 4096, IPC_CREAT | 0600);
+  int ok = run(0);
+  for (int i = 0; i < WORKERS; i++) {
+    ok &= check(ids[i], i, i);
+  }
+  if (ok) {
+    puts("test_shm_bench ok");
+  }
+  if (run(1) && check(shared_id, 0, WORKERS - 1)) {
+    puts("test_shm_bench ok2");
+  }
+  for (int i = 0; i < WORKERS; i++) {
+    shmctl(ids[i], IPC_RMID, NULL);
+  }
+  shmctl(shared_id, IPC_RMID, NULL);
+}
+
+int main() {
+  test_shm_bench();
+  return 0;
+}
@@ -0,0 +1,41 @@
+#include <errno.h>
+#include <stdio.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+void test_shm_exit() {
+  int id = shmget(IPC_PRIVATE, 4096, IPC_CREAT | 0600);
+  int ready[2], done[2];
+  pipe(ready);
+  pipe(done);
+  pid_t pid = fork();
+  if (pid == 0) {
+    char c;
+    shmat(id, NULL, 0);
+    write(ready[1], "x", 1);
+    // Exit with the segment still attached.
+    read(done[0], &c, 1);
+    _exit(0);
+  }
+  char c;
+  read(ready[0], &c, 1);
+
+  // The segment lives on while the child has it attached.
+  struct shmid_ds ds;
+  shmctl(id, IPC_RMID, NULL);
+  if (shmctl(id, IPC_STAT, &ds) == 0 && ds.shm_nattch == 1) {
+    puts("test_shm_exit ok");
+  }
+  write(done[1], "x", 1);
+  waitpid(pid, NULL, 0);
+  if (shmctl(id, IPC_STAT, &ds) == -1 && errno == EINVAL) {
+    puts("test_shm_exit ok2");
+  }
+}
+
+int main() {
+  test_shm_exit();
+  return 0;
+}
@@ -0,0 +1,41 @@
+#include <stdio.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+void test_shm_fork() {
+  int id = shmget(IPC_PRIVATE, 4096, IPC_CREAT | 0600);
+  volatile char *mem = shmat(id, NULL, 0);
+  mem[0] = 0;
+  int ready[2], done[2];
+  pipe(ready);
+  pipe(done);
+  pid_t pid = fork();
+  if (pid == 0) {
+    char c;
+    // The attachment is inherited at the same address.
+    mem[0] = 42;
+    write(ready[1], "x", 1);
+    read(done[0], &c, 1);
+    _exit(0);
+  }
+  char c;
+  read(ready[0], &c, 1);
+  struct shmid_ds ds;
+  if (mem[0] == 42 && shmctl(id, IPC_STAT, &ds) == 0 && ds.shm_nattch == 2) {
+    puts("test_shm_fork ok");
+  }
+  write(done[1], "x", 1);
+  waitpid(pid, NULL, 0);
+  if (shmctl(id, IPC_STAT, &ds) == 0 && ds.shm_nattch == 1) {
+    puts("test_shm_fork ok2");
+  }
+  shmdt((void *)mem);
+  shmctl(id, IPC_RMID, NULL);
+}
+
+int main() {
+  test_shm_fork();
+  return 0;
+}
@@ -0,0 +1,64 @@
+#include <errno.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+
+#ifndef SHM_HUGETLB
+#define SHM_HUGETLB 04000
+#endif
+
+#define SHM_HUGE_SHIFT 26
+#define SHM_HUGE_2MB (21 << SHM_HUGE_SHIFT)
+#define SHM_HUGE_1GB (30 << SHM_HUGE_SHIFT)
+
+#define HUGE_PAGE (2 << 20)
+#define SIZE (4 << 20)
+
+void test_shm_huge() {
+  int id = shmget(IPC_PRIVATE, SIZE, IPC_CREAT | SHM_HUGETLB | 0600);
+  volatile char *mem = shmat(id, NULL, 0);
+  if (id != -1 && mem != (void *)-1) {
+    puts("test_shm_huge ok");
+  }
+  int ok = 1;
+  for (int i = 0; i < SIZE; i += 4096) {
+    mem[i] = i / 4096;
+  }
+  for (int i = 0; i < SIZE; i += 4096) {
+    ok &= mem[i] == (char)(i / 4096);
+  }
+  if (ok) {
+    puts("test_shm_huge ok2");
+  }
+  shmdt((void *)mem);
+  shmctl(id, IPC_RMID, NULL);
+}
+
+void test_shm_huge_size() {
+  int flags = IPC_CREAT | SHM_HUGETLB | 0600;
+  if (shmget(IPC_PRIVATE, SIZE, flags | SHM_HUGE_1GB) == -1 &&
+      errno == EINVAL) {
+    puts("test_shm_huge_size ok");
+  }
+  // The segment is mapped with 2MB pages, so it is attached on their
+  // boundaries only.
+  int id = shmget(IPC_PRIVATE, SIZE, flags | SHM_HUGE_2MB);
+  char *mem = shmat(id, NULL, 0);
+  if (mem != (void *)-1 && (uintptr_t)mem % HUGE_PAGE == 0) {
+    puts("test_shm_huge_size ok2");
+  }
+  shmdt(mem);
+  if (shmat(id, mem + 4096, 0) == (void *)-1 && errno == EINVAL &&
+      shmat(id, mem + 4096, SHM_RND) == mem) {
+    puts("test_shm_huge_size ok3");
+  }
+  shmdt(mem);
+  shmctl(id, IPC_RMID, NULL);
+}
+
+int main() {
+  test_shm_huge();
+  test_shm_huge_size();
+  return 0;
+}
@@ -0,0 +1,65 @@
+#include <stdio.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+#include <sys/sysinfo.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define SIZE (64 << 20)
+#define TOUCHED (8 << 20)
+
+static long free_ram() {
+  struct sysinfo info;
+  sysinfo(&info);
+  return (long)info.freeram * info.mem_unit;
+}
+
+void test_shm_lazy() {
+  long before = free_ram();
+  int id = shmget(IPC_PRIVATE, SIZE, IPC_CREAT | 0600);
+  char *mem = shmat(id, NULL, 0);
+  // Nothing is allocated until the pages are touched.
+  if (mem != (void *)-1 && before - free_ram() < TOUCHED) {
+    puts("test_shm_lazy ok");
+  }
+  for (long i = 0; i < TOUCHED; i += 4096) {
+    mem[i] = 1;
+  }
+  long used = before - free_ram();
+  if (used >= TOUCHED && used < SIZE / 2) {
+    puts("test_shm_lazy ok2");
+  }
+  shmdt(mem);
+  shmctl(id, IPC_RMID, NULL);
+}
+
+// The pages touched before and after the fork are shared with the child.
+void test_shm_lazy_fork() {
+  int id = shmget(IPC_PRIVATE, SIZE, IPC_CREAT | 0600);
+  char *mem = shmat(id, NULL, 0);
+  mem[0] = 1;
+  pid_t pid = fork();
+  if (pid == 0) {
+    int seen = mem[0] == 1;
+    mem[0] = 2;
+    mem[TOUCHED] = 3;
+    shmdt(mem);
+    _exit(!seen);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_shm_lazy_fork ok");
+  }
+  if (mem[0] == 2 && mem[TOUCHED] == 3) {
+    puts("test_shm_lazy_fork ok2");
+  }
+  shmdt(mem);
+  shmctl(id, IPC_RMID, NULL);
+}
+
+int main() {
+  test_shm_lazy();
+  test_shm_lazy_fork();
+  return 0;
+}
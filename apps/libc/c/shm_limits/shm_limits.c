@@ -0,0 +1,64 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+#include <unistd.h>
+
+static long read_limit(const char *path) {
+  char buf[32] = {0};
+  int fd = open(path, O_RDONLY);
+  read(fd, buf, sizeof(buf) - 1);
+  close(fd);
+  return atol(buf);
+}
+
+static void write_limit(const char *path, long value) {
+  char buf[32];
+  int len = snprintf(buf, sizeof(buf), "%ld\n", value);
+  int fd = open(path, O_WRONLY);
+  write(fd, buf, len);
+  close(fd);
+}
+
+void test_shmmni() {
+  const char *path = "/proc/sys/kernel/shmmni";
+  long shmmni = read_limit(path);
+  struct shm_info info;
+  shmctl(0, SHM_INFO, (struct shmid_ds *)&info);
+  // Leave room for two more segments.
+  write_limit(path, info.used_ids + 2);
+  int a = shmget(IPC_PRIVATE, 4096, IPC_CREAT | 0600);
+  int b = shmget(IPC_PRIVATE, 4096, IPC_CREAT | 0600);
+  if (a != -1 && b != -1 &&
+      shmget(IPC_PRIVATE, 4096, IPC_CREAT | 0600) == -1 && errno == ENOSPC) {
+    puts("test_shmmni ok");
+  }
+  shmctl(a, IPC_RMID, NULL);
+  shmctl(b, IPC_RMID, NULL);
+  write_limit(path, shmmni);
+  if (read_limit(path) == shmmni) {
+    puts("test_shmmni ok2");
+  }
+}
+
+void test_shmmax() {
+  const char *path = "/proc/sys/kernel/shmmax";
+  long shmmax = read_limit(path);
+  write_limit(path, 8192);
+  int id = shmget(IPC_PRIVATE, 8192, IPC_CREAT | 0600);
+  if (id != -1 && shmget(IPC_PRIVATE, 8193, IPC_CREAT | 0600) == -1 &&
+      errno == EINVAL) {
+    puts("test_shmmax ok");
+  }
+  shmctl(id, IPC_RMID, NULL);
+  write_limit(path, shmmax);
+}
+
+int main() {
+  test_shmmni();
+  test_shmmax();
+  return 0;
+}
@@ -0,0 +1,42 @@
+#include <errno.h>
+#include <stdio.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#ifndef SHM_LOCKED
+#define SHM_LOCKED 02000
+#endif
+
+static int locked(int id) {
+  struct shmid_ds ds;
+  return shmctl(id, IPC_STAT, &ds) == 0 && ds.shm_perm.mode & SHM_LOCKED;
+}
+
+void test_shm_lock() {
+  int id = shmget(IPC_PRIVATE, 8192, IPC_CREAT | 0666);
+  if (!locked(id) && shmctl(id, SHM_LOCK, NULL) == 0 && locked(id)) {
+    puts("test_shm_lock ok");
+  }
+  if (shmctl(id, SHM_UNLOCK, NULL) == 0 && !locked(id)) {
+    puts("test_shm_lock ok2");
+  }
+  // Other users may not lock the segment.
+  pid_t pid = fork();
+  if (pid == 0) {
+    setuid(1000);
+    _exit(shmctl(id, SHM_LOCK, NULL) == -1 && errno == EPERM ? 0 : 1);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0 && !locked(id)) {
+    puts("test_shm_lock ok3");
+  }
+  shmctl(id, IPC_RMID, NULL);
+}
+
+int main() {
+  test_shm_lock();
+  return 0;
+}
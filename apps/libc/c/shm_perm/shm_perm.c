@@ -0,0 +1,77 @@
+#include <errno.h>
+#include <stdio.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+// Run `fn(id)` in a child process with the user id `uid`, and return its
+// exit status.
+static int run_as(uid_t uid, int (*fn)(int), int id) {
+  pid_t pid = fork();
+  if (pid == 0) {
+    setuid(uid);
+    _exit(fn(id));
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  return WIFEXITED(status) ? WEXITSTATUS(status) : -1;
+}
+
+static int create(int id) {
+  (void)id;
+  return shmget(0x5e9a, 4096, IPC_CREAT | 0600) == -1;
+}
+
+static int attach(int id) {
+  void *addr = shmat(id, NULL, 0);
+  if (addr == (void *)-1) {
+    return errno == EACCES ? 2 : 1;
+  }
+  return 0;
+}
+
+static int set(int id) {
+  struct shmid_ds ds;
+  if (shmctl(id, IPC_STAT, &ds) == -1) {
+    return 1;
+  }
+  ds.shm_perm.mode = 0644;
+  if (shmctl(id, IPC_SET, &ds) == -1) {
+    return errno == EPERM ? 2 : 1;
+  }
+  return 0;
+}
+
+static int remove_segment(int id) {
+  if (shmctl(id, IPC_RMID, NULL) == -1) {
+    return errno == EPERM ? 2 : 1;
+  }
+  return 0;
+}
+
+void test_shm_perm() {
+  run_as(1000, create, 0);
+  int id = shmget(0x5e9a, 4096, 0);
+  struct shmid_ds ds;
+  if (shmctl(id, IPC_STAT, &ds) == 0 && ds.shm_perm.uid == 1000 &&
+      ds.shm_perm.cuid == 1000) {
+    puts("test_shm_perm ok");
+  }
+  // Only the owner and root may use the segment.
+  if (run_as(1001, attach, id) == 2 && run_as(1000, attach, id) == 0 &&
+      attach(id) == 0) {
+    puts("test_shm_perm ok2");
+  }
+  // Only the owner and root may change or remove it, even with read access.
+  if (run_as(1000, set, id) == 0 && run_as(1001, set, id) == 2 &&
+      run_as(1001, remove_segment, id) == 2 && shmget(0x5e9a, 4096, 0) == id) {
+    puts("test_shm_perm ok3");
+  }
+  shmctl(id, IPC_RMID, NULL);
+}
+
+int main() {
+  test_shm_perm();
+  return 0;
+}
@@ -0,0 +1,71 @@
+#include <pthread.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+
+#define THREADS 4
+#define SEGMENTS 16
+#define SIZE (3 * 4096)
+
+static int ids[THREADS][SEGMENTS];
+static char *addrs[THREADS][SEGMENTS];
+
+static void *attach_all(void *arg) {
+  long t = (long)arg;
+  for (int i = 0; i < SEGMENTS; i++) {
+    addrs[t][i] = shmat(ids[t][i], NULL, 0);
+    if (addrs[t][i] != (void *)-1) {
+      addrs[t][i][0] = t;
+      addrs[t][i][SIZE - 1] = i;
+    }
+  }
+  return NULL;
+}
+
+void test_shm_race() {
+  for (int t = 0; t < THREADS; t++) {
+    for (int i = 0; i < SEGMENTS; i++) {
+      ids[t][i] = shmget(IPC_PRIVATE, SIZE, IPC_CREAT | 0600);
+    }
+  }
+  pthread_t threads[THREADS];
+  for (long t = 0; t < THREADS; t++) {
+    pthread_create(&threads[t], NULL, attach_all, (void *)t);
+  }
+  for (int t = 0; t < THREADS; t++) {
+    pthread_join(threads[t], NULL);
+  }
+
+  // No two attaches overlap, and each segment kept what was written to it.
+  int ok = 1;
+  for (int n = 0; n < THREADS * SEGMENTS; n++) {
+    char *a = addrs[n / SEGMENTS][n % SEGMENTS];
+    if (a == (void *)-1 || a[0] != n / SEGMENTS ||
+        a[SIZE - 1] != n % SEGMENTS) {
+      ok = 0;
+      continue;
+    }
+    for (int m = 0; m < n; m++) {
+      char *b = addrs[m / SEGMENTS][m % SEGMENTS];
+      if ((uintptr_t)a < (uintptr_t)b + SIZE &&
+          (uintptr_t)b < (uintptr_t)a + SIZE) {
+        ok = 0;
+      }
+    }
+  }
+  if (ok) {
+    puts("test_shm_race ok");
+  }
+  for (int t = 0; t < THREADS; t++) {
+    for (int i = 0; i < SEGMENTS; i++) {
+      shmdt(addrs[t][i]);
+      shmctl(ids[t][i], IPC_RMID, NULL);
+    }
+  }
+}
+
+int main() {
+  test_shm_race();
+  return 0;
+}
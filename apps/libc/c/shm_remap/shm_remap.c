@@ -0,0 +1,47 @@
+#include <errno.h>
+#include <stdio.h>
+#include <sys/ipc.h>
+#include <sys/mman.h>
+#include <sys/shm.h>
+
+#ifndef SHM_REMAP
+#define SHM_REMAP 040000
+#endif
+
+void test_shm_remap() {
+  int id = shmget(IPC_PRIVATE, 8192, IPC_CREAT | 0600);
+  char *addr = mmap(NULL, 8192, PROT_READ | PROT_WRITE,
+                    MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  addr[0] = 'x';
+  addr[4096] = 'x';
+  if (shmat(id, addr, 0) == (void *)-1 && errno == EINVAL &&
+      shmat(id, NULL, SHM_REMAP) == (void *)-1 && errno == EINVAL) {
+    puts("test_shm_remap ok");
+  }
+  // The segment replaces the anonymous memory.
+  char *mem = shmat(id, addr, SHM_REMAP);
+  if (mem == addr && mem[0] == 0 && mem[4096] == 0) {
+    puts("test_shm_remap ok2");
+  }
+  mem[0] = 'y';
+  shmdt(mem);
+  char *other = shmat(id, NULL, 0);
+  if (other[0] == 'y') {
+    puts("test_shm_remap ok3");
+  }
+  // A segment only partly covered by the new one cannot be detached, so the
+  // attach fails and leaves its mapping in place.
+  int small = shmget(IPC_PRIVATE, 4096, IPC_CREAT | 0600);
+  other[4096] = 'z';
+  if (shmat(small, other + 4096, SHM_REMAP) == (void *)-1 && errno == EINVAL &&
+      other[0] == 'y' && other[4096] == 'z' && shmdt(other) == 0) {
+    puts("test_shm_remap ok4");
+  }
+  shmctl(small, IPC_RMID, NULL);
+  shmctl(id, IPC_RMID, NULL);
+}
+
+int main() {
+  test_shm_remap();
+  return 0;
+}
@@ -0,0 +1,37 @@
+#include <stdio.h>
+#include <string.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+
+// Create a segment of `size` bytes, check that it reads as zeros, and dirty
+// it before removing it so that the next one may get the same memory.
+static int new_segment_is_zero(size_t size) {
+  int id = shmget(IPC_PRIVATE, size, IPC_CREAT | 0600);
+  char *mem = shmat(id, NULL, 0);
+  if (mem == (void *)-1) {
+    return 0;
+  }
+  int zero = 1;
+  for (size_t i = 0; i < size; i++) {
+    zero &= mem[i] == 0;
+  }
+  memset(mem, 0xa5, size);
+  shmdt(mem);
+  shmctl(id, IPC_RMID, NULL);
+  return zero;
+}
+
+void test_shm_zero() {
+  if (new_segment_is_zero(8192) && new_segment_is_zero(8192)) {
+    puts("test_shm_zero ok");
+  }
+  // Large segments are populated on demand.
+  if (new_segment_is_zero(4 << 20) && new_segment_is_zero(4 << 20)) {
+    puts("test_shm_zero ok2");
+  }
+}
+
+int main() {
+  test_shm_zero();
+  return 0;
+}
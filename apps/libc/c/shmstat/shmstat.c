@@ -0,0 +1,51 @@
+#include <errno.h>
+#include <stdio.h>
+#include <sys/ipc.h>
+#include <sys/shm.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#ifndef SHM_STAT_ANY
+#define SHM_STAT_ANY 15
+#endif
+
+static int index_of(int id) {
+  struct shm_info info;
+  int max = shmctl(0, SHM_INFO, (struct shmid_ds *)&info);
+  for (int i = 0; i <= max; i++) {
+    struct shmid_ds ds;
+    if (shmctl(i, SHM_STAT_ANY, &ds) == id) {
+      return i;
+    }
+  }
+  return -1;
+}
+
+void test_stat_perm() {
+  int id = shmget(IPC_PRIVATE, 4096, IPC_CREAT | 0600);
+  int index = index_of(id);
+  struct shmid_ds ds;
+  if (shmctl(id, IPC_STAT, &ds) == 0 && shmctl(index, SHM_STAT, &ds) == id) {
+    puts("test_stat_perm ok");
+  }
+
+  pid_t pid = fork();
+  if (pid == 0) {
+    setuid(1000);
+    int denied = shmctl(id, IPC_STAT, &ds) == -1 && errno == EACCES &&
+                 shmctl(index, SHM_STAT, &ds) == -1 && errno == EACCES;
+    int any = shmctl(index, SHM_STAT_ANY, &ds) == id;
+    _exit(denied && any ? 0 : 1);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_stat_perm ok2");
+  }
+  shmctl(id, IPC_RMID, NULL);
+}
+
+int main() {
+  test_stat_perm();
+  return 0;
+}
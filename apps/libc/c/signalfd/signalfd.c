@@ -0,0 +1,41 @@
+#include <errno.h>
+#include <poll.h>
+#include <signal.h>
+#include <stdio.h>
+#include <sys/signalfd.h>
+#include <unistd.h>
+
+void test_signalfd() {
+  sigset_t mask;
+  sigemptyset(&mask);
+  sigaddset(&mask, SIGUSR1);
+  sigprocmask(SIG_BLOCK, &mask, NULL);
+  int fd = signalfd(-1, &mask, SFD_NONBLOCK | SFD_CLOEXEC);
+
+  struct signalfd_siginfo info;
+  if (read(fd, &info, sizeof(info)) == -1 && errno == EAGAIN) {
+    puts("test_signalfd ok");
+  }
+
+  raise(SIGUSR1);
+  struct pollfd pfd = {.fd = fd, .events = POLLIN};
+  if (poll(&pfd, 1, 0) == 1 && (pfd.revents & POLLIN)) {
+    puts("test_signalfd ok2");
+  }
+  if (read(fd, &info, sizeof(info)) == sizeof(info) &&
+      info.ssi_signo == SIGUSR1) {
+    puts("test_signalfd ok3");
+  }
+  // The signal was taken by the read, so it is no longer pending.
+  sigset_t pending;
+  sigpending(&pending);
+  if (!sigismember(&pending, SIGUSR1) && poll(&pfd, 1, 0) == 0) {
+    puts("test_signalfd ok4");
+  }
+  close(fd);
+}
+
+int main() {
+  test_signalfd();
+  return 0;
+}
@@ -0,0 +1,69 @@
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <sys/resource.h>
+#include <unistd.h>
+
+void test_limit() {
+  struct rlimit old, lim = {2, 2};
+  getrlimit(RLIMIT_SIGPENDING, &old);
+  setrlimit(RLIMIT_SIGPENDING, &lim);
+
+  // Ignored signals are dropped, so they are not charged.
+  signal(SIGRTMIN, SIG_IGN);
+  int sent = 0;
+  for (int i = 0; i < 5; i++) {
+    sent += kill(getpid(), SIGRTMIN) == 0;
+  }
+  if (sent == 5) {
+    puts("test_limit ok");
+  }
+
+  sigset_t set;
+  sigemptyset(&set);
+  sigaddset(&set, SIGRTMIN);
+  sigprocmask(SIG_BLOCK, &set, NULL);
+  signal(SIGRTMIN, SIG_DFL);
+  if (kill(getpid(), SIGRTMIN) == 0 && kill(getpid(), SIGRTMIN) == 0 &&
+      kill(getpid(), SIGRTMIN) == -1 && errno == EAGAIN) {
+    puts("test_limit ok2");
+  }
+
+  // Taking the signals off the queue releases their charge.
+  int sig;
+  sigwait(&set, &sig);
+  sigwait(&set, &sig);
+  if (kill(getpid(), SIGRTMIN) == 0) {
+    puts("test_limit ok3");
+  }
+  sigwait(&set, &sig);
+  sigprocmask(SIG_UNBLOCK, &set, NULL);
+  setrlimit(RLIMIT_SIGPENDING, &old);
+}
+
+static void handler(int signum) { (void)signum; }
+
+void test_sigpending() {
+  sigset_t set, pending;
+  sigemptyset(&set);
+  sigaddset(&set, SIGUSR1);
+  sigprocmask(SIG_BLOCK, &set, NULL);
+  signal(SIGUSR1, handler);
+  raise(SIGUSR1);
+  if (sigpending(&pending) == 0 && sigismember(&pending, SIGUSR1) &&
+      !sigismember(&pending, SIGUSR2)) {
+    puts("test_sigpending ok");
+  }
+  // The signal is delivered once it is unblocked.
+  sigprocmask(SIG_UNBLOCK, &set, NULL);
+  if (sigpending(&pending) == 0 && !sigismember(&pending, SIGUSR1)) {
+    puts("test_sigpending ok2");
+  }
+  signal(SIGUSR1, SIG_DFL);
+}
+
+int main() {
+  test_limit();
+  test_sigpending();
+  return 0;
+}
@@ -0,0 +1,58 @@
+#include <errno.h>
+#include <signal.h>
+#include <stdio.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+static volatile sig_atomic_t received;
+
+static void handler(int signum) { received = signum; }
+
+// Make a pipe whose read end is closed, and return its write end.
+static int broken_pipe() {
+  int fds[2];
+  pipe(fds);
+  close(fds[0]);
+  return fds[1];
+}
+
+void test_sigpipe_ignored() {
+  signal(SIGPIPE, SIG_IGN);
+  int fd = broken_pipe();
+  if (write(fd, "x", 1) == -1 && errno == EPIPE) {
+    puts("test_sigpipe_ignored ok");
+  }
+  close(fd);
+}
+
+void test_sigpipe_handler() {
+  signal(SIGPIPE, handler);
+  int fd = broken_pipe();
+  if (write(fd, "x", 1) == -1 && errno == EPIPE && received == SIGPIPE) {
+    puts("test_sigpipe_handler ok");
+  }
+  close(fd);
+}
+
+void test_sigpipe_default() {
+  signal(SIGPIPE, SIG_DFL);
+  int fd = broken_pipe();
+  pid_t pid = fork();
+  if (pid == 0) {
+    write(fd, "x", 1);
+    _exit(0);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFSIGNALED(status) && WTERMSIG(status) == SIGPIPE) {
+    puts("test_sigpipe_default ok");
+  }
+  close(fd);
+}
+
+int main() {
+  test_sigpipe_ignored();
+  test_sigpipe_handler();
+  test_sigpipe_default();
+  return 0;
+}
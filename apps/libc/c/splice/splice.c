@@ -0,0 +1,62 @@
+#define _GNU_SOURCE
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+#define PATH "splice_file"
+
+// Read what is left in the pipe without blocking.
+static int drain(int fd, char *buf, int len) {
+  fcntl(fd, F_SETFL, O_NONBLOCK);
+  int n = read(fd, buf, len);
+  fcntl(fd, F_SETFL, 0);
+  return n;
+}
+
+void test_splice() {
+  int fds[2];
+  pipe(fds);
+  int fd = open(PATH, O_CREAT | O_TRUNC | O_RDWR, 0644);
+  off_t off = 0;
+  char buf[16] = {0};
+
+  // Nothing is moved for a zero length.
+  write(fds[1], "hello", 5);
+  if (splice(fds[0], NULL, fd, &off, 0, 0) == 0 && off == 0 &&
+      drain(fds[0], buf, sizeof(buf)) == 5 && memcmp(buf, "hello", 5) == 0) {
+    puts("test_splice ok");
+  }
+
+  // Only the bytes written to the file leave the pipe.
+  write(fds[1], "hello", 5);
+  memset(buf, 0, sizeof(buf));
+  if (splice(fds[0], NULL, fd, &off, 3, 0) == 3 && off == 3 &&
+      pread(fd, buf, sizeof(buf), 0) == 3 && memcmp(buf, "hel", 3) == 0 &&
+      drain(fds[0], buf, sizeof(buf)) == 2 && memcmp(buf, "lo", 2) == 0) {
+    puts("test_splice ok2");
+  }
+  close(fd);
+
+  // A failed write leaves the data in the pipe: O_DIRECT needs aligned
+  // lengths, which five bytes are not.
+  fd = open(PATH, O_WRONLY | O_DIRECT);
+  write(fds[1], "hello", 5);
+  off = 0;
+  memset(buf, 0, sizeof(buf));
+  if (fd >= 0 && splice(fds[0], NULL, fd, &off, 5, 0) == -1 &&
+      errno == EINVAL && off == 0 && drain(fds[0], buf, sizeof(buf)) == 5 &&
+      memcmp(buf, "hello", 5) == 0) {
+    puts("test_splice ok3");
+  }
+  close(fd);
+  close(fds[0]);
+  close(fds[1]);
+  unlink(PATH);
+}
+
+int main() {
+  test_splice();
+  return 0;
+}
@@ -0,0 +1,29 @@
+#include <fcntl.h>
+#include <stdio.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+#define PATH "/tmp/st_blocks_test"
+
+void test_st_blocks() {
+  int fd = open(PATH, O_CREAT | O_TRUNC | O_RDWR, 0644);
+  struct stat st, lst;
+  if (fstat(fd, &st) == 0 && st.st_size == 0 && st.st_blocks == 0 &&
+      lstat(PATH, &lst) == 0 && lst.st_blocks == 0) {
+    puts("test_st_blocks ok");
+  }
+
+  // A single byte takes a block, of which st_blksize is a multiple.
+  write(fd, "x", 1);
+  if (fstat(fd, &st) == 0 && st.st_blocks > 0 && st.st_blksize >= 512 &&
+      st.st_blksize % 512 == 0) {
+    puts("test_st_blocks ok2");
+  }
+  close(fd);
+  unlink(PATH);
+}
+
+int main() {
+  test_st_blocks();
+  return 0;
+}
@@ -0,0 +1,85 @@
+#include <signal.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/resource.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+#define SMALL_STACK (256 * 1024)
+
+static char *self;
+
+// Use about `pages` pages of stack.
+static int use_stack(int pages) {
+  volatile char buf[4096];
+  buf[0] = buf[sizeof(buf) - 1] = 1;
+  if (pages > 1) {
+    return use_stack(pages - 1) + buf[0];
+  }
+  return buf[sizeof(buf) - 1];
+}
+
+static int wait_exit(pid_t pid) {
+  int status;
+  waitpid(pid, &status, 0);
+  return WIFEXITED(status) ? WEXITSTATUS(status) : -1;
+}
+
+// The new image gets a stack of the soft RLIMIT_STACK set before exec.
+void test_exec_stack() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    struct rlimit rl = {.rlim_cur = SMALL_STACK, .rlim_max = RLIM_INFINITY};
+    setrlimit(RLIMIT_STACK, &rl);
+    char *argv[] = {self, "check", NULL};
+    execv(self, argv);
+    _exit(2);
+  }
+  if (wait_exit(pid) == 0) {
+    puts("test_exec_stack ok");
+  }
+}
+
+static int check_stack() {
+  struct rlimit rl;
+  if (getrlimit(RLIMIT_STACK, &rl) != 0 || rl.rlim_cur != SMALL_STACK) {
+    return 1;
+  }
+  return use_stack(SMALL_STACK / 4096 / 2) > 0 ? 0 : 1;
+}
+
+// A stack overflow runs into the guard page and faults, instead of writing
+// over whatever is mapped below the stack.
+void test_stack_overflow() {
+  pid_t pid = fork();
+  if (pid == 0) {
+    struct rlimit rl = {.rlim_cur = SMALL_STACK, .rlim_max = RLIM_INFINITY};
+    setrlimit(RLIMIT_STACK, &rl);
+    use_stack(2 * SMALL_STACK / 4096);
+    _exit(0);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  if (WIFSIGNALED(status) && WTERMSIG(status) == SIGSEGV) {
+    puts("test_stack_overflow ok");
+  }
+}
+
+// Deep recursion grows the stack far past the part mapped at exec, within
+// the default RLIMIT_STACK of 8 MiB.
+void test_stack_grow() {
+  if (use_stack(1024) == 1024) {
+    puts("test_stack_grow ok");
+  }
+}
+
+int main(int argc, char **argv) {
+  self = argv[0];
+  if (argc > 1 && strcmp(argv[1], "check") == 0) {
+    return check_stack();
+  }
+  test_exec_stack();
+  test_stack_overflow();
+  test_stack_grow();
+  return 0;
+}
@@ -0,0 +1,59 @@
+#include <signal.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+// A function that only returns.
+#if defined(__x86_64__)
+static const unsigned char ret_code[] = {0xc3};
+#elif defined(__aarch64__)
+static const unsigned char ret_code[] = {0xc0, 0x03, 0x5f, 0xd6};
+#elif defined(__riscv)
+static const unsigned char ret_code[] = {0x67, 0x80, 0x00, 0x00};
+#elif defined(__loongarch__)
+static const unsigned char ret_code[] = {0x20, 0x00, 0x00, 0x4c};
+#endif
+
+// Call a copy of `ret_code` on the stack, after making its page executable
+// if `make_exec` is set, and return how the process ended.
+static int call_on_stack(int make_exec) {
+  pid_t pid = fork();
+  if (pid == 0) {
+    unsigned char code[64] __attribute__((aligned(16)));
+    memcpy(code, ret_code, sizeof(ret_code));
+    __builtin___clear_cache((char *)code, (char *)code + sizeof(ret_code));
+    if (make_exec) {
+      long page = sysconf(_SC_PAGESIZE);
+      void *start = (void *)((uintptr_t)code & ~(page - 1));
+      if (mprotect(start, page, PROT_READ | PROT_WRITE | PROT_EXEC) != 0) {
+        _exit(1);
+      }
+    }
+    ((void (*)(void))code)();
+    _exit(0);
+  }
+  int status;
+  waitpid(pid, &status, 0);
+  return status;
+}
+
+// Programs built with a non-executable PT_GNU_STACK cannot run code on the
+// stack.
+void test_stack_exec() {
+  int status = call_on_stack(0);
+  if (WIFSIGNALED(status) && WTERMSIG(status) == SIGSEGV) {
+    puts("test_stack_exec ok");
+  }
+  status = call_on_stack(1);
+  if (WIFEXITED(status) && WEXITSTATUS(status) == 0) {
+    puts("test_stack_exec ok2");
+  }
+}
+
+int main() {
+  test_stack_exec();
+  return 0;
+}
@@ -0,0 +1,32 @@
+#include <linux/magic.h>
+#include <stdio.h>
+#include <sys/vfs.h>
+#include <unistd.h>
+
+void test_statfs() {
+  struct statfs st;
+  // /tmp is the ramfs, which is the tmpfs of Linux.
+  if (statfs("/tmp", &st) == 0 && st.f_type == TMPFS_MAGIC) {
+    puts("test_statfs ok");
+  }
+  if (statfs("/proc", &st) == 0 && st.f_type == PROC_SUPER_MAGIC) {
+    puts("test_statfs ok2");
+  }
+}
+
+void test_fstatfs() {
+  int fds[2];
+  pipe(fds);
+  struct statfs st;
+  if (fstatfs(fds[0], &st) == 0 && st.f_type == PIPEFS_MAGIC) {
+    puts("test_fstatfs ok");
+  }
+  close(fds[0]);
+  close(fds[1]);
+}
+
+int main() {
+  test_statfs();
+  test_fstatfs();
+  return 0;
+}
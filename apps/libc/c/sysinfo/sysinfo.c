@@ -0,0 +1,24 @@
+#include <stdio.h>
+#include <sys/sysinfo.h>
+#include <time.h>
+
+void test_sysinfo() {
+  struct sysinfo info;
+  if (sysinfo(&info) == 0 && info.mem_unit >= 1 && info.totalram > 0 &&
+      info.freeram > 0 && info.freeram <= info.totalram && info.procs >= 1) {
+    puts("test_sysinfo ok");
+  }
+
+  // The uptime is the monotonic clock in seconds.
+  struct timespec ts;
+  clock_gettime(CLOCK_MONOTONIC, &ts);
+  long diff = info.uptime - ts.tv_sec;
+  if (diff >= -1 && diff <= 1) {
+    puts("test_sysinfo ok2");
+  }
+}
+
+int main() {
+  test_sysinfo();
+  return 0;
+}
@@ -0,0 +1,110 @@
+#include <errno.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <sys/epoll.h>
+#include <sys/eventfd.h>
+#include <sys/timerfd.h>
+#include <time.h>
+#include <unistd.h>
+
+static long elapsed_ms(struct timespec *start) {
+  struct timespec now;
+  clock_gettime(CLOCK_MONOTONIC, &now);
+  return (now.tv_sec - start->tv_sec) * 1000 +
+         (now.tv_nsec - start->tv_nsec) / 1000000;
+}
+
+void test_read() {
+  int tfd = timerfd_create(CLOCK_MONOTONIC, 0);
+  struct itimerspec its = {.it_value = {.tv_nsec = 20 * 1000000}};
+  struct timespec start;
+  clock_gettime(CLOCK_MONOTONIC, &start);
+  timerfd_settime(tfd, 0, &its, NULL);
+  uint64_t count = 0;
+  if (read(tfd, &count, sizeof(count)) == sizeof(count) && count == 1 &&
+      elapsed_ms(&start) >= 20) {
+    puts("test_read ok");
+  }
+  // A one-shot timer is disarmed once it expires.
+  struct itimerspec curr;
+  timerfd_gettime(tfd, &curr);
+  if (curr.it_value.tv_sec == 0 && curr.it_value.tv_nsec == 0) {
+    puts("test_read ok2");
+  }
+  close(tfd);
+
+  tfd = timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK);
+  if (read(tfd, &count, sizeof(count)) == -1 && errno == EAGAIN) {
+    puts("test_read ok3");
+  }
+  close(tfd);
+}
+
+void test_epoll() {
+  int tfd = timerfd_create(CLOCK_MONOTONIC, TFD_NONBLOCK);
+  int efd = eventfd(0, EFD_NONBLOCK);
+  int epfd = epoll_create1(0);
+  struct epoll_event ev = {.events = EPOLLIN, .data.fd = tfd};
+  epoll_ctl(epfd, EPOLL_CTL_ADD, tfd, &ev);
+  ev.data.fd = efd;
+  epoll_ctl(epfd, EPOLL_CTL_ADD, efd, &ev);
+
+  uint64_t one = 1;
+  write(efd, &one, sizeof(one));
+  struct epoll_event out[2];
+  if (epoll_wait(epfd, out, 2, 0) == 1 && out[0].data.fd == efd) {
+    puts("test_epoll ok");
+  }
+  uint64_t count;
+  read(efd, &count, sizeof(count));
+
+  // A periodic timer wakes the waiter up every interval.
+  struct itimerspec its = {.it_value = {.tv_nsec = 10 * 1000000},
+                           .it_interval = {.tv_nsec = 10 * 1000000}};
+  timerfd_settime(tfd, 0, &its, NULL);
+  int expirations = 0;
+  for (int i = 0; i < 3; i++) {
+    if (epoll_wait(epfd, out, 2, 1000) == 1 && out[0].data.fd == tfd &&
+        read(tfd, &count, sizeof(count)) == sizeof(count)) {
+      expirations += count;
+    }
+  }
+  if (expirations >= 3) {
+    puts("test_epoll ok2");
+  }
+
+  struct itimerspec old, off = {0};
+  timerfd_settime(tfd, 0, &off, &old);
+  if (old.it_interval.tv_nsec == 10 * 1000000 &&
+      epoll_wait(epfd, out, 2, 30) == 0) {
+    puts("test_epoll ok3");
+  }
+
+  // The wait ends when the timer expires, long before its timeout.
+  struct timespec start;
+  clock_gettime(CLOCK_MONOTONIC, &start);
+  struct itimerspec once = {.it_value = {.tv_nsec = 20 * 1000000}};
+  timerfd_settime(tfd, 0, &once, NULL);
+  if (epoll_wait(epfd, out, 2, 5000) == 1 && out[0].data.fd == tfd &&
+      elapsed_ms(&start) < 1000) {
+    puts("test_epoll ok4");
+  }
+  read(tfd, &count, sizeof(count));
+
+  // Both are reported when they are ready together.
+  write(efd, &one, sizeof(one));
+  timerfd_settime(tfd, 0, &once, NULL);
+  usleep(30 * 1000);
+  if (epoll_wait(epfd, out, 2, 0) == 2) {
+    puts("test_epoll ok5");
+  }
+  close(epfd);
+  close(efd);
+  close(tfd);
+}
+
+int main() {
+  test_read();
+  test_epoll();
+  return 0;
+}
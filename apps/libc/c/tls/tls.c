@@ -0,0 +1,84 @@
+#include <pthread.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+static __thread int counter = 1;
+static __thread int tdata_var = 42;
+static __thread int tbss_var[16];
+static void *thread_tp;
+static int thread_counter;
+
+#ifdef __aarch64__
+// aarch64 has no syscall to set the thread pointer: user space writes
+// TPIDR_EL0 itself, and the kernel must keep it across traps.
+static uintptr_t read_tp() {
+  uintptr_t tp;
+  __asm__ volatile("mrs %0, tpidr_el0" : "=r"(tp));
+  return tp;
+}
+
+static void write_tp(uintptr_t tp) {
+  __asm__ volatile("msr tpidr_el0, %0" ::"r"(tp));
+}
+#endif
+
+static void *worker(void *arg) {
+  thread_tp = __builtin_thread_pointer();
+  counter += 10;
+  thread_counter = counter;
+  return NULL;
+}
+
+// Each thread, set up with CLONE_SETTLS, has its own thread pointer and
+// copy of a __thread variable.
+void test_tls() {
+  counter = 2;
+  pthread_t thread;
+  pthread_create(&thread, NULL, worker, NULL);
+  pthread_join(thread, NULL);
+  if (thread_tp != __builtin_thread_pointer() && thread_counter == 11 &&
+      counter == 2) {
+    puts("test_tls ok");
+  }
+}
+
+// The thread pointer is kept across a syscall.
+void test_tls_syscall() {
+#ifdef __aarch64__
+  uintptr_t tp = read_tp();
+  write_tp(tp ^ 0x10);
+  syscall(SYS_sched_yield);
+  uintptr_t new_tp = read_tp();
+  write_tp(tp);
+  if (new_tp == (tp ^ 0x10)) {
+    puts("test_tls ok2");
+  }
+#else
+  void *tp = __builtin_thread_pointer();
+  syscall(SYS_sched_yield);
+  if (__builtin_thread_pointer() == tp) {
+    puts("test_tls ok2");
+  }
+#endif
+}
+
+// The main thread of a static binary starts with .tdata copied and .tbss
+// zeroed.
+void test_tls_init() {
+  int zeroed = 1;
+  for (int i = 0; i < 16; i++) {
+    zeroed &= tbss_var[i] == 0;
+  }
+  if (tdata_var == 42 && counter == 1 && zeroed) {
+    puts("test_tls_init ok");
+  }
+}
+
+int main() {
+  test_tls_init();
+  test_tls();
+  test_tls_syscall();
+  return 0;
+}
@@ -0,0 +1,40 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+#define PATH "/tmp/truncate_test"
+
+void test_truncate() {
+  int fd = open(PATH, O_CREAT | O_TRUNC | O_RDWR, 0644);
+  write(fd, "0123456789", 10);
+  close(fd);
+
+  struct stat st;
+  if (truncate(PATH, 4) == 0 && stat(PATH, &st) == 0 && st.st_size == 4) {
+    puts("test_truncate ok");
+  }
+
+  // Growing the file fills it with zeros.
+  char buf[8];
+  fd = open(PATH, O_RDONLY);
+  if (truncate(PATH, 8) == 0 && read(fd, buf, sizeof(buf)) == 8 &&
+      memcmp(buf, "0123\0\0\0\0", 8) == 0) {
+    puts("test_truncate ok2");
+  }
+  close(fd);
+
+  if (truncate("/tmp", 0) == -1 && errno == EISDIR &&
+      truncate(PATH, -1) == -1 && errno == EINVAL &&
+      truncate("/tmp/truncate_missing", 0) == -1 && errno == ENOENT) {
+    puts("test_truncate ok3");
+  }
+  unlink(PATH);
+}
+
+int main() {
+  test_truncate();
+  return 0;
+}
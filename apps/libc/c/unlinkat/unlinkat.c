@@ -0,0 +1,39 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+#define DIR "/tmp/unlinkat_dir"
+
+static int fails_with(int ret, int err) { return ret == -1 && errno == err; }
+
+void test_unlinkat() {
+  mkdir(DIR, 0755);
+  close(open(DIR "/file", O_CREAT | O_WRONLY, 0644));
+
+  if (fails_with(unlinkat(AT_FDCWD, DIR "/file", AT_REMOVEDIR), ENOTDIR)) {
+    puts("test_unlinkat ok");
+  }
+  if (fails_with(unlinkat(AT_FDCWD, DIR, 0), EISDIR)) {
+    puts("test_unlinkat ok2");
+  }
+  if (fails_with(unlinkat(AT_FDCWD, DIR, AT_REMOVEDIR), ENOTEMPTY)) {
+    puts("test_unlinkat ok3");
+  }
+  if (fails_with(unlinkat(AT_FDCWD, DIR "/file", 0x1), EINVAL) &&
+      fails_with(unlinkat(AT_FDCWD, DIR "/missing", 0), ENOENT)) {
+    puts("test_unlinkat ok4");
+  }
+
+  // The failed calls left both in place.
+  if (unlinkat(AT_FDCWD, DIR "/file", 0) == 0 &&
+      unlinkat(AT_FDCWD, DIR, AT_REMOVEDIR) == 0 && access(DIR, F_OK) == -1) {
+    puts("test_unlinkat ok5");
+  }
+}
+
+int main() {
+  test_unlinkat();
+  return 0;
+}
@@ -0,0 +1,447 @@
+//! Copy-on-write sharing of anonymous memory between forked processes.
+//!
+//! On fork, the 4K pages of the areas that `axmm` allocates are mapped into
+//! the child instead of being copied. They stay in their areas, only their
+//! page table entries are changed in place.
+//!
+//! A frame mapped more than once is owned here rather than by `axmm`. It is
+//! reference counted, and freed with its last mapping. The mappings of such
+//! frames are cleared before `axmm` unmaps their pages, so that it does not
+//! free them too.
+//!
+//! Private pages are mapped without `WRITE` once they are shared, although
+//! their area has it, and the first process to write one gets a copy of its
+//! frame. Huge pages stay with `axmm`, which copies them on fork.
+//!
+//! The frames of shared memory segments that are populated on demand are
+//! mapped the same way, in the areas reserved for the segments, but they are
+//! freed by their segment.
+//!
+//! The address spaces must tell this module about the pages they unmap with
+//! [`release`], and change the protection of their pages with [`protect`].
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use axalloc::global_allocator;
+use axerrno::{AxError, AxResult};
+use axhal::{
+    mem::{PAGE_SIZE_4K, phys_to_virt, virt_to_phys},
+    paging::{MappingFlags, PageTable},
+};
+use axmm::{AddrSpace, Backend};
+use memory_addr::{MemoryAddr, PhysAddr, VirtAddr};
+use page_table_multiarch::{GenericPTE, PageTable64, PagingHandler, PagingMetaData};
+use spin::Mutex;
+
+use crate::task::on_each_cpu;
+
+/// The layout of a page table type.
+trait Layout {
+    type Entry: GenericPTE;
+    const LEVELS: usize;
+}
+
+impl<M: PagingMetaData, PTE: GenericPTE, H: PagingHandler> Layout for PageTable64<M, PTE, H> {
+    type Entry = PTE;
+    const LEVELS: usize = M::LEVELS;
+}
+
+/// A page table entry of the user address spaces.
+type Entry = <PageTable as Layout>::Entry;
+
+/// The number of entries in a page table.
+const ENTRY_COUNT: usize = 512;
+
+/// A frame that `axmm` does not own, since it was mapped by more than one
+/// page or belongs to a shared memory segment.
+struct Frame {
+    /// The number of pages mapping it.
+    maps: usize,
+    /// Whether it is written through all its pages, as those of `MAP_SHARED`
+    /// mappings are. Otherwise it is copied on write.
+    shared: bool,
+    /// Whether it belongs to a shared memory segment, which frees it.
+    segment: bool,
+}
+
+static FRAMES: Mutex<BTreeMap<PhysAddr, Frame>> = Mutex::new(BTreeMap::new());
+
+fn free_frame(frame: PhysAddr) {
+    global_allocator().dealloc_pages(phys_to_virt(frame).as_usize(), 1);
+}
+
+/// Copy the frame `src` to a newly allocated one.
+fn copy_frame(src: PhysAddr) -> AxResult<PhysAddr> {
+    let vaddr = global_allocator()
+        .alloc_pages(1, PAGE_SIZE_4K)
+        .map_err(|_| AxError::NoMemory)?;
+    unsafe {
+        core::ptr::copy_nonoverlapping(phys_to_virt(src).as_ptr(), vaddr as *mut u8, PAGE_SIZE_4K)
+    };
+    Ok(virt_to_phys(vaddr.into()))
+}
+
+/// Flush the TLB of the current CPU and of those in `cpus`.
+fn flush_tlb_on(cpus: usize) {
+    on_each_cpu(cpus | (1 << axhal::cpu::this_cpu_id()), || {
+        axhal::arch::flush_tlb(None)
+    });
+}
+
+/// Get the entry for `vaddr` in the page table at `table`, of level `level`.
+fn table_entry<'a>(table: PhysAddr, vaddr: VirtAddr, level: usize) -> &'a mut Entry {
+    let index = (vaddr.as_usize() >> (12 + 9 * level)) % ENTRY_COUNT;
+    unsafe { &mut *phys_to_virt(table).as_mut_ptr_of::<Entry>().add(index) }
+}
+
+/// Get the entry of the 4K page at `page` of `aspace`, if the tables down to
+/// it exist.
+fn entry(aspace: &mut AddrSpace, page: VirtAddr) -> Option<&mut Entry> {
+    let mut table = aspace.page_table_root();
+    for level in (1..<PageTable as Layout>::LEVELS).rev() {
+        let entry = table_entry(table, page, level);
+        if !entry.is_present() || entry.is_huge() {
+            return None;
+        }
+        table = entry.paddr();
+    }
+    Some(table_entry(table, page, 0))
+}
+
+/// Get the entry of the 4K page at `page` of `aspace`, allocating the tables
+/// down to it as `axmm` would.
+fn entry_or_alloc(aspace: &mut AddrSpace, page: VirtAddr) -> AxResult<&mut Entry> {
+    let mut table = aspace.page_table_root();
+    for level in (1..<PageTable as Layout>::LEVELS).rev() {
+        let entry = table_entry(table, page, level);
+        if entry.is_unused() {
+            let vaddr = global_allocator()
+                .alloc_pages(1, PAGE_SIZE_4K)
+                .map_err(|_| AxError::NoMemory)?;
+            unsafe { core::ptr::write_bytes(vaddr as *mut u8, 0, PAGE_SIZE_4K) };
+            *entry = Entry::new_table(virt_to_phys(vaddr.into()));
+        } else if !entry.is_present() || entry.is_huge() {
+            return Err(AxError::AlreadyExists);
+        }
+        table = entry.paddr();
+    }
+    Ok(table_entry(table, page, 0))
+}
+
+/// Call `f` with the entries of the 4K pages of `aspace` in `[start, end)`
+/// that map a frame, and the address of their page.
+fn for_each_entry(
+    aspace: &mut AddrSpace,
+    start: VirtAddr,
+    end: VirtAddr,
+    mut f: impl FnMut(VirtAddr, &mut Entry),
+) {
+    fn walk(
+        table: PhysAddr,
+        level: usize,
+        base: usize,
+        range: (usize, usize),
+        f: &mut dyn FnMut(VirtAddr, &mut Entry),
+    ) {
+        let shift = 12 + 9 * level;
+        let entries = unsafe {
+            core::slice::from_raw_parts_mut(
+                phys_to_virt(table).as_mut_ptr_of::<Entry>(),
+                ENTRY_COUNT,
+            )
+        };
+        for (index, entry) in entries.iter_mut().enumerate() {
+            let vaddr = base + (index << shift);
+            if vaddr + (1 << shift) <= range.0 || vaddr >= range.1 {
+                continue;
+            }
+            if level == 0 {
+                if !entry.is_unused() {
+                    f(VirtAddr::from(vaddr), entry);
+                }
+            } else if entry.is_present() && !entry.is_huge() {
+                walk(entry.paddr(), level - 1, vaddr, range, f);
+            }
+        }
+    }
+    let levels = <PageTable as Layout>::LEVELS;
+    let range = (start.as_usize(), end.as_usize());
+    walk(aspace.page_table_root(), levels - 1, 0, range, &mut f);
+}
+
+/// Get the start and end of the areas of `aspace` that `axmm` allocates.
+fn alloc_areas(aspace: &AddrSpace) -> Vec<(VirtAddr, VirtAddr)> {
+    let mut areas = Vec::new();
+    for area in aspace.areas() {
+        if matches!(area.backend(), Backend::Alloc { .. }) {
+            areas.push((area.start(), area.end()));
+        }
+    }
+    areas
+}
+
+/// Get the flags of the area of `aspace` that `vaddr` is in.
+fn area_flags(aspace: &AddrSpace, vaddr: VirtAddr) -> Option<MappingFlags> {
+    for area in aspace.areas() {
+        if area.start() <= vaddr && vaddr < area.end() {
+            return Some(area.flags());
+        }
+    }
+    None
+}
+
+/// Map the page at `page` of `aspace` with the flags of its area, `flags`,
+/// giving it a frame of its own if it is copied on write.
+///
+/// The CPUs in `cpus` may hold translations of `aspace`.
+fn break_cow(aspace: &mut AddrSpace, cpus: usize, page: VirtAddr, flags: MappingFlags) -> AxResult {
+    let mut frames = FRAMES.lock();
+    let entry = entry(aspace, page)
+        .filter(|entry| !entry.is_unused())
+        .ok_or(AxError::BadAddress)?;
+    let frame = entry.paddr();
+    match frames.get_mut(&frame) {
+        Some(owned) if !owned.shared && owned.maps > 1 => {
+            let copy = copy_frame(frame)?;
+            owned.maps -= 1;
+            *entry = Entry::new_page(copy, flags, false);
+            drop(frames);
+            // The other threads may still read the old frame.
+            flush_tlb_on(cpus);
+            return Ok(());
+        }
+        // The other pages mapping the frame are gone, it goes back to `axmm`.
+        Some(owned) if owned.maps == 1 && !owned.segment => {
+            frames.remove(&frame);
+        }
+        _ => {}
+    }
+    entry.set_flags(flags, false);
+    axhal::arch::flush_tlb(Some(page));
+    Ok(())
+}
+
+/// Handle a page fault at `vaddr` in `aspace`, whose translations the CPUs in
+/// `cpus` may hold.
+///
+/// A write to a copy-on-write page gives it its own frame. Other faults are
+/// handled by `axmm`.
+///
+/// Returns whether the fault was resolved.
+pub fn handle_page_fault(
+    aspace: &mut AddrSpace,
+    cpus: usize,
+    vaddr: VirtAddr,
+    access_flags: MappingFlags,
+) -> bool {
+    let page = vaddr.align_down_4k();
+    let flags = entry(aspace, page)
+        .filter(|entry| !entry.is_unused())
+        .map(|entry| entry.flags());
+    let Some(flags) = flags else {
+        return aspace.handle_page_fault(vaddr, access_flags);
+    };
+    if flags.contains(access_flags) {
+        // Another thread got there first.
+        return true;
+    }
+    match area_flags(aspace, page) {
+        Some(area_flags) if area_flags.contains(access_flags) => {
+            break_cow(aspace, cpus, page, area_flags).is_ok()
+        }
+        _ => false,
+    }
+}
+
+/// Clone `aspace` for a forked child, sharing its 4K pages with it.
+///
+/// The pages for which `is_shared` holds stay writable in both address
+/// spaces. The others are mapped read-only, and copied by the first process
+/// that writes them. The TLBs of the CPUs in `cpus`, which may hold
+/// translations of `aspace`, are flushed before returning.
+pub fn fork(
+    aspace: &mut AddrSpace,
+    cpus: usize,
+    is_shared: impl Fn(VirtAddr) -> bool,
+) -> AxResult<AddrSpace> {
+    // The pages are taken out of the page table while it is cloned, so that
+    // `axmm` does not copy them.
+    let mut pages = Vec::new();
+    for (start, end) in alloc_areas(aspace) {
+        for_each_entry(aspace, start, end, |page, entry| {
+            pages.push((page, *entry, is_shared(page)));
+            entry.clear();
+        });
+    }
+    let child = aspace.try_clone().and_then(|mut child| {
+        for &(page, ..) in &pages {
+            entry_or_alloc(&mut child, page)?;
+        }
+        Ok(child)
+    });
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            for &(page, saved, _) in &pages {
+                if let Some(entry) = entry(aspace, page) {
+                    *entry = saved;
+                }
+            }
+            return Err(err);
+        }
+    };
+
+    let mut frames = FRAMES.lock();
+    for (page, mut mapped, shared) in pages {
+        let frame = mapped.paddr();
+        let shared = frames.get(&frame).map_or(shared, |frame| frame.shared);
+        if !shared {
+            mapped.set_flags(mapped.flags() - MappingFlags::WRITE, false);
+        }
+        if let Some(entry) = entry(&mut child, page) {
+            // The areas populated up front have their own frames already.
+            if !entry.is_unused() {
+                free_frame(entry.paddr());
+            }
+            *entry = mapped;
+            frames
+                .entry(frame)
+                .or_insert(Frame {
+                    maps: 1,
+                    shared,
+                    segment: false,
+                })
+                .maps += 1;
+        }
+        if let Some(entry) = entry(aspace, page) {
+            *entry = mapped;
+        }
+    }
+    drop(frames);
+    flush_tlb_on(cpus);
+    Ok(child)
+}
+
+/// Map `frame`, a frame of a shared memory segment, at `page` of `aspace`
+/// with `flags`. The page is in the area reserved for the segment.
+pub fn map_segment_frame(
+    aspace: &mut AddrSpace,
+    page: VirtAddr,
+    frame: PhysAddr,
+    flags: MappingFlags,
+) -> AxResult {
+    let entry = entry_or_alloc(aspace, page)?;
+    if !entry.is_unused() {
+        // Another thread got there first.
+        return Ok(());
+    }
+    *entry = Entry::new_page(frame, flags, false);
+    FRAMES
+        .lock()
+        .entry(frame)
+        .or_insert(Frame {
+            maps: 0,
+            shared: true,
+            segment: true,
+        })
+        .maps += 1;
+    Ok(())
+}
+
+/// Give the copy-on-write pages in `[start, start + size)` of `aspace` their
+/// own frame, before the kernel writes to them. The pages that may not be
+/// written are left alone.
+///
+/// The CPUs in `cpus` may hold translations of `aspace`.
+pub fn unshare(aspace: &mut AddrSpace, cpus: usize, start: VirtAddr, size: usize) -> AxResult {
+    let end = VirtAddr::from(start.as_usize().saturating_add(size));
+    let mut read_only = Vec::new();
+    for_each_entry(aspace, start.align_down_4k(), end, |page, entry| {
+        if !entry.flags().contains(MappingFlags::WRITE) {
+            read_only.push(page);
+        }
+    });
+    for page in read_only {
+        match area_flags(aspace, page) {
+            Some(flags) if flags.contains(MappingFlags::WRITE) => {
+                break_cow(aspace, cpus, page, flags)?
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Change the protection of `[start, start + size)` of `aspace` to `flags`.
+/// The copy-on-write pages stay read-only until they have their own frame.
+pub fn protect(
+    aspace: &mut AddrSpace,
+    start: VirtAddr,
+    size: usize,
+    flags: MappingFlags,
+) -> AxResult {
+    // They are taken out of the page table while `axmm` changes it.
+    let mut cow = Vec::new();
+    {
+        let frames = FRAMES.lock();
+        let end = VirtAddr::from(start.as_usize().saturating_add(size));
+        for_each_entry(aspace, start.align_down_4k(), end, |page, entry| {
+            if frames
+                .get(&entry.paddr())
+                .is_some_and(|frame| !frame.shared)
+            {
+                cow.push((page, *entry));
+                entry.clear();
+            }
+        });
+    }
+    let result = aspace.protect(start, size, flags);
+    for (page, mut saved) in cow {
+        if result.is_ok() {
+            saved.set_flags(flags - MappingFlags::WRITE, false);
+        }
+        if let Some(entry) = entry(aspace, page) {
+            *entry = saved;
+        }
+    }
+    axhal::arch::flush_tlb(None);
+    result
+}
+
+/// Get the flags the copy-on-write page at `vaddr` of `aspace` gets back
+/// with its own frame, or `None` if it is not copied on write.
+pub fn cow_flags(aspace: &AddrSpace, vaddr: VirtAddr) -> Option<MappingFlags> {
+    let (frame, ..) = aspace.page_table().query(vaddr).ok()?;
+    let frame = frame.align_down_4k();
+    if !FRAMES.lock().get(&frame).is_some_and(|frame| !frame.shared) {
+        return None;
+    }
+    area_flags(aspace, vaddr)
+}
+
+/// Drop the mappings of the frames owned here in `[start, start + size)` of
+/// `aspace`, before `axmm` unmaps the range. The frames are freed with their
+/// last mapping.
+pub fn release(aspace: &mut AddrSpace, start: VirtAddr, size: usize) {
+    let mut frames = FRAMES.lock();
+    let end = VirtAddr::from(start.as_usize().saturating_add(size));
+    for_each_entry(aspace, start.align_down_4k(), end, |_, entry| {
+        let frame = entry.paddr();
+        let Some(owned) = frames.get_mut(&frame) else {
+            return;
+        };
+        entry.clear();
+        owned.maps -= 1;
+        if owned.maps == 0 && !frames.remove(&frame).is_some_and(|frame| frame.segment) {
+            free_frame(frame);
+        }
+    });
+}
+
+/// Drop the mappings of all the frames owned here of `aspace`, before its
+/// user mappings are unmapped or it is dropped.
+pub fn release_all(aspace: &mut AddrSpace) {
+    for (start, end) in alloc_areas(aspace) {
+        release(aspace, start, end - start);
+    }
+}
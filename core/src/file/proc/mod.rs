@@ -3,6 +3,9 @@
 use alloc::sync::Arc;
 
 pub mod selfs;
+pub mod stat;
+pub mod sys;
+pub mod system;
 
 /// Initialize the process filesystem by setting up /proc directories.
 pub fn init_procfs() {
@@ -11,4 +14,27 @@ pub fn init_procfs() {
 
     let self_exe = selfs::SelfExe;
     let _ = procfs.add_node("exe", Arc::new(self_exe));
+    let _ = procfs.add_node("status", Arc::new(selfs::SelfStatus));
+    let _ = procfs.add_node("stat", Arc::new(stat::ProcStat::new(None)));
+
+    if let Ok(proc) = axfs::fops::Directory::open_dir("/proc", &opts) {
+        let _ = proc.add_node(
+            "uptime",
+            Arc::new(system::GeneratedFile::new(system::uptime)),
+        );
+        let _ = proc.add_node("stat", Arc::new(system::GeneratedFile::new(system::stat)));
+        let _ = proc.add_node(
+            "cpuinfo",
+            Arc::new(system::GeneratedFile::new(system::cpuinfo)),
+        );
+    }
+
+    let _ = axfs::api::create_dir("/proc/sys");
+    let _ = axfs::api::create_dir("/proc/sys/kernel");
+    if let Ok(kernel) = axfs::fops::Directory::open_dir("/proc/sys/kernel", &opts) {
+        let limits = &crate::shm::SHM_LIMITS;
+        let _ = kernel.add_node("shmmax", Arc::new(sys::SysctlNode::new(&limits.shmmax)));
+        let _ = kernel.add_node("shmall", Arc::new(sys::SysctlNode::new(&limits.shmall)));
+        let _ = kernel.add_node("shmmni", Arc::new(sys::SysctlNode::new(&limits.shmmni)));
+    }
 }
@@ -1,8 +1,10 @@
-//! Implements the node for /proc/self/exe.
+//! Implements the nodes for /proc/self/exe and /proc/self/status.
+use alloc::{format, string::String};
 use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeType, VfsResult};
 use axtask::{TaskExtRef, current};
 
-use crate::file::resolve_symlink_path;
+use super::stat::process_state;
+use crate::{file::resolve_symlink_path, mm::memory_usage};
 
 /// SelfExe 结构体用于表示 /proc/self/exe 的符号链接节点。
 /// 该节点用于获取当前进程的可执行文件路径。
@@ -35,3 +37,59 @@ impl VfsNodeOps for SelfExe {
 
     axfs_vfs::impl_vfs_non_dir_default! {}
 }
+
+/// SelfStatus 结构体用于表示 /proc/self/status 文件节点。
+/// 每次读取时根据当前进程和线程的状态生成内容。
+pub struct SelfStatus;
+
+impl SelfStatus {
+    fn content() -> String {
+        let curr = current();
+        let process = curr.task_ext().thread.process();
+        let process_data = curr.task_ext().process_data();
+
+        let name = curr.task_ext().thread_data().comm();
+        let (state, state_name) = process_state(process, true);
+        let ppid = process.parent().map_or(0, |parent| parent.pid());
+        let usage = memory_usage(&process_data.aspace.lock());
+
+        format!(
+            "Name:\t{}\nState:\t{} ({})\nTgid:\t{}\nPid:\t{}\nPPid:\t{}\n\
+             VmSize:\t{:8} kB\nVmRSS:\t{:8} kB\nThreads:\t{}\n",
+            name,
+            state,
+            state_name,
+            process.pid(),
+            process.pid(),
+            ppid,
+            usage.virt / 1024,
+            usage.resident / 1024,
+            process.threads().len(),
+        )
+    }
+}
+
+/// VfsNodeOps trait 的实现，读取时返回生成的状态文本。
+impl VfsNodeOps for SelfStatus {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            axfs_vfs::VfsNodePerm::from_bits_truncate(0o444),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = Self::content();
+        let src = content
+            .as_bytes()
+            .get(offset as usize..)
+            .unwrap_or_default();
+        let len = buf.len().min(src.len());
+        buf[..len].copy_from_slice(&src[..len]);
+        Ok(len)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
@@ -0,0 +1,122 @@
+//! Implements the node for /proc/[pid]/stat.
+use alloc::{format, string::String, sync::Arc};
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodeType, VfsResult};
+use axprocess::{Pid, Process};
+use axtask::{TaskExtRef, current};
+use linux_raw_sys::general::RLIMIT_RSS;
+
+use crate::{
+    mm::memory_usage,
+    task::{ProcessData, ThreadData, comm_of, exe_name, get_process, get_thread},
+};
+
+/// The unit of the times in the file, `USER_HZ`.
+const CLOCK_TICKS_PER_SEC: usize = 100;
+
+/// The state of a process, as a letter and a description.
+///
+/// Only the calling process is known to be running; the others are reported
+/// as sleeping until they exit.
+pub(super) fn process_state(process: &Process, is_current: bool) -> (char, &'static str) {
+    if process.is_zombie() {
+        ('Z', "zombie")
+    } else if is_current {
+        ('R', "running")
+    } else {
+        ('S', "sleeping")
+    }
+}
+
+/// ProcStat 结构体表示 /proc/[pid]/stat 文件节点。
+/// 每次读取时生成 `ps` 与 `top` 解析的单行状态，未记录的字段为 0。
+pub struct ProcStat {
+    /// The process to describe, or `None` for the current process.
+    pid: Option<Pid>,
+}
+
+impl ProcStat {
+    /// 创建描述进程 `pid` 的节点，`None` 表示当前进程。
+    pub const fn new(pid: Option<Pid>) -> Self {
+        Self { pid }
+    }
+
+    fn content(&self) -> VfsResult<String> {
+        let curr = current();
+        let current_process = curr.task_ext().thread.process();
+        let process: Arc<Process> = match self.pid {
+            None => current_process.clone(),
+            Some(pid) => get_process(pid).map_err(|_| VfsError::NotFound)?,
+        };
+        let data = process.data::<ProcessData>().ok_or(VfsError::NotFound)?;
+        let is_current = Arc::ptr_eq(&process, current_process);
+
+        // The name of the main thread, or of the executable once it exited.
+        let comm = get_thread(process.pid())
+            .ok()
+            .and_then(|thread| Some(thread.data::<ThreadData>()?.comm()))
+            .unwrap_or_else(|| comm_of(exe_name(&data.exe_path.read())));
+        let (state, _) = process_state(&process, is_current);
+        let group = process.group();
+        let usage = memory_usage(&data.aspace.lock());
+        // The times of other tasks cannot be read safely, so only those of
+        // the calling thread are known.
+        let (utime_ns, stime_ns) = if is_current {
+            curr.task_ext().time_stat_output()
+        } else {
+            (0, 0)
+        };
+        let ticks = |ns: usize| (ns / (1_000_000_000 / CLOCK_TICKS_PER_SEC)) as i128;
+
+        // Fields 4 to 52, numbered as in proc(5).
+        let mut fields = [0i128; 49];
+        let mut set = |field: usize, value: i128| fields[field - 4] = value;
+        set(4, process.parent().map_or(0, |parent| parent.pid()) as _);
+        set(5, group.pgid() as _);
+        set(6, group.session().sid() as _);
+        // tpgid: there is no controlling terminal.
+        set(8, -1);
+        set(14, ticks(utime_ns));
+        set(15, ticks(stime_ns));
+        // The priority of the default nice value.
+        set(18, 20);
+        set(20, process.threads().len() as _);
+        set(22, ticks(data.start_time.as_nanos() as _));
+        set(23, usage.virt as _);
+        set(24, (usage.resident / axhal::mem::PAGE_SIZE_4K) as _);
+        set(25, data.rlimits.read().get(RLIMIT_RSS).soft as _);
+        set(38, data.exit_signal.map_or(0, |signo| signo as i128));
+        set(47, data.get_heap_bottom() as _);
+
+        let mut content = format!("{} ({}) {}", process.pid(), comm, state);
+        for value in fields {
+            content += &format!(" {}", value);
+        }
+        content.push('\n');
+        Ok(content)
+    }
+}
+
+/// VfsNodeOps trait 的实现，读取时返回生成的状态行。
+impl VfsNodeOps for ProcStat {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            axfs_vfs::VfsNodePerm::from_bits_truncate(0o444),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = self.content()?;
+        let src = content
+            .as_bytes()
+            .get(offset as usize..)
+            .unwrap_or_default();
+        let len = buf.len().min(src.len());
+        buf[..len].copy_from_slice(&src[..len]);
+        Ok(len)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
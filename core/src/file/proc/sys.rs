@@ -0,0 +1,60 @@
+//! Implements the tunable nodes under /proc/sys.
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::format;
+use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodeOps, VfsNodeType, VfsResult};
+use axtask::{TaskExtRef, current};
+
+/// SysctlNode 结构体表示 /proc/sys 下的一个整数参数文件。
+/// 读取时返回当前值，写入时解析十进制整数并更新该值。
+pub struct SysctlNode {
+    value: &'static AtomicUsize,
+}
+
+impl SysctlNode {
+    /// 创建一个读写 `value` 的节点。
+    pub const fn new(value: &'static AtomicUsize) -> Self {
+        Self { value }
+    }
+}
+
+/// VfsNodeOps trait 的实现，只有 root 可以修改参数。
+impl VfsNodeOps for SysctlNode {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            axfs_vfs::VfsNodePerm::from_bits_truncate(0o644),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = format!("{}\n", self.value.load(Ordering::Relaxed));
+        let src = content
+            .as_bytes()
+            .get(offset as usize..)
+            .unwrap_or_default();
+        let len = buf.len().min(src.len());
+        buf[..len].copy_from_slice(&src[..len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        if !current().task_ext().process_data().cred.read().is_root() {
+            return Err(VfsError::PermissionDenied);
+        }
+        let value = core::str::from_utf8(buf)
+            .ok()
+            .and_then(|s| s.trim().parse::<usize>().ok())
+            .ok_or(VfsError::InvalidInput)?;
+        self.value.store(value, Ordering::Relaxed);
+        Ok(buf.len())
+    }
+
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
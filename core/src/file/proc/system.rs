@@ -0,0 +1,117 @@
+//! Implements the system-wide nodes /proc/uptime, /proc/stat and /proc/cpuinfo.
+use alloc::{format, string::String};
+use axconfig::plat::CPU_NUM;
+use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodeType, VfsResult};
+use axhal::time::{monotonic_time, wall_time};
+
+use crate::task::total_forks;
+
+/// The unit of the times in /proc/stat, `USER_HZ`.
+const CLOCK_TICKS_PER_SEC: u128 = 100;
+
+/// GeneratedFile 结构体表示一个只读文件节点，
+/// 每次读取时调用 `generate` 生成内容。
+pub struct GeneratedFile {
+    generate: fn() -> String,
+}
+
+impl GeneratedFile {
+    /// 创建内容由 `generate` 生成的节点。
+    pub const fn new(generate: fn() -> String) -> Self {
+        Self { generate }
+    }
+}
+
+/// VfsNodeOps trait 的实现，读取时返回生成的内容。
+impl VfsNodeOps for GeneratedFile {
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            axfs_vfs::VfsNodePerm::from_bits_truncate(0o444),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.generate)();
+        let src = content
+            .as_bytes()
+            .get(offset as usize..)
+            .unwrap_or_default();
+        let len = buf.len().min(src.len());
+        buf[..len].copy_from_slice(&src[..len]);
+        Ok(len)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+/// 生成 /proc/uptime 的内容：开机以来的秒数与各 CPU 空闲时间之和。
+///
+/// CPU time is not accounted system-wide, so every CPU shows as idle the
+/// whole time, here and in /proc/stat.
+pub fn uptime() -> String {
+    let uptime = monotonic_time().as_millis() / 10;
+    let idle = uptime * CPU_NUM as u128;
+    format!(
+        "{}.{:02} {}.{:02}\n",
+        uptime / 100,
+        uptime % 100,
+        idle / 100,
+        idle % 100
+    )
+}
+
+/// 生成 /proc/stat 的内容：CPU 时间、开机时刻与创建过的线程数。
+pub fn stat() -> String {
+    let now = monotonic_time();
+    let idle = now.as_millis() * CLOCK_TICKS_PER_SEC / 1000;
+    // user nice system idle iowait irq softirq steal guest guest_nice
+    let cpu_line = |name: String, idle: u128| format!("{} 0 0 0 {} 0 0 0 0 0 0\n", name, idle);
+
+    let mut content = cpu_line("cpu ".into(), idle * CPU_NUM as u128);
+    for cpu in 0..CPU_NUM {
+        content += &cpu_line(format!("cpu{}", cpu), idle);
+    }
+    let boot_time = wall_time().saturating_sub(now);
+    content += &format!(
+        "intr 0\nctxt 0\nbtime {}\nprocesses {}\nprocs_running 1\nprocs_blocked 0\n",
+        boot_time.as_secs(),
+        total_forks()
+    );
+    content
+}
+
+/// 生成 /proc/cpuinfo 的内容：每个在线 CPU 一段。
+pub fn cpuinfo() -> String {
+    let mut content = String::new();
+    for cpu in 0..CPU_NUM {
+        content += &format!("processor\t: {}\n", cpu);
+        #[cfg(target_arch = "x86_64")]
+        {
+            content += "vendor_id\t: unknown\nmodel name\t: x86_64 processor\n";
+            content += &format!(
+                "physical id\t: 0\ncore id\t\t: {}\ncpu cores\t: {}\nflags\t\t: fpu sse sse2\n",
+                cpu, CPU_NUM
+            );
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            content += "model name\t: ARMv8 Processor\nFeatures\t: fp asimd\n";
+        }
+        #[cfg(target_arch = "riscv64")]
+        {
+            content += &format!(
+                "hart\t\t: {}\nmodel name\t: RISC-V processor\nisa\t\t: rv64imafdc\nmmu\t\t: sv39\n",
+                cpu
+            );
+        }
+        #[cfg(target_arch = "loongarch64")]
+        {
+            content += "model name\t: LoongArch processor\nisa\t\t: loongarch64\n";
+        }
+        content.push('\n');
+    }
+    content
+}
@@ -8,9 +8,11 @@
 extern crate axlog;
 extern crate alloc;
 
+pub mod cow;
 pub mod file;
 pub mod futex;
 pub mod mm;
+pub mod resource;
 pub mod shm;
 pub mod task;
 mod time;
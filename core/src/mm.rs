@@ -1,8 +1,8 @@
 //! User address space management.
 
-use core::ffi::CStr;
+use core::{any::Any, ffi::CStr};
 
-use alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+use alloc::{borrow::ToOwned, collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
 use axerrno::{AxError, AxResult};
 use axhal::{
     mem::virt_to_phys,
@@ -10,8 +10,11 @@ use axhal::{
 };
 use axmm::{AddrSpace, kernel_aspace};
 use kernel_elf_parser::{AuxvEntry, ELFParser, app_stack_region};
-use memory_addr::{MemoryAddr, PAGE_SIZE_4K, VirtAddr};
-use xmas_elf::{ElfFile, program::SegmentData};
+use memory_addr::{MemoryAddr, PAGE_SIZE_4K, VirtAddr, VirtAddrRange};
+use xmas_elf::{
+    ElfFile,
+    program::{SegmentData, Type},
+};
 
 /// Creates a new empty user address space.
 pub fn new_user_aspace_empty() -> AxResult<AddrSpace> {
@@ -33,6 +36,132 @@ pub fn copy_from_kernel(aspace: &mut AddrSpace) -> AxResult {
     Ok(())
 }
 
+/// Memory usage of a user address space, in bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryUsage {
+    /// Total size of all mapped areas (`VmSize`).
+    pub virt: usize,
+    /// Size of the pages actually backed by physical memory (`VmRSS`).
+    pub resident: usize,
+}
+
+/// Physical memory of the system, in bytes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemMemory {
+    /// All the memory managed by the global allocator.
+    pub total: usize,
+    /// The part of it that is not allocated.
+    pub free: usize,
+}
+
+/// Computes the physical memory of the system from the pages of the global
+/// allocator, which the kernel heap is also carved from.
+pub fn system_memory() -> SystemMemory {
+    let allocator = axalloc::global_allocator();
+    let free = allocator.available_pages();
+    SystemMemory {
+        total: (allocator.used_pages() + free) * PAGE_SIZE_4K,
+        free: free * PAGE_SIZE_4K,
+    }
+}
+
+/// Computes the memory usage of the address space.
+///
+/// Every mapped area counts towards the virtual size, and the page table is
+/// queried to tell which of its pages are populated.
+pub fn memory_usage(aspace: &AddrSpace) -> MemoryUsage {
+    let mut usage = MemoryUsage::default();
+    for area in aspace.areas() {
+        let (start, end) = (area.start(), area.end());
+        usage.virt += end - start;
+        let mut vaddr = start;
+        while vaddr < end {
+            match aspace.page_table().query(vaddr) {
+                Ok((_, _, page_size)) => {
+                    let next = vaddr.align_down(page_size) + page_size as usize;
+                    usage.resident += next.min(end) - vaddr;
+                    vaddr = next;
+                }
+                Err(_) => vaddr += PAGE_SIZE_4K,
+            }
+        }
+    }
+    usage
+}
+
+/// What backs a mapping created by `mmap`, other than private anonymous
+/// memory.
+#[derive(Clone)]
+pub struct MmapBacking {
+    /// The size of the mapping.
+    pub size: usize,
+    /// Whether changes are shared with other mappings (`MAP_SHARED`).
+    pub shared: bool,
+    /// The mapped file and the offset of the mapping in it.
+    pub file: Option<(Arc<dyn Any + Send + Sync>, u64)>,
+}
+
+/// The shared and file-backed mappings of an address space, keyed by start
+/// address.
+#[derive(Clone, Default)]
+pub struct MmapBackings(BTreeMap<VirtAddr, MmapBacking>);
+
+impl MmapBackings {
+    /// Record the mapping at `start`, replacing those in its range.
+    pub fn insert(&mut self, start: VirtAddr, backing: MmapBacking) {
+        self.remove(start, backing.size);
+        self.0.insert(start, backing);
+    }
+
+    /// Forget the mappings in `[start, start + size)`. The parts of a mapping
+    /// outside of the range are kept.
+    pub fn remove(&mut self, start: VirtAddr, size: usize) {
+        let end = start + size;
+        let overlapping = self
+            .overlapping(start, size)
+            .map(|(addr, _)| addr)
+            .collect::<Vec<_>>();
+        for addr in overlapping {
+            let backing = self.0.remove(&addr).unwrap();
+            if addr < start {
+                let head = MmapBacking {
+                    size: start - addr,
+                    ..backing.clone()
+                };
+                self.0.insert(addr, head);
+            }
+            if addr + backing.size > end {
+                let tail = MmapBacking {
+                    size: addr + backing.size - end,
+                    shared: backing.shared,
+                    file: backing
+                        .file
+                        .map(|(file, offset)| (file, offset + (end - addr) as u64)),
+                };
+                self.0.insert(end, tail);
+            }
+        }
+    }
+
+    /// Iterate over the mappings overlapping `[start, start + size)`, with
+    /// their start addresses.
+    pub fn overlapping(
+        &self,
+        start: VirtAddr,
+        size: usize,
+    ) -> impl Iterator<Item = (VirtAddr, &MmapBacking)> {
+        self.0
+            .range(..start + size)
+            .filter(move |(addr, backing)| **addr + backing.size > start)
+            .map(|(addr, backing)| (*addr, backing))
+    }
+
+    /// Forget all the mappings.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
 /// Map the signal trampoline to the user address space.
 pub fn map_trampoline(aspace: &mut AddrSpace) -> AxResult {
     let signal_trampoline_paddr = virt_to_phys(axsignal::arch::signal_trampoline_address().into());
@@ -97,22 +226,116 @@ fn map_elf(uspace: &mut AddrSpace, elf: &ElfFile) -> AxResult<(VirtAddr, [AuxvEn
     ))
 }
 
+/// The space reserved for the thread control block next to the initial TLS
+/// block.
+const TLS_TCB_SIZE: usize = 64;
+
+/// Map the initial TLS block of the `PT_TLS` segment of `elf`, with `.tdata`
+/// copied and `.tbss` zeroed.
+///
+/// Returns the thread pointer for the TLS layout of the architecture, or
+/// `None` if there is no `PT_TLS` segment.
+fn map_tls(uspace: &mut AddrSpace, elf: &ElfFile) -> AxResult<Option<VirtAddr>> {
+    let Some(ph) = elf.program_iter().find(|ph| ph.get_type() == Ok(Type::Tls)) else {
+        return Ok(None);
+    };
+    let file_size = ph.file_size() as usize;
+    let mem_size = ph.mem_size() as usize;
+    let align = (ph.align() as usize)
+        .max(size_of::<usize>())
+        .next_power_of_two();
+    let offset = ph.offset() as usize;
+    let tdata = elf
+        .input
+        .get(offset..offset + file_size)
+        .filter(|_| file_size <= mem_size)
+        .ok_or(AxError::InvalidData)?;
+
+    // The offsets of the TLS block and of the thread pointer in the area.
+    let (block_offset, tp_offset, area_size) = if cfg!(target_arch = "x86_64") {
+        // Variant II: the block ends at the thread pointer, which points to
+        // the TCB.
+        let tp = mem_size.next_multiple_of(align);
+        (tp - mem_size, tp, tp + TLS_TCB_SIZE)
+    } else if cfg!(target_arch = "aarch64") {
+        // Variant I: the thread pointer points to a 16-byte TCB followed by
+        // the block.
+        let block = 16usize.next_multiple_of(align);
+        (block, 0, block + mem_size)
+    } else {
+        // RISC-V and LoongArch: the block starts at the thread pointer, the
+        // TCB is below it.
+        let tp = TLS_TCB_SIZE.next_multiple_of(align);
+        (tp, tp, tp + mem_size)
+    };
+
+    let area_size = area_size.align_up_4k();
+    let range = VirtAddrRange::new(uspace.base(), uspace.end());
+    let area = uspace
+        .find_free_area(uspace.base(), area_size, range, PageSize::Size4K)
+        .ok_or(AxError::NoMemory)?;
+    uspace.map_alloc(
+        area,
+        area_size,
+        MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
+        true,
+        PageSize::Size4K,
+    )?;
+    uspace.write(area + block_offset, PageSize::Size4K, tdata)?;
+
+    let tp = area + tp_offset;
+    if cfg!(target_arch = "x86_64") {
+        // The first word of the TCB points to itself.
+        uspace.write(tp, PageSize::Size4K, &tp.as_usize().to_ne_bytes())?;
+    }
+    Ok(Some(tp))
+}
+
+/// `PT_GNU_STACK`, the program header holding the permissions of the stack.
+const PT_GNU_STACK: u32 = 0x6474_e551;
+
+/// The flags to map the user stack with, executable only if the `PT_GNU_STACK`
+/// header of `elf` asks for it.
+fn stack_mapping_flags(elf: &ElfFile) -> MappingFlags {
+    let executable = elf.program_iter().any(|ph| {
+        matches!(ph.get_type(), Ok(Type::OsSpecific(PT_GNU_STACK))) && ph.flags().is_execute()
+    });
+    let mut flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    if executable {
+        flags |= MappingFlags::EXECUTE;
+    }
+    flags
+}
+
+/// The largest user stack, used when `RLIMIT_STACK` is above it or
+/// unlimited.
+const MAX_USER_STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// The size of the guard area below the user stack.
+const USER_STACK_GUARD_SIZE: usize = PAGE_SIZE_4K;
+
 /// Load the user app to the user address space.
 ///
 /// # Arguments
 /// - `uspace`: The address space of the user app.
 /// - `args`: The arguments of the user app. The first argument is the path of the user app.
 /// - `envs`: The environment variables of the user app.
+/// - `stack_size`: The size the user stack may grow to, usually the soft
+///   `RLIMIT_STACK`. Defaults to `USER_STACK_SIZE` in the platform config if
+///   `None`.
 ///
 /// # Returns
 /// - The entry point of the user app.
 /// - The stack pointer of the user app.
+/// - The bottom of the user stack, to be passed to [`grow_user_stack`].
+/// - The initial thread pointer, if the app has a `PT_TLS` segment.
 pub fn load_user_app(
     uspace: &mut AddrSpace,
     path: &str,
     args: &[String],
     envs: &[String],
-) -> AxResult<(VirtAddr, VirtAddr)> {
+    stack_size: Option<usize>,
+) -> AxResult<(VirtAddr, VirtAddr, VirtAddr, Option<VirtAddr>)> {
     if args.is_empty() {
         return Err(AxError::InvalidInput);
     }
@@ -133,7 +356,7 @@ pub fn load_user_app(
                 .collect();
 
             if !new_args.is_empty() {
-                return load_user_app(uspace, &new_args[0], &new_args, envs);
+                return load_user_app(uspace, &new_args[0], &new_args, envs, stack_size);
             }
         }
     }
@@ -141,7 +364,7 @@ pub fn load_user_app(
 
     if let Some(interp) = elf
         .program_iter()
-        .find(|ph| ph.get_type() == Ok(xmas_elf::program::Type::Interp))
+        .find(|ph| ph.get_type() == Ok(Type::Interp))
     {
         let interp = match interp.get_data(&elf) {
             Ok(SegmentData::Undefined(data)) => data,
@@ -168,33 +391,66 @@ pub fn load_user_app(
         // Set the first argument to the path of the user app.
         let mut new_args = vec![interp_path];
         new_args.extend_from_slice(args);
-        return load_user_app(uspace, &new_args[0], &new_args, envs);
+        return load_user_app(uspace, &new_args[0], &new_args, envs, stack_size);
     }
 
     let (entry, mut auxv) = map_elf(uspace, &elf)?;
+    let ustack_flags = stack_mapping_flags(&elf);
     // The user stack is divided into two parts:
     // `ustack_start` -> `ustack_pointer`: It is the stack space that users actually read and write.
     // `ustack_pointer` -> `ustack_end`: It is the space that contains the arguments, environment variables and auxv passed to the app.
     //  When the app starts running, the stack pointer points to `ustack_pointer`.
     let ustack_end = VirtAddr::from_usize(axconfig::plat::USER_STACK_TOP);
-    let ustack_size = axconfig::plat::USER_STACK_SIZE;
+    let ustack_limit = stack_limit(stack_size);
+    let stack_data = app_stack_region(
+        args,
+        envs,
+        &mut auxv,
+        ustack_end - ustack_limit,
+        ustack_limit,
+    );
+    // Leave at least one page for the app to run on.
+    if stack_data.len() + PAGE_SIZE_4K > ustack_limit {
+        return Err(AxError::NoMemory);
+    }
+    // Only `USER_STACK_SIZE` is mapped at first, the rest of the limit is
+    // mapped by `grow_user_stack` when the app runs into the guard page.
+    let ustack_size = axconfig::plat::USER_STACK_SIZE
+        .min(ustack_limit)
+        .max((stack_data.len() + PAGE_SIZE_4K).align_up_4k());
     let ustack_start = ustack_end - ustack_size;
     debug!(
         "Mapping user stack: {:#x?} -> {:#x?}",
         ustack_start, ustack_end
     );
 
-    let stack_data = app_stack_region(args, envs, &mut auxv, ustack_start, ustack_size);
+    // The stack is populated on demand, only the pages holding the arguments
+    // are allocated up front.
     uspace.map_alloc(
         ustack_start,
         ustack_size,
-        MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
-        true,
+        ustack_flags,
+        false,
         PageSize::Size4K,
     )?;
 
     let heap_start = VirtAddr::from_usize(axconfig::plat::USER_HEAP_BASE);
     let heap_size = axconfig::plat::USER_HEAP_SIZE;
+
+    // Reserve an inaccessible guard page below the stack, so that an overflow
+    // faults instead of running into whatever is mapped beneath it.
+    let guard_start = ustack_start - USER_STACK_GUARD_SIZE;
+    if guard_start < heap_start + heap_size {
+        return Err(AxError::NoMemory);
+    }
+    uspace.map_alloc(
+        guard_start,
+        USER_STACK_GUARD_SIZE,
+        MappingFlags::empty(),
+        false,
+        PageSize::Size4K,
+    )?;
+
     uspace.map_alloc(
         heap_start,
         heap_size,
@@ -205,9 +461,92 @@ pub fn load_user_app(
 
     let user_sp = ustack_end - stack_data.len();
 
+    let populated_start = user_sp.align_down_4k();
+    uspace.populate_area(
+        populated_start,
+        ustack_end - populated_start,
+        MappingFlags::READ | MappingFlags::WRITE,
+    )?;
     uspace.write(user_sp, PageSize::Size4K, stack_data.as_slice())?;
 
-    Ok((entry, user_sp))
+    let thread_pointer = map_tls(uspace, &elf)?;
+
+    Ok((entry, user_sp, ustack_start, thread_pointer))
+}
+
+/// The size the user stack may grow to, from the soft `RLIMIT_STACK`.
+fn stack_limit(stack_size: Option<usize>) -> usize {
+    stack_size
+        .map_or(axconfig::plat::USER_STACK_SIZE, |size| {
+            size.min(MAX_USER_STACK_SIZE)
+        })
+        .align_down_4k()
+}
+
+/// Grow the user stack whose lowest mapped address is `stack_bottom` down to
+/// the page containing `vaddr`, moving the guard page below it.
+///
+/// Only faults below the stack and within `stack_size` (the soft
+/// `RLIMIT_STACK`) grow the stack, and only if the new pages are not used by
+/// another mapping. Other faults are wild accesses.
+///
+/// Returns the new bottom of the stack, or `None` if it was not grown.
+pub fn grow_user_stack(
+    uspace: &mut AddrSpace,
+    stack_bottom: VirtAddr,
+    vaddr: VirtAddr,
+    stack_size: Option<usize>,
+) -> Option<VirtAddr> {
+    let ustack_end = VirtAddr::from_usize(axconfig::plat::USER_STACK_TOP);
+    let new_bottom = vaddr.align_down_4k();
+    if vaddr >= stack_bottom || new_bottom < ustack_end - stack_limit(stack_size) {
+        return None;
+    }
+
+    let guard_start = stack_bottom - USER_STACK_GUARD_SIZE;
+    let new_guard_start = new_bottom - USER_STACK_GUARD_SIZE;
+    if new_guard_start < guard_start {
+        let range = VirtAddrRange::new(uspace.base(), uspace.end());
+        let size = guard_start - new_guard_start;
+        if uspace.find_free_area(new_guard_start, size, range, PageSize::Size4K)
+            != Some(new_guard_start)
+        {
+            return None;
+        }
+    }
+
+    debug!(
+        "Growing user stack: {:#x?} -> {:#x?}",
+        stack_bottom, new_bottom
+    );
+    // Keep the stack executable if it was mapped so.
+    let mut flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    if uspace.check_region_access(
+        VirtAddrRange::from_start_size(stack_bottom, PAGE_SIZE_4K),
+        MappingFlags::EXECUTE,
+    ) {
+        flags |= MappingFlags::EXECUTE;
+    }
+    uspace.unmap(guard_start, USER_STACK_GUARD_SIZE).ok()?;
+    uspace
+        .map_alloc(
+            new_bottom,
+            stack_bottom - new_bottom,
+            flags,
+            false,
+            PageSize::Size4K,
+        )
+        .ok()?;
+    uspace
+        .map_alloc(
+            new_guard_start,
+            USER_STACK_GUARD_SIZE,
+            MappingFlags::empty(),
+            false,
+            PageSize::Size4K,
+        )
+        .ok()?;
+    Some(new_bottom)
 }
 
 #[percpu::def_percpu]
@@ -0,0 +1,57 @@
+//! Resource limits of processes (`RLIMIT_*`).
+
+use linux_raw_sys::general::{
+    RLIM_NLIMITS, RLIMIT_CORE, RLIMIT_MEMLOCK, RLIMIT_NOFILE, RLIMIT_SIGPENDING, RLIMIT_STACK,
+};
+
+/// The value of an unlimited resource.
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+/// The soft and hard limit of a resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rlimit {
+    /// The limit that is enforced.
+    pub soft: u64,
+    /// The ceiling of the soft limit.
+    pub hard: u64,
+}
+
+impl Rlimit {
+    /// Creates a limit with the same soft and hard value.
+    pub const fn new(limit: u64) -> Self {
+        Self {
+            soft: limit,
+            hard: limit,
+        }
+    }
+}
+
+/// The resource limits of a process, inherited on fork and kept across
+/// `execve`.
+#[derive(Debug, Clone)]
+pub struct Rlimits([Rlimit; RLIM_NLIMITS as usize]);
+
+impl Default for Rlimits {
+    fn default() -> Self {
+        let mut limits = [Rlimit::new(RLIM_INFINITY); RLIM_NLIMITS as usize];
+        limits[RLIMIT_STACK as usize].soft = 8 * 1024 * 1024;
+        limits[RLIMIT_CORE as usize].soft = 0;
+        // The size of the file descriptor table.
+        limits[RLIMIT_NOFILE as usize] = Rlimit::new(1024);
+        limits[RLIMIT_MEMLOCK as usize] = Rlimit::new(8 * 1024 * 1024);
+        limits[RLIMIT_SIGPENDING as usize] = Rlimit::new(4096);
+        Self(limits)
+    }
+}
+
+impl Rlimits {
+    /// Gets the limit of `resource`, which must be below `RLIM_NLIMITS`.
+    pub fn get(&self, resource: u32) -> Rlimit {
+        self.0[resource as usize]
+    }
+
+    /// Sets the limit of `resource`, which must be below `RLIM_NLIMITS`.
+    pub fn set(&mut self, resource: u32, limit: Rlimit) {
+        self.0[resource as usize] = limit;
+    }
+}
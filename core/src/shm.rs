@@ -8,14 +8,19 @@
 
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use axalloc::global_allocator;
 use axerrno::{AxError, AxResult};
-use axhal::mem::{PAGE_SIZE_4K, virt_to_phys};
+use axhal::{
+    mem::{PAGE_SIZE_4K, virt_to_phys},
+    paging::{MappingFlags, PageSize},
+};
+use axmm::AddrSpace;
 use axsync::Mutex;
 use axtask::{TaskExtRef, current};
-use core::sync::atomic::AtomicBool;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use lazy_static::lazy_static;
-use memory_addr::{PhysAddr, VirtAddr, align_up_4k};
+use memory_addr::{MemoryAddr, PhysAddr, VirtAddr, align_down_4k, align_up, align_up_4k};
 
 /// Shared memory segment identifier.
 pub type ShmId = i32;
@@ -26,6 +31,51 @@ pub type ShmKey = i32;
 /// IPC_PRIVATE key value.
 pub const IPC_PRIVATE: ShmKey = 0;
 
+/// Flag of `shmget` asking for huge pages.
+pub const SHM_HUGETLB: i32 = 0o4000;
+/// Shift of the huge page size encoded in the `shmget` flags, as log2 of the
+/// page size.
+pub const SHM_HUGE_SHIFT: i32 = 26;
+/// Mask of the huge page size encoded in the `shmget` flags.
+pub const SHM_HUGE_MASK: i32 = 0x3f;
+/// Ask for 2MB huge pages.
+pub const SHM_HUGE_2MB: i32 = 21 << SHM_HUGE_SHIFT;
+/// Bit of `shm_perm.mode` set while the segment is locked in memory.
+pub const SHM_LOCKED: u32 = 0o2000;
+
+/// Segments of at least this many bytes get their pages on first touch
+/// instead of all at creation.
+pub const SHM_LAZY_THRESHOLD: usize = 1 << 20;
+
+/// Default maximum size in bytes of a shared memory segment.
+pub const SHMMAX: usize = 1 << 30;
+/// Minimum size in bytes of a shared memory segment.
+pub const SHMMIN: usize = 1;
+/// Default maximum number of shared memory segments system-wide.
+pub const SHMMNI: usize = 4096;
+/// Maximum number of segments a process can attach.
+pub const SHMSEG: usize = SHMMNI;
+/// Default maximum number of pages of shared memory system-wide.
+pub const SHMALL: usize = usize::MAX - (1 << 24);
+
+/// The system-wide shared memory limits, tunable through
+/// `/proc/sys/kernel/shm{max,all,mni}`.
+pub struct ShmLimits {
+    /// Maximum size in bytes of a segment.
+    pub shmmax: AtomicUsize,
+    /// Maximum total number of pages of all segments.
+    pub shmall: AtomicUsize,
+    /// Maximum number of segments.
+    pub shmmni: AtomicUsize,
+}
+
+/// The current shared memory limits.
+pub static SHM_LIMITS: ShmLimits = ShmLimits {
+    shmmax: AtomicUsize::new(SHMMAX),
+    shmall: AtomicUsize::new(SHMALL),
+    shmmni: AtomicUsize::new(SHMMNI),
+};
+
 lazy_static! {
     /// Global shared memory manager instance.
     static ref SHM_MANAGER: Mutex<ShmManager> = Mutex::new(ShmManager::new());
@@ -55,6 +105,42 @@ pub struct ShmidDs {
     pub shm_unused: [u32; 4],
 }
 
+/// Shared memory limits returned by IPC_INFO (shminfo)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ShmInfo {
+    /// Maximum segment size
+    pub shmmax: usize,
+    /// Minimum segment size
+    pub shmmin: usize,
+    /// Maximum number of segments
+    pub shmmni: usize,
+    /// Maximum number of segments that a process can attach
+    pub shmseg: usize,
+    /// Maximum number of pages of shared memory
+    pub shmall: usize,
+    /// Unused fields for future expansion
+    pub __unused: [usize; 4],
+}
+
+/// Shared memory usage returned by SHM_INFO (shm_info)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ShmUsage {
+    /// Number of currently existing segments
+    pub used_ids: i32,
+    /// Total number of shared memory pages
+    pub shm_tot: usize,
+    /// Number of resident shared memory pages
+    pub shm_rss: usize,
+    /// Number of swapped shared memory pages
+    pub shm_swp: usize,
+    /// Unused since Linux 2.4
+    pub swap_attempts: usize,
+    /// Unused since Linux 2.4
+    pub swap_successes: usize,
+}
+
 /// IPC permission structure
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -77,15 +163,27 @@ pub struct IpcPerm {
     pub _unused1: [u32; 5],
 }
 
+/// Physical memory backing a shared memory segment.
+#[derive(Debug)]
+pub enum ShmBacking {
+    /// One physically contiguous range, mapped linearly on attach.
+    Contiguous(PhysAddr),
+    /// 4K frames allocated and zeroed on first touch, keyed by their page
+    /// index in the segment. Every attacher maps the same frames.
+    Lazy(Mutex<BTreeMap<usize, PhysAddr>>),
+}
+
 /// Shared memory segment.
 #[derive(Debug)]
 pub struct ShmSegment {
     /// Shared memory segment identifier.
     pub id: ShmId,
-    /// Physical address of the segment.
-    pub paddr: PhysAddr,
+    /// Physical memory of the segment.
+    pub backing: ShmBacking,
     /// Size of the segment in bytes.
     pub size: usize,
+    /// Size of the pages backing the segment.
+    pub page_size: PageSize,
     /// Standard Linux shmid_ds structure (protected by mutex).
     pub shmid_ds: Mutex<ShmidDs>,
     /// Whether this segment is marked for deletion.
@@ -94,23 +192,56 @@ pub struct ShmSegment {
 
 impl ShmSegment {
     /// Creates a new shared memory segment.
-    pub fn new(id: ShmId, key: ShmKey, size: usize, mode: u16) -> AxResult<Self> {
-        let aligned_size = align_up_4k(size);
-
-        let vaddr = global_allocator()
-            .alloc_pages(aligned_size / PAGE_SIZE_4K, PAGE_SIZE_4K)
-            .map_err(|_| AxError::NoMemory)?;
+    ///
+    /// If `huge` is set, the segment is backed by 2MB pages when possible,
+    /// falling back to 4K pages if they cannot be allocated. Otherwise
+    /// segments of at least [`SHM_LAZY_THRESHOLD`] bytes are populated on
+    /// demand.
+    pub fn new(id: ShmId, key: ShmKey, size: usize, mode: u16, huge: bool) -> AxResult<Self> {
+        let huge_alloc = if huge {
+            let huge_size = align_up(size, PageSize::Size2M as usize);
+            global_allocator()
+                .alloc_pages(huge_size / PAGE_SIZE_4K, PageSize::Size2M as usize)
+                .ok()
+                .map(|vaddr| (vaddr, huge_size, PageSize::Size2M))
+        } else {
+            None
+        };
+        let (backing, aligned_size, page_size) = match huge_alloc {
+            Some((vaddr, aligned_size, page_size)) => (
+                Self::contiguous(vaddr, aligned_size),
+                aligned_size,
+                page_size,
+            ),
+            None if align_up_4k(size) >= SHM_LAZY_THRESHOLD => (
+                ShmBacking::Lazy(Mutex::new(BTreeMap::new())),
+                align_up_4k(size),
+                PageSize::Size4K,
+            ),
+            None => {
+                let aligned_size = align_up_4k(size);
+                let vaddr = global_allocator()
+                    .alloc_pages(aligned_size / PAGE_SIZE_4K, PAGE_SIZE_4K)
+                    .map_err(|_| AxError::NoMemory)?;
+                (
+                    Self::contiguous(vaddr, aligned_size),
+                    aligned_size,
+                    PageSize::Size4K,
+                )
+            }
+        };
 
-        let paddr = virt_to_phys(vaddr.into());
         let current_time = axhal::time::wall_time().as_secs();
-        let creator_pid = current().task_ext().thread.process().pid() as i32;
+        let curr = current();
+        let creator_pid = curr.task_ext().thread.process().pid() as i32;
+        let cred = *curr.task_ext().process_data().cred.read();
 
         let ipc_perm = IpcPerm {
             key,
-            uid: 0,
-            gid: 0,
-            cuid: 0,
-            cgid: 0,
+            uid: cred.euid,
+            gid: cred.egid,
+            cuid: cred.euid,
+            cgid: cred.egid,
             mode: mode as u32,
             seq: 0,
             _unused1: [0; 5],
@@ -130,13 +261,60 @@ impl ShmSegment {
 
         Ok(Self {
             id,
-            paddr,
+            backing,
             size: aligned_size,
+            page_size,
             shmid_ds: Mutex::new(shmid_ds),
             marked_for_deletion: AtomicBool::new(false),
         })
     }
 
+    /// Zeroes freshly allocated pages and wraps them as a contiguous backing.
+    fn contiguous(vaddr: usize, size: usize) -> ShmBacking {
+        // Shared memory must start out zero-filled, never with stale contents
+        // of the freed pages.
+        unsafe { core::ptr::write_bytes(vaddr as *mut u8, 0, size) };
+        ShmBacking::Contiguous(virt_to_phys(vaddr.into()))
+    }
+
+    /// Whether the pages of the segment are allocated on first touch.
+    pub fn is_lazy(&self) -> bool {
+        matches!(self.backing, ShmBacking::Lazy(_))
+    }
+
+    /// Gets the frame holding the 4K page at `offset` in the segment,
+    /// allocating a zeroed one if it has not been touched yet.
+    pub fn frame(&self, offset: usize) -> AxResult<PhysAddr> {
+        if offset >= self.size {
+            return Err(AxError::InvalidInput);
+        }
+        match &self.backing {
+            ShmBacking::Contiguous(paddr) => Ok(*paddr + align_down_4k(offset)),
+            ShmBacking::Lazy(frames) => {
+                let mut frames = frames.lock();
+                let index = offset / PAGE_SIZE_4K;
+                if let Some(&paddr) = frames.get(&index) {
+                    return Ok(paddr);
+                }
+                let vaddr = global_allocator()
+                    .alloc_pages(1, PAGE_SIZE_4K)
+                    .map_err(|_| AxError::NoMemory)?;
+                unsafe { core::ptr::write_bytes(vaddr as *mut u8, 0, PAGE_SIZE_4K) };
+                let paddr = virt_to_phys(vaddr.into());
+                frames.insert(index, paddr);
+                Ok(paddr)
+            }
+        }
+    }
+
+    /// Gets the number of bytes of the segment backed by physical memory.
+    pub fn resident_size(&self) -> usize {
+        match &self.backing {
+            ShmBacking::Contiguous(_) => self.size,
+            ShmBacking::Lazy(frames) => frames.lock().len() * PAGE_SIZE_4K,
+        }
+    }
+
     /// Increments the attachment count for this segment.
     pub fn inc_attach(&self) {
         let mut ds = self.shmid_ds.lock();
@@ -144,15 +322,45 @@ impl ShmSegment {
         ds.shm_atime = axhal::time::wall_time().as_secs() as i64;
     }
 
+    /// Increments the attachment count unless the segment is marked for
+    /// deletion, in which case `false` is returned.
+    ///
+    /// The check and the increment happen under the segment lock, so a
+    /// concurrent `IPC_RMID` either sees the new attachment or wins.
+    pub fn try_attach(&self) -> bool {
+        let mut ds = self.shmid_ds.lock();
+        if self.marked_for_deletion.load(Ordering::SeqCst) {
+            return false;
+        }
+        ds.shm_nattch += 1;
+        ds.shm_atime = axhal::time::wall_time().as_secs() as i64;
+        true
+    }
+
     /// Decrements the attachment count for this segment.
-    pub fn dec_attach(&self) {
+    ///
+    /// Returns `true` if this was the last attachment of a segment marked for
+    /// deletion, meaning the caller must remove it from the manager.
+    pub fn dec_attach(&self) -> bool {
         let mut ds = self.shmid_ds.lock();
         if ds.shm_nattch > 0 {
             ds.shm_nattch -= 1;
             if ds.shm_nattch == 0 {
                 ds.shm_dtime = axhal::time::wall_time().as_secs() as i64;
+                return self.marked_for_deletion.load(Ordering::SeqCst);
             }
         }
+        false
+    }
+
+    /// Marks the segment for deletion (`IPC_RMID`).
+    ///
+    /// Returns `true` if the segment is not attached anywhere and must be
+    /// removed from the manager right away. This happens at most once.
+    pub fn mark_for_deletion(&self) -> bool {
+        let ds = self.shmid_ds.lock();
+        let was_marked = self.marked_for_deletion.swap(true, Ordering::SeqCst);
+        !was_marked && ds.shm_nattch == 0
     }
 
     /// Gets the current attachment count for this segment.
@@ -166,15 +374,21 @@ impl ShmSegment {
     }
 
     /// Checks if the given user has the required permissions for this segment.
+    ///
+    /// Root (uid 0) is always allowed.
     pub fn check_permissions(&self, uid: u32, gid: u32, access: u16) -> bool {
+        if uid == 0 {
+            return true;
+        }
+
         let ds = self.shmid_ds.lock();
         let mode = ds.shm_perm.mode;
 
-        if uid == ds.shm_perm.uid {
+        if uid == ds.shm_perm.uid || uid == ds.shm_perm.cuid {
             return (mode & ((access as u32) << 6)) == ((access as u32) << 6);
         }
 
-        if gid == ds.shm_perm.gid {
+        if gid == ds.shm_perm.gid || gid == ds.shm_perm.cgid {
             return (mode & ((access as u32) << 3)) == ((access as u32) << 3);
         }
 
@@ -193,15 +407,11 @@ impl ShmSegment {
             return false;
         }
 
-        if ds.shm_segsz == 0 || ds.shm_segsz > (1usize << 30) {
+        if ds.shm_segsz == 0 || self.size == 0 {
             return false;
         }
 
-        if self.size == 0 || self.size > (1usize << 30) {
-            return false;
-        }
-
-        if self.paddr.as_usize() == 0 {
+        if matches!(self.backing, ShmBacking::Contiguous(paddr) if paddr.as_usize() == 0) {
             return false;
         }
 
@@ -213,20 +423,66 @@ impl ShmSegment {
         *self.shmid_ds.lock()
     }
 
+    /// Whether the segment is locked in memory (`SHM_LOCK`).
+    pub fn is_locked(&self) -> bool {
+        self.shmid_ds.lock().shm_perm.mode & SHM_LOCKED != 0
+    }
+
+    /// Locks or unlocks the segment in memory.
+    ///
+    /// Segments are never swapped out, so this only records the state
+    /// reported by `IPC_STAT`.
+    pub fn set_locked(&self, locked: bool) {
+        let mut ds = self.shmid_ds.lock();
+        if locked {
+            ds.shm_perm.mode |= SHM_LOCKED;
+        } else {
+            ds.shm_perm.mode &= !SHM_LOCKED;
+        }
+    }
+
     /// Updates permissions from user space (for IPC_SET).
+    ///
+    /// Only the permission bits of `mode` are taken, state bits such as
+    /// [`SHM_LOCKED`] are kept.
     pub fn set_perm(&self, uid: u32, gid: u32, mode: u32) {
         let mut ds = self.shmid_ds.lock();
         ds.shm_perm.uid = uid;
         ds.shm_perm.gid = gid;
-        ds.shm_perm.mode = mode;
+        ds.shm_perm.mode = (ds.shm_perm.mode & !0o777) | (mode & 0o777);
         ds.shm_ctime = axhal::time::wall_time().as_secs() as i64;
     }
 }
 
 impl Drop for ShmSegment {
     fn drop(&mut self) {
-        let vaddr = axhal::mem::phys_to_virt(self.paddr);
-        global_allocator().dealloc_pages(vaddr.as_usize(), self.size / PAGE_SIZE_4K);
+        match &self.backing {
+            ShmBacking::Contiguous(paddr) => {
+                let vaddr = axhal::mem::phys_to_virt(*paddr);
+                global_allocator().dealloc_pages(vaddr.as_usize(), self.size / PAGE_SIZE_4K);
+            }
+            ShmBacking::Lazy(frames) => {
+                for paddr in frames.lock().values() {
+                    let vaddr = axhal::mem::phys_to_virt(*paddr);
+                    global_allocator().dealloc_pages(vaddr.as_usize(), 1);
+                }
+            }
+        }
+    }
+}
+
+/// Checks whether `shmget` flags ask for huge pages.
+///
+/// Only 2MB pages are supported, so any other explicitly encoded size is
+/// rejected rather than silently served with a different page size.
+fn huge_pages_requested(flags: i32) -> AxResult<bool> {
+    if flags & SHM_HUGETLB == 0 {
+        return Ok(false);
+    }
+    match (flags >> SHM_HUGE_SHIFT) & SHM_HUGE_MASK {
+        // The default size, or log2 of 2MB.
+        0 | 21 => Ok(true),
+        _ => Err(AxError::InvalidInput),
     }
 }
 
@@ -278,6 +534,23 @@ impl ShmManager {
         }
     }
 
+    /// Checks that a new segment of `size` bytes fits in [`SHM_LIMITS`].
+    fn check_limits(&self, size: usize) -> AxResult {
+        if size < SHMMIN || size > SHM_LIMITS.shmmax.load(Ordering::Relaxed) {
+            return Err(AxError::InvalidInput);
+        }
+        if self.segments.len() >= SHM_LIMITS.shmmni.load(Ordering::Relaxed) {
+            return Err(AxError::StorageFull);
+        }
+        let pages = self.usage().shm_tot;
+        if pages.saturating_add(align_up_4k(size) / PAGE_SIZE_4K)
+            > SHM_LIMITS.shmall.load(Ordering::Relaxed)
+        {
+            return Err(AxError::StorageFull);
+        }
+        Ok(())
+    }
+
     /// Creates or gets a shared memory segment.
     pub fn get_or_create(
         &mut self,
@@ -288,10 +561,12 @@ impl ShmManager {
         let create_flag = flags & 0o01000;
         let excl_flag = flags & 0o02000;
         let mode = (flags & 0o777) as u16;
+        let huge = huge_pages_requested(flags)?;
 
         if key == IPC_PRIVATE {
+            self.check_limits(size)?;
             let id = self.alloc_id()?;
-            let segment = Arc::new(ShmSegment::new(id, key, size, mode)?);
+            let segment = Arc::new(ShmSegment::new(id, key, size, mode, huge)?);
             self.segments.insert(id, segment.clone());
             return Ok(segment);
         }
@@ -302,12 +577,12 @@ impl ShmManager {
             }
 
             if let Some(segment) = self.segments.get(&existing_id) {
-                if segment
-                    .marked_for_deletion
-                    .load(core::sync::atomic::Ordering::SeqCst)
-                {
+                if segment.marked_for_deletion.load(Ordering::SeqCst) {
                     return Err(AxError::NotFound);
                 }
+                if size > segment.get_stat().shm_segsz {
+                    return Err(AxError::InvalidInput);
+                }
                 return Ok(segment.clone());
             } else {
                 self.key_to_id.remove(&key);
@@ -316,8 +591,9 @@ impl ShmManager {
         }
 
         if create_flag != 0 {
+            self.check_limits(size)?;
             let id = self.alloc_id()?;
-            let segment = Arc::new(ShmSegment::new(id, key, size, mode)?);
+            let segment = Arc::new(ShmSegment::new(id, key, size, mode, huge)?);
             self.segments.insert(id, segment.clone());
             self.key_to_id.insert(key, id);
             Ok(segment)
@@ -344,6 +620,52 @@ impl ShmManager {
         }
     }
 
+    /// Gets the segment at `index` in the segment table, for SHM_STAT.
+    pub fn get_by_index(&self, index: usize) -> AxResult<Arc<ShmSegment>> {
+        self.segments
+            .values()
+            .nth(index)
+            .cloned()
+            .ok_or(AxError::InvalidInput)
+    }
+
+    /// Gets the highest used index in the segment table, or 0 if empty.
+    pub fn max_index(&self) -> usize {
+        self.segments.len().saturating_sub(1)
+    }
+
+    /// Gets the system-wide usage of shared memory, for SHM_INFO.
+    pub fn usage(&self) -> ShmUsage {
+        let pages = self
+            .segments
+            .values()
+            .map(|segment| segment.size / PAGE_SIZE_4K)
+            .sum();
+        let resident = self
+            .segments
+            .values()
+            .map(|segment| segment.resident_size() / PAGE_SIZE_4K)
+            .sum();
+        ShmUsage {
+            used_ids: self.segments.len() as i32,
+            shm_tot: pages,
+            shm_rss: resident,
+            shm_swp: 0,
+            swap_attempts: 0,
+            swap_successes: 0,
+        }
+    }
+
+    /// Gets the total size of the locked segments owned by `uid`, which is
+    /// charged against its `RLIMIT_MEMLOCK`.
+    pub fn locked_size(&self, uid: u32) -> usize {
+        self.segments
+            .values()
+            .filter(|segment| segment.is_locked() && segment.get_stat().shm_perm.uid == uid)
+            .map(|segment| segment.size)
+            .sum()
+    }
+
     /// Lists all segments (for debugging/info purposes).
     pub fn list_segments(&self) -> impl Iterator<Item = &Arc<ShmSegment>> {
         self.segments.values()
@@ -359,6 +681,8 @@ pub struct ShmAttach {
     pub addr: VirtAddr,
     /// Segment reference.
     pub segment: Arc<ShmSegment>,
+    /// Mapping flags of the attachment.
+    pub flags: MappingFlags,
 }
 
 /// Per-process shared memory tracking.
@@ -375,11 +699,48 @@ impl ProcessShmData {
     }
 
     /// Attaches a shared memory segment.
-    pub fn attach(&mut self, id: ShmId, addr: VirtAddr, segment: Arc<ShmSegment>) {
-        let attach = ShmAttach { id, addr, segment };
+    pub fn attach(
+        &mut self,
+        id: ShmId,
+        addr: VirtAddr,
+        segment: Arc<ShmSegment>,
+        flags: MappingFlags,
+    ) {
+        let attach = ShmAttach {
+            id,
+            addr,
+            segment,
+            flags,
+        };
         self.attached.insert(addr, attach);
     }
 
+    /// Handles a page fault in an attached segment populated on demand, by
+    /// mapping the segment's frame at the page, in the area reserved for the
+    /// segment.
+    ///
+    /// Returns `None` if `vaddr` is not in such a segment, otherwise whether
+    /// the fault was resolved.
+    pub fn handle_page_fault(
+        &self,
+        aspace: &mut AddrSpace,
+        vaddr: VirtAddr,
+        access_flags: MappingFlags,
+    ) -> Option<bool> {
+        let (&addr, attach) = self.attached.range(..=vaddr).next_back()?;
+        if vaddr >= addr + attach.segment.size || !attach.segment.is_lazy() {
+            return None;
+        }
+        if !attach.flags.contains(access_flags) {
+            return Some(false);
+        }
+        let page = vaddr.align_down_4k();
+        let Ok(frame) = attach.segment.frame(page - addr) else {
+            return Some(false);
+        };
+        Some(crate::cow::map_segment_frame(aspace, page, frame, attach.flags).is_ok())
+    }
+
     /// Detaches a shared memory segment.
     pub fn detach(&mut self, addr: VirtAddr) -> Option<ShmAttach> {
         self.attached.remove(&addr)
@@ -389,6 +750,87 @@ impl ProcessShmData {
     pub fn find_by_addr(&self, addr: VirtAddr) -> Option<&ShmAttach> {
         self.attached.get(&addr)
     }
+
+    /// Duplicates the attachments for a forked child process.
+    ///
+    /// The child maps the same physical pages, so every segment gains one
+    /// more attachment.
+    pub fn fork(&self) -> Self {
+        let attached = self
+            .attached
+            .iter()
+            .map(|(&addr, attach)| {
+                attach.segment.inc_attach();
+                let attach = ShmAttach {
+                    id: attach.id,
+                    addr,
+                    segment: attach.segment.clone(),
+                    flags: attach.flags,
+                };
+                (addr, attach)
+            })
+            .collect();
+        Self { attached }
+    }
+
+    /// Lists the segments attached in `[start, start + size)`, with whether
+    /// each lies entirely inside it.
+    fn overlapping(&self, start: VirtAddr, size: usize) -> Vec<(VirtAddr, bool)> {
+        let end = start + size;
+        self.attached
+            .range(..end)
+            .filter(|(addr, attach)| **addr + attach.segment.size > start)
+            .map(|(addr, attach)| (*addr, *addr >= start && *addr + attach.segment.size <= end))
+            .collect()
+    }
+
+    /// Checks that [`Self::detach_range`] would succeed on the range, so the
+    /// caller can do it before tearing down the mappings.
+    pub fn check_detach_range(&self, start: VirtAddr, size: usize) -> AxResult {
+        if self
+            .overlapping(start, size)
+            .iter()
+            .any(|(_, contained)| !contained)
+        {
+            return Err(AxError::InvalidInput);
+        }
+        Ok(())
+    }
+
+    /// Detaches the segments attached inside `[start, start + size)`, whose
+    /// mappings are about to be replaced by the caller.
+    ///
+    /// A segment that only partly overlaps the range cannot be split, so
+    /// `InvalidInput` is returned for it and nothing is detached.
+    pub fn detach_range(&mut self, start: VirtAddr, size: usize, pid: i32) -> AxResult {
+        self.check_detach_range(start, size)?;
+        for (addr, _) in self.overlapping(start, size) {
+            let attach = self.attached.remove(&addr).unwrap();
+            attach.segment.set_last_pid(pid);
+            if attach.segment.dec_attach() {
+                let _ = SHM_MANAGER.lock().remove(attach.id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Detaches all segments, as done on process exit and `execve`.
+    ///
+    /// Segments marked for deletion are removed once their last attachment
+    /// is gone, freeing their frames, so these are no longer mapped in
+    /// `aspace` afterwards. The areas themselves are left to the caller.
+    pub fn detach_all(&mut self, aspace: &mut AddrSpace, pid: i32) {
+        if self.attached.is_empty() {
+            return;
+        }
+        for attach in core::mem::take(&mut self.attached).into_values() {
+            crate::cow::release(aspace, attach.addr, attach.segment.size);
+            attach.segment.set_last_pid(pid);
+            if attach.segment.dec_attach() {
+                let _ = SHM_MANAGER.lock().remove(attach.id);
+            }
+        }
+    }
 }
 
 /// Gets the global shared memory manager.
@@ -3,11 +3,12 @@
 use core::{
     alloc::Layout,
     cell::RefCell,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
     time::Duration,
 };
 
 use alloc::{
+    collections::btree_set::BTreeSet,
     string::String,
     sync::{Arc, Weak},
     vec::Vec,
@@ -15,22 +16,29 @@ use alloc::{
 use axerrno::{LinuxError, LinuxResult};
 use axhal::{
     arch::UspaceContext,
-    time::{NANOS_PER_MICROS, NANOS_PER_SEC, monotonic_time_nanos},
+    time::{NANOS_PER_MICROS, NANOS_PER_SEC, monotonic_time, monotonic_time_nanos},
 };
 use axmm::{AddrSpace, kernel_aspace};
 use axns::{AxNamespace, AxNamespaceIf};
 use axprocess::{Pid, Process, ProcessGroup, Session, Thread};
 use axsignal::{
-    Signo,
+    SignalSet, Signo,
     api::{ProcessSignalManager, SignalActions, ThreadSignalManager},
 };
 use axsync::{Mutex, RawMutex};
 use axtask::{TaskExtRef, TaskInner, WaitQueue, current};
-use memory_addr::VirtAddrRange;
+use linux_raw_sys::{general::RLIMIT_STACK, prctl::PR_MCE_KILL_DEFAULT};
+use memory_addr::{VirtAddr, VirtAddrRange};
 use spin::{Once, RwLock};
 use weak_map::WeakMap;
 
-use crate::{futex::FutexTable, shm::ProcessShmData, time::TimeStat};
+use crate::{
+    futex::FutexTable,
+    mm::{MmapBackings, grow_user_stack},
+    resource::Rlimits,
+    shm::ProcessShmData,
+    time::TimeStat,
+};
 
 /// Create a new user task.
 pub fn new_user_task(
@@ -41,10 +49,15 @@ pub fn new_user_task(
     TaskInner::new(
         move || {
             let curr = axtask::current();
+            let affinity = curr.task_ext().thread_data().cpu_affinity();
+            if affinity != online_cpus_mask() && !set_current_affinity_mask(affinity) {
+                warn!("Failed to restrict user task to CPUs {:#x}", affinity);
+            }
             if let Some(tid) = set_child_tid {
-                *tid = curr.id().as_u64() as Pid;
+                *tid = curr.task_ext().thread.tid();
             }
 
+            curr.task_ext().process_data().mark_user_cpu();
             let kstack_top = curr.kernel_stack_top().unwrap();
             info!(
                 "Enter user space: entry={:#x}, ustack={:#x}, kstack={:#x}",
@@ -59,6 +72,52 @@ pub fn new_user_task(
     )
 }
 
+/// The mask of all the CPUs.
+pub fn online_cpus_mask() -> usize {
+    match 1usize.checked_shl(axconfig::plat::CPU_NUM as u32) {
+        Some(bit) => bit - 1,
+        None => usize::MAX,
+    }
+}
+
+/// Restrict the current task to run on the CPUs set in `mask`.
+pub fn set_current_affinity_mask(mask: usize) -> bool {
+    let mut cpumask = axtask::AxCpuMask::new();
+    for cpu in 0..axconfig::plat::CPU_NUM.min(usize::BITS as usize) {
+        if mask & (1 << cpu) != 0 {
+            cpumask.set(cpu, true);
+        }
+    }
+    axtask::set_current_affinity(cpumask)
+}
+
+/// Run `f` on each of the online CPUs set in `cpus`.
+///
+/// `axhal` has no cross-CPU call, so the current task moves to each of the
+/// other CPUs in turn, and gets its affinity back afterwards.
+pub fn on_each_cpu(cpus: usize, mut f: impl FnMut()) {
+    let cpus = cpus & online_cpus_mask();
+    let this_cpu = axhal::cpu::this_cpu_id();
+    if cpus & (1 << this_cpu) != 0 {
+        f();
+    }
+    let others = cpus & !(1 << this_cpu);
+    if others == 0 {
+        return;
+    }
+    for cpu in (0..usize::BITS as usize).filter(|cpu| others & (1 << cpu) != 0) {
+        if set_current_affinity_mask(1 << cpu) {
+            f();
+        } else {
+            warn!("Failed to run on CPU {}", cpu);
+        }
+    }
+    let affinity = current().task_ext().thread_data().cpu_affinity();
+    if !set_current_affinity_mask(affinity) {
+        warn!("Failed to restrict user task to CPUs {:#x}", affinity);
+    }
+}
+
 /// Task extended data for the monolithic kernel.
 pub struct TaskExt {
     /// The time statistics
@@ -162,6 +221,20 @@ pub struct ThreadData {
 
     /// The thread-level signal manager
     pub signal: ThreadSignalManager<RawMutex, WaitQueueWrapper>,
+
+    /// The CPUs the thread may run on, one bit per CPU.
+    cpu_affinity: AtomicUsize,
+    /// Whether `cpu_affinity` was changed by another thread, and is yet to
+    /// be applied by this one.
+    affinity_changed: AtomicBool,
+
+    /// The name of the thread, `comm` in Linux. The task has the same name,
+    /// but that of another task cannot be read.
+    comm: Mutex<String>,
+
+    /// The signal mask to restore once the signals that interrupted a system
+    /// call waiting with a temporary mask, like `epoll_pwait`, are handled.
+    pub saved_sigmask: Mutex<Option<SignalSet>>,
 }
 
 impl ThreadData {
@@ -172,6 +245,13 @@ impl ThreadData {
             clear_child_tid: AtomicUsize::new(0),
 
             signal: ThreadSignalManager::new(proc.signal.clone()),
+
+            cpu_affinity: AtomicUsize::new(online_cpus_mask()),
+            affinity_changed: AtomicBool::new(false),
+
+            comm: Mutex::new(comm_of(exe_name(&proc.exe_path.read()))),
+
+            saved_sigmask: Mutex::new(None),
         }
     }
 
@@ -185,6 +265,83 @@ impl ThreadData {
         self.clear_child_tid
             .store(clear_child_tid, Ordering::Relaxed);
     }
+
+    /// Get the name of the thread.
+    pub fn comm(&self) -> String {
+        self.comm.lock().clone()
+    }
+
+    /// Set the name of the thread, truncated to `TASK_COMM_LEN - 1` bytes.
+    pub fn set_comm(&self, name: &str) {
+        *self.comm.lock() = comm_of(name);
+    }
+
+    /// Get the CPU affinity mask of the thread.
+    pub fn cpu_affinity(&self) -> usize {
+        self.cpu_affinity.load(Ordering::Acquire)
+    }
+
+    /// Set the CPU affinity mask of the thread.
+    ///
+    /// It takes effect when the thread starts, or right away if it is set
+    /// with [`set_current_affinity_mask`] by the thread itself.
+    pub fn set_cpu_affinity(&self, mask: usize) {
+        self.cpu_affinity.store(mask, Ordering::Release);
+    }
+
+    /// Set the CPU affinity mask of the thread from another one. The thread
+    /// applies it the next time it returns to user space, see
+    /// [`ThreadData::take_changed_affinity`].
+    pub fn change_cpu_affinity(&self, mask: usize) {
+        self.cpu_affinity.store(mask, Ordering::Release);
+        self.affinity_changed.store(true, Ordering::Release);
+    }
+
+    /// Get the CPU affinity mask of the thread if another thread changed it
+    /// since the last call.
+    pub fn take_changed_affinity(&self) -> Option<usize> {
+        self.affinity_changed
+            .swap(false, Ordering::AcqRel)
+            .then(|| self.cpu_affinity())
+    }
+}
+
+/// The length of the name of a thread, including the terminating null byte.
+pub const TASK_COMM_LEN: usize = 16;
+
+/// The name of a thread called `name`.
+pub(crate) fn comm_of(name: &str) -> String {
+    let mut len = name.len().min(TASK_COMM_LEN - 1);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    name[..len].into()
+}
+
+/// The file name of the executable at `path`, which names the threads
+/// running it.
+pub fn exe_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// User and group identities of a process.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Credentials {
+    /// Real user ID
+    pub uid: u32,
+    /// Real group ID
+    pub gid: u32,
+    /// Effective user ID
+    pub euid: u32,
+    /// Effective group ID
+    pub egid: u32,
+}
+
+impl Credentials {
+    /// Whether the process has root privileges.
+    pub fn is_root(&self) -> bool {
+        self.euid == 0
+    }
 }
 
 /// Extended data for [`Process`].
@@ -195,24 +352,60 @@ pub struct ProcessData {
     pub aspace: Arc<Mutex<AddrSpace>>,
     /// The resource namespace
     pub ns: AxNamespace,
+    /// The credentials
+    pub cred: RwLock<Credentials>,
+    /// The resource limits
+    pub rlimits: RwLock<Rlimits>,
     /// The user heap bottom
     heap_bottom: AtomicUsize,
     /// The user heap top
     heap_top: AtomicUsize,
+    /// The lowest address of the main thread's stack, which grows down on
+    /// page faults.
+    stack_bottom: AtomicUsize,
+    /// The monotonic time when the process was created.
+    pub start_time: Duration,
 
     /// The child exit wait queue
     pub child_exit_wq: WaitQueue,
+    /// Woken when a thread of the process exits.
+    pub thread_exit_wq: WaitQueue,
     /// The exit signal of the thread
     pub exit_signal: Option<Signo>,
 
     /// The process signal manager
     pub signal: Arc<ProcessSignalManager<RawMutex, WaitQueueWrapper>>,
+    /// The number of real-time signals queued to the process or its threads,
+    /// bounded by `RLIMIT_SIGPENDING`.
+    pub queued_rt_signals: AtomicUsize,
 
     /// The futex table.
     pub futex_table: FutexTable,
 
     /// The shared memory data.
+    ///
+    /// Lock order: `aspace` is always locked before `shm_data`, then
+    /// `mmap_backings`.
     pub shm_data: Mutex<ProcessShmData>,
+    /// The shared and file-backed mappings created by `mmap`.
+    pub mmap_backings: Mutex<MmapBackings>,
+
+    /// The thread running `execve`, or 0 if there is none. All the other
+    /// threads exit once they notice it.
+    exec_tid: AtomicU32,
+
+    /// The `MEMBARRIER_CMD_REGISTER_*` commands the address space was
+    /// registered with.
+    pub membarrier_registrations: AtomicU32,
+    /// The CPUs that have run the threads of the process in user space,
+    /// which the expedited `membarrier` commands must reach. A CPU is not
+    /// removed once the threads leave it.
+    user_cpus: AtomicUsize,
+
+    /// The machine-check error kill policy (`PR_MCE_KILL_*`).
+    pub mce_kill_policy: AtomicU32,
+    /// User pages hit by an uncorrectable memory error.
+    pub poisoned_pages: Mutex<BTreeSet<VirtAddr>>,
 }
 
 impl ProcessData {
@@ -227,22 +420,53 @@ impl ProcessData {
             exe_path: RwLock::new(exe_path),
             aspace,
             ns: AxNamespace::new_thread_local(),
+            cred: RwLock::new(Credentials::default()),
+            rlimits: RwLock::new(Rlimits::default()),
             heap_bottom: AtomicUsize::new(axconfig::plat::USER_HEAP_BASE),
             heap_top: AtomicUsize::new(axconfig::plat::USER_HEAP_BASE),
+            stack_bottom: AtomicUsize::new(
+                axconfig::plat::USER_STACK_TOP - axconfig::plat::USER_STACK_SIZE,
+            ),
+            start_time: monotonic_time(),
 
             child_exit_wq: WaitQueue::new(),
+            thread_exit_wq: WaitQueue::new(),
             exit_signal,
 
             signal: Arc::new(ProcessSignalManager::new(
                 signal_actions,
                 axconfig::plat::SIGNAL_TRAMPOLINE,
             )),
+            queued_rt_signals: AtomicUsize::new(0),
 
             futex_table: FutexTable::new(),
             shm_data: Mutex::new(ProcessShmData::new()),
+            mmap_backings: Mutex::new(MmapBackings::default()),
+
+            exec_tid: AtomicU32::new(0),
+            membarrier_registrations: AtomicU32::new(0),
+            user_cpus: AtomicUsize::new(0),
+
+            mce_kill_policy: AtomicU32::new(PR_MCE_KILL_DEFAULT),
+            poisoned_pages: Mutex::new(BTreeSet::new()),
         }
     }
 
+    /// Record that the current CPU is about to run a thread of the process
+    /// in user space.
+    pub fn mark_user_cpu(&self) {
+        let cpu = axhal::cpu::this_cpu_id();
+        if cpu < usize::BITS as usize {
+            self.user_cpus.fetch_or(1 << cpu, Ordering::AcqRel);
+        }
+    }
+
+    /// Get the mask of the CPUs that may be running the threads of the
+    /// process in user space.
+    pub fn user_cpus(&self) -> usize {
+        self.user_cpus.load(Ordering::Acquire)
+    }
+
     /// Get the bottom address of the user heap.
     pub fn get_heap_bottom(&self) -> usize {
         self.heap_bottom.load(Ordering::Acquire)
@@ -263,6 +487,78 @@ impl ProcessData {
         self.heap_top.store(top, Ordering::Release)
     }
 
+    /// Get the bottom address of the user stack.
+    pub fn get_stack_bottom(&self) -> usize {
+        self.stack_bottom.load(Ordering::Acquire)
+    }
+
+    /// Set the bottom address of the user stack.
+    pub fn set_stack_bottom(&self, bottom: usize) {
+        self.stack_bottom.store(bottom, Ordering::Release)
+    }
+
+    /// Grow the user stack down to `vaddr` if the page fault there is a
+    /// legitimate stack access. See [`grow_user_stack`].
+    ///
+    /// Returns whether the stack was grown.
+    pub fn grow_stack(&self, aspace: &mut AddrSpace, vaddr: VirtAddr) -> bool {
+        let limit = self.rlimits.read().get(RLIMIT_STACK).soft as usize;
+        let bottom = VirtAddr::from_usize(self.get_stack_bottom());
+        match grow_user_stack(aspace, bottom, vaddr, Some(limit)) {
+            Some(new_bottom) => {
+                self.set_stack_bottom(new_bottom.as_usize());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Mark the thread `tid` as running `execve`.
+    ///
+    /// Returns `false` if another thread is already running `execve`.
+    pub fn begin_exec(&self, tid: Pid) -> bool {
+        self.exec_tid
+            .compare_exchange(0, tid, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    /// Clear the thread running `execve`.
+    pub fn end_exec(&self) {
+        self.exec_tid.store(0, Ordering::Release);
+    }
+
+    /// Get the thread running `execve`, if any.
+    pub fn exec_tid(&self) -> Option<Pid> {
+        match self.exec_tid.load(Ordering::Acquire) {
+            0 => None,
+            tid => Some(tid),
+        }
+    }
+
+    /// Whether the page containing `vaddr` is hit by a memory error.
+    pub fn is_poisoned(&self, vaddr: VirtAddr) -> bool {
+        self.poisoned_pages.lock().contains(&vaddr.align_down_4k())
+    }
+
+    /// Whether any page in `[start, start + size)` is hit by a memory error.
+    pub fn is_range_poisoned(&self, start: VirtAddr, size: usize) -> bool {
+        let end = (start + size).align_up_4k();
+        self.poisoned_pages
+            .lock()
+            .range(start.align_down_4k()..end)
+            .next()
+            .is_some()
+    }
+
+    /// Forget the memory errors in `[start, start + size)`, once the pages
+    /// there are unmapped or replaced.
+    pub fn clear_poisoned(&self, start: VirtAddr, size: usize) {
+        let end = (start + size).align_up_4k();
+        self.poisoned_pages
+            .lock()
+            .retain(|&vaddr| !(start.align_down_4k()..end).contains(&vaddr));
+    }
+
     /// Linux manual: A "clone" child is one which delivers no signal, or a
     /// signal other than SIGCHLD to its parent upon termination.
     pub fn is_clone_child(&self) -> bool {
@@ -272,6 +568,11 @@ impl ProcessData {
 
 impl Drop for ProcessData {
     fn drop(&mut self) {
+        // The pages shared copy-on-write go away with the last user of the
+        // address space.
+        if Arc::strong_count(&self.aspace) == 1 {
+            crate::cow::release_all(&mut self.aspace.lock());
+        }
         if !cfg!(target_arch = "aarch64") && !cfg!(target_arch = "loongarch64") {
             // See [`crate::new_user_aspace`]
             let kernel = kernel_aspace().lock();
@@ -310,11 +611,64 @@ static PROCESS_TABLE: RwLock<WeakMap<Pid, Weak<Process>>> = RwLock::new(WeakMap:
 static PROCESS_GROUP_TABLE: RwLock<WeakMap<Pid, Weak<ProcessGroup>>> = RwLock::new(WeakMap::new());
 static SESSION_TABLE: RwLock<WeakMap<Pid, Weak<Session>>> = RwLock::new(WeakMap::new());
 
+/// The largest PID that can be assigned, like `/proc/sys/kernel/pid_max`.
+pub const PID_MAX: Pid = 1 << 22;
+
+/// The PIDs given to new threads that are not in the tables yet.
+static RESERVED_PIDS: Mutex<BTreeSet<Pid>> = Mutex::new(BTreeSet::new());
+
+/// Checks whether `pid` is used by a live thread, process, process group or
+/// session, so that it cannot be handed out again.
+pub fn pid_in_use(pid: Pid) -> bool {
+    THREAD_TABLE.read().get(&pid).is_some()
+        || PROCESS_TABLE.read().get(&pid).is_some()
+        || PROCESS_GROUP_TABLE.read().get(&pid).is_some()
+        || SESSION_TABLE.read().get(&pid).is_some()
+}
+
+/// Reserves `pid` for a new thread, unless it is in use or reserved already.
+///
+/// The reservation lasts until the thread is added by
+/// [`add_thread_to_table`], so that no other thread is given the same PID in
+/// the meantime.
+pub fn reserve_pid(pid: Pid) -> bool {
+    let mut reserved = RESERVED_PIDS.lock();
+    if reserved.contains(&pid) || pid_in_use(pid) {
+        return false;
+    }
+    reserved.insert(pid);
+    true
+}
+
+/// Reserves the first free PID from `hint` on, skipping those that are in
+/// use or reserved, like [`reserve_pid`].
+pub fn alloc_pid(hint: Pid) -> Pid {
+    let mut reserved = RESERVED_PIDS.lock();
+    let mut pid = hint;
+    while reserved.contains(&pid) || pid_in_use(pid) {
+        pid = if pid >= PID_MAX { 2 } else { pid + 1 };
+    }
+    reserved.insert(pid);
+    pid
+}
+
+/// The number of threads created since boot.
+static TOTAL_FORKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of threads created since boot, like the `processes`
+/// line of `/proc/stat`.
+pub fn total_forks() -> usize {
+    TOTAL_FORKS.load(Ordering::Relaxed)
+}
+
 /// Add the thread and possibly its process, process group and session to the
 /// corresponding tables.
 pub fn add_thread_to_table(thread: &Arc<Thread>) {
-    let mut thread_table = THREAD_TABLE.write();
-    thread_table.insert(thread.tid(), thread);
+    TOTAL_FORKS.fetch_add(1, Ordering::Relaxed);
+    THREAD_TABLE.write().insert(thread.tid(), thread);
+    // Taken after the table lock is released, which `reserve_pid` takes
+    // with the reservations held.
+    RESERVED_PIDS.lock().remove(&thread.tid());
 
     let mut process_table = PROCESS_TABLE.write();
     let process = thread.process();
@@ -338,6 +692,49 @@ pub fn add_thread_to_table(thread: &Arc<Thread>) {
     session_table.insert(session.sid(), &session);
 }
 
+/// Give the current thread the TID of its process, once the leader thread has
+/// exited, as Linux does for a thread other than the leader calling
+/// `execve`.
+///
+/// The thread is replaced by a new one with that TID, which keeps its signal
+/// mask, CPU affinity and name.
+pub fn take_over_leader_tid() {
+    let curr = current();
+    let old = curr.task_ext().thread.clone();
+    let process = old.process().clone();
+    if old.tid() == process.pid() {
+        return;
+    }
+
+    let old_data = curr.task_ext().thread_data();
+    let data = ThreadData::new(curr.task_ext().process_data());
+    let blocked = old_data.signal.with_blocked_mut(|blocked| *blocked);
+    data.signal.with_blocked_mut(|new| *new = blocked);
+    data.set_cpu_affinity(old_data.cpu_affinity());
+    data.set_comm(&old_data.comm());
+
+    let thread = process.new_thread(process.pid()).data(data).build();
+    THREAD_TABLE.write().insert(thread.tid(), &thread);
+    old.exit(0);
+    // SAFETY: the extended data of a task is only accessed by the task
+    // itself, and the caller holds no reference to its old thread.
+    unsafe { (*(curr.task_ext_ptr() as *mut TaskExt)).thread = thread };
+}
+
+/// Add a process group created by `setpgid` or `setsid`, and its session if
+/// it is new as well, to the corresponding tables.
+pub fn add_process_group_to_table(process_group: &Arc<ProcessGroup>) {
+    PROCESS_GROUP_TABLE
+        .write()
+        .insert(process_group.pgid(), process_group);
+
+    let mut session_table = SESSION_TABLE.write();
+    let session = process_group.session();
+    if !session_table.contains_key(&session.sid()) {
+        session_table.insert(session.sid(), &session);
+    }
+}
+
 /// Lists all processes.
 pub fn processes() -> Vec<Arc<Process>> {
     PROCESS_TABLE.read().values().collect()
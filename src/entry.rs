@@ -4,7 +4,7 @@ use axhal::arch::UspaceContext;
 use axprocess::{Pid, init_proc};
 use axsignal::Signo;
 use axsync::Mutex;
-use starry_api::file::FD_TABLE;
+use starry_api::file::{FD_TABLE, add_proc_pid_dir};
 use starry_core::{
     mm::{copy_from_kernel, load_user_app, map_trampoline, new_user_aspace_empty},
     task::{ProcessData, TaskExt, ThreadData, add_thread_to_table, new_user_task},
@@ -23,10 +23,14 @@ pub fn run_user_app(args: &[String], envs: &[String]) -> Option<i32> {
     let (dir, name) = exe_path.rsplit_once('/').unwrap_or(("", &exe_path));
     set_current_dir(dir).expect("Failed to set current dir");
 
-    let (entry_vaddr, ustack_top) = load_user_app(&mut uspace, &exe_path, args, envs)
-        .unwrap_or_else(|e| panic!("Failed to load user app: {}", e));
+    let (entry_vaddr, ustack_top, ustack_bottom, thread_pointer) =
+        load_user_app(&mut uspace, &exe_path, args, envs, None)
+            .unwrap_or_else(|e| panic!("Failed to load user app: {}", e));
 
-    let uctx = UspaceContext::new(entry_vaddr.into(), ustack_top, 2333);
+    let mut uctx = UspaceContext::new(entry_vaddr.into(), ustack_top, 2333);
+    if let Some(tp) = thread_pointer {
+        uctx.set_tls(tp.as_usize());
+    }
 
     let mut task = new_user_task(name, uctx, None);
     task.ctx_mut().set_page_table_root(uspace.page_table_root());
@@ -37,6 +41,7 @@ pub fn run_user_app(args: &[String], envs: &[String]) -> Option<i32> {
         Arc::default(),
         Some(Signo::SIGCHLD),
     );
+    process_data.set_stack_bottom(ustack_bottom.as_usize());
 
     FD_TABLE
         .deref_from(&process_data.ns)
@@ -50,6 +55,7 @@ pub fn run_user_app(args: &[String], envs: &[String]) -> Option<i32> {
 
     let tid = task.id().as_u64() as Pid;
     let process = init_proc().fork(tid).data(process_data).build();
+    add_proc_pid_dir(process.pid());
 
     let thread = process
         .new_thread(tid)
@@ -5,8 +5,8 @@ use axhal::{
 };
 use axtask::{TaskExtRef, current};
 use linux_raw_sys::general::SIGSEGV;
-use starry_api::do_exit;
-use starry_core::mm::is_accessing_user_memory;
+use starry_api::{do_exit, signal::send_memory_failure_signal};
+use starry_core::{cow, mm::is_accessing_user_memory};
 
 #[register_trap_handler(PAGE_FAULT)]
 fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool) -> bool {
@@ -19,13 +19,33 @@ fn handle_page_fault(vaddr: VirtAddr, access_flags: MappingFlags, is_user: bool)
     }
 
     let curr = current();
-    if !curr
-        .task_ext()
-        .process_data()
-        .aspace
-        .lock()
-        .handle_page_fault(vaddr, access_flags)
-    {
+    // The kernel checks user buffers for memory errors before accessing them
+    // and fails with `EFAULT`, so only user accesses get here.
+    if is_user && curr.task_ext().process_data().is_poisoned(vaddr) {
+        warn!(
+            "{} ({:?}): memory error at {:#x}",
+            curr.id_name(),
+            curr.task_ext().thread,
+            vaddr
+        );
+        let _ = send_memory_failure_signal(&curr.task_ext().thread, vaddr, true);
+        return true;
+    }
+    let process_data = curr.task_ext().process_data();
+    let cpus = process_data.user_cpus();
+    let handled = {
+        let mut aspace = process_data.aspace.lock();
+        process_data
+            .shm_data
+            .lock()
+            .handle_page_fault(&mut aspace, vaddr, access_flags)
+            .unwrap_or_else(|| {
+                cow::handle_page_fault(&mut aspace, cpus, vaddr, access_flags)
+                    || (process_data.grow_stack(&mut aspace, vaddr)
+                        && cow::handle_page_fault(&mut aspace, cpus, vaddr, access_flags))
+            })
+    };
+    if !handled {
         warn!(
             "{} ({:?}): segmentation fault at {:#x}, exit!",
             curr.id_name(),